@@ -30,7 +30,12 @@ const LibraryGeneralSettingsScreen = ({
 	const { mutate: editLibrary } = useBridgeMutation('library.edit');
 
 	useAutoForm(form, (value) => {
-		editLibrary({ description: value.description, name: value.name, id: library.uuid });
+		editLibrary({
+			description: value.description,
+			name: value.name,
+			id: library.uuid,
+			sync_retention_days: null
+		});
 		// console.log('Updated', value);
 		// TODO: Show toast
 	});
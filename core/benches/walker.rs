@@ -0,0 +1,56 @@
+//! Compares the indexer's walker reading many directories at once against it reading one at a time,
+//! as it did before, run it with `cargo bench -p sd-core --features bench --bench walker`
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use sd_core::indexer_bench::walk;
+use std::fs;
+use tempfile::{tempdir, TempDir};
+use tokio::runtime::Runtime;
+
+const DIRS: usize = 500;
+const FILES_PER_DIR: usize = 100;
+
+fn prepare_location() -> TempDir {
+	let root = tempdir().unwrap();
+
+	for i in 0..DIRS {
+		// Nesting a bit, so the queue has directories from many levels at once
+		let dir = root.path().join(format!("{}/dir_{i}", i % 10));
+		fs::create_dir_all(&dir).unwrap();
+		for j in 0..FILES_PER_DIR {
+			fs::File::create(dir.join(format!("file_{j}.txt"))).unwrap();
+		}
+	}
+
+	root
+}
+
+fn bench(c: &mut Criterion) {
+	let runtime = Runtime::new().unwrap();
+	let root = prepare_location();
+	let root_path = root.path();
+
+	let mut group = c.benchmark_group("walker");
+	group.sample_size(20);
+
+	// A single directory at a time is the baseline, reading them like the walker used to
+	for max_concurrent_dirs in [1, 4, 16, 64] {
+		group.bench_with_input(
+			BenchmarkId::new("concurrent", max_concurrent_dirs),
+			&max_concurrent_dirs,
+			|b, &max_concurrent_dirs| {
+				b.to_async(&runtime).iter(|| async move {
+					assert_eq!(
+						walk(root_path, max_concurrent_dirs).await,
+						10 + DIRS + DIRS * FILES_PER_DIR
+					);
+				})
+			},
+		);
+	}
+
+	group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
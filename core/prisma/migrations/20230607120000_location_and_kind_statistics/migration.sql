@@ -0,0 +1,14 @@
+-- CreateTable
+CREATE TABLE "location_statistics" (
+    "location_id" INTEGER NOT NULL PRIMARY KEY,
+    "file_path_count" INTEGER NOT NULL DEFAULT 0,
+    "total_bytes" BIGINT NOT NULL DEFAULT 0,
+    CONSTRAINT "location_statistics_location_id_fkey" FOREIGN KEY ("location_id") REFERENCES "location" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateTable
+CREATE TABLE "kind_statistics" (
+    "kind" INTEGER NOT NULL PRIMARY KEY,
+    "object_count" INTEGER NOT NULL DEFAULT 0,
+    "total_bytes" BIGINT NOT NULL DEFAULT 0
+);
@@ -0,0 +1,34 @@
+-- AlterTable
+ALTER TABLE "file_path" ADD COLUMN "items_count" INTEGER NOT NULL DEFAULT 0;
+
+-- Aggregate the sizes and items counts of every existing directory. A directory's descendants are
+-- the file paths whose materialized path starts with the directory's own path followed by '/',
+-- which we check with a range so the (location_id, materialized_path) index can be used
+CREATE TEMPORARY TABLE "directory_prefix" AS
+    SELECT
+        "id",
+        "location_id",
+        CASE WHEN "name" = '' THEN "materialized_path" ELSE "materialized_path" || "name" || '/' END AS "prefix"
+    FROM "file_path"
+    WHERE "is_dir";
+
+UPDATE "file_path" SET
+    "size_in_bytes" = (
+        SELECT COALESCE(SUM("child"."size_in_bytes"), 0)
+        FROM "directory_prefix" AS "dir"
+        JOIN "file_path" AS "child" ON "child"."location_id" = "dir"."location_id"
+            AND "child"."materialized_path" >= "dir"."prefix"
+            AND "child"."materialized_path" < substr("dir"."prefix", 1, length("dir"."prefix") - 1) || '0'
+        WHERE "dir"."id" = "file_path"."id" AND NOT "child"."is_dir"
+    ),
+    "items_count" = (
+        SELECT COUNT(*)
+        FROM "directory_prefix" AS "dir"
+        JOIN "file_path" AS "child" ON "child"."location_id" = "dir"."location_id"
+            AND "child"."materialized_path" >= "dir"."prefix"
+            AND "child"."materialized_path" < substr("dir"."prefix", 1, length("dir"."prefix") - 1) || '0'
+        WHERE "dir"."id" = "file_path"."id" AND "child"."id" <> "dir"."id"
+    )
+WHERE "is_dir";
+
+DROP TABLE "directory_prefix";
@@ -0,0 +1,13 @@
+-- AlterTable
+ALTER TABLE "location" ADD COLUMN "follow_symlinks" BOOLEAN NOT NULL DEFAULT false;
+
+-- AlterTable
+ALTER TABLE "file_path" ADD COLUMN "symlink_target" TEXT;
+ALTER TABLE "file_path" ADD COLUMN "is_hardlink" BOOLEAN NOT NULL DEFAULT false;
+
+-- DropIndex
+-- Hardlinks are file paths sharing their inode and device, so these can't be unique anymore
+DROP INDEX "file_path_location_id_inode_device_key";
+
+-- CreateIndex
+CREATE INDEX "file_path_location_id_inode_device_idx" ON "file_path"("location_id", "inode", "device");
@@ -0,0 +1,5 @@
+-- AlterTable
+ALTER TABLE "file_path" ADD COLUMN "permissions" INTEGER;
+ALTER TABLE "file_path" ADD COLUMN "uid" BIGINT;
+ALTER TABLE "file_path" ADD COLUMN "gid" BIGINT;
+ALTER TABLE "file_path" ADD COLUMN "xattrs" TEXT;
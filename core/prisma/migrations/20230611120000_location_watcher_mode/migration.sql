@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE "location" ADD COLUMN "watcher_mode" INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,6 @@
+-- CreateTable
+CREATE TABLE "watcher_checkpoint" (
+    "location_id" INTEGER NOT NULL PRIMARY KEY,
+    "checkpoint" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT "watcher_checkpoint_location_id_fkey" FOREIGN KEY ("location_id") REFERENCES "location" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
@@ -0,0 +1,16 @@
+-- AlterTable
+ALTER TABLE "volume" ADD COLUMN "uuid" TEXT;
+ALTER TABLE "volume" ADD COLUMN "label" TEXT;
+ALTER TABLE "volume" ADD COLUMN "is_mounted" BOOLEAN NOT NULL DEFAULT true;
+
+-- AlterTable
+ALTER TABLE "location" ADD COLUMN "volume_id" INTEGER CONSTRAINT "location_volume_id_fkey" REFERENCES "volume" ("id") ON DELETE SET NULL ON UPDATE CASCADE;
+
+-- DropIndex
+DROP INDEX "volume_node_id_mount_point_name_key";
+
+-- CreateIndex
+CREATE INDEX "volume_node_id_mount_point_name_idx" ON "volume"("node_id", "mount_point", "name");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "volume_node_id_uuid_key" ON "volume"("node_id", "uuid");
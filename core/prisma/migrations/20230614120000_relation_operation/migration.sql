@@ -0,0 +1,12 @@
+-- CreateTable
+CREATE TABLE "relation_operation" (
+    "id" BLOB NOT NULL PRIMARY KEY,
+    "timestamp" BIGINT NOT NULL,
+    "relation" TEXT NOT NULL,
+    "item_id" BLOB NOT NULL,
+    "group_id" BLOB NOT NULL,
+    "kind" TEXT NOT NULL,
+    "data" BLOB NOT NULL,
+    "node_id" INTEGER NOT NULL,
+    CONSTRAINT "relation_operation_node_id_fkey" FOREIGN KEY ("node_id") REFERENCES "node" ("id") ON DELETE RESTRICT ON UPDATE CASCADE
+);
@@ -0,0 +1,8 @@
+-- CreateIndex
+CREATE INDEX "owned_operation_node_id_timestamp_idx" ON "owned_operation"("node_id", "timestamp");
+
+-- CreateIndex
+CREATE INDEX "shared_operation_node_id_timestamp_idx" ON "shared_operation"("node_id", "timestamp");
+
+-- CreateIndex
+CREATE INDEX "relation_operation_node_id_timestamp_idx" ON "relation_operation"("node_id", "timestamp");
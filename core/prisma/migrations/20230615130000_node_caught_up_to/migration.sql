@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE "node" ADD COLUMN "caught_up_to" BIGINT;
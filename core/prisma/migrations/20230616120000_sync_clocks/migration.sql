@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE "node" ADD COLUMN "sync_clocks" BLOB;
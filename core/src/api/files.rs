@@ -1,23 +1,40 @@
 use crate::{
-	api::utils::library,
+	api::{
+		locations::{impl_object_with_file_paths_from_db, ObjectWithFilePaths},
+		utils::library,
+	},
 	invalidate_query,
-	location::{file_path_helper::IsolatedFilePathData, find_location, LocationError},
+	location::{
+		check_location_is_online, file_path_helper::IsolatedFilePathData, find_location,
+		LocationError,
+	},
 	object::fs::{
 		copy::FileCopierJobInit, cut::FileCutterJobInit, decrypt::FileDecryptorJobInit,
 		delete::FileDeleterJobInit, encrypt::FileEncryptorJobInit, erase::FileEraserJobInit,
 	},
-	prisma::{location, object},
+	prisma::{location, media_data, object},
 };
 
 use chrono::Utc;
 use rspc::{alpha::AlphaRouter, ErrorCode};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use specta::Type;
 use std::path::Path;
 use tokio::fs;
 
 use super::{Ctx, R};
 
+object::include!(object_with_file_paths_and_media_data { file_paths media_data });
+
+impl_object_with_file_paths_from_db!(object_with_file_paths_and_media_data);
+
+#[derive(Serialize, Type, Debug)]
+pub struct ObjectWithMediaData {
+	#[serde(flatten)]
+	pub object: ObjectWithFilePaths,
+	pub media_data: Option<media_data::Data>,
+}
+
 pub(crate) fn mount() -> AlphaRouter<Ctx> {
 	R.router()
 		.procedure("get", {
@@ -31,9 +48,13 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						.db
 						.object()
 						.find_unique(object::id::equals(args.id))
-						.include(object::include!({ file_paths media_data }))
+						.include(object_with_file_paths_and_media_data::include())
 						.exec()
-						.await?)
+						.await?
+						.map(|mut object| ObjectWithMediaData {
+							media_data: object.media_data.take(),
+							object: object.into(),
+						}))
 				})
 		})
 		.procedure("setNote", {
@@ -137,42 +158,59 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 		.procedure("encryptFiles", {
 			R.with2(library())
 				.mutation(|(_, library), args: FileEncryptorJobInit| async move {
+					check_location_is_online(&library, args.location_id).await?;
+
 					library.spawn_job(args).await.map_err(Into::into)
 				})
 		})
 		.procedure("decryptFiles", {
 			R.with2(library())
 				.mutation(|(_, library), args: FileDecryptorJobInit| async move {
+					check_location_is_online(&library, args.location_id).await?;
+
 					library.spawn_job(args).await.map_err(Into::into)
 				})
 		})
 		.procedure("deleteFiles", {
 			R.with2(library())
 				.mutation(|(_, library), args: FileDeleterJobInit| async move {
+					check_location_is_online(&library, args.location_id).await?;
+
 					library.spawn_job(args).await.map_err(Into::into)
 				})
 		})
 		.procedure("eraseFiles", {
 			R.with2(library())
 				.mutation(|(_, library), args: FileEraserJobInit| async move {
+					check_location_is_online(&library, args.location_id).await?;
+
 					library.spawn_job(args).await.map_err(Into::into)
 				})
 		})
 		.procedure("duplicateFiles", {
 			R.with2(library())
 				.mutation(|(_, library), args: FileCopierJobInit| async move {
+					check_location_is_online(&library, args.source_location_id).await?;
+					check_location_is_online(&library, args.target_location_id).await?;
+
 					library.spawn_job(args).await.map_err(Into::into)
 				})
 		})
 		.procedure("copyFiles", {
 			R.with2(library())
 				.mutation(|(_, library), args: FileCopierJobInit| async move {
+					check_location_is_online(&library, args.source_location_id).await?;
+					check_location_is_online(&library, args.target_location_id).await?;
+
 					library.spawn_job(args).await.map_err(Into::into)
 				})
 		})
 		.procedure("cutFiles", {
 			R.with2(library())
 				.mutation(|(_, library), args: FileCutterJobInit| async move {
+					check_location_is_online(&library, args.source_location_id).await?;
+					check_location_is_online(&library, args.target_location_id).await?;
+
 					library.spawn_job(args).await.map_err(Into::into)
 				})
 		})
@@ -191,6 +229,8 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				     file_name,
 				     new_file_name,
 				 }: RenameFileArgs| async move {
+					check_location_is_online(&library, location_id).await?;
+
 					let location = find_location(&library, location_id)
 						.select(location::select!({ path }))
 						.exec()
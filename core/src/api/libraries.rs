@@ -1,13 +1,14 @@
 use crate::{
 	invalidate_query,
-	library::LibraryConfig,
-	prisma::statistics,
-	volume::{get_volumes, save_volume},
+	library::{volumes_capacity, LibraryConfig, LIBRARY_STATISTICS_ID},
+	prisma::{kind_statistics, location_statistics, statistics},
+	util::db::{db_int_to_size, size_to_db_int},
 };
 
-use chrono::Utc;
+use chrono::{DateTime, FixedOffset, Utc};
 use rspc::alpha::AlphaRouter;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 use specta::Type;
 use tracing::debug;
 use uuid::Uuid;
@@ -17,6 +18,90 @@ use super::{
 	Ctx, R,
 };
 
+/// The byte counts of the statistics are unsigned 64 bit integers, which JavaScript numbers
+/// can't hold, so they're sent as strings
+#[serde_as]
+#[derive(Serialize, Type)]
+pub struct Statistics {
+	id: i32,
+	date_captured: DateTime<FixedOffset>,
+	total_object_count: i32,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	library_db_size: u64,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	total_bytes_used: u64,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	total_bytes_capacity: u64,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	total_unique_bytes: u64,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	total_bytes_free: u64,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	preview_media_bytes: u64,
+}
+
+impl From<statistics::Data> for Statistics {
+	fn from(data: statistics::Data) -> Self {
+		Self {
+			id: data.id,
+			date_captured: data.date_captured,
+			total_object_count: data.total_object_count,
+			library_db_size: db_int_to_size(data.library_db_size),
+			total_bytes_used: db_int_to_size(data.total_bytes_used),
+			total_bytes_capacity: db_int_to_size(data.total_bytes_capacity),
+			total_unique_bytes: db_int_to_size(data.total_unique_bytes),
+			total_bytes_free: db_int_to_size(data.total_bytes_free),
+			preview_media_bytes: db_int_to_size(data.preview_media_bytes),
+		}
+	}
+}
+
+#[serde_as]
+#[derive(Serialize, Type)]
+pub struct KindStatistics {
+	kind: i32,
+	object_count: i32,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	total_bytes: u64,
+}
+
+impl From<kind_statistics::Data> for KindStatistics {
+	fn from(data: kind_statistics::Data) -> Self {
+		Self {
+			kind: data.kind,
+			object_count: data.object_count,
+			total_bytes: db_int_to_size(data.total_bytes),
+		}
+	}
+}
+
+#[serde_as]
+#[derive(Serialize, Type)]
+pub struct LocationStatistics {
+	location_id: i32,
+	file_path_count: i32,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	total_bytes: u64,
+}
+
+impl From<location_statistics::Data> for LocationStatistics {
+	fn from(data: location_statistics::Data) -> Self {
+		Self {
+			location_id: data.location_id,
+			file_path_count: data.file_path_count,
+			total_bytes: db_int_to_size(data.total_bytes),
+		}
+	}
+}
+
 pub(crate) fn mount() -> AlphaRouter<Ctx> {
 	R.router()
 		.procedure("list", {
@@ -26,26 +111,22 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 		})
 		.procedure("statistics", {
 			R.with2(library()).query(|(_, library), _: ()| async move {
-				let _statistics = library
+				// These are kept up to date by the indexer and the location watcher
+				let locations_statistics = library
 					.db
-					.statistics()
-					.find_unique(statistics::id::equals(library.node_local_id))
+					.location_statistics()
+					.find_many(vec![])
+					.exec()
+					.await?;
+				let kinds_statistics = library
+					.db
+					.kind_statistics()
+					.find_many(vec![])
 					.exec()
 					.await?;
 
-				// TODO: get from database, not sys
-				let volumes = get_volumes();
-				save_volume(&library).await?;
-
-				let mut available_capacity: u64 = 0;
-				let mut total_capacity: u64 = 0;
-
-				if let Ok(volumes) = volumes {
-					for volume in volumes {
-						total_capacity += volume.total_capacity;
-						available_capacity += volume.available_capacity;
-					}
-				}
+				// Kept up to date by the volume monitor
+				let capacity = volumes_capacity(&library.db, library.node_local_id).await?;
 
 				let library_db_size = get_size(
 					library
@@ -64,27 +145,74 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 
 				use statistics::*;
 				let params = vec![
-					id::set(1), // Each library is a database so only one of these ever exists
+					id::set(LIBRARY_STATISTICS_ID),
 					date_captured::set(Utc::now().into()),
-					total_object_count::set(0),
-					library_db_size::set(library_db_size.to_string()),
-					total_bytes_used::set(0.to_string()),
-					total_bytes_capacity::set(total_capacity.to_string()),
-					total_unique_bytes::set(0.to_string()),
-					total_bytes_free::set(available_capacity.to_string()),
-					preview_media_bytes::set(thumbnail_folder_size.to_string()),
+					total_object_count::set(
+						kinds_statistics
+							.iter()
+							.map(|kind| kind.object_count)
+							.fold(0, i32::saturating_add),
+					),
+					library_db_size::set(size_to_db_int(library_db_size)),
+					total_bytes_used::set(
+						locations_statistics
+							.iter()
+							.map(|location| location.total_bytes)
+							.fold(0, i64::saturating_add),
+					),
+					total_bytes_capacity::set(size_to_db_int(capacity.total)),
+					total_unique_bytes::set(
+						kinds_statistics
+							.iter()
+							.map(|kind| kind.total_bytes)
+							.fold(0, i64::saturating_add),
+					),
+					total_bytes_free::set(size_to_db_int(capacity.available)),
+					preview_media_bytes::set(size_to_db_int(thumbnail_folder_size)),
 				];
 
-				Ok(library
-					.db
-					.statistics()
-					.upsert(
-						statistics::id::equals(1), // Each library is a database so only one of these ever exists
-						statistics::create(params.clone()),
-						params,
-					)
-					.exec()
-					.await?)
+				Ok(Statistics::from(
+					library
+						.db
+						.statistics()
+						.upsert(
+							statistics::id::equals(LIBRARY_STATISTICS_ID),
+							statistics::create(params.clone()),
+							params,
+						)
+						.exec()
+						.await?,
+				))
+			})
+		})
+		.procedure("statisticsBreakdown", {
+			#[derive(Serialize, Type)]
+			pub struct StatisticsBreakdown {
+				kinds: Vec<KindStatistics>,
+				locations: Vec<LocationStatistics>,
+			}
+
+			R.with2(library()).query(|(_, library), _: ()| async move {
+				Ok(StatisticsBreakdown {
+					kinds: library
+						.db
+						.kind_statistics()
+						.find_many(vec![])
+						.exec()
+						.await?
+						.into_iter()
+						.map(Into::into)
+						.collect(),
+					locations: library
+						.db
+						.location_statistics()
+						.find_many(vec![])
+						.exec()
+						.await?
+						.into_iter()
+						.map(Into::into)
+						.collect(),
+				})
 			})
 		})
 		.procedure("create", {
@@ -122,12 +250,18 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				pub id: Uuid,
 				pub name: Option<String>,
 				pub description: Option<String>,
+				pub sync_retention_days: Option<u32>,
 			}
 
 			R.mutation(|ctx, args: EditLibraryArgs| async move {
 				Ok(ctx
 					.library_manager
-					.edit(args.id, args.name, args.description)
+					.edit(
+						args.id,
+						args.name,
+						args.description,
+						args.sync_retention_days,
+					)
 					.await?)
 			})
 		})
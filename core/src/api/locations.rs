@@ -1,20 +1,33 @@
 use crate::{
 	invalidate_query,
 	location::{
-		delete_location, find_location, indexer::rules::IndexerRuleCreateArgs, light_scan_location,
-		location_with_indexer_rules, relink_location, scan_location, LocationCreateArgs,
-		LocationError, LocationUpdateArgs,
+		delete_location, find_location, find_locations_with_indexer_rules,
+		indexer::{
+			preview::preview_indexer_rules,
+			rules::{
+				IndexerRule, IndexerRuleCreateArgs, IndexerRuleUpdateArgs, RuleKind, RulePerKind,
+			},
+			rules_document::{IndexerRulesDocument, RulesDocumentFormat, RulesImportConflict},
+			IndexerError,
+		},
+		light_scan_location, location_with_indexer_rules, reevaluate_locations_indexer_rules,
+		relink_location, scan_location, LocationCreateArgs, LocationError, LocationUpdateArgs,
 	},
 	prisma::{file_path, indexer_rule, indexer_rules_in_location, location, object, tag},
-	util::debug_initializer::AbortOnDrop,
+	util::{
+		db::{db_int_to_size, db_int_to_u64},
+		debug_initializer::AbortOnDrop,
+	},
 };
 
+use chrono::{DateTime, FixedOffset};
 use rspc::{self, alpha::AlphaRouter, ErrorCode};
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 use specta::Type;
 use std::path::PathBuf;
 
-use super::{utils::library, Ctx, R};
+use super::{utils::library, CoreEvent, Ctx, R};
 
 #[derive(Serialize, Deserialize, Type, Debug)]
 #[serde(tag = "type")]
@@ -30,11 +43,15 @@ pub enum ExplorerItem {
 	Path {
 		// has_thumbnail is determined by the local existence of a thumbnail
 		has_thumbnail: bool,
-		item: file_path_with_object::Data,
+		// offline items can still be listed from the index, but not acted upon
+		online: bool,
+		item: FilePathWithObject,
 	},
 	Object {
 		has_thumbnail: bool,
-		item: object_with_file_paths::Data,
+		// if any of its paths is in an online location
+		online: bool,
+		item: ObjectWithFilePaths,
 	},
 }
 
@@ -48,6 +65,153 @@ pub struct ExplorerData {
 file_path::include!(file_path_with_object { object });
 object::include!(object_with_file_paths { file_paths });
 
+/// A file path as sent to the frontend.
+///
+/// Sizes, inodes and devices are unsigned 64 bit integers, which JavaScript numbers can't hold,
+/// so they're sent as strings.
+#[serde_as]
+#[derive(Serialize, Deserialize, Type, Debug)]
+pub struct FilePath {
+	pub id: i32,
+	pub pub_id: Vec<u8>,
+	pub is_dir: bool,
+	pub cas_id: Option<String>,
+	pub integrity_checksum: Option<String>,
+	pub location_id: i32,
+	pub materialized_path: String,
+	pub name: String,
+	pub extension: String,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	pub size_in_bytes: u64,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	pub inode: u64,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	pub device: u64,
+	pub items_count: i32,
+	pub symlink_target: Option<String>,
+	pub is_hardlink: bool,
+	pub object_id: Option<i32>,
+	pub key_id: Option<i32>,
+	pub permissions: Option<i32>,
+	pub uid: Option<i64>,
+	pub gid: Option<i64>,
+	pub xattrs: Option<String>,
+	pub date_created: DateTime<FixedOffset>,
+	pub date_modified: DateTime<FixedOffset>,
+	pub date_indexed: DateTime<FixedOffset>,
+}
+
+macro_rules! impl_file_path_from_db {
+	($($module:ident),+) => {
+		$(
+			impl From<$module::Data> for FilePath {
+				fn from(data: $module::Data) -> Self {
+					Self {
+						id: data.id,
+						pub_id: data.pub_id,
+						is_dir: data.is_dir,
+						cas_id: data.cas_id,
+						integrity_checksum: data.integrity_checksum,
+						location_id: data.location_id,
+						materialized_path: data.materialized_path,
+						name: data.name,
+						extension: data.extension,
+						size_in_bytes: db_int_to_size(data.size_in_bytes),
+						inode: db_int_to_u64(data.inode),
+						device: db_int_to_u64(data.device),
+						items_count: data.items_count,
+						symlink_target: data.symlink_target,
+						is_hardlink: data.is_hardlink,
+						object_id: data.object_id,
+						key_id: data.key_id,
+						permissions: data.permissions,
+						uid: data.uid,
+						gid: data.gid,
+						xattrs: data.xattrs,
+						date_created: data.date_created,
+						date_modified: data.date_modified,
+						date_indexed: data.date_indexed,
+					}
+				}
+			}
+		)+
+	};
+}
+
+impl_file_path_from_db!(file_path, file_path_with_object);
+
+#[derive(Serialize, Deserialize, Type, Debug)]
+pub struct FilePathWithObject {
+	#[serde(flatten)]
+	pub file_path: FilePath,
+	pub object: Option<object::Data>,
+}
+
+impl From<file_path_with_object::Data> for FilePathWithObject {
+	fn from(mut data: file_path_with_object::Data) -> Self {
+		Self {
+			object: data.object.take(),
+			file_path: data.into(),
+		}
+	}
+}
+
+/// An object and its file paths, as sent to the frontend.
+#[derive(Serialize, Deserialize, Type, Debug)]
+pub struct ObjectWithFilePaths {
+	pub id: i32,
+	pub pub_id: Vec<u8>,
+	pub kind: i32,
+	pub key_id: Option<i32>,
+	pub hidden: bool,
+	pub favorite: bool,
+	pub important: bool,
+	pub has_thumbnail: bool,
+	pub has_thumbstrip: bool,
+	pub has_video_preview: bool,
+	pub ipfs_id: Option<String>,
+	pub note: Option<String>,
+	pub date_created: DateTime<FixedOffset>,
+	pub date_accessed: Option<DateTime<FixedOffset>>,
+	pub file_paths: Vec<FilePath>,
+}
+
+/// Implements the conversion from any include of an object that has its `file_paths`.
+macro_rules! impl_object_with_file_paths_from_db {
+	($($module:ident),+) => {
+		$(
+			impl From<$module::Data> for ObjectWithFilePaths {
+				fn from(data: $module::Data) -> Self {
+					Self {
+						id: data.id,
+						pub_id: data.pub_id,
+						kind: data.kind,
+						key_id: data.key_id,
+						hidden: data.hidden,
+						favorite: data.favorite,
+						important: data.important,
+						has_thumbnail: data.has_thumbnail,
+						has_thumbstrip: data.has_thumbstrip,
+						has_video_preview: data.has_video_preview,
+						ipfs_id: data.ipfs_id,
+						note: data.note,
+						date_created: data.date_created,
+						date_accessed: data.date_accessed,
+						file_paths: data.file_paths.into_iter().map(Into::into).collect(),
+					}
+				}
+			}
+		)+
+	};
+}
+
+pub(crate) use impl_object_with_file_paths_from_db;
+
+impl_object_with_file_paths_from_db!(object_with_file_paths);
+
 pub(crate) fn mount() -> AlphaRouter<Ctx> {
 	R.router()
 		.procedure("list", {
@@ -164,6 +328,46 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					Ok(AbortOnDrop(handle))
 				})
 		})
+		.procedure("sizeTree", {
+			#[derive(Clone, Serialize, Deserialize, Type, Debug)]
+			pub struct SizeTreeArgs {
+				pub location_id: i32,
+				#[specta(optional)]
+				pub sub_path: Option<String>,
+				#[specta(optional)]
+				pub max_depth: Option<u32>,
+			}
+
+			// Deeper trees get too big to send in one go, the frontend can drill down with `sub_path`
+			const DEFAULT_MAX_DEPTH: u32 = 3;
+
+			R.with2(library())
+				.query(|(_, library), args: SizeTreeArgs| async move {
+					find_location(&library, args.location_id)
+						.select(location::select!({ id }))
+						.exec()
+						.await?
+						.ok_or(LocationError::IdNotFound(args.location_id))?;
+
+					let tree = library
+						.size_tree_cache
+						.get_or_compute(args.location_id, &library.db)
+						.await?;
+
+					let sub_path = args.sub_path.unwrap_or_default();
+
+					tree.find(&sub_path)
+						.map(|node| {
+							node.with_max_depth(args.max_depth.unwrap_or(DEFAULT_MAX_DEPTH))
+						})
+						.ok_or_else(|| {
+							rspc::Error::new(
+								ErrorCode::NotFound,
+								format!("Directory <path='{sub_path}'> not found in location"),
+							)
+						})
+				})
+		})
 		.procedure(
 			"online",
 			R.subscription(|ctx, _: ()| async move {
@@ -182,6 +386,38 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				}
 			}),
 		)
+		.procedure("relinked", {
+			R.with2(library())
+				.subscription(|(ctx, library), _: ()| async move {
+					let mut event_bus_rx = ctx.event_bus.0.subscribe();
+					async_stream::stream! {
+						while let Ok(event) = event_bus_rx.recv().await {
+							match event {
+								CoreEvent::LocationRelinked(event) if event.library_id == library.id => {
+									yield event
+								}
+								_ => {}
+							}
+						}
+					}
+				})
+		})
+		.procedure("watchLimited", {
+			R.with2(library())
+				.subscription(|(ctx, library), _: ()| async move {
+					let mut event_bus_rx = ctx.event_bus.0.subscribe();
+					async_stream::stream! {
+						while let Ok(event) = event_bus_rx.recv().await {
+							match event {
+								CoreEvent::LocationWatchLimited(event) if event.library_id == library.id => {
+									yield event
+								}
+								_ => {}
+							}
+						}
+					}
+				})
+		})
 		.merge("indexer_rules.", mount_indexer_rule_routes())
 }
 
@@ -197,6 +433,142 @@ fn mount_indexer_rule_routes() -> AlphaRouter<Ctx> {
 					Ok(())
 				})
 		})
+		.procedure("preview", {
+			#[derive(Type, Deserialize)]
+			pub struct IndexerRulesPreviewArgs {
+				pub location_id: i32,
+				#[specta(optional)]
+				pub sub_path: Option<String>,
+				/// Saved indexer rules to walk with, like the ones the location already uses
+				pub indexer_rules_ids: Vec<i32>,
+				/// A proposed rule, in the same format of `IndexerRuleCreateArgs`
+				pub rules: Vec<(RuleKind, Vec<String>)>,
+				#[specta(optional)]
+				pub sample_size: Option<u32>,
+			}
+
+			const DEFAULT_SAMPLE_SIZE: u32 = 50;
+
+			R.with2(library())
+				.query(|(_, library), args: IndexerRulesPreviewArgs| async move {
+					let location = find_location(&library, args.location_id)
+						.select(location::select!({ path follow_symlinks }))
+						.exec()
+						.await?
+						.ok_or(LocationError::IdNotFound(args.location_id))?;
+
+					let saved_rules = library
+						.db
+						.indexer_rule()
+						.find_many(vec![indexer_rule::id::in_vec(
+							args.indexer_rules_ids.clone(),
+						)])
+						.exec()
+						.await?;
+
+					if let Some(missing_id) = args
+						.indexer_rules_ids
+						.iter()
+						.find(|id| !saved_rules.iter().any(|rule| rule.id == **id))
+					{
+						return Err(IndexerError::IndexerRuleNotFound(*missing_id).into());
+					}
+
+					let mut indexer_rules = saved_rules
+						.iter()
+						.map(IndexerRule::try_from)
+						.collect::<Result<Vec<_>, _>>()?;
+
+					if !args.rules.is_empty() {
+						indexer_rules.push(IndexerRule::new(
+							"Proposed rule".to_string(),
+							false,
+							args.rules
+								.into_iter()
+								.map(|(kind, parameters)| {
+									RulePerKind::from_kind_and_parameters(kind, parameters)
+								})
+								.collect::<Result<Vec<_>, _>>()?,
+						));
+					}
+
+					preview_indexer_rules(
+						args.location_id,
+						&location.path,
+						args.sub_path,
+						&indexer_rules,
+						location.follow_symlinks,
+						args.sample_size.unwrap_or(DEFAULT_SAMPLE_SIZE) as usize,
+					)
+					.await
+					.map_err(Into::into)
+				})
+		})
+		.procedure("update", {
+			R.with2(library())
+				.mutation(|(_, library), args: IndexerRuleUpdateArgs| async move {
+					let indexer_rule_id = args.id;
+					let rules_changed = args.rules.is_some();
+
+					args.update(&library).await?;
+
+					if rules_changed {
+						// Every location using this rule must be walked again to match the new rules
+						reevaluate_locations_indexer_rules(
+							&library,
+							find_locations_with_indexer_rules(&library.db, vec![indexer_rule_id])
+								.await?,
+						)
+						.await?;
+					}
+
+					invalidate_query!(library, "locations.indexer_rules.list");
+
+					Ok(())
+				})
+		})
+		.procedure("export", {
+			#[derive(Type, Deserialize)]
+			pub struct IndexerRulesExportArgs {
+				pub indexer_rules_ids: Vec<i32>,
+				pub format: RulesDocumentFormat,
+			}
+
+			R.with2(library())
+				.query(|(_, library), args: IndexerRulesExportArgs| async move {
+					IndexerRulesDocument::export(args.indexer_rules_ids, &library.db)
+						.await?
+						.render(args.format)
+						.map_err(Into::into)
+				})
+		})
+		.procedure("import", {
+			#[derive(Type, Deserialize)]
+			pub struct IndexerRulesImportArgs {
+				pub document: String,
+				pub format: RulesDocumentFormat,
+				pub conflict: RulesImportConflict,
+			}
+
+			R.with2(library())
+				.mutation(|(_, library), args: IndexerRulesImportArgs| async move {
+					let report = IndexerRulesDocument::parse(args.format, &args.document)?
+						.import(args.conflict, &library)
+						.await?;
+
+					// The locations using the replaced rules must match their new rules
+					reevaluate_locations_indexer_rules(
+						&library,
+						find_locations_with_indexer_rules(&library.db, report.replaced_ids.clone())
+							.await?,
+					)
+					.await?;
+
+					invalidate_query!(library, "locations.indexer_rules.list");
+
+					Ok(report)
+				})
+		})
 		.procedure("delete", {
 			R.with2(library())
 				.mutation(|(_, library), indexer_rule_id: i32| async move {
@@ -221,6 +593,11 @@ fn mount_indexer_rule_routes() -> AlphaRouter<Ctx> {
 						));
 					}
 
+					// Found before the rule is unlinked from them
+					let location_ids =
+						find_locations_with_indexer_rules(&library.db, vec![indexer_rule_id])
+							.await?;
+
 					library
 						.db
 						.indexer_rules_in_location()
@@ -235,6 +612,9 @@ fn mount_indexer_rule_routes() -> AlphaRouter<Ctx> {
 						.exec()
 						.await?;
 
+					// What the rule rejected may be accepted now
+					reevaluate_locations_indexer_rules(&library, location_ids).await?;
+
 					invalidate_query!(library, "locations.indexer_rules.list");
 
 					Ok(())
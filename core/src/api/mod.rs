@@ -3,7 +3,12 @@ use serde::{Deserialize, Serialize};
 use specta::Type;
 use std::sync::Arc;
 
-use crate::{node::NodeConfig, Node};
+use crate::{
+	location::{LocationRelinkedEvent, LocationWatchLimitedEvent},
+	node::NodeConfig,
+	volume::VolumeEvent,
+	Node,
+};
 
 use utils::{InvalidRequests, InvalidateOperationEvent};
 
@@ -17,6 +22,9 @@ pub type Router = rspc::Router<Ctx>;
 #[derive(Debug, Clone, Serialize, Type)]
 pub enum CoreEvent {
 	NewThumbnail { cas_id: String },
+	LocationRelinked(LocationRelinkedEvent),
+	LocationWatchLimited(LocationWatchLimitedEvent),
+	Volume(VolumeEvent),
 	InvalidateOperation(InvalidateOperationEvent),
 }
 
@@ -5,6 +5,7 @@ use chrono::{DateTime, FixedOffset, Utc};
 use prisma_client_rust::operator::or;
 use rspc::{alpha::AlphaRouter, ErrorCode};
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 use specta::Type;
 
 use crate::{
@@ -13,9 +14,9 @@ use crate::{
 		utils::library,
 	},
 	library::Library,
-	location::{find_location, LocationError},
-	prisma::{self, file_path, object, tag, tag_on_object},
-	util::db::chain_optional_iter,
+	location::{find_location, online_location_ids, LocationError},
+	prisma::{self, file_path, media_data, object, tag, tag_on_object},
+	util::db::{chain_optional_iter, size_to_db_int},
 };
 
 use super::{Ctx, R};
@@ -33,6 +34,34 @@ struct OptionalRange<T> {
 	to: Option<T>,
 }
 
+impl<T> OptionalRange<T> {
+	fn into_params<P>(self, gte: impl FnOnce(T) -> P, lte: impl FnOnce(T) -> P) -> [Option<P>; 2] {
+		[self.from.map(gte), self.to.map(lte)]
+	}
+}
+
+/// Same as [`OptionalRange`], but sizes are received as strings like in [`crate::volume::Volume`],
+/// so they don't lose precision on the frontend
+#[serde_as]
+#[derive(Deserialize, Default, Type, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SizeRange {
+	#[specta(type = Option<String>)]
+	#[serde_as(as = "Option<DisplayFromStr>")]
+	#[serde(default)]
+	from: Option<u64>,
+	#[specta(type = Option<String>)]
+	#[serde_as(as = "Option<DisplayFromStr>")]
+	#[serde(default)]
+	to: Option<u64>,
+}
+
+impl From<SizeRange> for OptionalRange<u64> {
+	fn from(SizeRange { from, to }: SizeRange) -> Self {
+		Self { from, to }
+	}
+}
+
 #[derive(Deserialize, Type, Debug, Clone, Copy)]
 enum SortOrder {
 	Asc,
@@ -113,12 +142,61 @@ struct FilePathFilterArgs {
 	extension: Option<String>,
 	#[serde(default)]
 	created_at: OptionalRange<DateTime<Utc>>,
+	#[serde(default)]
+	modified_at: OptionalRange<DateTime<Utc>>,
+	#[serde(default)]
+	indexed_at: OptionalRange<DateTime<Utc>>,
+	#[serde(default)]
+	size_in_bytes: SizeRange,
 	#[specta(optional)]
 	path: Option<String>,
 	#[specta(optional)]
 	object: Option<ObjectFilterArgs>,
 }
 
+impl FilePathFilterArgs {
+	/// Every filter but the directory one, as its materialized path is only known once it's checked
+	/// that the directory exists
+	fn into_params(
+		self,
+		directory_materialized_path: Option<String>,
+	) -> Vec<file_path::WhereParam> {
+		chain_optional_iter(
+			self.search
+				.split(' ')
+				.map(str::to_string)
+				.map(file_path::name::contains),
+			[
+				self.location_id.map(file_path::location_id::equals),
+				self.extension.map(file_path::extension::equals),
+				directory_materialized_path.map(file_path::materialized_path::equals),
+				self.object.and_then(|obj| {
+					let params = obj.into_params();
+
+					(!params.is_empty()).then(|| file_path::object::is(params))
+				}),
+			]
+			.into_iter()
+			.chain(self.created_at.into_params(
+				|v| file_path::date_created::gte(v.into()),
+				|v| file_path::date_created::lte(v.into()),
+			))
+			.chain(self.modified_at.into_params(
+				|v| file_path::date_modified::gte(v.into()),
+				|v| file_path::date_modified::lte(v.into()),
+			))
+			.chain(self.indexed_at.into_params(
+				|v| file_path::date_indexed::gte(v.into()),
+				|v| file_path::date_indexed::lte(v.into()),
+			))
+			.chain(OptionalRange::from(self.size_in_bytes).into_params(
+				|v| file_path::size_in_bytes::gte(size_to_db_int(v)),
+				|v| file_path::size_in_bytes::lte(size_to_db_int(v)),
+			)),
+		)
+	}
+}
+
 #[derive(Deserialize, Type, Debug)]
 #[serde(rename_all = "camelCase")]
 struct FilePathSearchArgs {
@@ -136,12 +214,18 @@ struct FilePathSearchArgs {
 #[serde(rename_all = "camelCase")]
 enum ObjectSearchOrdering {
 	DateAccessed(SortOrder),
+	DateCreated(SortOrder),
+	Kind(SortOrder),
+	MediaData(Box<MediaDataSearchOrdering>),
 }
 
 impl ObjectSearchOrdering {
 	fn get_sort_order(&self) -> prisma::SortOrder {
 		(*match self {
 			Self::DateAccessed(v) => v,
+			Self::DateCreated(v) => v,
+			Self::Kind(v) => v,
+			Self::MediaData(v) => return v.get_sort_order(),
 		})
 		.into()
 	}
@@ -151,6 +235,44 @@ impl ObjectSearchOrdering {
 		use object::*;
 		match self {
 			Self::DateAccessed(_) => date_accessed::order(dir),
+			Self::DateCreated(_) => date_created::order(dir),
+			Self::Kind(_) => kind::order(dir),
+			Self::MediaData(v) => media_data::order(vec![v.into_param()]),
+		}
+	}
+}
+
+#[derive(Deserialize, Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+enum MediaDataSearchOrdering {
+	PixelWidth(SortOrder),
+	PixelHeight(SortOrder),
+	DurationSeconds(SortOrder),
+	Latitude(SortOrder),
+	Longitude(SortOrder),
+}
+
+impl MediaDataSearchOrdering {
+	fn get_sort_order(&self) -> prisma::SortOrder {
+		(*match self {
+			Self::PixelWidth(v) => v,
+			Self::PixelHeight(v) => v,
+			Self::DurationSeconds(v) => v,
+			Self::Latitude(v) => v,
+			Self::Longitude(v) => v,
+		})
+		.into()
+	}
+
+	fn into_param(self) -> media_data::OrderByWithRelationParam {
+		let dir = self.get_sort_order();
+		use media_data::*;
+		match self {
+			Self::PixelWidth(_) => pixel_width::order(dir),
+			Self::PixelHeight(_) => pixel_height::order(dir),
+			Self::DurationSeconds(_) => duration_seconds::order(dir),
+			Self::Latitude(_) => latitude::order(dir),
+			Self::Longitude(_) => longitude::order(dir),
 		}
 	}
 }
@@ -185,6 +307,8 @@ struct ObjectFilterArgs {
 	kind: BTreeSet<i32>,
 	#[serde(default)]
 	tags: Vec<i32>,
+	#[specta(optional)]
+	media_data: Option<MediaDataFilterArgs>,
 }
 
 impl ObjectFilterArgs {
@@ -204,11 +328,65 @@ impl ObjectFilterArgs {
 
 					object::tags::some(vec![tags_on_object])
 				}),
+				self.media_data.and_then(|media_data| {
+					let params = media_data.into_params();
+
+					(!params.is_empty()).then(|| object::media_data::is(params))
+				}),
 			],
 		)
 	}
 }
 
+#[derive(Deserialize, Type, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct MediaDataFilterArgs {
+	#[serde(default)]
+	pixel_width: OptionalRange<i32>,
+	#[serde(default)]
+	pixel_height: OptionalRange<i32>,
+	#[serde(default)]
+	duration_seconds: OptionalRange<i32>,
+	#[serde(default)]
+	latitude: OptionalRange<f64>,
+	#[serde(default)]
+	longitude: OptionalRange<f64>,
+	#[specta(optional)]
+	capture_device_make: Option<String>,
+	#[specta(optional)]
+	capture_device_model: Option<String>,
+}
+
+impl MediaDataFilterArgs {
+	fn into_params(self) -> Vec<media_data::WhereParam> {
+		use media_data::*;
+
+		chain_optional_iter(
+			[],
+			[
+				self.capture_device_make.map(capture_device_make::contains),
+				self.capture_device_model
+					.map(capture_device_model::contains),
+			]
+			.into_iter()
+			.chain(
+				self.pixel_width
+					.into_params(pixel_width::gte, pixel_width::lte),
+			)
+			.chain(
+				self.pixel_height
+					.into_params(pixel_height::gte, pixel_height::lte),
+			)
+			.chain(
+				self.duration_seconds
+					.into_params(duration_seconds::gte, duration_seconds::lte),
+			)
+			.chain(self.latitude.into_params(latitude::gte, latitude::lte))
+			.chain(self.longitude.into_params(longitude::gte, longitude::lte)),
+		)
+	}
+}
+
 #[derive(Deserialize, Type, Debug)]
 #[serde(rename_all = "camelCase")]
 struct ObjectSearchArgs {
@@ -231,7 +409,7 @@ pub fn mount() -> AlphaRouter<Ctx> {
 				     take,
 				     order,
 				     cursor,
-				     filter,
+				     mut filter,
 				 }| async move {
 					let Library { db, .. } = &library;
 
@@ -246,7 +424,7 @@ pub fn mount() -> AlphaRouter<Ctx> {
 						None
 					};
 
-					let directory_materialized_path_str = match (filter.path, location) {
+					let directory_materialized_path_str = match (filter.path.take(), location) {
 						(Some(path), Some(location)) if !path.is_empty() && path != "/" => {
 							let parent_iso_file_path =
 								IsolatedFilePathData::from_relative_str(location.id, &path);
@@ -265,32 +443,7 @@ pub fn mount() -> AlphaRouter<Ctx> {
 						_ => None,
 					};
 
-					let params = chain_optional_iter(
-						filter
-							.search
-							.split(' ')
-							.map(str::to_string)
-							.map(file_path::name::contains),
-						[
-							filter.location_id.map(file_path::location_id::equals),
-							filter.extension.map(file_path::extension::equals),
-							filter
-								.created_at
-								.from
-								.map(|v| file_path::date_created::gte(v.into())),
-							filter
-								.created_at
-								.to
-								.map(|v| file_path::date_created::lte(v.into())),
-							directory_materialized_path_str
-								.map(file_path::materialized_path::equals),
-							filter.object.and_then(|obj| {
-								let params = obj.into_params();
-
-								(!params.is_empty()).then(|| file_path::object::is(params))
-							}),
-						],
-					);
+					let params = filter.into_params(directory_materialized_path_str);
 
 					let take = take.unwrap_or(100);
 
@@ -318,6 +471,8 @@ pub fn mount() -> AlphaRouter<Ctx> {
 						(paths, cursor)
 					};
 
+					let online_location_ids = online_location_ids(&library).await?;
+
 					let mut items = Vec::with_capacity(file_paths.len());
 
 					for file_path in file_paths {
@@ -332,7 +487,8 @@ pub fn mount() -> AlphaRouter<Ctx> {
 
 						items.push(ExplorerItem::Path {
 							has_thumbnail,
-							item: file_path,
+							online: online_location_ids.contains(&file_path.location_id),
+							item: file_path.into(),
 						})
 					}
 
@@ -380,6 +536,8 @@ pub fn mount() -> AlphaRouter<Ctx> {
 						(objects, cursor)
 					};
 
+					let online_location_ids = online_location_ids(&library).await?;
+
 					let mut items = Vec::with_capacity(objects.len());
 
 					for object in objects {
@@ -403,7 +561,11 @@ pub fn mount() -> AlphaRouter<Ctx> {
 
 						items.push(ExplorerItem::Object {
 							has_thumbnail,
-							item: object,
+							online: object
+								.file_paths
+								.iter()
+								.any(|fp| online_location_ids.contains(&fp.location_id)),
+							item: object.into(),
 						});
 					}
 
@@ -412,3 +574,303 @@ pub fn mount() -> AlphaRouter<Ctx> {
 			)
 		})
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		prisma::PrismaClient,
+		util::db::{create_test_location, test_db, uuid_to_bytes},
+	};
+
+	use chrono::TimeZone;
+	use sd_file_ext::kind::ObjectKind;
+	use serde_json::{json, Value};
+	use uuid::Uuid;
+
+	fn date(month: u32) -> DateTime<Utc> {
+		Utc.with_ymd_and_hms(2023, month, 1, 0, 0, 0).unwrap()
+	}
+
+	async fn create_file_path(
+		db: &PrismaClient,
+		location_id: i32,
+		inode: i64,
+		name: &str,
+		params: Vec<file_path::SetParam>,
+	) {
+		db.file_path()
+			.create_unchecked(
+				uuid_to_bytes(Uuid::new_v4()),
+				location_id,
+				"/".to_string(),
+				name.to_string(),
+				String::new(),
+				inode,
+				0,
+				params,
+			)
+			.exec()
+			.await
+			.unwrap();
+	}
+
+	async fn create_object(
+		db: &PrismaClient,
+		name: &str,
+		kind: ObjectKind,
+		date_created: DateTime<Utc>,
+		media_data: Vec<media_data::SetParam>,
+	) -> i32 {
+		let object = db
+			.object()
+			.create(
+				uuid_to_bytes(Uuid::new_v4()),
+				vec![
+					object::kind::set(kind as i32),
+					object::date_created::set(date_created.into()),
+					object::note::set(Some(name.to_string())),
+				],
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		db.media_data()
+			.create_unchecked(object.id, media_data)
+			.exec()
+			.await
+			.unwrap();
+
+		object.id
+	}
+
+	async fn search_paths(db: &PrismaClient, filter: Value, order: Value) -> Vec<String> {
+		let filter = serde_json::from_value::<FilePathFilterArgs>(filter).unwrap();
+		let order = serde_json::from_value::<FilePathSearchOrdering>(order).unwrap();
+
+		db.file_path()
+			.find_many(filter.into_params(None))
+			.order_by(order.into_param())
+			.exec()
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|file_path| file_path.name)
+			.collect()
+	}
+
+	async fn search_objects(db: &PrismaClient, filter: Value, order: Value) -> Vec<String> {
+		let filter = serde_json::from_value::<ObjectFilterArgs>(filter).unwrap();
+		let order = serde_json::from_value::<ObjectSearchOrdering>(order).unwrap();
+
+		db.object()
+			.find_many(filter.into_params())
+			.order_by(order.into_param())
+			.exec()
+			.await
+			.unwrap()
+			.into_iter()
+			.filter_map(|object| object.note)
+			.collect()
+	}
+
+	#[tokio::test]
+	async fn sizes_are_filtered_and_ordered_as_numbers() {
+		let (db, data_dir) = test_db().await;
+		let location = create_test_location(&db, data_dir.path()).await;
+
+		// As strings, "9" would be bigger than "10000"
+		for (inode, (name, size_in_bytes)) in [
+			("small", 9),
+			("medium", 10_000),
+			("huge", 5_000_000_000_u64),
+		]
+		.into_iter()
+		.enumerate()
+		{
+			create_file_path(
+				&db,
+				location.id,
+				inode as i64,
+				name,
+				vec![file_path::size_in_bytes::set(size_to_db_int(size_in_bytes))],
+			)
+			.await;
+		}
+
+		assert_eq!(
+			search_paths(&db, json!({}), json!({ "sizeInBytes": "Desc" })).await,
+			["huge", "medium", "small"]
+		);
+		assert_eq!(
+			search_paths(
+				&db,
+				json!({ "sizeInBytes": { "from": "10", "to": "5000000000" } }),
+				json!({ "sizeInBytes": "Asc" })
+			)
+			.await,
+			["medium", "huge"]
+		);
+		// Bigger than any size that can be stored, not a negative one
+		assert_eq!(
+			search_paths(
+				&db,
+				json!({ "sizeInBytes": { "to": u64::MAX.to_string() } }),
+				json!({ "sizeInBytes": "Asc" })
+			)
+			.await,
+			["small", "medium", "huge"]
+		);
+		assert_eq!(
+			search_paths(
+				&db,
+				json!({ "sizeInBytes": { "to": "9" } }),
+				json!({ "name": "Asc" })
+			)
+			.await,
+			["small"]
+		);
+	}
+
+	#[tokio::test]
+	async fn dates_are_filtered_and_ordered() {
+		let (db, data_dir) = test_db().await;
+		let location = create_test_location(&db, data_dir.path()).await;
+
+		for (inode, (name, created, modified)) in [
+			("january", date(1), date(6)),
+			("march", date(3), date(2)),
+			("february", date(2), date(4)),
+		]
+		.into_iter()
+		.enumerate()
+		{
+			create_file_path(
+				&db,
+				location.id,
+				inode as i64,
+				name,
+				vec![
+					file_path::date_created::set(created.into()),
+					file_path::date_modified::set(modified.into()),
+				],
+			)
+			.await;
+		}
+
+		assert_eq!(
+			search_paths(
+				&db,
+				json!({ "modifiedAt": { "from": date(3) } }),
+				json!({ "dateCreated": "Desc" })
+			)
+			.await,
+			["february", "january"]
+		);
+		assert_eq!(
+			search_paths(
+				&db,
+				json!({ "createdAt": { "from": date(2), "to": date(3) } }),
+				json!({ "dateModified": "Asc" })
+			)
+			.await,
+			["march", "february"]
+		);
+	}
+
+	#[tokio::test]
+	async fn media_data_is_filtered_and_objects_are_ordered() {
+		let (db, data_dir) = test_db().await;
+		let location = create_test_location(&db, data_dir.path()).await;
+
+		let photo = create_object(
+			&db,
+			"photo",
+			ObjectKind::Image,
+			date(3),
+			vec![
+				media_data::pixel_width::set(Some(4032)),
+				media_data::capture_device_make::set(Some("Apple".to_string())),
+				media_data::latitude::set(Some(38.7)),
+			],
+		)
+		.await;
+		let video = create_object(
+			&db,
+			"video",
+			ObjectKind::Video,
+			date(1),
+			vec![
+				media_data::pixel_width::set(Some(1920)),
+				media_data::duration_seconds::set(Some(60)),
+			],
+		)
+		.await;
+		create_object(&db, "document", ObjectKind::Document, date(2), vec![]).await;
+
+		create_file_path(
+			&db,
+			location.id,
+			0,
+			"photo",
+			vec![file_path::object_id::set(Some(photo))],
+		)
+		.await;
+		create_file_path(
+			&db,
+			location.id,
+			1,
+			"video",
+			vec![file_path::object_id::set(Some(video))],
+		)
+		.await;
+
+		assert_eq!(
+			search_objects(&db, json!({}), json!({ "kind": "Asc" })).await,
+			["document", "photo", "video"]
+		);
+		assert_eq!(
+			search_objects(&db, json!({}), json!({ "dateCreated": "Asc" })).await,
+			["video", "document", "photo"]
+		);
+		assert_eq!(
+			search_objects(
+				&db,
+				json!({ "mediaData": { "pixelWidth": { "from": 2000 } } }),
+				json!({ "kind": "Asc" })
+			)
+			.await,
+			["photo"]
+		);
+		assert_eq!(
+			search_objects(
+				&db,
+				json!({ "mediaData": { "durationSeconds": { "from": 30, "to": 90 } } }),
+				json!({ "kind": "Asc" })
+			)
+			.await,
+			["video"]
+		);
+		assert_eq!(
+			search_objects(
+				&db,
+				json!({ "mediaData": { "captureDeviceMake": "Apple", "latitude": { "to": 40.0 } } }),
+				json!({ "kind": "Asc" })
+			)
+			.await,
+			["photo"]
+		);
+
+		// Through the objects of the file paths
+		assert_eq!(
+			search_paths(
+				&db,
+				json!({ "object": { "mediaData": { "pixelWidth": { "to": 2000 } } } }),
+				json!({ "object": { "kind": "Desc" } })
+			)
+			.await,
+			["video"]
+		);
+	}
+}
@@ -1,6 +1,6 @@
 use rspc::alpha::AlphaRouter;
 
-use crate::sync::SyncMessage;
+use crate::sync::{GetOpsArgs, SyncMessage, OPS_PER_PAGE};
 
 use super::{utils::library, Ctx, R};
 
@@ -22,7 +22,19 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				})
 		})
 		.procedure("messages", {
-			R.with2(library())
-				.query(|(_, library), _: ()| async move { Ok(library.sync.get_ops().await?) })
+			R.with2(library()).query(|(_, library), _: ()| async move {
+				Ok(library
+					.sync
+					.get_ops(GetOpsArgs {
+						clocks: vec![],
+						count: OPS_PER_PAGE,
+					})
+					.await?)
+			})
+		})
+		.procedure("stats", {
+			R.with2(library()).query(|(_, library), _: ()| async move {
+				Ok(library.sync.stats(library.config.sync_retention()).await?)
+			})
 		})
 }
@@ -108,24 +108,61 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 
 			R.with2(library())
 				.mutation(|(_, library), args: TagAssignArgs| async move {
+					let Library { db, sync, .. } = &library;
+
+					let tag = db
+						.tag()
+						.find_unique(tag::id::equals(args.tag_id))
+						.select(tag::select!({ pub_id }))
+						.exec()
+						.await?
+						.ok_or(rspc::Error::new(
+							ErrorCode::NotFound,
+							"Error finding tag in db".into(),
+						))?;
+
+					let object = db
+						.object()
+						.find_unique(object::id::equals(args.object_id))
+						.select(object::select!({ pub_id }))
+						.exec()
+						.await?
+						.ok_or(rspc::Error::new(
+							ErrorCode::NotFound,
+							"Error finding object in db".into(),
+						))?;
+
+					let (item, group) = Uuid::from_slice(&object.pub_id)
+						.and_then(|item| Ok((item, Uuid::from_slice(&tag.pub_id)?)))
+						.map_err(|e| {
+							rspc::Error::with_cause(
+								ErrorCode::InternalServerError,
+								"Invalid pub id".into(),
+								e,
+							)
+						})?;
+
 					if args.unassign {
-						library
-							.db
-							.tag_on_object()
-							.delete(tag_on_object::tag_id_object_id(args.tag_id, args.object_id))
-							.exec()
-							.await?;
+						sync.write_op(
+							db,
+							sync.relation_delete::<tag_on_object::Types>(item, group),
+							db.tag_on_object().delete(tag_on_object::tag_id_object_id(
+								args.tag_id,
+								args.object_id,
+							)),
+						)
+						.await?;
 					} else {
-						library
-							.db
-							.tag_on_object()
-							.create(
+						sync.write_op(
+							db,
+							sync.relation_create::<tag_on_object::Types>(item, group),
+							db.tag_on_object().create(
 								tag::id::equals(args.tag_id),
 								object::id::equals(args.object_id),
 								vec![],
-							)
-							.exec()
-							.await?;
+							),
+						)
+						.await?;
 					}
 
 					invalidate_query!(library, "tags.getForObject");
@@ -192,12 +229,25 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 			"delete",
 			R.with2(library())
 				.mutation(|(_, library), tag_id: i32| async move {
-					library
-						.db
+					let Library { db, sync, .. } = &library;
+
+					let tag = db
 						.tag()
-						.delete(tag::id::equals(tag_id))
+						.find_unique(tag::id::equals(tag_id))
+						.select(tag::select!({ pub_id }))
 						.exec()
-						.await?;
+						.await?
+						.ok_or(rspc::Error::new(
+							ErrorCode::NotFound,
+							"Error finding tag in db".into(),
+						))?;
+
+					sync.write_op(
+						db,
+						sync.shared_delete(sync::tag::SyncId { pub_id: tag.pub_id }),
+						db.tag().delete(tag::id::equals(tag_id)),
+					)
+					.await?;
 
 					invalidate_query!(library, "tags.list");
 
@@ -2,10 +2,23 @@ use rspc::alpha::AlphaRouter;
 
 use crate::volume::get_volumes;
 
-use super::{Ctx, R};
+use super::{CoreEvent, Ctx, R};
 
 pub(crate) fn mount() -> AlphaRouter<Ctx> {
-	R.router().procedure("list", {
-		R.query(|_, _: ()| async move { Ok(get_volumes()?) })
-	})
+	R.router()
+		.procedure("list", {
+			R.query(|_, _: ()| async move { Ok(get_volumes()?) })
+		})
+		.procedure("events", {
+			R.subscription(|ctx, _: ()| async move {
+				let mut event_bus_rx = ctx.event_bus.0.subscribe();
+				async_stream::stream! {
+					while let Ok(event) = event_bus_rx.recv().await {
+						if let CoreEvent::Volume(event) = event {
+							yield event
+						}
+					}
+				}
+			})
+		})
 }
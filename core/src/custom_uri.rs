@@ -1,5 +1,9 @@
 use crate::{
-	location::file_path_helper::{file_path_to_handle_custom_uri, IsolatedFilePathData},
+	location::{
+		check_location_is_online,
+		file_path_helper::{file_path_to_handle_custom_uri, IsolatedFilePathData},
+		LocationError,
+	},
 	prisma::file_path,
 	util::error::FileIOError,
 	Node,
@@ -201,13 +205,26 @@ async fn handle_file(
 			lru_entry
 		};
 
-	let file = File::open(&file_path_full_path).await.map_err(|err| {
-		if err.kind() == io::ErrorKind::NotFound {
-			HandleCustomUriError::NotFound("file")
-		} else {
-			FileIOError::from((&file_path_full_path, err)).into()
+	let file = match File::open(&file_path_full_path).await {
+		Ok(file) => file,
+		Err(err) => {
+			// The file is still indexed when its location's drive is unplugged, so the request is
+			// valid, it just can't be served right now
+			if let Some(library) = node.library_manager.get_library(library_id).await {
+				if let Err(LocationError::Offline(_)) =
+					check_location_is_online(&library, location_id).await
+				{
+					return Err(HandleCustomUriError::LocationOffline);
+				}
+			}
+
+			return Err(if err.kind() == io::ErrorKind::NotFound {
+				HandleCustomUriError::NotFound("file")
+			} else {
+				FileIOError::from((&file_path_full_path, err)).into()
+			});
 		}
-	})?;
+	};
 
 	// TODO: This should be determined from magic bytes when the file is indexed and stored it in the DB on the file path
 	// https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_types/Common_types
@@ -391,6 +408,8 @@ pub enum HandleCustomUriError {
 	RangeNotSatisfiable(&'static str),
 	#[error("resource '{0}' not found")]
 	NotFound(&'static str),
+	#[error("the file's location is offline")]
+	LocationOffline,
 }
 
 impl From<HandleCustomUriError> for Response<Vec<u8>> {
@@ -433,6 +452,9 @@ impl From<HandleCustomUriError> for Response<Vec<u8>> {
 					.as_bytes()
 					.to_vec(),
 			),
+			HandleCustomUriError::LocationOffline => builder
+				.status(StatusCode::SERVICE_UNAVAILABLE)
+				.body(b"The file's location is offline".to_vec()),
 		})
 		// SAFETY: This unwrap is ok as we have an hardcoded the response builders.
 		.expect("internal error building hardcoded HTTP error response")
@@ -76,6 +76,8 @@ pub enum JobError {
 	MissingFromDb(&'static str, String),
 	#[error("the cas id is not set on the path data")]
 	MissingCasId,
+	#[error("location is offline, its drive may have been unplugged <id='{0}'>")]
+	LocationOffline(i32),
 
 	// Not errors
 	#[error("step completed with errors")]
@@ -6,7 +6,7 @@ use crate::{
 	library::LibraryManager,
 	location::{LocationManager, LocationManagerError},
 	node::NodeConfigManager,
-	p2p::P2PManager,
+	p2p::{P2PManager, P2PSyncEvent},
 };
 
 pub use sd_prisma::*;
@@ -37,13 +37,16 @@ pub(crate) mod sync;
 pub(crate) mod util;
 pub(crate) mod volume;
 
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub use location::indexer::bench as indexer_bench;
+
 #[derive(Clone)]
 pub struct NodeContext {
 	pub config: Arc<NodeConfigManager>,
 	pub jobs: Arc<JobManager>,
 	pub location_manager: Arc<LocationManager>,
 	pub event_bus_tx: broadcast::Sender<CoreEvent>,
-	pub p2p: Arc<P2PManager>,
 }
 
 pub struct Node {
@@ -82,9 +85,9 @@ impl Node {
 				config: config.clone(),
 				jobs: jobs.clone(),
 				location_manager: location_manager.clone(),
-				p2p: p2p.clone(),
 				event_bus_tx: event_bus.0.clone(),
 			},
+			p2p.clone(),
 		)
 		.await?;
 
@@ -95,28 +98,78 @@ impl Node {
 
 		tokio::spawn({
 			let library_manager = library_manager.clone();
+			let p2p = p2p.clone();
 
 			async move {
-				while let Ok((library_id, operations)) = p2p_rx.recv().await {
-					debug!("going to ingest {} operations", operations.len());
-
-					let Some(library) = library_manager.get_library(library_id).await else {
-						warn!("no library found!");
-						continue;
-					};
-
-					for op in operations {
-						library.sync.ingest_op(op).await.unwrap_or_else(|err| {
-							error!(
-								"error ingesting operation for library '{}': {err:?}",
-								library.id
-							);
-						});
+				while let Some(event) = p2p_rx.recv().await {
+					match event {
+						P2PSyncEvent::Operations {
+							library_id,
+							operations,
+						} => {
+							debug!("going to ingest {} operations", operations.len());
+
+							let Some(library) = library_manager.get_library(library_id).await
+							else {
+								warn!("no library found!");
+								continue;
+							};
+
+							for op in operations {
+								library.sync.ingest_op(op).await.unwrap_or_else(|err| {
+									error!(
+										"error ingesting operation for library '{}': {err:?}",
+										library.id
+									);
+								});
+							}
+						}
+						P2PSyncEvent::CatchUp {
+							library_id,
+							node,
+							args,
+							stream,
+						} => {
+							let library = library_manager.get_library(library_id).await;
+
+							tokio::spawn(async move {
+								let sync = library.as_ref().map(|library| library.sync.as_ref());
+
+								if let Err(err) =
+									P2PManager::serve_catch_up(stream, node, args, sync).await
+								{
+									error!("error serving catch up for library '{library_id}': {err:?}");
+								}
+							});
+						}
+						P2PSyncEvent::PeerConnected(peer_id) => {
+							for library in library_manager.get_all_libraries().await {
+								let p2p = p2p.clone();
+
+								tokio::spawn(async move {
+									if let Err(err) =
+										p2p.catch_up(peer_id, library.id, &library.sync).await
+									{
+										error!(
+											"error catching up with peer '{peer_id}' for library '{}': {err:?}",
+											library.id
+										);
+									}
+								});
+							}
+						}
 					}
 				}
 			}
 		});
 
+		tokio::spawn(volume::monitor_volumes(
+			library_manager.clone(),
+			event_bus.0.clone(),
+		));
+
+		tokio::spawn(sync::compact_operations(library_manager.clone()));
+
 		let router = api::mount();
 		let node = Node {
 			data_dir: data_dir.to_path_buf(),
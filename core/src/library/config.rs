@@ -1,4 +1,4 @@
-use std::{marker::PhantomData, path::PathBuf};
+use std::{marker::PhantomData, path::PathBuf, time::Duration};
 
 use serde::{Deserialize, Serialize};
 use specta::Type;
@@ -14,6 +14,9 @@ const MIGRATOR: FileMigrator<LibraryConfig> = FileMigrator {
 	phantom: PhantomData,
 };
 
+/// Days operations are kept in the sync log by default, once every node has them
+const DEFAULT_SYNC_RETENTION_DAYS: u32 = 7;
+
 /// LibraryConfig holds the configuration for a specific library. This is stored as a '{uuid}.sdlibrary' file.
 #[derive(Debug, Serialize, Deserialize, Clone, Type, Default)]
 pub struct LibraryConfig {
@@ -21,6 +24,9 @@ pub struct LibraryConfig {
 	pub name: String,
 	/// description is a user set description of the library. This is used in the UI and is set by the user.
 	pub description: String,
+	/// sync_retention_days is how many days operations are kept in the sync log once every node has them, before they're compacted.
+	#[serde(default)]
+	pub sync_retention_days: Option<u32>,
 	// /// is_encrypted is a flag that is set to true if the library is encrypted.
 	// #[serde(default)]
 	// pub is_encrypted: bool,
@@ -32,6 +38,15 @@ impl LibraryConfig {
 		MIGRATOR.load(&file_dir).map_err(Into::into)
 	}
 
+	/// sync_retention is how long operations are kept in the sync log once every node has them.
+	pub fn sync_retention(&self) -> Duration {
+		let days = self
+			.sync_retention_days
+			.unwrap_or(DEFAULT_SYNC_RETENTION_DAYS);
+
+		Duration::from_secs(u64::from(days) * 24 * 60 * 60)
+	}
+
 	/// save will write the configuration back to disk
 	pub(super) fn save(
 		file_dir: PathBuf,
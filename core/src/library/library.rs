@@ -3,6 +3,7 @@ use crate::{
 	job::{IntoJob, JobInitData, JobManagerError, StatefulJob},
 	location::{
 		file_path_helper::{file_path_to_full_path, IsolatedFilePathData},
+		size_tree::SizeTreeCache,
 		LocationManager,
 	},
 	node::NodeConfigManager,
@@ -45,6 +46,8 @@ pub struct Library {
 	/// node_context holds the node context for the node which this library is running on.
 	pub(super) node_context: NodeContext,
 	pub orphan_remover: OrphanRemoverActor,
+	/// cached size trees of this library's locations, see `locations.sizeTree`
+	pub size_tree_cache: SizeTreeCache,
 }
 
 impl Debug for Library {
@@ -82,6 +85,12 @@ impl Library {
 		}
 	}
 
+	/// The events emitted by this library from now on, for tests that expect one
+	#[cfg(test)]
+	pub(crate) fn subscribe(&self) -> tokio::sync::broadcast::Receiver<CoreEvent> {
+		self.node_context.event_bus_tx.subscribe()
+	}
+
 	pub(crate) fn config(&self) -> Arc<NodeConfigManager> {
 		self.node_context.config.clone()
 	}
@@ -118,3 +127,58 @@ impl Library {
 			}))
 	}
 }
+
+/// A library with its own database and node, for tests that need one. It has none of the node's
+/// P2P, so the sync operations it creates go nowhere
+#[cfg(test)]
+pub(crate) async fn test_library() -> (Library, tempfile::TempDir) {
+	use crate::{job::JobManager, util::db::test_db};
+
+	use tokio::sync::broadcast;
+
+	let (db, data_dir) = test_db().await;
+	let db = Arc::new(db);
+
+	let config = NodeConfigManager::new(data_dir.path().to_path_buf())
+		.await
+		.expect("failed to create the test node config");
+	let node_config = config.get().await;
+
+	let node = db
+		.node()
+		.create(
+			node_config.id.as_bytes().to_vec(),
+			node_config.name.clone(),
+			vec![],
+		)
+		.exec()
+		.await
+		.expect("failed to create the test node");
+
+	let id = Uuid::new_v4();
+	let (sync, _) = SyncManager::new(&db, id, node_config.id);
+
+	let library = Library {
+		id,
+		local_id: node.id,
+		config: Default::default(),
+		sync: Arc::new(sync),
+		key_manager: Arc::new(
+			KeyManager::new(vec![])
+				.await
+				.expect("failed to create the test key manager"),
+		),
+		orphan_remover: OrphanRemoverActor::spawn(db.clone()),
+		size_tree_cache: Default::default(),
+		db,
+		node_local_id: node.id,
+		node_context: NodeContext {
+			config,
+			jobs: JobManager::new(),
+			location_manager: LocationManager::new(),
+			event_bus_tx: broadcast::channel(1024).0,
+		},
+	};
+
+	(library, data_dir)
+}
@@ -1,9 +1,14 @@
 use crate::{
 	invalidate_query,
 	location::LocationManagerError,
+	migrations,
 	node::Platform,
 	object::orphan_remover::OrphanRemoverActor,
-	prisma::{location, node, PrismaClient},
+	p2p::P2PManager,
+	prisma::{
+		job, location, node, owned_operation, relation_operation, shared_operation, volume,
+		PrismaClient,
+	},
 	sync::{SyncManager, SyncMessage},
 	util::{
 		db::{load_and_migrate, MigrationError},
@@ -27,7 +32,11 @@ use std::{
 };
 
 use thiserror::Error;
-use tokio::{fs, io, sync::RwLock, try_join};
+use tokio::{
+	fs, io,
+	sync::{broadcast, RwLock},
+	try_join,
+};
 use tracing::{debug, error, warn};
 use uuid::Uuid;
 
@@ -41,6 +50,8 @@ pub struct LibraryManager {
 	libraries: RwLock<Vec<Library>>,
 	/// node_context holds the context for the node which this library manager is running on.
 	node_context: NodeContext,
+	/// p2p broadcasts the sync operations created in the libraries to the other nodes.
+	p2p: Arc<P2PManager>,
 }
 
 #[derive(Error, Debug)]
@@ -135,10 +146,60 @@ pub async fn seed_keymanager(
 	Ok(())
 }
 
+/// Libraries used to sync as their own id on every node, and link what's local to that node.
+/// What's local is moved to the node's own id, and so are the operations logged until then, as
+/// no node is ever caught up with the library's id, so they'd never be sent to other nodes
+async fn migrate_library_node(
+	db: &PrismaClient,
+	library_id: Uuid,
+	node_local_id: i32,
+) -> Result<(), LibraryManagerError> {
+	let legacy = match db
+		.node()
+		.find_unique(node::pub_id::equals(library_id.as_bytes().to_vec()))
+		.exec()
+		.await?
+	{
+		Some(legacy) => legacy,
+		None => return Ok(()),
+	};
+
+	db._batch((
+		db.location().update_many(
+			vec![location::node_id::equals(legacy.id)],
+			vec![location::node_id::set(node_local_id)],
+		),
+		db.volume().update_many(
+			vec![volume::node_id::equals(legacy.id)],
+			vec![volume::node_id::set(node_local_id)],
+		),
+		db.job().update_many(
+			vec![job::node_id::equals(legacy.id)],
+			vec![job::node_id::set(node_local_id)],
+		),
+		db.shared_operation().update_many(
+			vec![shared_operation::node_id::equals(legacy.id)],
+			vec![shared_operation::node_id::set(node_local_id)],
+		),
+		db.relation_operation().update_many(
+			vec![relation_operation::node_id::equals(legacy.id)],
+			vec![relation_operation::node_id::set(node_local_id)],
+		),
+		db.owned_operation().update_many(
+			vec![owned_operation::node_id::equals(legacy.id)],
+			vec![owned_operation::node_id::set(node_local_id)],
+		),
+	))
+	.await?;
+
+	Ok(())
+}
+
 impl LibraryManager {
 	pub(crate) async fn new(
 		libraries_dir: PathBuf,
 		node_context: NodeContext,
+		p2p: Arc<P2PManager>,
 	) -> Result<Arc<Self>, LibraryManagerError> {
 		fs::create_dir_all(&libraries_dir)
 			.await
@@ -187,8 +248,16 @@ impl LibraryManager {
 				}
 
 				let config = LibraryConfig::read(entry_path)?;
-				libraries
-					.push(Self::load(library_id, &db_path, config, node_context.clone()).await?);
+				libraries.push(
+					Self::load(
+						library_id,
+						&db_path,
+						config,
+						node_context.clone(),
+						p2p.clone(),
+					)
+					.await?,
+				);
 			}
 		}
 
@@ -196,6 +265,7 @@ impl LibraryManager {
 			libraries: RwLock::new(libraries),
 			libraries_dir,
 			node_context,
+			p2p,
 		});
 
 		debug!("LibraryManager initialized");
@@ -232,6 +302,7 @@ impl LibraryManager {
 			self.libraries_dir.join(format!("{id}.db")),
 			config.clone(),
 			self.node_context.clone(),
+			self.p2p.clone(),
 		)
 		.await?;
 
@@ -256,15 +327,16 @@ impl LibraryManager {
 			.collect()
 	}
 
-	// pub(crate) async fn get_all_libraries(&self) -> Vec<Library> {
-	// 	self.libraries.read().await.clone()
-	// }
+	pub(crate) async fn get_all_libraries(&self) -> Vec<Library> {
+		self.libraries.read().await.clone()
+	}
 
 	pub(crate) async fn edit(
 		&self,
 		id: Uuid,
 		name: Option<String>,
 		description: Option<String>,
+		sync_retention_days: Option<u32>,
 	) -> Result<(), LibraryManagerError> {
 		// check library is valid
 		let mut libraries = self.libraries.write().await;
@@ -280,6 +352,9 @@ impl LibraryManager {
 		if let Some(description) = description {
 			library.config.description = description;
 		}
+		if let Some(sync_retention_days) = sync_retention_days {
+			library.config.sync_retention_days = Some(sync_retention_days);
+		}
 
 		LibraryConfig::save(
 			Path::new(&self.libraries_dir).join(format!("{id}.sdlibrary")),
@@ -363,7 +438,30 @@ impl LibraryManager {
 		db_path: impl AsRef<Path>,
 		config: LibraryConfig,
 		node_context: NodeContext,
+		p2p: Arc<P2PManager>,
 	) -> Result<Library, LibraryManagerError> {
+		let (library, mut sync_rx) =
+			Self::load_without_p2p(id, db_path, config, node_context).await?;
+
+		tokio::spawn(async move {
+			while let Ok(op) = sync_rx.recv().await {
+				let SyncMessage::Created(op) = op else { continue; };
+
+				p2p.broadcast_sync_events(id, vec![op]).await;
+			}
+		});
+
+		Ok(library)
+	}
+
+	/// Loads the library like [`Self::load`], but hands the sync operations it creates to the
+	/// caller instead of broadcasting them to the other nodes
+	pub(crate) async fn load_without_p2p(
+		id: Uuid,
+		db_path: impl AsRef<Path>,
+		config: LibraryConfig,
+		node_context: NodeContext,
+	) -> Result<(Library, broadcast::Receiver<SyncMessage>), LibraryManagerError> {
 		let db_path = db_path.as_ref();
 		let db = Arc::new(
 			load_and_migrate(&format!(
@@ -375,6 +473,8 @@ impl LibraryManager {
 			.await?,
 		);
 
+		migrations::migration_library_db(&db).await?;
+
 		let node_config = node_context.config.get().await;
 
 		let platform = match env::consts::OS {
@@ -384,7 +484,15 @@ impl LibraryManager {
 			_ => Platform::Unknown,
 		};
 
-		let uuid_vec = id.as_bytes().to_vec();
+		let uuid_vec = node_config.id.as_bytes().to_vec();
+
+		let existed = db
+			.node()
+			.find_unique(node::pub_id::equals(uuid_vec.clone()))
+			.exec()
+			.await?
+			.is_some();
+
 		let node_data = db
 			.node()
 			.upsert(
@@ -399,22 +507,14 @@ impl LibraryManager {
 			.exec()
 			.await?;
 
+		if !existed {
+			migrate_library_node(&db, id, node_data.id).await?;
+		}
+
 		let key_manager = Arc::new(KeyManager::new(vec![]).await?);
 		seed_keymanager(&db, &key_manager).await?;
 
-		let (sync_manager, mut sync_rx) = SyncManager::new(&db, id);
-
-		tokio::spawn({
-			let node_context = node_context.clone();
-
-			async move {
-				while let Ok(op) = sync_rx.recv().await {
-					let SyncMessage::Created(op) = op else { continue; };
-
-					node_context.p2p.broadcast_sync_events(id, vec![op]).await;
-				}
-			}
-		});
+		let (sync_manager, sync_rx) = SyncManager::new(&db, id, node_config.id);
 
 		let library = Library {
 			id,
@@ -423,6 +523,7 @@ impl LibraryManager {
 			key_manager,
 			sync: Arc::new(sync_manager),
 			orphan_remover: OrphanRemoverActor::spawn(db.clone()),
+			size_tree_cache: Default::default(),
 			db,
 			node_local_id: node_data.id,
 			node_context,
@@ -452,6 +553,6 @@ impl LibraryManager {
 			error!("Failed to resume jobs for library. {:#?}", e);
 		}
 
-		Ok(library)
+		Ok((library, sync_rx))
 	}
 }
@@ -3,8 +3,10 @@ mod config;
 #[allow(clippy::module_inception)]
 mod library;
 mod manager;
+mod statistics;
 
 pub use cat::*;
 pub use config::*;
 pub use library::*;
 pub use manager::*;
+pub use statistics::*;
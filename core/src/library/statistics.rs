@@ -0,0 +1,397 @@
+use crate::{
+	location::LocationId,
+	prisma::{kind_statistics, location, location_statistics, object, volume, PrismaClient},
+};
+
+use std::{collections::HashMap, ops::AddAssign};
+
+use prisma_client_rust::{raw, QueryError};
+use serde::Deserialize;
+
+/// Each library is a database so only one row of `Statistics` ever exists
+pub const LIBRARY_STATISTICS_ID: i32 = 1;
+
+/// Change in the amount and total size of the non directory file paths of a location
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FilePathsStatisticsDelta {
+	pub count: i32,
+	pub bytes: i64,
+}
+
+impl FilePathsStatisticsDelta {
+	pub fn added(size_in_bytes: i64) -> Self {
+		Self {
+			count: 1,
+			bytes: size_in_bytes,
+		}
+	}
+
+	pub fn removed(size_in_bytes: i64) -> Self {
+		Self {
+			count: -1,
+			bytes: -size_in_bytes,
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.count == 0 && self.bytes == 0
+	}
+}
+
+impl AddAssign for FilePathsStatisticsDelta {
+	fn add_assign(&mut self, rhs: Self) {
+		self.count += rhs.count;
+		self.bytes += rhs.bytes;
+	}
+}
+
+impl FromIterator<FilePathsStatisticsDelta> for FilePathsStatisticsDelta {
+	fn from_iter<I: IntoIterator<Item = FilePathsStatisticsDelta>>(iter: I) -> Self {
+		iter.into_iter().fold(Self::default(), |mut acc, delta| {
+			acc += delta;
+			acc
+		})
+	}
+}
+
+pub async fn update_location_statistics(
+	db: &PrismaClient,
+	location_id: LocationId,
+	delta: FilePathsStatisticsDelta,
+) -> Result<(), QueryError> {
+	if delta.is_empty() {
+		return Ok(());
+	}
+
+	db.location_statistics()
+		.upsert(
+			location_statistics::location_id::equals(location_id),
+			location_statistics::create(
+				location::id::equals(location_id),
+				vec![
+					location_statistics::file_path_count::set(delta.count),
+					location_statistics::total_bytes::set(delta.bytes),
+				],
+			),
+			vec![
+				location_statistics::file_path_count::increment(delta.count),
+				location_statistics::total_bytes::increment(delta.bytes),
+			],
+		)
+		.exec()
+		.await
+		.map(|_| ())
+}
+
+/// Changes the statistics of an object kind, `bytes` must account for each object only once
+pub async fn update_kind_statistics(
+	db: &PrismaClient,
+	kind: i32,
+	object_count: i32,
+	bytes: i64,
+) -> Result<(), QueryError> {
+	if object_count == 0 && bytes == 0 {
+		return Ok(());
+	}
+
+	use kind_statistics::*;
+
+	db.kind_statistics()
+		.upsert(
+			kind::equals(kind),
+			create(
+				kind,
+				vec![object_count::set(object_count), total_bytes::set(bytes)],
+			),
+			vec![
+				object_count::increment(object_count),
+				total_bytes::increment(bytes),
+			],
+		)
+		.exec()
+		.await
+		.map(|_| ())
+}
+
+/// Same as [`update_kind_statistics`] for many objects at once, as `(kind, object_count, bytes)`
+/// changes that are summed up by kind first
+pub async fn update_kinds_statistics(
+	db: &PrismaClient,
+	changes: impl IntoIterator<Item = (i32, i32, i64)>,
+) -> Result<(), QueryError> {
+	let mut deltas = HashMap::<_, (i32, i64)>::new();
+	for (kind, object_count, bytes) in changes {
+		let delta = deltas.entry(kind).or_default();
+		delta.0 += object_count;
+		delta.1 += bytes;
+	}
+
+	for (kind, (object_count, bytes)) in deltas {
+		update_kind_statistics(db, kind, object_count, bytes).await?;
+	}
+
+	Ok(())
+}
+
+/// Takes the objects left without any file path out of the statistics of their kinds, given the
+/// `(object_id, size_in_bytes)` of the file paths that were just removed. Orphan objects aren't
+/// counted anymore, even before the orphan remover deletes them
+pub async fn remove_orphans_from_kind_statistics(
+	db: &PrismaClient,
+	removed_file_paths: impl IntoIterator<Item = (i32, i64)>,
+) -> Result<(), QueryError> {
+	let mut sizes = HashMap::new();
+	for (object_id, size_in_bytes) in removed_file_paths {
+		let size = sizes.entry(object_id).or_insert(size_in_bytes);
+		*size = (*size).max(size_in_bytes);
+	}
+
+	if sizes.is_empty() {
+		return Ok(());
+	}
+
+	let orphans = db
+		.object()
+		.find_many(vec![
+			object::id::in_vec(sizes.keys().copied().collect()),
+			// https://www.prisma.io/docs/reference/api-reference/prisma-client-reference#none
+			object::file_paths::none(vec![]),
+		])
+		.select(object::select!({ id kind }))
+		.exec()
+		.await?;
+
+	update_kinds_statistics(
+		db,
+		orphans
+			.into_iter()
+			.map(|object| (object.kind, -1, -sizes[&object.id])),
+	)
+	.await
+}
+
+/// Total and available bytes of the volumes mounted on a node
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VolumesCapacity {
+	pub total: u64,
+	pub available: u64,
+}
+
+/// Sums the capacities of the volumes mounted on a node, as last saved by the volume monitor
+pub async fn volumes_capacity(
+	db: &PrismaClient,
+	node_id: i32,
+) -> Result<VolumesCapacity, QueryError> {
+	Ok(db
+		.volume()
+		.find_many(vec![
+			volume::node_id::equals(node_id),
+			volume::is_mounted::equals(true),
+		])
+		.select(volume::select!({ total_bytes_capacity total_bytes_available }))
+		.exec()
+		.await?
+		.into_iter()
+		.fold(VolumesCapacity::default(), |mut capacity, volume| {
+			capacity.total += volume.total_bytes_capacity.parse::<u64>().unwrap_or(0);
+			capacity.available += volume.total_bytes_available.parse::<u64>().unwrap_or(0);
+			capacity
+		}))
+}
+
+/// Recomputes the statistics of every location from scratch
+pub async fn refresh_location_statistics(db: &PrismaClient) -> Result<(), QueryError> {
+	#[derive(Deserialize)]
+	struct LocationRow {
+		location_id: LocationId,
+		file_path_count: i32,
+		total_bytes: i64,
+	}
+
+	let rows = db
+		._query_raw::<LocationRow>(raw!(
+			"SELECT location_id, COUNT(*) AS file_path_count, \
+				COALESCE(SUM(CASE WHEN is_hardlink THEN 0 ELSE size_in_bytes END), 0) AS total_bytes \
+				FROM file_path WHERE NOT is_dir GROUP BY location_id"
+		))
+		.exec()
+		.await?;
+
+	db._batch((
+		db.location_statistics().delete_many(vec![]),
+		db.location_statistics().create_many(
+			rows.into_iter()
+				.map(|row| {
+					location_statistics::create_unchecked(
+						row.location_id,
+						vec![
+							location_statistics::file_path_count::set(row.file_path_count),
+							location_statistics::total_bytes::set(row.total_bytes),
+						],
+					)
+				})
+				.collect(),
+		),
+	))
+	.await
+	.map(|_| ())
+}
+
+/// Recomputes the statistics of every object kind from scratch, only counting the objects that
+/// still have file paths, like the incremental updates do
+pub async fn refresh_object_statistics(db: &PrismaClient) -> Result<(), QueryError> {
+	#[derive(Deserialize)]
+	struct KindRow {
+		kind: i32,
+		object_count: i32,
+		total_bytes: i64,
+	}
+
+	// An object's size is the size of any of its file paths, we take the biggest one
+	// in case some of them weren't updated yet
+	let rows = db
+		._query_raw::<KindRow>(raw!(
+			"SELECT object.kind AS kind, COUNT(*) AS object_count, \
+				COALESCE(SUM(object_size.size_in_bytes), 0) AS total_bytes \
+				FROM object INNER JOIN ( \
+					SELECT object_id, MAX(size_in_bytes) AS size_in_bytes FROM file_path \
+					WHERE object_id IS NOT NULL GROUP BY object_id \
+				) AS object_size ON object_size.object_id = object.id \
+				GROUP BY object.kind"
+		))
+		.exec()
+		.await?;
+
+	db._batch((
+		db.kind_statistics().delete_many(vec![]),
+		db.kind_statistics().create_many(
+			rows.into_iter()
+				.map(|row| {
+					kind_statistics::create_unchecked(
+						row.kind,
+						vec![
+							kind_statistics::object_count::set(row.object_count),
+							kind_statistics::total_bytes::set(row.total_bytes),
+						],
+					)
+				})
+				.collect(),
+		),
+	))
+	.await
+	.map(|_| ())
+}
+
+/// Recomputes all cached statistics, used by the library database migration that introduced them,
+/// as libraries indexed before didn't keep track of them
+pub async fn refresh_library_statistics(db: &PrismaClient) -> Result<(), QueryError> {
+	refresh_location_statistics(db).await?;
+	refresh_object_statistics(db).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		prisma::file_path,
+		util::db::{create_test_location, test_db, uuid_to_bytes},
+	};
+
+	use uuid::Uuid;
+
+	#[tokio::test]
+	async fn test_volumes_capacity() {
+		let (db, _data_dir) = test_db().await;
+
+		for (node_id, mount_point, total, available, is_mounted) in [
+			(1, "/", 1000, 400, true),
+			(1, "/mnt/backup", 500, 100, true),
+			// Only kept because it holds locations
+			(1, "/mnt/usb", 64, 64, false),
+			// Another node's
+			(2, "/", 2000, 2000, true),
+		] {
+			db.volume()
+				.create(
+					node_id,
+					"Volume".to_string(),
+					mount_point.to_string(),
+					vec![
+						volume::total_bytes_capacity::set(u64::to_string(&total)),
+						volume::total_bytes_available::set(u64::to_string(&available)),
+						volume::is_mounted::set(is_mounted),
+					],
+				)
+				.exec()
+				.await
+				.unwrap();
+		}
+
+		assert_eq!(
+			volumes_capacity(&db, 1).await.unwrap(),
+			VolumesCapacity {
+				total: 1500,
+				available: 500,
+			}
+		);
+	}
+
+	#[tokio::test]
+	async fn test_orphans_are_removed_from_kind_statistics() {
+		let (db, data_dir) = test_db().await;
+		let location = create_test_location(&db, data_dir.path()).await;
+
+		let object = db
+			.object()
+			.create(uuid_to_bytes(Uuid::new_v4()), vec![object::kind::set(5)])
+			.exec()
+			.await
+			.unwrap();
+		update_kind_statistics(&db, 5, 1, 10).await.unwrap();
+
+		let mut file_paths = vec![];
+		for name in ["original", "copy"] {
+			file_paths.push(
+				db.file_path()
+					.create_unchecked(
+						uuid_to_bytes(Uuid::new_v4()),
+						location.id,
+						"/".to_string(),
+						name.to_string(),
+						"txt".to_string(),
+						file_paths.len() as i64,
+						0,
+						vec![
+							file_path::size_in_bytes::set(10),
+							file_path::object_id::set(Some(object.id)),
+						],
+					)
+					.exec()
+					.await
+					.unwrap(),
+			);
+		}
+
+		let statistics_of_kind = || async {
+			db.kind_statistics()
+				.find_unique(kind_statistics::kind::equals(5))
+				.exec()
+				.await
+				.unwrap()
+				.map(|statistics| (statistics.object_count, statistics.total_bytes))
+		};
+
+		for (file_path, expected) in file_paths.iter().zip([(1, 10), (0, 0)]) {
+			db.file_path()
+				.delete(file_path::id::equals(file_path.id))
+				.exec()
+				.await
+				.unwrap();
+			remove_orphans_from_kind_statistics(&db, [(object.id, file_path.size_in_bytes)])
+				.await
+				.unwrap();
+
+			assert_eq!(statistics_of_kind().await, Some(expected));
+		}
+	}
+}
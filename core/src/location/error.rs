@@ -1,4 +1,4 @@
-use crate::util::error::FileIOError;
+use crate::{job::JobManagerError, util::error::FileIOError};
 
 use std::path::PathBuf;
 
@@ -7,7 +7,8 @@ use thiserror::Error;
 use uuid::Uuid;
 
 use super::{
-	file_path_helper::FilePathError, manager::LocationManagerError, metadata::LocationMetadataError,
+	file_path_helper::FilePathError, indexer::IndexerError, manager::LocationManagerError,
+	metadata::LocationMetadataError,
 };
 
 /// Error type for location related errors
@@ -46,6 +47,8 @@ pub enum LocationError {
 	LocationAlreadyExists(PathBuf),
 	#[error("nested location currently not supported <path='{}'>", .0.display())]
 	NestedLocation(PathBuf),
+	#[error("location is offline, its drive may have been unplugged <id='{0}'>")]
+	Offline(i32),
 
 	// Internal Errors
 	#[error(transparent)]
@@ -66,6 +69,10 @@ pub enum LocationError {
 	FilePathError(#[from] FilePathError),
 	#[error(transparent)]
 	FileIO(#[from] FileIOError),
+	#[error(transparent)]
+	JobManager(#[from] JobManagerError),
+	#[error(transparent)]
+	Indexer(#[from] IndexerError),
 }
 
 impl From<LocationError> for rspc::Error {
@@ -81,7 +88,8 @@ impl From<LocationError> for rspc::Error {
 			// User's fault errors
 			LocationError::NotDirectory(_)
 			| LocationError::NestedLocation(_)
-			| LocationError::LocationAlreadyExists(_) => {
+			| LocationError::LocationAlreadyExists(_)
+			| LocationError::Offline(_) => {
 				rspc::Error::with_cause(ErrorCode::BadRequest, err.to_string(), err)
 			}
 
@@ -94,6 +102,8 @@ impl From<LocationError> for rspc::Error {
 				rspc::Error::with_cause(ErrorCode::Conflict, "ADD_LIBRARY".to_owned(), err)
 			}
 
+			LocationError::JobManager(job_manager_err) => job_manager_err.into(),
+
 			_ => rspc::Error::with_cause(ErrorCode::InternalServerError, err.to_string(), err),
 		}
 	}
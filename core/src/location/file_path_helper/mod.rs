@@ -4,12 +4,14 @@ use crate::{
 };
 
 use std::{
+	collections::HashMap,
 	fs::Metadata,
 	path::{Path, PathBuf},
 	time::SystemTime,
 };
 
 use chrono::{DateTime, Utc};
+use itertools::Itertools;
 use prisma_client_rust::QueryError;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -17,8 +19,10 @@ use tokio::{fs, io};
 use tracing::error;
 
 pub mod isolated_file_path_data;
+pub mod posix_metadata;
 
 pub use isolated_file_path_data::IsolatedFilePathData;
+pub use posix_metadata::FilePathPermissions;
 
 use super::LocationId;
 
@@ -93,6 +97,109 @@ pub struct FilePathMetadata {
 	pub size_in_bytes: u64,
 	pub created_at: DateTime<Utc>,
 	pub modified_at: DateTime<Utc>,
+	#[serde(default)]
+	pub permissions: Option<FilePathPermissions>,
+}
+
+/// Change that adding or removing a file path causes on the recursive size and items count
+/// of each one of its ancestor directories
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DirectorySizeDelta {
+	pub bytes: i64,
+	pub items: i32,
+}
+
+impl DirectorySizeDelta {
+	/// `items_count` is the file path's own items count, so it's always 0 for files
+	pub fn added(size_in_bytes: i64, items_count: i32) -> Self {
+		Self {
+			bytes: size_in_bytes,
+			items: items_count + 1,
+		}
+	}
+
+	pub fn removed(size_in_bytes: i64, items_count: i32) -> Self {
+		Self {
+			bytes: -size_in_bytes,
+			items: -(items_count + 1),
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.bytes == 0 && self.items == 0
+	}
+}
+
+/// Accumulates the [`DirectorySizeDelta`]s of many file paths, so each ancestor directory
+/// is updated only once no matter how many of its descendants changed
+#[derive(Debug, Default)]
+pub struct DirectorySizesDeltas {
+	// Keyed by the materialized path and name of each directory
+	deltas: HashMap<(String, String), DirectorySizeDelta>,
+}
+
+impl DirectorySizesDeltas {
+	pub fn add(&mut self, materialized_path: &str, name: &str, delta: DirectorySizeDelta) {
+		// The location root is its own parent, but it must not be counted inside itself
+		if materialized_path == "/" && name.is_empty() {
+			return;
+		}
+
+		// Given "/a/b/", the ancestors are the root ("/", ""), then ("/", "a") and ("/a/", "b")
+		let ancestors = materialized_path
+			.match_indices('/')
+			.map(|(idx, _)| idx)
+			.tuple_windows()
+			.map(|(start, end)| {
+				(
+					&materialized_path[..=start],
+					&materialized_path[start + 1..end],
+				)
+			});
+
+		for (ancestor_materialized_path, ancestor_name) in [("/", "")].into_iter().chain(ancestors)
+		{
+			let ancestor_delta = self
+				.deltas
+				.entry((
+					ancestor_materialized_path.to_string(),
+					ancestor_name.to_string(),
+				))
+				.or_default();
+
+			ancestor_delta.bytes += delta.bytes;
+			ancestor_delta.items += delta.items;
+		}
+	}
+
+	/// Directory sizes are derived from their descendants on each node, so they aren't synced
+	pub async fn apply(self, location_id: LocationId, db: &PrismaClient) -> Result<(), QueryError> {
+		let updates = self
+			.deltas
+			.into_iter()
+			.filter(|(_, delta)| !delta.is_empty())
+			.map(|((materialized_path, name), delta)| {
+				db.file_path().update_many(
+					vec![
+						file_path::location_id::equals(location_id),
+						file_path::materialized_path::equals(materialized_path),
+						file_path::name::equals(name),
+						file_path::is_dir::equals(true),
+					],
+					vec![
+						file_path::size_in_bytes::increment(delta.bytes),
+						file_path::items_count::increment(delta.items),
+					],
+				)
+			})
+			.collect::<Vec<_>>();
+
+		if updates.is_empty() {
+			return Ok(());
+		}
+
+		db._batch(updates).await.map(|_| ())
+	}
 }
 
 #[derive(Error, Debug)]
@@ -144,8 +251,14 @@ pub async fn create_file_path(
 	}: IsolatedFilePathData<'_>,
 	cas_id: Option<String>,
 	metadata: FilePathMetadata,
+	xattrs: Option<String>,
+	symlink_target: Option<String>,
 ) -> Result<file_path::Data, FilePathError> {
-	use crate::{prisma::location, sync, util::db::uuid_to_bytes};
+	use crate::{
+		prisma::location,
+		sync,
+		util::db::{size_to_db_int, u64_to_db_int, uuid_to_bytes},
+	};
 
 	use serde_json::json;
 	use uuid::Uuid;
@@ -158,6 +271,22 @@ pub async fn create_file_path(
 		.await?
 		.ok_or(FilePathError::LocationNotFound(location_id))?;
 
+	let (permissions, uid, gid) = posix_metadata::permissions_db_values(metadata.permissions);
+
+	// Same as the indexer, a file sharing its inode and device with one already in this location
+	// is a hardlink to it, so its size isn't counted again
+	let is_hardlink = !is_dir
+		&& db
+			.file_path()
+			.count(vec![
+				file_path::location_id::equals(location.id),
+				file_path::inode::equals(u64_to_db_int(metadata.inode)),
+				file_path::device::equals(u64_to_db_int(metadata.device)),
+				file_path::is_dir::equals(false),
+			])
+			.exec()
+			.await? > 0;
+
 	let params = {
 		use file_path::*;
 
@@ -174,13 +303,19 @@ pub async fn create_file_path(
 			(extension::NAME, json!(extension)),
 			(
 				size_in_bytes::NAME,
-				json!(metadata.size_in_bytes.to_string()),
+				json!(size_to_db_int(metadata.size_in_bytes)),
 			),
-			(inode::NAME, json!(metadata.inode.to_le_bytes())),
-			(device::NAME, json!(metadata.device.to_le_bytes())),
+			(inode::NAME, json!(u64_to_db_int(metadata.inode))),
+			(device::NAME, json!(u64_to_db_int(metadata.device))),
 			(is_dir::NAME, json!(is_dir)),
+			(is_hardlink::NAME, json!(is_hardlink)),
 			(date_created::NAME, json!(metadata.created_at)),
 			(date_modified::NAME, json!(metadata.modified_at)),
+			(permissions::NAME, json!(permissions)),
+			(uid::NAME, json!(uid)),
+			(gid::NAME, json!(gid)),
+			(xattrs::NAME, json!(xattrs)),
+			(symlink_target::NAME, json!(symlink_target)),
 		]
 	};
 
@@ -201,16 +336,22 @@ pub async fn create_file_path(
 				materialized_path.into_owned(),
 				name.into_owned(),
 				extension.into_owned(),
-				metadata.inode.to_le_bytes().into(),
-				metadata.device.to_le_bytes().into(),
+				u64_to_db_int(metadata.inode),
+				u64_to_db_int(metadata.device),
 				{
 					use file_path::*;
 					vec![
 						cas_id::set(cas_id),
 						is_dir::set(is_dir),
-						size_in_bytes::set(metadata.size_in_bytes.to_string()),
+						is_hardlink::set(is_hardlink),
+						size_in_bytes::set(size_to_db_int(metadata.size_in_bytes)),
 						date_created::set(metadata.created_at.into()),
 						date_modified::set(metadata.modified_at.into()),
+						permissions::set(permissions),
+						uid::set(uid),
+						gid::set(gid),
+						xattrs::set(xattrs),
+						symlink_target::set(symlink_target),
 					]
 				},
 			),
@@ -462,3 +603,40 @@ impl MetadataExt for Metadata {
 		self.modified().unwrap_or_else(|_| SystemTime::now())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn expected(
+		materialized_path: &str,
+		name: &str,
+		bytes: i64,
+		items: i32,
+	) -> ((String, String), DirectorySizeDelta) {
+		(
+			(materialized_path.to_string(), name.to_string()),
+			DirectorySizeDelta { bytes, items },
+		)
+	}
+
+	#[test]
+	fn directory_sizes_deltas_reach_every_ancestor() {
+		let mut deltas = DirectorySizesDeltas::default();
+
+		deltas.add("/a/b/", "file", DirectorySizeDelta::added(10, 0));
+		deltas.add("/a/", "c", DirectorySizeDelta::added(0, 0));
+		deltas.add("/", "other", DirectorySizeDelta::removed(5, 0));
+		// The root itself doesn't change anything
+		deltas.add("/", "", DirectorySizeDelta::added(100, 10));
+
+		assert_eq!(
+			deltas.deltas,
+			HashMap::from([
+				expected("/", "", 5, 1),
+				expected("/", "a", 10, 2),
+				expected("/a/", "b", 10, 1),
+			])
+		);
+	}
+}
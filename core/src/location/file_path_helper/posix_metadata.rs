@@ -0,0 +1,266 @@
+//! POSIX permissions, owner and extended attributes of file paths. They're only captured on unix
+//! systems, elsewhere these functions find nothing and preserve nothing.
+
+use crate::util::error::FileIOError;
+
+use std::{
+	fs::Metadata,
+	io,
+	path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::task::{self, JoinError};
+
+#[cfg(target_family = "unix")]
+use std::collections::BTreeMap;
+
+#[cfg(target_family = "unix")]
+use base64::{engine::general_purpose::STANDARD, Engine};
+#[cfg(target_family = "unix")]
+use tokio::fs;
+#[cfg(target_family = "unix")]
+use tracing::debug;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilePathPermissions {
+	/// Just the permission bits, without the file type
+	pub mode: u32,
+	pub uid: u32,
+	pub gid: u32,
+}
+
+impl FilePathPermissions {
+	pub fn from_metadata(metadata: &Metadata) -> Option<Self> {
+		#[cfg(target_family = "unix")]
+		{
+			use std::os::unix::fs::MetadataExt;
+
+			Some(Self {
+				mode: metadata.mode() & 0o7777,
+				uid: metadata.uid(),
+				gid: metadata.gid(),
+			})
+		}
+
+		#[cfg(not(target_family = "unix"))]
+		{
+			let _ = metadata; // To avoid unused variable warning
+			None
+		}
+	}
+}
+
+/// The values of the `permissions`, `uid` and `gid` columns of a file path
+pub fn permissions_db_values(
+	permissions: Option<FilePathPermissions>,
+) -> (Option<i32>, Option<i64>, Option<i64>) {
+	permissions.map_or(
+		(None, None, None),
+		|FilePathPermissions { mode, uid, gid }| {
+			(Some(mode as i32), Some(uid as i64), Some(gid as i64))
+		},
+	)
+}
+
+/// Reads the extended attributes of a path as a JSON object of their names to their base64 encoded
+/// values, which is how they're stored. Paths without any, or on filesystems that don't support
+/// them, have `None`
+pub fn read_extended_attributes(path: impl AsRef<Path>) -> Result<Option<String>, FileIOError> {
+	#[cfg(target_family = "unix")]
+	{
+		let path = path.as_ref();
+
+		let names = match xattr::list(path) {
+			Ok(names) => names,
+			Err(e) if is_unsupported(&e) => return Ok(None),
+			Err(e) => return Err(FileIOError::from((path, e))),
+		};
+
+		let mut xattrs = BTreeMap::new();
+		for name in names {
+			// Removed between listing and reading it
+			let Some(value) = xattr::get(path, &name).map_err(|e| FileIOError::from((path, e)))?
+			else {
+				continue;
+			};
+
+			xattrs.insert(name.to_string_lossy().to_string(), STANDARD.encode(value));
+		}
+
+		Ok((!xattrs.is_empty()).then(|| {
+			serde_json::to_string(&xattrs).expect("a map of strings is always valid JSON")
+		}))
+	}
+
+	#[cfg(not(target_family = "unix"))]
+	{
+		let _ = path; // To avoid unused variable warning
+		Ok(None)
+	}
+}
+
+/// Same as [`read_extended_attributes`], but on a blocking thread, as listing and reading them are
+/// blocking syscalls
+pub async fn read_extended_attributes_async(
+	path: impl AsRef<Path>,
+) -> Result<Option<String>, FileIOError> {
+	let path = path.as_ref().to_path_buf();
+
+	task::spawn_blocking({
+		let path = path.clone();
+		move || read_extended_attributes(path)
+	})
+	.await
+	.map_err(|e| FileIOError::from((path, io::Error::new(io::ErrorKind::Other, e))))?
+}
+
+/// Reads the extended attributes of every path, in order, on a single blocking thread, so a batch of
+/// paths doesn't stall the runtime nor spawn a blocking task for each one of them
+pub async fn read_extended_attributes_in_batch(
+	paths: Vec<PathBuf>,
+) -> Result<Vec<Result<Option<String>, FileIOError>>, JoinError> {
+	task::spawn_blocking(move || paths.iter().map(read_extended_attributes).collect()).await
+}
+
+/// Copies the owner, mode bits and extended attributes of `source` to `target`. Changing the owner
+/// needs privileges we usually don't have, and the target filesystem may not support some extended
+/// attributes, so those failures are only logged
+pub async fn preserve_posix_metadata(
+	source: impl AsRef<Path>,
+	target: impl AsRef<Path>,
+) -> Result<(), FileIOError> {
+	#[cfg(target_family = "unix")]
+	{
+		let (source, target) = (source.as_ref().to_path_buf(), target.as_ref().to_path_buf());
+
+		let metadata = fs::symlink_metadata(&source)
+			.await
+			.map_err(|e| FileIOError::from((&source, e)))?;
+
+		let Some(permissions) = FilePathPermissions::from_metadata(&metadata) else {
+			return Ok(());
+		};
+
+		// `lchown` and the extended attributes calls are blocking syscalls
+		task::spawn_blocking({
+			let target = target.clone();
+			move || copy_posix_metadata(&source, &target, permissions, metadata.is_symlink())
+		})
+		.await
+		.map_err(|e| FileIOError::from((target, io::Error::new(io::ErrorKind::Other, e))))?
+	}
+
+	#[cfg(not(target_family = "unix"))]
+	{
+		let _ = (source, target); // To avoid unused variable warning
+		Ok(())
+	}
+}
+
+#[cfg(target_family = "unix")]
+fn copy_posix_metadata(
+	source: &Path,
+	target: &Path,
+	FilePathPermissions { mode, uid, gid }: FilePathPermissions,
+	is_symlink: bool,
+) -> Result<(), FileIOError> {
+	use std::os::unix::fs::PermissionsExt;
+
+	// The owner goes first, as changing it may clear the setuid and setgid bits
+	if let Err(e) = change_owner(target, uid, gid) {
+		debug!(
+			"Failed to change the owner of {} to {uid}:{gid}: {e}",
+			target.display()
+		);
+	}
+
+	// Symbolic links don't have permissions of their own
+	if !is_symlink {
+		std::fs::set_permissions(target, std::fs::Permissions::from_mode(mode))
+			.map_err(|e| FileIOError::from((target, e)))?;
+	}
+
+	let names = match xattr::list(source) {
+		Ok(names) => names,
+		Err(e) if is_unsupported(&e) => return Ok(()),
+		Err(e) => return Err(FileIOError::from((source, e))),
+	};
+
+	for name in names {
+		let Some(value) = xattr::get(source, &name).map_err(|e| FileIOError::from((source, e)))?
+		else {
+			continue;
+		};
+
+		if let Err(e) = xattr::set(target, &name, &value) {
+			debug!(
+				"Failed to copy the extended attribute {} to {}: {e}",
+				name.to_string_lossy(),
+				target.display()
+			);
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(target_family = "unix")]
+fn is_unsupported(e: &io::Error) -> bool {
+	e.raw_os_error() == Some(libc::ENOTSUP) || e.kind() == io::ErrorKind::Unsupported
+}
+
+#[cfg(target_family = "unix")]
+fn change_owner(path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+	use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+	let path = CString::new(path.as_os_str().as_bytes())?;
+
+	// SAFETY: `path` is a valid nul terminated string that outlives the call
+	if unsafe { libc::lchown(path.as_ptr(), uid, gid) } == 0 {
+		Ok(())
+	} else {
+		Err(io::Error::last_os_error())
+	}
+}
+
+#[cfg(all(test, target_family = "unix"))]
+mod tests {
+	use super::*;
+
+	use std::os::unix::fs::PermissionsExt;
+
+	use tempfile::tempdir;
+
+	#[tokio::test]
+	async fn extended_attributes_are_read_and_preserved() {
+		let dir = tempdir().unwrap();
+		let source = dir.path().join("source.txt");
+		let target = dir.path().join("target.txt");
+		fs::write(&source, b"source").await.unwrap();
+		fs::write(&target, b"target").await.unwrap();
+
+		fs::set_permissions(&source, std::fs::Permissions::from_mode(0o640))
+			.await
+			.unwrap();
+
+		// Some filesystems, like tmpfs on older kernels, don't support user extended attributes
+		let supports_xattrs = xattr::set(&source, "user.sd.origin", b"\x00somewhere").is_ok();
+
+		preserve_posix_metadata(&source, &target).await.unwrap();
+
+		let target_permissions =
+			FilePathPermissions::from_metadata(&fs::symlink_metadata(&target).await.unwrap())
+				.unwrap();
+		assert_eq!(target_permissions.mode, 0o640);
+
+		if supports_xattrs {
+			let xattrs = read_extended_attributes(&target).unwrap().unwrap();
+			assert_eq!(
+				serde_json::from_str::<BTreeMap<String, String>>(&xattrs).unwrap()
+					["user.sd.origin"],
+				STANDARD.encode(b"\x00somewhere")
+			);
+		}
+	}
+}
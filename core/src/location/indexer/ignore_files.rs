@@ -0,0 +1,160 @@
+use crate::util::error::FileIOError;
+
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use ignore::{
+	gitignore::{Gitignore, GitignoreBuilder},
+	Match,
+};
+use tokio::{fs, io::ErrorKind};
+use tracing::warn;
+
+use super::{rules::IndexerRuleError, IndexerError};
+
+/// Parsed ignore files, keyed by their paths, so we don't parse the same file again for each
+/// directory below it
+pub(super) type IgnoreFilesCache = HashMap<PathBuf, Arc<Gitignore>>;
+
+/// The ignore files that apply to the entries of a directory, from the outermost to the innermost
+#[derive(Debug, Default)]
+pub(super) struct IgnoreFiles {
+	matchers: Vec<Arc<Gitignore>>,
+}
+
+impl IgnoreFiles {
+	/// Loads the ignore files at `paths`, a file that fails to load is reported and skipped, so
+	/// we still respect the others
+	pub(super) async fn load(
+		paths: &[PathBuf],
+		cache: &mut IgnoreFilesCache,
+		errors: &mut Vec<IndexerError>,
+	) -> Self {
+		let mut matchers = Vec::with_capacity(paths.len());
+
+		for path in paths {
+			if let Some(matcher) = cache.get(path) {
+				matchers.push(Arc::clone(matcher));
+				continue;
+			}
+
+			match load_ignore_file(path).await {
+				Ok(matcher) => {
+					let matcher = Arc::new(matcher);
+					cache.insert(path.clone(), Arc::clone(&matcher));
+					matchers.push(matcher);
+				}
+				Err(e) => errors.push(e.into()),
+			}
+		}
+
+		Self { matchers }
+	}
+
+	/// Like git, the innermost ignore file that has an opinion about the path wins, so a nested
+	/// ignore file can re-include (with `!pattern`) a path ignored by an outer one
+	pub(super) fn is_ignored(&self, path: impl AsRef<Path>, is_dir: bool) -> bool {
+		let path = path.as_ref();
+
+		self.matchers
+			.iter()
+			.rev()
+			.find_map(|matcher| match matcher.matched(path, is_dir) {
+				Match::None => None,
+				Match::Ignore(_) => Some(true),
+				Match::Whitelist(_) => Some(false),
+			})
+			.unwrap_or(false)
+	}
+}
+
+/// Finds the ignore files named `file_names` directly inside `dir`, keeping the order of `file_names`
+pub(super) async fn find_ignore_files(
+	dir: impl AsRef<Path>,
+	file_names: &[String],
+) -> Result<Vec<PathBuf>, IndexerRuleError> {
+	let dir = dir.as_ref();
+
+	let mut found = vec![];
+	for file_name in file_names {
+		let path = dir.join(file_name);
+
+		match fs::metadata(&path).await {
+			Ok(metadata) if metadata.is_file() => found.push(path),
+			Ok(_) => {}
+			Err(e) if e.kind() == ErrorKind::NotFound => {}
+			Err(e) => return Err(IndexerRuleError::IgnoreFileIO(FileIOError::from((path, e)))),
+		}
+	}
+
+	Ok(found)
+}
+
+async fn load_ignore_file(path: &Path) -> Result<Gitignore, IndexerRuleError> {
+	let contents = fs::read_to_string(path)
+		.await
+		.map_err(|e| IndexerRuleError::IgnoreFileIO(FileIOError::from((path, e))))?;
+
+	// Patterns in an ignore file are relative to the directory where the file is
+	let mut builder = GitignoreBuilder::new(path.parent().unwrap_or(path));
+
+	for line in contents.lines() {
+		if let Err(e) = builder.add_line(Some(path.to_path_buf()), line) {
+			warn!(
+				"Skipping invalid line in ignore file <path='{}'>: {e}",
+				path.display()
+			);
+		}
+	}
+
+	builder.build().map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::rules::SD_IGNORE_FILE_NAME;
+	use super::*;
+	use tempfile::tempdir;
+
+	#[tokio::test]
+	async fn test_nested_ignore_files_and_negations() {
+		let root = tempdir().unwrap();
+		let root_path = root.path();
+		let project = root_path.join("project");
+
+		fs::create_dir(&project).await.unwrap();
+		fs::write(root_path.join(".gitignore"), "*.log\nbuild/\n")
+			.await
+			.unwrap();
+		fs::write(project.join(".gitignore"), "!important.log\n")
+			.await
+			.unwrap();
+		fs::write(project.join(SD_IGNORE_FILE_NAME), "secret.txt\n")
+			.await
+			.unwrap();
+
+		let file_names = [".gitignore".to_string(), SD_IGNORE_FILE_NAME.to_string()];
+
+		let mut paths = find_ignore_files(root_path, &file_names).await.unwrap();
+		assert_eq!(paths, vec![root_path.join(".gitignore")]);
+		paths.extend(find_ignore_files(&project, &file_names).await.unwrap());
+		assert_eq!(paths.len(), 3);
+
+		let mut cache = IgnoreFilesCache::new();
+		let mut errors = vec![];
+		let ignore_files = IgnoreFiles::load(&paths, &mut cache, &mut errors).await;
+		assert!(errors.is_empty());
+		assert_eq!(cache.len(), 3);
+
+		assert!(ignore_files.is_ignored(root_path.join("debug.log"), false));
+		assert!(ignore_files.is_ignored(project.join("debug.log"), false));
+		assert!(!ignore_files.is_ignored(project.join("important.log"), false));
+		assert!(ignore_files.is_ignored(project.join("secret.txt"), false));
+		assert!(ignore_files.is_ignored(project.join("build"), true));
+		assert!(!ignore_files.is_ignored(project.join("build"), false));
+		assert!(!ignore_files.is_ignored(project.join("main.rs"), false));
+	}
+}
@@ -1,11 +1,15 @@
 use crate::{
 	file_paths_db_fetcher_fn,
 	job::{JobError, JobInitData, JobResult, JobState, StatefulJob, WorkerContext},
-	location::file_path_helper::{
-		ensure_file_path_exists, ensure_sub_path_is_directory, ensure_sub_path_is_in_location,
-		IsolatedFilePathData,
+	location::{
+		file_path_helper::{
+			ensure_file_path_exists, ensure_sub_path_is_directory, ensure_sub_path_is_in_location,
+			IsolatedFilePathData,
+		},
+		size_tree::invalidate_size_tree,
 	},
 	to_remove_db_fetcher_fn,
+	volume::directory_mtimes_are_reliable,
 };
 
 use std::{path::Path, sync::Arc};
@@ -13,18 +17,22 @@ use std::{path::Path, sync::Arc};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use tokio::time::Instant;
+use tracing::info;
 
 use super::{
-	execute_indexer_save_step, finalize_indexer, iso_file_path_factory,
-	remove_non_existing_file_paths,
+	execute_indexer_refresh_step, execute_indexer_save_step, fetch_indexed_directory,
+	finalize_indexer, iso_file_path_factory, remove_non_existing_file_paths,
 	rules::IndexerRule,
-	update_notifier_fn,
+	sort_parents_first, update_notifier_fn,
 	walk::{keep_walking, walk, ToWalkEntry, WalkResult},
-	IndexerError, IndexerJobData, IndexerJobInit, IndexerJobSaveStep, ScanProgress,
+	IndexerError, IndexerJobData, IndexerJobInit, IndexerJobRefreshStep, IndexerJobSaveStep,
+	ScanProgress,
 };
 
 /// BATCH_SIZE is the number of files to index at each step, writing the chunk of files metadata in the database.
 const BATCH_SIZE: usize = 1000;
+/// How many directories the walker reads at the same time
+pub(super) const MAX_CONCURRENT_DIRS_WALKS: usize = 16;
 
 /// A `IndexerJob` is a stateful job that walks a directory and indexes all files.
 /// First it walks the directory and generates a list of files to index, chunked into
@@ -41,6 +49,8 @@ pub enum IndexerJobStepInput {
 	/// `IndexerJobStepEntry`. The size of this vector is given by the [`BATCH_SIZE`] constant.
 	Save(IndexerJobSaveStep),
 	Walk(ToWalkEntry),
+	/// Runs after the save steps of a walk, see [`IndexerJobRefreshStep`]
+	Refresh(IndexerJobRefreshStep),
 }
 
 #[async_trait::async_trait]
@@ -97,21 +107,39 @@ impl StatefulJob for IndexerJob {
 			location_path.to_path_buf()
 		};
 
+		let incremental = state.init.incremental
+			&& IndexerRule::allow_skipping_unchanged_dirs(&indexer_rules)
+			&& directory_mtimes_are_reliable(location_path);
+
+		if state.init.incremental && !incremental {
+			info!(
+				"Reading every directory of {}, as its filesystem or indexer rules \
+					don't allow skipping unchanged ones",
+				location_path.display()
+			);
+		}
+
 		let scan_start = Instant::now();
 		let WalkResult {
 			walked,
 			to_walk,
 			to_remove,
+			to_refresh,
 			errors,
 		} = {
 			walk(
 				&to_walk_path,
 				&indexer_rules,
+				state.init.location.follow_symlinks,
 				update_notifier_fn(BATCH_SIZE, &mut ctx),
 				file_paths_db_fetcher_fn!(&db),
 				to_remove_db_fetcher_fn!(location_id, location_path, &db),
+				|iso_file_path| {
+					fetch_indexed_directory(incremental, location_id, iso_file_path, &db)
+				},
 				iso_file_path_factory(location_id, location_path),
 				50_000,
+				MAX_CONCURRENT_DIRS_WALKS,
 			)
 			.await?
 		};
@@ -119,14 +147,14 @@ impl StatefulJob for IndexerJob {
 
 		let db_delete_start = Instant::now();
 		// TODO pass these uuids to sync system
-		let removed_count = remove_non_existing_file_paths(to_remove, &db).await?;
+		let removed_count = remove_non_existing_file_paths(location_id, to_remove, &db).await?;
 		let db_delete_time = db_delete_start.elapsed();
 
 		let total_paths = &mut 0;
 		let to_walk_count = to_walk.len();
 
 		state.steps.extend(
-			walked
+			sort_parents_first(walked)
 				.chunks(BATCH_SIZE)
 				.into_iter()
 				.enumerate()
@@ -140,7 +168,12 @@ impl StatefulJob for IndexerJob {
 						walked: chunk_steps,
 					})
 				})
-				.chain(to_walk.into_iter().map(IndexerJobStepInput::Walk)),
+				.chain(to_walk.into_iter().map(IndexerJobStepInput::Walk))
+				.chain((!to_refresh.is_empty()).then(|| {
+					IndexerJobStepInput::Refresh(IndexerJobRefreshStep {
+						refreshed: to_refresh,
+					})
+				})),
 		);
 
 		IndexerJobData::on_scan_progress(
@@ -159,7 +192,12 @@ impl StatefulJob for IndexerJob {
 			total_paths: *total_paths,
 			indexed_count: 0,
 			removed_count,
-			total_save_steps: state.steps.len() as u64 - to_walk_count as u64,
+			total_save_steps: state
+				.steps
+				.iter()
+				.filter(|step| matches!(step, IndexerJobStepInput::Save(_)))
+				.count() as u64,
+			incremental,
 		});
 
 		if !errors.is_empty() {
@@ -208,6 +246,7 @@ impl StatefulJob for IndexerJob {
 				let location_id = state.init.location.id;
 				let location_path = Path::new(&state.init.location.path);
 				let db = Arc::clone(&ctx.library.db);
+				let incremental = data.incremental;
 
 				let scan_start = Instant::now();
 
@@ -215,6 +254,7 @@ impl StatefulJob for IndexerJob {
 					walked,
 					to_walk,
 					to_remove,
+					to_refresh,
 					errors,
 				} = {
 					keep_walking(
@@ -223,6 +263,9 @@ impl StatefulJob for IndexerJob {
 						update_notifier_fn(BATCH_SIZE, &mut ctx),
 						file_paths_db_fetcher_fn!(&db),
 						to_remove_db_fetcher_fn!(location_id, location_path, &db),
+						|iso_file_path| {
+							fetch_indexed_directory(incremental, location_id, iso_file_path, &db)
+						},
 						iso_file_path_factory(location_id, location_path),
 					)
 					.await?
@@ -232,14 +275,15 @@ impl StatefulJob for IndexerJob {
 
 				let db_delete_time = Instant::now();
 				// TODO pass these uuids to sync system
-				data.removed_count += remove_non_existing_file_paths(to_remove, &db).await?;
+				data.removed_count +=
+					remove_non_existing_file_paths(location_id, to_remove, &db).await?;
 				data.db_write_time += db_delete_time.elapsed();
 
 				let old_total = data.total_paths;
 				let old_steps_count = state.steps.len() as u64;
 
 				state.steps.extend(
-					walked
+					sort_parents_first(walked)
 						.chunks(BATCH_SIZE)
 						.into_iter()
 						.enumerate()
@@ -252,7 +296,12 @@ impl StatefulJob for IndexerJob {
 								walked: chunk_steps,
 							})
 						})
-						.chain(to_walk.into_iter().map(IndexerJobStepInput::Walk)),
+						.chain(to_walk.into_iter().map(IndexerJobStepInput::Walk))
+						.chain((!to_refresh.is_empty()).then(|| {
+							IndexerJobStepInput::Refresh(IndexerJobRefreshStep {
+								refreshed: to_refresh,
+							})
+						})),
 				);
 
 				IndexerJobData::on_scan_progress(
@@ -270,12 +319,21 @@ impl StatefulJob for IndexerJob {
 					));
 				}
 			}
+			IndexerJobStepInput::Refresh(step) => {
+				let start_time = Instant::now();
+
+				execute_indexer_refresh_step(step, &ctx.library).await?;
+
+				data.db_write_time += start_time.elapsed();
+			}
 		}
 
 		Ok(())
 	}
 
 	async fn finalize(&mut self, ctx: WorkerContext, state: &mut JobState<Self>) -> JobResult {
+		invalidate_size_tree(&ctx.library, state.init.location.id).await;
+
 		finalize_indexer(&state.init.location.path, state, ctx)
 	}
 }
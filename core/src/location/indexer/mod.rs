@@ -1,46 +1,69 @@
 use crate::{
 	invalidate_query,
 	job::{JobReportUpdate, JobResult, JobState, StatefulJob, WorkerContext},
-	library::Library,
-	prisma::{file_path, PrismaClient},
+	library::{
+		remove_orphans_from_kind_statistics, update_location_statistics, FilePathsStatisticsDelta,
+		Library,
+	},
+	prisma::{file_path, PrismaClient, SortOrder},
 	sync,
-	util::{db::uuid_to_bytes, error::FileIOError},
+	util::{
+		db::{size_to_db_int, u64_to_db_int, uuid_to_bytes},
+		error::FileIOError,
+	},
 };
 
 use std::{
+	collections::HashSet,
 	hash::{Hash, Hasher},
 	path::{Path, PathBuf},
 	time::Duration,
 };
 
+use chrono::Utc;
+use itertools::Itertools;
+use prisma_client_rust::{operator, QueryError};
 use rspc::ErrorCode;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::json;
 use thiserror::Error;
-use tracing::info;
+use tracing::{info, warn};
 
 use super::{
-	file_path_helper::{file_path_just_pub_id, FilePathError, IsolatedFilePathData},
+	file_path_helper::{
+		file_path_just_pub_id, file_path_to_isolate, posix_metadata, DirectorySizeDelta,
+		DirectorySizesDeltas, FilePathError, IsolatedFilePathData,
+	},
 	location_with_indexer_rules, LocationId,
 };
 
+mod ignore_files;
 pub mod indexer_job;
+mod poll;
+pub mod preview;
 pub mod rules;
+pub mod rules_document;
 mod shallow;
 mod walk;
 
 use rules::IndexerRuleError;
-use walk::WalkedEntry;
+use walk::{IndexedDirectory, RefreshedDirectory, WalkedEntry};
 
+pub(crate) use walk::followable_symlink_dir;
+
+pub use poll::*;
 pub use shallow::*;
 
 /// `IndexerJobInit` receives a `location::Data` object to be indexed
 /// and possibly a `sub_path` to be indexed. The `sub_path` is used when
 /// we want do index just a part of a location.
+/// With `incremental`, directories that didn't change since they were indexed aren't read again,
+/// unless the location's filesystem or indexer rules don't allow it.
 #[derive(Serialize, Deserialize)]
 pub struct IndexerJobInit {
 	pub location: location_with_indexer_rules::Data,
 	pub sub_path: Option<PathBuf>,
+	pub incremental: bool,
 }
 
 impl Hash for IndexerJobInit {
@@ -64,6 +87,7 @@ pub struct IndexerJobData {
 	total_save_steps: u64,
 	indexed_count: u64,
 	removed_count: u64,
+	incremental: bool,
 }
 
 impl IndexerJobData {
@@ -87,6 +111,11 @@ pub struct IndexerJobSaveStep {
 	walked: Vec<WalkedEntry>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IndexerJobRefreshStep {
+	refreshed: Vec<RefreshedDirectory>,
+}
+
 #[derive(Clone)]
 pub enum ScanProgress {
 	ChunkCount(usize),
@@ -110,6 +139,8 @@ pub enum IndexerError {
 	FileIO(#[from] FileIOError),
 	#[error(transparent)]
 	FilePath(#[from] FilePathError),
+	#[error("failed to read extended attributes on a blocking thread")]
+	ExtendedAttributesTask(#[from] tokio::task::JoinError),
 
 	// Mixed errors
 	#[error(transparent)]
@@ -130,6 +161,47 @@ impl From<IndexerError> for rspc::Error {
 	}
 }
 
+/// Leaves out the walked entries that were indexed since they were walked, by the watcher or by an
+/// interrupted run of the same step, as inserting them is skipped and they must not be counted
+/// again in the directory sizes and the location statistics
+async fn filter_already_indexed<'a>(
+	location_id: LocationId,
+	walked: &'a [WalkedEntry],
+	db: &PrismaClient,
+) -> Result<Vec<&'a WalkedEntry>, IndexerError> {
+	if walked.is_empty() {
+		return Ok(vec![]);
+	}
+
+	let materialized_paths = walked
+		.iter()
+		.map(|entry| entry.iso_file_path.materialized_path.to_string())
+		.collect::<HashSet<_>>();
+	let names = walked
+		.iter()
+		.map(|entry| entry.iso_file_path.name.to_string())
+		.collect::<HashSet<_>>();
+
+	let already_indexed = db
+		.file_path()
+		.find_many(vec![
+			file_path::location_id::equals(location_id),
+			file_path::materialized_path::in_vec(materialized_paths.into_iter().collect()),
+			file_path::name::in_vec(names.into_iter().collect()),
+		])
+		.select(file_path_to_isolate::select())
+		.exec()
+		.await?
+		.into_iter()
+		.map(IsolatedFilePathData::from)
+		.collect::<HashSet<_>>();
+
+	Ok(walked
+		.iter()
+		.filter(|entry| !already_indexed.contains(&entry.iso_file_path))
+		.collect())
+}
+
 async fn execute_indexer_save_step(
 	location: &location_with_indexer_rules::Data,
 	save_step: &IndexerJobSaveStep,
@@ -137,10 +209,74 @@ async fn execute_indexer_save_step(
 ) -> Result<i64, IndexerError> {
 	let Library { sync, db, .. } = &library;
 
-	let (sync_stuff, paths): (Vec<_>, Vec<_>) = save_step
-		.walked
+	let walked = filter_already_indexed(location.id, &save_step.walked, db).await?;
+
+	let mut directory_sizes = DirectorySizesDeltas::default();
+
+	// A file with the same inode and device of one already indexed, or of an earlier one of this
+	// step, is a hardlink to it, so its size is only counted once
+	let mut indexed_inodes = db
+		.file_path()
+		.find_many(vec![
+			file_path::location_id::equals(location.id),
+			file_path::is_dir::equals(false),
+			file_path::inode::in_vec(
+				walked
+					.iter()
+					.filter(|entry| !entry.iso_file_path.is_dir)
+					.map(|entry| u64_to_db_int(entry.metadata.inode))
+					.collect(),
+			),
+			file_path::device::in_vec(
+				walked
+					.iter()
+					.filter(|entry| !entry.iso_file_path.is_dir)
+					.map(|entry| u64_to_db_int(entry.metadata.device))
+					.collect(),
+			),
+		])
+		.select(file_path::select!({ inode device }))
+		.exec()
+		.await?
+		.into_iter()
+		.map(|file_path| (file_path.inode, file_path.device))
+		.collect::<HashSet<_>>();
+
+	let hardlinks = walked
 		.iter()
 		.map(|entry| {
+			!entry.iso_file_path.is_dir
+				&& !indexed_inodes.insert((
+					u64_to_db_int(entry.metadata.inode),
+					u64_to_db_int(entry.metadata.device),
+				))
+		})
+		.collect::<Vec<_>>();
+
+	// Extended attributes can be big, so they're read while saving instead of being kept in the
+	// job state with the rest of the walked metadata, all of the step's ones on one blocking thread
+	let location_path = Path::new(&location.path);
+	let xattrs = posix_metadata::read_extended_attributes_in_batch(
+		walked
+			.iter()
+			.map(|entry| location_path.join(&entry.iso_file_path))
+			.collect(),
+	)
+	.await?
+	.into_iter()
+	.map(|xattrs| {
+		xattrs.unwrap_or_else(|e| {
+			warn!("Failed to read extended attributes: {e:#?}");
+			None
+		})
+	})
+	.collect::<Vec<_>>();
+
+	let (sync_stuff, paths): (Vec<_>, Vec<_>) = walked
+		.iter()
+		.zip(hardlinks.iter().copied())
+		.zip(xattrs)
+		.map(|((entry, is_hardlink), xattrs)| {
 			let IsolatedFilePathData {
 				materialized_path,
 				is_dir,
@@ -149,6 +285,22 @@ async fn execute_indexer_save_step(
 				..
 			} = &entry.iso_file_path;
 
+			// A directory's size is the sum of its descendants, which are added to it below
+			let size = if *is_dir {
+				0
+			} else {
+				size_to_db_int(entry.metadata.size_in_bytes)
+			};
+
+			directory_sizes.add(
+				materialized_path,
+				name,
+				DirectorySizeDelta::added(if is_hardlink { 0 } else { size }, 0),
+			);
+
+			let (permissions, uid, gid) =
+				posix_metadata::permissions_db_values(entry.metadata.permissions);
+
 			use file_path::*;
 
 			(
@@ -161,14 +313,17 @@ async fn execute_indexer_save_step(
 						(name::NAME, json!(name)),
 						(is_dir::NAME, json!(*is_dir)),
 						(extension::NAME, json!(extension)),
-						(
-							size_in_bytes::NAME,
-							json!(entry.metadata.size_in_bytes.to_string()),
-						),
-						(inode::NAME, json!(entry.metadata.inode.to_le_bytes())),
-						(device::NAME, json!(entry.metadata.device.to_le_bytes())),
+						(size_in_bytes::NAME, json!(size)),
+						(inode::NAME, json!(u64_to_db_int(entry.metadata.inode))),
+						(device::NAME, json!(u64_to_db_int(entry.metadata.device))),
 						(date_created::NAME, json!(entry.metadata.created_at)),
 						(date_modified::NAME, json!(entry.metadata.modified_at)),
+						(symlink_target::NAME, json!(entry.symlink_target)),
+						(is_hardlink::NAME, json!(is_hardlink)),
+						(permissions::NAME, json!(permissions)),
+						(uid::NAME, json!(uid)),
+						(gid::NAME, json!(gid)),
+						(xattrs::NAME, json!(xattrs)),
 					],
 				),
 				file_path::create_unchecked(
@@ -177,13 +332,19 @@ async fn execute_indexer_save_step(
 					materialized_path.to_string(),
 					name.to_string(),
 					extension.to_string(),
-					entry.metadata.inode.to_le_bytes().into(),
-					entry.metadata.device.to_le_bytes().into(),
+					u64_to_db_int(entry.metadata.inode),
+					u64_to_db_int(entry.metadata.device),
 					vec![
 						is_dir::set(*is_dir),
-						size_in_bytes::set(entry.metadata.size_in_bytes.to_string()),
+						size_in_bytes::set(size),
 						date_created::set(entry.metadata.created_at.into()),
 						date_modified::set(entry.metadata.modified_at.into()),
+						symlink_target::set(entry.symlink_target.clone()),
+						is_hardlink::set(is_hardlink),
+						permissions::set(permissions),
+						uid::set(uid),
+						gid::set(gid),
+						xattrs::set(xattrs),
 					],
 				),
 			)
@@ -202,9 +363,71 @@ async fn execute_indexer_save_step(
 
 	info!("Inserted {count} records");
 
+	// Entries are saved sorted by materialized path, so the directories of this step were
+	// already created, either in this step or in a previous one
+	directory_sizes.apply(location.id, db).await?;
+
+	update_location_statistics(
+		db,
+		location.id,
+		walked
+			.iter()
+			.zip(hardlinks)
+			.filter(|(entry, _)| !entry.iso_file_path.is_dir)
+			.map(|(entry, is_hardlink)| {
+				FilePathsStatisticsDelta::added(if is_hardlink {
+					0
+				} else {
+					size_to_db_int(entry.metadata.size_in_bytes)
+				})
+			})
+			.collect(),
+	)
+	.await?;
+
 	Ok(count)
 }
 
+/// Updates the modification times of the directories that changed since they were indexed, it must
+/// run after their new entries are saved
+async fn execute_indexer_refresh_step(
+	refresh_step: &IndexerJobRefreshStep,
+	library: &Library,
+) -> Result<(), IndexerError> {
+	let Library { sync, db, .. } = &library;
+
+	let indexed_at = Utc::now();
+
+	sync.write_ops(
+		db,
+		refresh_step
+			.refreshed
+			.iter()
+			.map(|refreshed| {
+				(
+					sync.shared_update(
+						sync::file_path::SyncId {
+							pub_id: refreshed.pub_id.clone(),
+						},
+						file_path::date_modified::NAME,
+						json!(refreshed.modified_at),
+					),
+					db.file_path().update(
+						file_path::pub_id::equals(refreshed.pub_id.clone()),
+						vec![
+							file_path::date_modified::set(refreshed.modified_at.into()),
+							file_path::date_indexed::set(indexed_at.into()),
+						],
+					),
+				)
+			})
+			.unzip::<_, _, _, Vec<_>>(),
+	)
+	.await?;
+
+	Ok(())
+}
+
 fn finalize_indexer<SJob, Init, Step>(
 	location_path: impl AsRef<Path>,
 	state: &JobState<SJob>,
@@ -258,18 +481,335 @@ fn iso_file_path_factory(
 	}
 }
 
+/// Parents must be saved before their children, so they can receive their sizes
+fn sort_parents_first(
+	walked: impl Iterator<Item = WalkedEntry>,
+) -> impl Iterator<Item = WalkedEntry> {
+	walked.sorted_by(|a, b| {
+		a.iso_file_path
+			.materialized_path
+			.cmp(&b.iso_file_path.materialized_path)
+	})
+}
+
+/// Fetches an indexed directory with the names of its children directories, so the walker can skip
+/// it if it didn't change. When `incremental` is false no directory is found, so all of them are read
+async fn fetch_indexed_directory(
+	incremental: bool,
+	location_id: LocationId,
+	iso_file_path: IsolatedFilePathData<'static>,
+	db: &PrismaClient,
+) -> Result<Option<IndexedDirectory>, IndexerError> {
+	if !incremental {
+		return Ok(None);
+	}
+
+	let Some(materialized_path_for_children) = iso_file_path.materialized_path_for_children()
+	else {
+		return Ok(None);
+	};
+
+	// The location root isn't a file path, so it's never found
+	let Some(directory) = db
+		.file_path()
+		.find_unique(iso_file_path.into())
+		.select(file_path::select!({ pub_id date_modified date_indexed }))
+		.exec()
+		.await?
+	else {
+		return Ok(None);
+	};
+
+	let children_dirs_names = db
+		.file_path()
+		.find_many(vec![
+			file_path::location_id::equals(location_id),
+			file_path::materialized_path::equals(materialized_path_for_children),
+			file_path::is_dir::equals(true),
+		])
+		.select(file_path::select!({ name }))
+		.exec()
+		.await?
+		.into_iter()
+		.map(|child| child.name)
+		.collect();
+
+	Ok(Some(IndexedDirectory {
+		pub_id: directory.pub_id,
+		modified_at: directory.date_modified.into(),
+		indexed_at: directory.date_indexed.into(),
+		children_dirs_names,
+	}))
+}
+
 async fn remove_non_existing_file_paths(
+	location_id: LocationId,
 	to_remove: impl IntoIterator<Item = file_path_just_pub_id::Data>,
 	db: &PrismaClient,
 ) -> Result<u64, IndexerError> {
-	db.file_path()
-		.delete_many(vec![file_path::pub_id::in_vec(
-			to_remove.into_iter().map(|data| data.pub_id).collect(),
-		)])
+	let pub_ids = to_remove
+		.into_iter()
+		.map(|data| data.pub_id)
+		.collect::<Vec<_>>();
+
+	let removed = db
+		.file_path()
+		.find_many(vec![file_path::pub_id::in_vec(pub_ids.clone())])
+		.select(file_path::select!({
+			materialized_path
+			name
+			is_dir
+			size_in_bytes
+			items_count
+			inode
+			device
+			is_hardlink
+			object_id
+		}))
 		.exec()
+		.await?;
+
+	// The walker doesn't enter directories that no longer exist or that are now rejected by
+	// the indexer rules, so their descendants must be removed along with them
+	let descendants_params = removed
+		.iter()
+		.filter(|file_path| file_path.is_dir)
+		.map(|file_path| {
+			file_path::materialized_path::starts_with(format!(
+				"{}{}/",
+				file_path.materialized_path, file_path.name
+			))
+		})
+		.collect::<Vec<_>>();
+
+	let descendants = if descendants_params.is_empty() {
+		vec![]
+	} else {
+		db.file_path()
+			.find_many(vec![
+				file_path::location_id::equals(location_id),
+				operator::or(descendants_params.clone()),
+			])
+			.select(file_path::select!({ is_dir size_in_bytes inode device is_hardlink object_id }))
+			.exec()
+			.await?
+	};
+
+	// Hardlinks don't count their sizes, and the ones of the files they share an inode with
+	// are moved to them below
+	let removed_files = removed
+		.iter()
+		.map(|file_path| {
+			(
+				file_path.is_dir,
+				file_path.is_hardlink,
+				file_path.size_in_bytes,
+				(file_path.inode, file_path.device),
+			)
+		})
+		.chain(descendants.iter().map(|file_path| {
+			(
+				file_path.is_dir,
+				file_path.is_hardlink,
+				file_path.size_in_bytes,
+				(file_path.inode, file_path.device),
+			)
+		}))
+		.filter(|(is_dir, ..)| !is_dir)
+		.collect::<Vec<_>>();
+
+	let removed_statistics = removed_files
+		.iter()
+		.map(|(_, is_hardlink, size_in_bytes, _)| {
+			FilePathsStatisticsDelta::removed(if *is_hardlink { 0 } else { *size_in_bytes })
+		})
+		.collect();
+
+	// Descendants are already accounted in the sizes of the removed directories
+	let mut directory_sizes = DirectorySizesDeltas::default();
+	for file_path in &removed {
+		directory_sizes.add(
+			&file_path.materialized_path,
+			&file_path.name,
+			DirectorySizeDelta::removed(
+				if file_path.is_hardlink {
+					0
+				} else {
+					file_path.size_in_bytes
+				},
+				file_path.items_count,
+			),
+		);
+	}
+
+	let mut delete_params = vec![file_path::pub_id::in_vec(pub_ids)];
+	if !descendants_params.is_empty() {
+		delete_params.push(operator::and(vec![
+			file_path::location_id::equals(location_id),
+			operator::or(descendants_params),
+		]));
+	}
+
+	let count = db
+		.file_path()
+		.delete_many(vec![operator::or(delete_params)])
+		.exec()
+		.await?;
+
+	update_location_statistics(db, location_id, removed_statistics).await?;
+	directory_sizes.apply(location_id, db).await?;
+
+	remove_orphans_from_kind_statistics(
+		db,
+		removed
+			.iter()
+			.map(|file_path| (file_path.object_id, file_path.size_in_bytes))
+			.chain(
+				descendants
+					.iter()
+					.map(|file_path| (file_path.object_id, file_path.size_in_bytes)),
+			)
+			.filter_map(|(object_id, size_in_bytes)| {
+				object_id.map(|object_id| (object_id, size_in_bytes))
+			}),
+	)
+	.await?;
+
+	promote_hardlinks(
+		location_id,
+		removed_files
+			.into_iter()
+			.filter(|(_, is_hardlink, ..)| !is_hardlink)
+			.map(|(.., inode_and_device)| inode_and_device)
+			.collect(),
+		db,
+	)
+	.await?;
+
+	Ok(count as u64)
+}
+
+/// Removes every symbolic link of a location, along with the descendants of the followed ones,
+/// so they're indexed again after the location starts or stops following them
+pub async fn remove_symlink_file_paths(
+	location_id: LocationId,
+	db: &PrismaClient,
+) -> Result<u64, IndexerError> {
+	remove_non_existing_file_paths(
+		location_id,
+		db.file_path()
+			.find_many(vec![
+				file_path::location_id::equals(location_id),
+				file_path::symlink_target::not(None),
+			])
+			.select(file_path_just_pub_id::select())
+			.exec()
+			.await?,
+		db,
+	)
+	.await
+}
+
+/// When the file path counting the size of an inode is removed, the oldest of its remaining
+/// hardlinks takes its place
+pub(crate) async fn promote_hardlinks(
+	location_id: LocationId,
+	mut removed_inodes: HashSet<(i64, i64)>,
+	db: &PrismaClient,
+) -> Result<(), QueryError> {
+	if removed_inodes.is_empty() {
+		return Ok(());
+	}
+
+	let promoted = db
+		.file_path()
+		.find_many(vec![
+			file_path::location_id::equals(location_id),
+			file_path::is_hardlink::equals(true),
+			file_path::inode::in_vec(removed_inodes.iter().map(|(inode, _)| *inode).collect()),
+			file_path::device::in_vec(removed_inodes.iter().map(|(_, device)| *device).collect()),
+		])
+		.order_by(file_path::id::order(SortOrder::Asc))
+		.select(file_path::select!({ id materialized_path name size_in_bytes inode device }))
+		.exec()
+		.await?
+		.into_iter()
+		.filter(|file_path| removed_inodes.remove(&(file_path.inode, file_path.device)))
+		.collect::<Vec<_>>();
+
+	if promoted.is_empty() {
+		return Ok(());
+	}
+
+	db._batch(
+		promoted
+			.iter()
+			.map(|file_path| {
+				db.file_path().update(
+					file_path::id::equals(file_path.id),
+					vec![file_path::is_hardlink::set(false)],
+				)
+			})
+			.collect::<Vec<_>>(),
+	)
+	.await?;
+
+	let mut directory_sizes = DirectorySizesDeltas::default();
+	for file_path in &promoted {
+		directory_sizes.add(
+			&file_path.materialized_path,
+			&file_path.name,
+			DirectorySizeDelta {
+				bytes: file_path.size_in_bytes,
+				items: 0,
+			},
+		);
+	}
+
+	update_location_statistics(
+		db,
+		location_id,
+		promoted
+			.iter()
+			.map(|file_path| FilePathsStatisticsDelta {
+				count: 0,
+				bytes: file_path.size_in_bytes,
+			})
+			.collect(),
+	)
+	.await?;
+	directory_sizes.apply(location_id, db).await?;
+
+	Ok(())
+}
+
+/// Entry points for the benchmarks in `core/benches`, which can only reach the public API
+#[cfg(feature = "bench")]
+pub mod bench {
+	use super::{walk, IsolatedFilePathData};
+
+	use std::path::Path;
+
+	/// Walks `root` without rules nor database, up to `max_concurrent_dirs` directories at a time,
+	/// and returns how many paths it found
+	pub async fn walk(root: &Path, max_concurrent_dirs: usize) -> usize {
+		walk::walk(
+			root,
+			&[],
+			false,
+			|_, _| {},
+			|_| async { Ok(vec![]) },
+			|_, _| async { Ok(vec![]) },
+			|_| async { Ok(None) },
+			|path, is_dir| IsolatedFilePathData::new(0, root, path, is_dir).map_err(Into::into),
+			u64::MAX,
+			max_concurrent_dirs,
+		)
 		.await
-		.map(|count| count as u64)
-		.map_err(Into::into)
+		.expect("walking without a database doesn't fail")
+		.walked
+		.count()
+	}
 }
 
 // TODO: Change this macro to a fn when we're able to return
@@ -318,3 +858,197 @@ macro_rules! to_remove_db_fetcher_fn {
 		}
 	}};
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		file_paths_db_fetcher_fn,
+		library::refresh_location_statistics,
+		location::file_path_helper::FilePathMetadata,
+		prisma::location_statistics,
+		to_remove_db_fetcher_fn,
+		util::db::{create_test_location, test_db},
+	};
+	use indexer_job::MAX_CONCURRENT_DIRS_WALKS;
+	use rules::{IndexerRule, RulePerKind};
+	use tokio::fs;
+	use uuid::Uuid;
+	use walk::walk;
+
+	#[tokio::test]
+	async fn test_filter_already_indexed() {
+		let (db, data_dir) = test_db().await;
+		let location = create_test_location(&db, data_dir.path()).await;
+
+		let walked_entry = |relative_path: &str, is_dir| WalkedEntry {
+			pub_id: Uuid::new_v4(),
+			iso_file_path: IsolatedFilePathData::new(
+				location.id,
+				data_dir.path(),
+				data_dir.path().join(relative_path),
+				is_dir,
+			)
+			.unwrap(),
+			metadata: FilePathMetadata {
+				inode: 0,
+				device: 0,
+				size_in_bytes: 0,
+				created_at: Utc::now(),
+				modified_at: Utc::now(),
+				permissions: None,
+			},
+			symlink_target: None,
+		};
+
+		// Indexed by the watcher after the indexer walked it
+		let IsolatedFilePathData {
+			materialized_path,
+			name,
+			extension,
+			..
+		} = walked_entry("photos/cat.jpg", false).iso_file_path;
+		db.file_path()
+			.create_unchecked(
+				uuid_to_bytes(Uuid::new_v4()),
+				location.id,
+				materialized_path.to_string(),
+				name.to_string(),
+				extension.to_string(),
+				1,
+				1,
+				vec![],
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		let walked = vec![
+			walked_entry("photos", true),
+			walked_entry("photos/cat.jpg", false),
+			walked_entry("photos/dog.jpg", false),
+			walked_entry("cat.jpg", false),
+		];
+
+		let not_indexed = filter_already_indexed(location.id, &walked, &db)
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|entry| entry.iso_file_path.to_string())
+			.collect::<Vec<_>>();
+
+		assert_eq!(not_indexed, ["photos", "photos/dog.jpg", "cat.jpg"]);
+	}
+
+	#[tokio::test]
+	async fn test_reevaluated_rules_remove_rejected_directories() {
+		let (db, location_dir) = test_db().await;
+		let location_path = location_dir.path().join("location");
+		let location = create_test_location(&db, &location_path).await;
+
+		fs::create_dir_all(location_path.join("src")).await.unwrap();
+		fs::write(location_path.join("src/main.rs"), [0u8; 10])
+			.await
+			.unwrap();
+		fs::create_dir_all(location_path.join("node_modules/react"))
+			.await
+			.unwrap();
+		fs::write(location_path.join("node_modules/react/index.js"), [0u8; 20])
+			.await
+			.unwrap();
+
+		// Indexed before the location had a rule rejecting `node_modules`
+		for (relative_path, is_dir, size_in_bytes, items_count) in [
+			("src", true, 10, 1),
+			("src/main.rs", false, 10, 0),
+			("node_modules", true, 20, 2),
+			("node_modules/react", true, 20, 1),
+			("node_modules/react/index.js", false, 20, 0),
+		] {
+			let IsolatedFilePathData {
+				materialized_path,
+				name,
+				extension,
+				..
+			} = IsolatedFilePathData::new(
+				location.id,
+				&location_path,
+				location_path.join(relative_path),
+				is_dir,
+			)
+			.unwrap();
+
+			db.file_path()
+				.create_unchecked(
+					uuid_to_bytes(Uuid::new_v4()),
+					location.id,
+					materialized_path.to_string(),
+					name.to_string(),
+					extension.to_string(),
+					0,
+					0,
+					vec![
+						file_path::is_dir::set(is_dir),
+						file_path::size_in_bytes::set(size_in_bytes),
+						file_path::items_count::set(items_count),
+					],
+				)
+				.exec()
+				.await
+				.unwrap();
+		}
+		refresh_location_statistics(&db).await.unwrap();
+
+		let rules = [IndexerRule::new(
+			"No node_modules".to_string(),
+			false,
+			vec![RulePerKind::new_reject_files_by_globs_str(["**/node_modules"]).unwrap()],
+		)];
+
+		let walk_result = walk(
+			&location_path,
+			&rules,
+			false,
+			|_, _| {},
+			file_paths_db_fetcher_fn!(&db),
+			to_remove_db_fetcher_fn!(location.id, &location_path, &db),
+			|iso_file_path| fetch_indexed_directory(false, location.id, iso_file_path, &db),
+			iso_file_path_factory(location.id, &location_path),
+			50_000,
+			MAX_CONCURRENT_DIRS_WALKS,
+		)
+		.await
+		.unwrap();
+
+		assert!(walk_result.errors.is_empty());
+		assert_eq!(walk_result.walked.count(), 0);
+
+		let removed_count = remove_non_existing_file_paths(location.id, walk_result.to_remove, &db)
+			.await
+			.unwrap();
+		assert_eq!(removed_count, 3);
+
+		let mut remaining = db
+			.file_path()
+			.find_many(vec![file_path::location_id::equals(location.id)])
+			.select(file_path_to_isolate::select())
+			.exec()
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|file_path| IsolatedFilePathData::from(file_path).to_string())
+			.collect::<Vec<_>>();
+		remaining.sort();
+		assert_eq!(remaining, ["src", "src/main.rs"]);
+
+		let statistics = db
+			.location_statistics()
+			.find_unique(location_statistics::location_id::equals(location.id))
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(statistics.file_path_count, 1);
+		assert_eq!(statistics.total_bytes, 10);
+	}
+}
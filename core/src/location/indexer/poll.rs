@@ -0,0 +1,250 @@
+use crate::{
+	file_paths_db_fetcher_fn, invalidate_query, library::Library,
+	location::size_tree::invalidate_size_tree, to_remove_db_fetcher_fn,
+	volume::directory_mtimes_are_reliable,
+};
+
+use std::{collections::BTreeSet, path::Path};
+
+use itertools::Itertools;
+use tracing::{error, trace};
+
+use super::{
+	execute_indexer_refresh_step, execute_indexer_save_step, fetch_indexed_directory,
+	indexer_job::MAX_CONCURRENT_DIRS_WALKS,
+	iso_file_path_factory, location_with_indexer_rules, remove_non_existing_file_paths,
+	rules::IndexerRule,
+	sort_parents_first,
+	walk::{walk, WalkResult},
+	IndexerError, IndexerJobRefreshStep, IndexerJobSaveStep,
+};
+
+/// BATCH_SIZE is the number of files to index at each step, writing the chunk of files metadata in the database.
+const BATCH_SIZE: usize = 1000;
+
+/// Walks a whole location to find the paths created and removed since it was last indexed, for
+/// filesystems that can't notify us of their changes. New paths are saved and the removed ones
+/// deleted, like the indexer job does, but without the overhead of a job for every poll.
+///
+/// When `incremental`, directories that didn't change since they were indexed are skipped, as long
+/// as their filesystem and the location's indexer rules allow it.
+///
+/// Returns the materialized paths of the directories that received new files, parents first, so
+/// they can be identified next.
+pub async fn poll(
+	location: &location_with_indexer_rules::Data,
+	library: &Library,
+	incremental: bool,
+) -> Result<BTreeSet<String>, IndexerError> {
+	let location_id = location.id;
+	let location_path = Path::new(&location.path);
+
+	let db = library.db.clone();
+
+	let indexer_rules = location
+		.indexer_rules
+		.iter()
+		.map(|rule| IndexerRule::try_from(&rule.indexer_rule))
+		.collect::<Result<Vec<_>, _>>()?;
+
+	let incremental = incremental
+		&& IndexerRule::allow_skipping_unchanged_dirs(&indexer_rules)
+		&& directory_mtimes_are_reliable(location_path);
+
+	let WalkResult {
+		walked,
+		to_remove,
+		to_refresh,
+		errors,
+		..
+	} = walk(
+		location_path,
+		&indexer_rules,
+		location.follow_symlinks,
+		|_, _| {},
+		file_paths_db_fetcher_fn!(&db),
+		to_remove_db_fetcher_fn!(location_id, location_path, &db),
+		|iso_file_path| fetch_indexed_directory(incremental, location_id, iso_file_path, &db),
+		iso_file_path_factory(location_id, location_path),
+		u64::MAX,
+		MAX_CONCURRENT_DIRS_WALKS,
+	)
+	.await?;
+
+	errors.into_iter().for_each(|e| error!("{e}"));
+
+	let removed_count = remove_non_existing_file_paths(location_id, to_remove, &db).await?;
+
+	let walked = sort_parents_first(walked).collect::<Vec<_>>();
+
+	let dirs_with_new_files = walked
+		.iter()
+		.filter(|entry| !entry.iso_file_path.is_dir)
+		.map(|entry| entry.iso_file_path.materialized_path.to_string())
+		.collect::<BTreeSet<_>>();
+
+	let new_count = walked.len();
+
+	for (chunk_idx, chunk) in walked
+		.into_iter()
+		.chunks(BATCH_SIZE)
+		.into_iter()
+		.enumerate()
+	{
+		execute_indexer_save_step(
+			location,
+			&IndexerJobSaveStep {
+				chunk_idx,
+				walked: chunk.collect(),
+			},
+			library,
+		)
+		.await?;
+	}
+
+	if !to_refresh.is_empty() {
+		execute_indexer_refresh_step(
+			&IndexerJobRefreshStep {
+				refreshed: to_refresh,
+			},
+			library,
+		)
+		.await?;
+	}
+
+	if new_count > 0 || removed_count > 0 {
+		trace!(
+			"Polled location <id='{location_id}'>: {new_count} new paths, {removed_count} removed"
+		);
+
+		invalidate_size_tree(library, location_id).await;
+		invalidate_query!(library, "search.paths");
+
+		library.orphan_remover.invoke().await;
+	}
+
+	Ok(dirs_with_new_files)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		library::test_library,
+		location::{
+			file_path_helper::{file_path_to_isolate, IsolatedFilePathData},
+			find_location,
+		},
+		prisma::file_path,
+		util::db::create_test_location,
+	};
+	use tokio::fs;
+
+	async fn indexed_paths(location_id: i32, library: &Library) -> Vec<String> {
+		let mut paths = library
+			.db
+			.file_path()
+			.find_many(vec![file_path::location_id::equals(location_id)])
+			.select(file_path_to_isolate::select())
+			.exec()
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|file_path| IsolatedFilePathData::from(file_path).to_string())
+			.collect::<Vec<_>>();
+		paths.sort();
+		paths
+	}
+
+	#[tokio::test]
+	async fn polls_save_created_paths_and_remove_deleted_ones() {
+		let (library, data_dir) = test_library().await;
+		let location_path = data_dir.path().join("location");
+
+		fs::create_dir_all(location_path.join("docs"))
+			.await
+			.unwrap();
+		fs::write(location_path.join("docs/report.pdf"), [0u8; 10])
+			.await
+			.unwrap();
+		fs::write(location_path.join("notes.txt"), [0u8; 20])
+			.await
+			.unwrap();
+
+		let location_id = create_test_location(&library.db, &location_path).await.id;
+		let location = find_location(&library, location_id)
+			.include(location_with_indexer_rules::include())
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+
+		// Nothing was indexed yet
+		assert_eq!(
+			poll(&location, &library, false).await.unwrap(),
+			BTreeSet::from(["/".to_string(), "/docs/".to_string()])
+		);
+		assert_eq!(
+			indexed_paths(location_id, &library).await,
+			["docs", "docs/report.pdf", "notes.txt"]
+		);
+
+		fs::remove_file(location_path.join("notes.txt"))
+			.await
+			.unwrap();
+		fs::create_dir(location_path.join("photos")).await.unwrap();
+		fs::write(location_path.join("photos/cat.jpg"), [0u8; 30])
+			.await
+			.unwrap();
+
+		assert_eq!(
+			poll(&location, &library, false).await.unwrap(),
+			BTreeSet::from(["/photos/".to_string()])
+		);
+		assert_eq!(
+			indexed_paths(location_id, &library).await,
+			["docs", "docs/report.pdf", "photos", "photos/cat.jpg"]
+		);
+
+		// Polling again without changes finds nothing new
+		assert!(poll(&location, &library, false).await.unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn incremental_polls_read_the_directories_that_changed() {
+		let (library, data_dir) = test_library().await;
+		let location_path = data_dir.path().join("location");
+
+		fs::create_dir_all(location_path.join("docs"))
+			.await
+			.unwrap();
+		fs::create_dir_all(location_path.join("photos"))
+			.await
+			.unwrap();
+
+		let location_id = create_test_location(&library.db, &location_path).await.id;
+		let location = find_location(&library, location_id)
+			.include(location_with_indexer_rules::include())
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+
+		poll(&location, &library, true).await.unwrap();
+
+		// So the new modification time of the directory differs, even on coarse filesystems
+		tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+		fs::write(location_path.join("photos/cat.jpg"), [0u8; 30])
+			.await
+			.unwrap();
+
+		assert_eq!(
+			poll(&location, &library, true).await.unwrap(),
+			BTreeSet::from(["/photos/".to_string()])
+		);
+		assert_eq!(
+			indexed_paths(location_id, &library).await,
+			["docs", "photos", "photos/cat.jpg"]
+		);
+	}
+}
@@ -0,0 +1,269 @@
+use crate::location::{
+	file_path_helper::{ensure_sub_path_is_directory, ensure_sub_path_is_in_location},
+	LocationId,
+};
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use specta::Type;
+use tracing::error;
+
+use super::{
+	iso_file_path_factory,
+	rules::{IndexerRule, RuleKind},
+	walk::{walk_preview, PreviewWalkResult, RejectedEntry},
+	IndexerError,
+};
+
+/// We stop walking after this many paths, a preview doesn't need the whole location to be useful
+const PREVIEW_WALK_LIMIT: u64 = 100_000;
+
+/// A path found while previewing indexer rules, with the kind of rule that decided its fate and
+/// the names of the rules of that kind which agreed with the decision
+#[derive(Serialize, Type, Debug)]
+pub struct PreviewedPath {
+	/// Relative to the location root
+	pub path: String,
+	pub rule_kind: Option<RuleKind>,
+	pub rule_names: Vec<String>,
+}
+
+/// Result of walking a location with a set of indexer rules without saving anything.
+/// The children of rejected directories aren't walked, so they aren't counted.
+#[derive(Serialize, Type, Debug)]
+pub struct IndexerRulesPreview {
+	pub accepted_count: u32,
+	pub rejected_count: u32,
+	pub accepted_sample: Vec<PreviewedPath>,
+	pub rejected_sample: Vec<PreviewedPath>,
+	/// If the walk stopped before reaching every directory, so counts are partial
+	pub truncated: bool,
+	pub errors_count: u32,
+}
+
+pub async fn preview_indexer_rules(
+	location_id: LocationId,
+	location_path: impl AsRef<Path>,
+	sub_path: Option<impl AsRef<Path>>,
+	indexer_rules: &[IndexerRule],
+	follow_symlinks: bool,
+	sample_size: usize,
+) -> Result<IndexerRulesPreview, IndexerError> {
+	let location_path = location_path.as_ref();
+
+	let to_walk_path = match sub_path {
+		Some(sub_path) if sub_path.as_ref() != Path::new("") => {
+			let full_path = ensure_sub_path_is_in_location(location_path, &sub_path).await?;
+			ensure_sub_path_is_directory(location_path, &sub_path).await?;
+
+			full_path
+		}
+		_ => location_path.to_path_buf(),
+	};
+
+	let PreviewWalkResult {
+		accepted,
+		mut rejected,
+		truncated,
+		errors,
+	} = walk_preview(
+		&to_walk_path,
+		indexer_rules,
+		follow_symlinks,
+		iso_file_path_factory(location_id, location_path),
+		PREVIEW_WALK_LIMIT,
+	)
+	.await;
+
+	errors.iter().for_each(|e| error!("{e}"));
+
+	let mut accepted = accepted
+		.into_iter()
+		.map(|iso_file_path| iso_file_path.to_string())
+		.collect::<Vec<_>>();
+	accepted.sort();
+	rejected.sort_by(|a, b| a.path.cmp(&b.path));
+
+	let mut accepted_sample = Vec::with_capacity(sample_size.min(accepted.len()));
+	for path in accepted.iter().take(sample_size) {
+		let (rule_kind, rule_names) =
+			accepting_rules(indexer_rules, location_path.join(path)).await;
+
+		accepted_sample.push(PreviewedPath {
+			path: path.clone(),
+			rule_kind,
+			rule_names,
+		});
+	}
+
+	let mut rejected_sample = Vec::with_capacity(sample_size.min(rejected.len()));
+	for RejectedEntry { path, kind } in rejected.iter().take(sample_size) {
+		rejected_sample.push(PreviewedPath {
+			path: relative_path(location_path, path),
+			rule_kind: Some(*kind),
+			rule_names: rejecting_rules(indexer_rules, path, *kind).await,
+		});
+	}
+
+	Ok(IndexerRulesPreview {
+		accepted_count: accepted.len() as u32,
+		rejected_count: rejected.len() as u32,
+		accepted_sample,
+		rejected_sample,
+		truncated,
+		errors_count: errors.len() as u32,
+	})
+}
+
+fn relative_path(location_path: &Path, path: &Path) -> String {
+	path.strip_prefix(location_path)
+		.unwrap_or(path)
+		.to_string_lossy()
+		.to_string()
+}
+
+/// The first accept kind that some rule passed for this path, if any accept rule applies
+async fn accepting_rules(
+	indexer_rules: &[IndexerRule],
+	path: PathBuf,
+) -> (Option<RuleKind>, Vec<String>) {
+	let mut rule_kind = None;
+	let mut rule_names = vec![];
+
+	for rule in indexer_rules {
+		let Ok(results) = rule.apply(&path).await else {
+			continue;
+		};
+
+		if let Some((kind, _)) = results.into_iter().find(|(kind, accepted)| {
+			*accepted
+				&& matches!(
+					kind,
+					RuleKind::AcceptFilesByGlob
+						| RuleKind::AcceptFilesBySize
+						| RuleKind::AcceptFilesByAge
+						| RuleKind::AcceptFilesByAttributes
+						| RuleKind::AcceptFilesByExtendedAttributes
+						| RuleKind::AcceptIfChildrenDirectoriesArePresent
+				)
+		}) {
+			rule_kind.get_or_insert(kind);
+			rule_names.push(rule.name.clone());
+		}
+	}
+
+	(rule_kind, rule_names)
+}
+
+/// The rules that rejected this path with `kind`. Some kinds, like the ignore files one, are
+/// decided by the walker, so we fallback to every rule with that kind.
+async fn rejecting_rules(
+	indexer_rules: &[IndexerRule],
+	path: &Path,
+	kind: RuleKind,
+) -> Vec<String> {
+	let mut rule_names = vec![];
+	let mut rules_with_kind = vec![];
+
+	for rule in indexer_rules {
+		let Ok(results) = rule.apply(path).await else {
+			continue;
+		};
+
+		if results.iter().any(|(result_kind, _)| *result_kind == kind) {
+			rules_with_kind.push(rule.name.clone());
+		}
+
+		if results
+			.iter()
+			.any(|(result_kind, passed)| *result_kind == kind && !passed)
+		{
+			rule_names.push(rule.name.clone());
+		}
+	}
+
+	if rule_names.is_empty() {
+		rules_with_kind
+	} else {
+		rule_names
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::rules::RulePerKind;
+	use super::*;
+	use tempfile::tempdir;
+	use tokio::fs;
+
+	#[tokio::test]
+	async fn test_preview_samples_with_deciding_rules() {
+		let root = tempdir().unwrap();
+		let root_path = root.path();
+
+		fs::create_dir(root_path.join("photos")).await.unwrap();
+		fs::write(root_path.join("photos/photo.png"), b"")
+			.await
+			.unwrap();
+		fs::write(root_path.join("photos/notes.txt"), b"")
+			.await
+			.unwrap();
+		fs::write(root_path.join("debug.log"), b"").await.unwrap();
+
+		let indexer_rules = [
+			IndexerRule::new(
+				"only photos".to_string(),
+				false,
+				vec![RulePerKind::from_kind_and_parameters(
+					RuleKind::AcceptFilesByGlob,
+					vec!["**/*.png".to_string()],
+				)
+				.unwrap()],
+			),
+			IndexerRule::new(
+				"no logs".to_string(),
+				false,
+				vec![RulePerKind::from_kind_and_parameters(
+					RuleKind::RejectFilesByGlob,
+					vec!["**/*.log".to_string()],
+				)
+				.unwrap()],
+			),
+		];
+
+		let preview = preview_indexer_rules(0, root_path, None::<&str>, &indexer_rules, 10)
+			.await
+			.unwrap();
+
+		assert_eq!(preview.errors_count, 0);
+		assert!(!preview.truncated);
+
+		assert_eq!(preview.accepted_count, 2);
+		let photo = preview
+			.accepted_sample
+			.iter()
+			.find(|previewed| previewed.path == "photos/photo.png")
+			.unwrap();
+		assert_eq!(photo.rule_kind, Some(RuleKind::AcceptFilesByGlob));
+		assert_eq!(photo.rule_names, vec!["only photos".to_string()]);
+
+		// The photos directory didn't pass the accept rule, but it's indexed for its child
+		assert_eq!(preview.rejected_count, 2);
+		let log = preview
+			.rejected_sample
+			.iter()
+			.find(|previewed| previewed.path == "debug.log")
+			.unwrap();
+		assert_eq!(log.rule_kind, Some(RuleKind::RejectFilesByGlob));
+		assert_eq!(log.rule_names, vec!["no logs".to_string()]);
+
+		let notes = preview
+			.rejected_sample
+			.iter()
+			.find(|previewed| previewed.path == "photos/notes.txt")
+			.unwrap();
+		assert_eq!(notes.rule_kind, Some(RuleKind::AcceptFilesByGlob));
+		assert_eq!(notes.rule_names, vec!["only photos".to_string()]);
+	}
+}
@@ -1,5 +1,6 @@
 use crate::{
 	library::Library,
+	location::file_path_helper::MetadataExt,
 	prisma::{indexer_rule, PrismaClient},
 	util::error::{FileIOError, NonUtf8PathError},
 };
@@ -13,11 +14,14 @@ use serde::{de, ser, Deserialize, Serialize};
 use specta::Type;
 use std::{
 	collections::{HashMap, HashSet},
+	fs::Metadata,
 	marker::PhantomData,
 	path::Path,
+	str::FromStr,
+	time::SystemTime,
 };
 use thiserror::Error;
-use tokio::fs;
+use tokio::{fs, task};
 use tracing::debug;
 
 #[derive(Error, Debug)]
@@ -25,10 +29,24 @@ pub enum IndexerRuleError {
 	// User errors
 	#[error("invalid indexer rule kind integer: {0}")]
 	InvalidRuleKindInt(i32),
-	#[error("glob builder error")]
+	#[error("glob builder error: {0}")]
 	Glob(#[from] globset::Error),
 	#[error(transparent)]
 	NonUtf8Path(#[from] NonUtf8PathError),
+	#[error("invalid ignore file")]
+	IgnoreFile(#[from] ignore::Error),
+	#[error("invalid parameters for indexer rule kind {kind:?}: {reason}")]
+	InvalidRuleParameters { kind: RuleKind, reason: String },
+	#[error("indexer rule not found: <id='{0}'>")]
+	NotFound(i32),
+	#[error("default indexer rules can't be edited: <id='{0}'>")]
+	DefaultRuleNotEditable(i32),
+	#[error("invalid JSON rules document: {0}")]
+	RulesDocumentJson(#[from] serde_json::Error),
+	#[error("invalid TOML rules document: {0}")]
+	RulesDocumentTomlDecode(#[from] toml::de::Error),
+	#[error("unsupported rules document version: {0}")]
+	UnsupportedRulesDocumentVersion(u32),
 
 	// Internal Errors
 	#[error("indexer rule parameters encode error")]
@@ -39,6 +57,16 @@ pub enum IndexerRuleError {
 	AcceptByItsChildrenFileIO(FileIOError),
 	#[error("reject by its children file I/O error")]
 	RejectByItsChildrenFileIO(FileIOError),
+	#[error("ignore file I/O error")]
+	IgnoreFileIO(FileIOError),
+	#[error("file metadata I/O error")]
+	FileMetadataIO(FileIOError),
+	#[error("extended attributes I/O error")]
+	ExtendedAttributesIO(FileIOError),
+	#[error("extended attributes task error")]
+	ExtendedAttributesTask(#[from] task::JoinError),
+	#[error("rules document TOML encode error")]
+	RulesDocumentTomlEncode(#[from] toml::ser::Error),
 	#[error("database error")]
 	Database(#[from] prisma_client_rust::QueryError),
 }
@@ -48,10 +76,22 @@ impl From<IndexerRuleError> for rspc::Error {
 		match err {
 			IndexerRuleError::InvalidRuleKindInt(_)
 			| IndexerRuleError::Glob(_)
-			| IndexerRuleError::NonUtf8Path(_) => {
+			| IndexerRuleError::NonUtf8Path(_)
+			| IndexerRuleError::InvalidRuleParameters { .. }
+			| IndexerRuleError::RulesDocumentJson(_)
+			| IndexerRuleError::RulesDocumentTomlDecode(_)
+			| IndexerRuleError::UnsupportedRulesDocumentVersion(_) => {
 				rspc::Error::with_cause(ErrorCode::BadRequest, err.to_string(), err)
 			}
 
+			IndexerRuleError::NotFound(_) => {
+				rspc::Error::with_cause(ErrorCode::NotFound, err.to_string(), err)
+			}
+
+			IndexerRuleError::DefaultRuleNotEditable(_) => {
+				rspc::Error::with_cause(ErrorCode::Forbidden, err.to_string(), err)
+			}
+
 			_ => rspc::Error::with_cause(ErrorCode::InternalServerError, err.to_string(), err),
 		}
 	}
@@ -65,6 +105,20 @@ impl From<IndexerRuleError> for rspc::Error {
 ///
 /// In case of `RuleKind::AcceptIfChildrenDirectoriesArePresent` or `RuleKind::RejectIfChildrenDirectoriesArePresent` the
 /// `parameters` field must be a vector of strings containing the names of the directories.
+///
+/// In case of `RuleKind::RejectByIgnoreFiles` the `parameters` field must be a vector of strings
+/// containing the names of the ignore files, like `.gitignore` or `.sdignore`.
+///
+/// In case of `RuleKind::AcceptFilesBySize` or `RuleKind::RejectFilesBySize` the `parameters` field
+/// must contain the minimum and maximum size in bytes, and in case of `RuleKind::AcceptFilesByAge` or
+/// `RuleKind::RejectFilesByAge` the minimum and maximum time since the last modification in seconds.
+/// An empty string means that the range is unbounded on that side.
+///
+/// In case of `RuleKind::AcceptFilesByAttributes` or `RuleKind::RejectFilesByAttributes` the
+/// `parameters` field must contain `hidden` and/or `system`.
+///
+/// In case of `RuleKind::AcceptFilesByExtendedAttributes` or `RuleKind::RejectFilesByExtendedAttributes`
+/// the `parameters` field must contain the names of the extended attributes, like `user.sd.ignore`.
 #[derive(Type, Deserialize)]
 pub struct IndexerRuleCreateArgs {
 	pub name: String,
@@ -92,24 +146,7 @@ impl IndexerRuleCreateArgs {
 			&self
 				.rules
 				.into_iter()
-				.map(|(kind, parameters)| match kind {
-					RuleKind::AcceptFilesByGlob => {
-						RulePerKind::new_accept_files_by_globs_str(parameters)
-					}
-					RuleKind::RejectFilesByGlob => {
-						RulePerKind::new_reject_files_by_globs_str(parameters)
-					}
-					RuleKind::AcceptIfChildrenDirectoriesArePresent => {
-						Ok(RulePerKind::AcceptIfChildrenDirectoriesArePresent(
-							parameters.into_iter().collect(),
-						))
-					}
-					RuleKind::RejectIfChildrenDirectoriesArePresent => {
-						Ok(RulePerKind::RejectIfChildrenDirectoriesArePresent(
-							parameters.into_iter().collect(),
-						))
-					}
-				})
+				.map(|(kind, parameters)| RulePerKind::from_kind_and_parameters(kind, parameters))
 				.collect::<Result<Vec<_>, _>>()?,
 		)?;
 
@@ -128,6 +165,63 @@ impl IndexerRuleCreateArgs {
 	}
 }
 
+/// `IndexerRuleUpdateArgs` is the argument received from the client using rspc to edit an indexer rule.
+/// When `rules` is present, it replaces all the current rules, using the same format of
+/// [`IndexerRuleCreateArgs`].
+#[derive(Type, Deserialize)]
+pub struct IndexerRuleUpdateArgs {
+	pub id: i32,
+	pub name: Option<String>,
+	pub rules: Option<Vec<(RuleKind, Vec<String>)>>,
+}
+
+impl IndexerRuleUpdateArgs {
+	pub async fn update(self, library: &Library) -> Result<indexer_rule::Data, IndexerRuleError> {
+		debug!(
+			"Trying to update indexer rule (id = {}, name = {:?}, params = {:?})",
+			self.id, self.name, self.rules
+		);
+
+		let indexer_rule = library
+			.db
+			.indexer_rule()
+			.find_unique(indexer_rule::id::equals(self.id))
+			.select(indexer_rule::select!({ default }))
+			.exec()
+			.await?
+			.ok_or(IndexerRuleError::NotFound(self.id))?;
+
+		if indexer_rule.default {
+			return Err(IndexerRuleError::DefaultRuleNotEditable(self.id));
+		}
+
+		let mut params = vec![indexer_rule::date_modified::set(Utc::now().into())];
+
+		if let Some(name) = self.name {
+			params.push(indexer_rule::name::set(name));
+		}
+
+		if let Some(rules) = self.rules {
+			params.push(indexer_rule::rules_per_kind::set(rmp_serde::to_vec_named(
+				&rules
+					.into_iter()
+					.map(|(kind, parameters)| {
+						RulePerKind::from_kind_and_parameters(kind, parameters)
+					})
+					.collect::<Result<Vec<_>, _>>()?,
+			)?));
+		}
+
+		library
+			.db
+			.indexer_rule()
+			.update(indexer_rule::id::equals(self.id), params)
+			.exec()
+			.await
+			.map_err(Into::into)
+	}
+}
+
 #[repr(i32)]
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Type, Eq, PartialEq, Hash)]
@@ -136,15 +230,181 @@ pub enum RuleKind {
 	RejectFilesByGlob = 1,
 	AcceptIfChildrenDirectoriesArePresent = 2,
 	RejectIfChildrenDirectoriesArePresent = 3,
+	RejectByIgnoreFiles = 4,
+	AcceptFilesBySize = 5,
+	RejectFilesBySize = 6,
+	AcceptFilesByAge = 7,
+	RejectFilesByAge = 8,
+	AcceptFilesByAttributes = 9,
+	RejectFilesByAttributes = 10,
+	AcceptFilesByExtendedAttributes = 11,
+	RejectFilesByExtendedAttributes = 12,
 }
 
 impl RuleKind {
 	pub const fn variant_count() -> usize {
 		// TODO: Use https://doc.rust-lang.org/std/mem/fn.variant_count.html if it ever gets stabilized
-		4
+		13
+	}
+}
+
+/// Inclusive range used by the rules on numeric properties of files, a missing bound
+/// means that the range is unbounded on that side
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RuleRange {
+	pub min: Option<u64>,
+	pub max: Option<u64>,
+}
+
+impl RuleRange {
+	pub fn contains(&self, value: u64) -> bool {
+		self.min.map_or(true, |min| value >= min) && self.max.map_or(true, |max| value <= max)
+	}
+
+	fn from_parameters(kind: RuleKind, parameters: &[String]) -> Result<Self, IndexerRuleError> {
+		let invalid = |reason: String| IndexerRuleError::InvalidRuleParameters { kind, reason };
+
+		let parse_bound = |bound: Option<&String>| {
+			bound
+				.map(|bound| bound.trim())
+				.filter(|bound| !bound.is_empty())
+				.map(|bound| {
+					bound
+						.parse::<u64>()
+						.map_err(|e| invalid(format!("invalid bound '{bound}': {e}")))
+				})
+				.transpose()
+		};
+
+		if parameters.len() > 2 {
+			return Err(invalid(format!(
+				"expected at most 2 bounds, received {}",
+				parameters.len()
+			)));
+		}
+
+		let range = Self {
+			min: parse_bound(parameters.first())?,
+			max: parse_bound(parameters.get(1))?,
+		};
+
+		match range {
+			Self {
+				min: Some(min),
+				max: Some(max),
+			} if min > max => Err(invalid(format!(
+				"minimum {min} is bigger than maximum {max}"
+			))),
+			Self {
+				min: None,
+				max: None,
+			} => Err(invalid("at least one bound is required".to_string())),
+			range => Ok(range),
+		}
+	}
+}
+
+/// Attributes that the operating system uses to keep files out of sight of users
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum FileAttribute {
+	/// Dotfiles on Unix, `FILE_ATTRIBUTE_HIDDEN` on Windows and the `UF_HIDDEN` flag on macOS
+	Hidden,
+	/// `FILE_ATTRIBUTE_SYSTEM` on Windows, no other platform has it
+	System,
+}
+
+impl FromStr for FileAttribute {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_lowercase().as_str() {
+			"hidden" => Ok(Self::Hidden),
+			"system" => Ok(Self::System),
+			_ => Err(format!("unknown file attribute '{s}'")),
+		}
 	}
 }
 
+impl FileAttribute {
+	fn from_parameters(
+		kind: RuleKind,
+		parameters: &[String],
+	) -> Result<HashSet<Self>, IndexerRuleError> {
+		if parameters.is_empty() {
+			return Err(IndexerRuleError::InvalidRuleParameters {
+				kind,
+				reason: "at least one attribute is required".to_string(),
+			});
+		}
+
+		parameters
+			.iter()
+			.map(|parameter| parameter.parse())
+			.collect::<Result<_, _>>()
+			.map_err(|reason| IndexerRuleError::InvalidRuleParameters { kind, reason })
+	}
+
+	fn is_set(&self, source: &Path, metadata: &Metadata) -> bool {
+		match self {
+			Self::Hidden => {
+				#[cfg(target_family = "unix")]
+				let is_dotfile = source
+					.file_name()
+					.and_then(|name| name.to_str())
+					.map_or(false, |name| name.starts_with('.'));
+
+				#[cfg(not(target_family = "unix"))]
+				let is_dotfile = {
+					let _ = source; // To avoid unused variable warning
+					false
+				};
+
+				#[cfg(target_os = "macos")]
+				let has_hidden_flag = {
+					use std::os::macos::fs::MetadataExt;
+
+					const UF_HIDDEN: u32 = 0x8000;
+					metadata.st_flags() & UF_HIDDEN != 0
+				};
+
+				#[cfg(target_os = "windows")]
+				let has_hidden_flag = {
+					use std::os::windows::fs::MetadataExt;
+
+					const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+					metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+				};
+
+				#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+				let has_hidden_flag = {
+					let _ = metadata; // To avoid unused variable warning
+					false
+				};
+
+				is_dotfile || has_hidden_flag
+			}
+			Self::System => {
+				#[cfg(target_os = "windows")]
+				{
+					use std::os::windows::fs::MetadataExt;
+
+					const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+					metadata.file_attributes() & FILE_ATTRIBUTE_SYSTEM != 0
+				}
+
+				#[cfg(not(target_os = "windows"))]
+				{
+					let _ = (source, metadata); // To avoid unused variable warning
+					false
+				}
+			}
+		}
+	}
+}
+
+/// Spacedrive's own ignore file, using the same syntax as `.gitignore` files
+pub const SD_IGNORE_FILE_NAME: &str = ".sdignore";
+
 /// `ParametersPerKind` is a mapping from `RuleKind` to the parameters required for each kind of rule.
 /// In case of doubt about globs, consult <https://docs.rs/globset/latest/globset/#syntax>
 ///
@@ -152,18 +412,79 @@ impl RuleKind {
 ///
 /// In case of `ParametersPerKind::AcceptIfChildrenDirectoriesArePresent` or `ParametersPerKind::RejectIfChildrenDirectoriesArePresent`
 /// first we change the data structure to a vector, then we serialize it.
+///
+/// In case of `ParametersPerKind::RejectByIgnoreFiles` we store the names of the ignore files, the
+/// walker looks for them in every directory and applies them to everything below that directory,
+/// following the `.gitignore` semantics (<https://git-scm.com/docs/gitignore>). When a directory has
+/// more than one of these files, the ones later in the list take precedence.
+///
+/// Rules on sizes and ages only apply to files, directories always pass them. Rules on attributes
+/// apply to directories too, and rejecting a directory also rejects its children.
 #[derive(Debug)]
 pub enum RulePerKind {
-	// TODO: Add an indexer rule that filter files based on their extended attributes
-	// https://learn.microsoft.com/en-us/windows/win32/fileio/file-attribute-constants
-	// https://en.wikipedia.org/wiki/Extended_file_attributes
 	AcceptFilesByGlob(Vec<Glob>, GlobSet),
 	RejectFilesByGlob(Vec<Glob>, GlobSet),
 	AcceptIfChildrenDirectoriesArePresent(HashSet<String>),
 	RejectIfChildrenDirectoriesArePresent(HashSet<String>),
+	RejectByIgnoreFiles(Vec<String>),
+	AcceptFilesBySize(RuleRange),
+	RejectFilesBySize(RuleRange),
+	/// Age is the time since the last modification, in seconds
+	AcceptFilesByAge(RuleRange),
+	RejectFilesByAge(RuleRange),
+	// https://learn.microsoft.com/en-us/windows/win32/fileio/file-attribute-constants
+	AcceptFilesByAttributes(HashSet<FileAttribute>),
+	RejectFilesByAttributes(HashSet<FileAttribute>),
+	// https://en.wikipedia.org/wiki/Extended_file_attributes
+	AcceptFilesByExtendedAttributes(HashSet<String>),
+	RejectFilesByExtendedAttributes(HashSet<String>),
 }
 
 impl RulePerKind {
+	/// Builds a rule from the parameters received from the frontend, see [`IndexerRuleCreateArgs`]
+	pub fn from_kind_and_parameters(
+		kind: RuleKind,
+		parameters: Vec<String>,
+	) -> Result<Self, IndexerRuleError> {
+		match kind {
+			RuleKind::AcceptFilesByGlob => RulePerKind::new_accept_files_by_globs_str(parameters),
+			RuleKind::RejectFilesByGlob => RulePerKind::new_reject_files_by_globs_str(parameters),
+			RuleKind::AcceptIfChildrenDirectoriesArePresent => {
+				Ok(RulePerKind::AcceptIfChildrenDirectoriesArePresent(
+					parameters.into_iter().collect(),
+				))
+			}
+			RuleKind::RejectIfChildrenDirectoriesArePresent => {
+				Ok(RulePerKind::RejectIfChildrenDirectoriesArePresent(
+					parameters.into_iter().collect(),
+				))
+			}
+			RuleKind::RejectByIgnoreFiles => Ok(RulePerKind::RejectByIgnoreFiles(parameters)),
+			RuleKind::AcceptFilesBySize => {
+				RuleRange::from_parameters(kind, &parameters).map(RulePerKind::AcceptFilesBySize)
+			}
+			RuleKind::RejectFilesBySize => {
+				RuleRange::from_parameters(kind, &parameters).map(RulePerKind::RejectFilesBySize)
+			}
+			RuleKind::AcceptFilesByAge => {
+				RuleRange::from_parameters(kind, &parameters).map(RulePerKind::AcceptFilesByAge)
+			}
+			RuleKind::RejectFilesByAge => {
+				RuleRange::from_parameters(kind, &parameters).map(RulePerKind::RejectFilesByAge)
+			}
+			RuleKind::AcceptFilesByAttributes => FileAttribute::from_parameters(kind, &parameters)
+				.map(RulePerKind::AcceptFilesByAttributes),
+			RuleKind::RejectFilesByAttributes => FileAttribute::from_parameters(kind, &parameters)
+				.map(RulePerKind::RejectFilesByAttributes),
+			RuleKind::AcceptFilesByExtendedAttributes => Ok(
+				RulePerKind::AcceptFilesByExtendedAttributes(parameters.into_iter().collect()),
+			),
+			RuleKind::RejectFilesByExtendedAttributes => Ok(
+				RulePerKind::RejectFilesByExtendedAttributes(parameters.into_iter().collect()),
+			),
+		}
+	}
+
 	fn new_files_by_globs_str_and_kind(
 		globs_str: impl IntoIterator<Item = impl AsRef<str>>,
 		kind_fn: impl Fn(Vec<Glob>, GlobSet) -> Self,
@@ -225,6 +546,65 @@ impl Serialize for RulePerKind {
 					"RejectIfChildrenDirectoriesArePresent",
 					children,
 				),
+			RulePerKind::RejectByIgnoreFiles(ref file_names) => serializer
+				.serialize_newtype_variant(
+					"ParametersPerKind",
+					4,
+					"RejectByIgnoreFiles",
+					file_names,
+				),
+			RulePerKind::AcceptFilesBySize(ref range) => serializer.serialize_newtype_variant(
+				"ParametersPerKind",
+				5,
+				"AcceptFilesBySize",
+				range,
+			),
+			RulePerKind::RejectFilesBySize(ref range) => serializer.serialize_newtype_variant(
+				"ParametersPerKind",
+				6,
+				"RejectFilesBySize",
+				range,
+			),
+			RulePerKind::AcceptFilesByAge(ref range) => serializer.serialize_newtype_variant(
+				"ParametersPerKind",
+				7,
+				"AcceptFilesByAge",
+				range,
+			),
+			RulePerKind::RejectFilesByAge(ref range) => serializer.serialize_newtype_variant(
+				"ParametersPerKind",
+				8,
+				"RejectFilesByAge",
+				range,
+			),
+			RulePerKind::AcceptFilesByAttributes(ref attributes) => serializer
+				.serialize_newtype_variant(
+					"ParametersPerKind",
+					9,
+					"AcceptFilesByAttributes",
+					attributes,
+				),
+			RulePerKind::RejectFilesByAttributes(ref attributes) => serializer
+				.serialize_newtype_variant(
+					"ParametersPerKind",
+					10,
+					"RejectFilesByAttributes",
+					attributes,
+				),
+			RulePerKind::AcceptFilesByExtendedAttributes(ref names) => serializer
+				.serialize_newtype_variant(
+					"ParametersPerKind",
+					11,
+					"AcceptFilesByExtendedAttributes",
+					names,
+				),
+			RulePerKind::RejectFilesByExtendedAttributes(ref names) => serializer
+				.serialize_newtype_variant(
+					"ParametersPerKind",
+					12,
+					"RejectFilesByExtendedAttributes",
+					names,
+				),
 		}
 	}
 }
@@ -239,6 +619,15 @@ impl<'de> Deserialize<'de> for RulePerKind {
 			"RejectFilesByGlob",
 			"AcceptIfChildrenDirectoriesArePresent",
 			"RejectIfChildrenDirectoriesArePresent",
+			"RejectByIgnoreFiles",
+			"AcceptFilesBySize",
+			"RejectFilesBySize",
+			"AcceptFilesByAge",
+			"RejectFilesByAge",
+			"AcceptFilesByAttributes",
+			"RejectFilesByAttributes",
+			"AcceptFilesByExtendedAttributes",
+			"RejectFilesByExtendedAttributes",
 		];
 
 		enum Fields {
@@ -246,6 +635,15 @@ impl<'de> Deserialize<'de> for RulePerKind {
 			RejectFilesByGlob,
 			AcceptIfChildrenDirectoriesArePresent,
 			RejectIfChildrenDirectoriesArePresent,
+			RejectByIgnoreFiles,
+			AcceptFilesBySize,
+			RejectFilesBySize,
+			AcceptFilesByAge,
+			RejectFilesByAge,
+			AcceptFilesByAttributes,
+			RejectFilesByAttributes,
+			AcceptFilesByExtendedAttributes,
+			RejectFilesByExtendedAttributes,
 		}
 
 		struct FieldsVisitor;
@@ -258,7 +656,16 @@ impl<'de> Deserialize<'de> for RulePerKind {
 					"`AcceptFilesByGlob` \
 				or `RejectFilesByGlob` \
 				or `AcceptIfChildrenDirectoriesArePresent` \
-				or `RejectIfChildrenDirectoriesArePresent`",
+				or `RejectIfChildrenDirectoriesArePresent` \
+				or `RejectByIgnoreFiles` \
+				or `AcceptFilesBySize` \
+				or `RejectFilesBySize` \
+				or `AcceptFilesByAge` \
+				or `RejectFilesByAge` \
+				or `AcceptFilesByAttributes` \
+				or `RejectFilesByAttributes` \
+				or `AcceptFilesByExtendedAttributes` \
+				or `RejectFilesByExtendedAttributes`",
 				)
 			}
 
@@ -271,9 +678,18 @@ impl<'de> Deserialize<'de> for RulePerKind {
 					1 => Ok(Fields::RejectFilesByGlob),
 					2 => Ok(Fields::AcceptIfChildrenDirectoriesArePresent),
 					3 => Ok(Fields::RejectIfChildrenDirectoriesArePresent),
+					4 => Ok(Fields::RejectByIgnoreFiles),
+					5 => Ok(Fields::AcceptFilesBySize),
+					6 => Ok(Fields::RejectFilesBySize),
+					7 => Ok(Fields::AcceptFilesByAge),
+					8 => Ok(Fields::RejectFilesByAge),
+					9 => Ok(Fields::AcceptFilesByAttributes),
+					10 => Ok(Fields::RejectFilesByAttributes),
+					11 => Ok(Fields::AcceptFilesByExtendedAttributes),
+					12 => Ok(Fields::RejectFilesByExtendedAttributes),
 					_ => Err(de::Error::invalid_value(
 						de::Unexpected::Unsigned(value),
-						&"variant index 0 <= i < 3",
+						&"variant index 0 <= i < 13",
 					)),
 				}
 			}
@@ -290,6 +706,19 @@ impl<'de> Deserialize<'de> for RulePerKind {
 					"RejectIfChildrenDirectoriesArePresent" => {
 						Ok(Fields::RejectIfChildrenDirectoriesArePresent)
 					}
+					"RejectByIgnoreFiles" => Ok(Fields::RejectByIgnoreFiles),
+					"AcceptFilesBySize" => Ok(Fields::AcceptFilesBySize),
+					"RejectFilesBySize" => Ok(Fields::RejectFilesBySize),
+					"AcceptFilesByAge" => Ok(Fields::AcceptFilesByAge),
+					"RejectFilesByAge" => Ok(Fields::RejectFilesByAge),
+					"AcceptFilesByAttributes" => Ok(Fields::AcceptFilesByAttributes),
+					"RejectFilesByAttributes" => Ok(Fields::RejectFilesByAttributes),
+					"AcceptFilesByExtendedAttributes" => {
+						Ok(Fields::AcceptFilesByExtendedAttributes)
+					}
+					"RejectFilesByExtendedAttributes" => {
+						Ok(Fields::RejectFilesByExtendedAttributes)
+					}
 					_ => Err(de::Error::unknown_variant(value, VARIANTS)),
 				}
 			}
@@ -306,6 +735,19 @@ impl<'de> Deserialize<'de> for RulePerKind {
 					b"RejectIfChildrenDirectoriesArePresent" => {
 						Ok(Fields::RejectIfChildrenDirectoriesArePresent)
 					}
+					b"RejectByIgnoreFiles" => Ok(Fields::RejectByIgnoreFiles),
+					b"AcceptFilesBySize" => Ok(Fields::AcceptFilesBySize),
+					b"RejectFilesBySize" => Ok(Fields::RejectFilesBySize),
+					b"AcceptFilesByAge" => Ok(Fields::AcceptFilesByAge),
+					b"RejectFilesByAge" => Ok(Fields::RejectFilesByAge),
+					b"AcceptFilesByAttributes" => Ok(Fields::AcceptFilesByAttributes),
+					b"RejectFilesByAttributes" => Ok(Fields::RejectFilesByAttributes),
+					b"AcceptFilesByExtendedAttributes" => {
+						Ok(Fields::AcceptFilesByExtendedAttributes)
+					}
+					b"RejectFilesByExtendedAttributes" => {
+						Ok(Fields::RejectFilesByExtendedAttributes)
+					}
 					_ => Err(de::Error::unknown_variant(
 						&String::from_utf8_lossy(bytes),
 						VARIANTS,
@@ -343,38 +785,20 @@ impl<'de> Deserialize<'de> for RulePerKind {
 				use de::Error;
 
 				de::EnumAccess::variant(data).and_then(|value| match value {
+					// Globs are parsed from strings so they're validated like the ones received
+					// from the frontend, as they can come from imported rule sets too
 					(Fields::AcceptFilesByGlob, accept_files_by_glob) => {
-						de::VariantAccess::newtype_variant::<Vec<Glob>>(accept_files_by_glob)
+						de::VariantAccess::newtype_variant::<Vec<String>>(accept_files_by_glob)
 							.and_then(|globs| {
-								globs
-									.iter()
-									.fold(&mut GlobSetBuilder::new(), |builder, glob| {
-										builder.add(glob.to_owned())
-									})
-									.build()
-									.map_or_else(
-										|e| Err(PPK::Error::custom(e)),
-										|glob_set| {
-											Ok(Self::Value::AcceptFilesByGlob(globs, glob_set))
-										},
-									)
+								RulePerKind::new_accept_files_by_globs_str(globs)
+									.map_err(PPK::Error::custom)
 							})
 					}
 					(Fields::RejectFilesByGlob, reject_files_by_glob) => {
-						de::VariantAccess::newtype_variant::<Vec<Glob>>(reject_files_by_glob)
+						de::VariantAccess::newtype_variant::<Vec<String>>(reject_files_by_glob)
 							.and_then(|globs| {
-								globs
-									.iter()
-									.fold(&mut GlobSetBuilder::new(), |builder, glob| {
-										builder.add(glob.to_owned())
-									})
-									.build()
-									.map_or_else(
-										|e| Err(PPK::Error::custom(e)),
-										|glob_set| {
-											Ok(Self::Value::RejectFilesByGlob(globs, glob_set))
-										},
-									)
+								RulePerKind::new_reject_files_by_globs_str(globs)
+									.map_err(PPK::Error::custom)
 							})
 					}
 					(
@@ -391,6 +815,52 @@ impl<'de> Deserialize<'de> for RulePerKind {
 						reject_if_children_directories_are_present,
 					)
 					.map(Self::Value::RejectIfChildrenDirectoriesArePresent),
+					(Fields::RejectByIgnoreFiles, reject_by_ignore_files) => {
+						de::VariantAccess::newtype_variant::<Vec<String>>(reject_by_ignore_files)
+							.map(Self::Value::RejectByIgnoreFiles)
+					}
+					(Fields::AcceptFilesBySize, accept_files_by_size) => {
+						de::VariantAccess::newtype_variant::<RuleRange>(accept_files_by_size)
+							.map(Self::Value::AcceptFilesBySize)
+					}
+					(Fields::RejectFilesBySize, reject_files_by_size) => {
+						de::VariantAccess::newtype_variant::<RuleRange>(reject_files_by_size)
+							.map(Self::Value::RejectFilesBySize)
+					}
+					(Fields::AcceptFilesByAge, accept_files_by_age) => {
+						de::VariantAccess::newtype_variant::<RuleRange>(accept_files_by_age)
+							.map(Self::Value::AcceptFilesByAge)
+					}
+					(Fields::RejectFilesByAge, reject_files_by_age) => {
+						de::VariantAccess::newtype_variant::<RuleRange>(reject_files_by_age)
+							.map(Self::Value::RejectFilesByAge)
+					}
+					(Fields::AcceptFilesByAttributes, accept_files_by_attributes) => {
+						de::VariantAccess::newtype_variant::<HashSet<FileAttribute>>(
+							accept_files_by_attributes,
+						)
+						.map(Self::Value::AcceptFilesByAttributes)
+					}
+					(Fields::RejectFilesByAttributes, reject_files_by_attributes) => {
+						de::VariantAccess::newtype_variant::<HashSet<FileAttribute>>(
+							reject_files_by_attributes,
+						)
+						.map(Self::Value::RejectFilesByAttributes)
+					}
+					(
+						Fields::AcceptFilesByExtendedAttributes,
+						accept_files_by_extended_attributes,
+					) => de::VariantAccess::newtype_variant::<HashSet<String>>(
+						accept_files_by_extended_attributes,
+					)
+					.map(Self::Value::AcceptFilesByExtendedAttributes),
+					(
+						Fields::RejectFilesByExtendedAttributes,
+						reject_files_by_extended_attributes,
+					) => de::VariantAccess::newtype_variant::<HashSet<String>>(
+						reject_files_by_extended_attributes,
+					)
+					.map(Self::Value::RejectFilesByExtendedAttributes),
 				})
 			}
 		}
@@ -428,6 +898,71 @@ impl RulePerKind {
 				RuleKind::RejectFilesByGlob,
 				reject_by_glob(source, reject_glob_set),
 			)),
+
+			// Ignore files depend on every directory above the source, so the walker checks them
+			// as it goes down the tree, see `IndexerRule::ignore_files_names`
+			RulePerKind::RejectByIgnoreFiles(_file_names) => {
+				Ok((RuleKind::RejectByIgnoreFiles, true))
+			}
+
+			RulePerKind::AcceptFilesBySize(range) => {
+				let metadata = rule_metadata(source).await?;
+				Ok((
+					RuleKind::AcceptFilesBySize,
+					metadata.is_dir() || range.contains(metadata.len()),
+				))
+			}
+			RulePerKind::RejectFilesBySize(range) => {
+				let metadata = rule_metadata(source).await?;
+				Ok((
+					RuleKind::RejectFilesBySize,
+					metadata.is_dir() || !range.contains(metadata.len()),
+				))
+			}
+			RulePerKind::AcceptFilesByAge(range) => {
+				let metadata = rule_metadata(source).await?;
+				Ok((
+					RuleKind::AcceptFilesByAge,
+					metadata.is_dir() || range.contains(age_in_seconds(&metadata)),
+				))
+			}
+			RulePerKind::RejectFilesByAge(range) => {
+				let metadata = rule_metadata(source).await?;
+				Ok((
+					RuleKind::RejectFilesByAge,
+					metadata.is_dir() || !range.contains(age_in_seconds(&metadata)),
+				))
+			}
+			RulePerKind::AcceptFilesByAttributes(attributes) => {
+				let source = source.as_ref();
+				let metadata = rule_metadata(source).await?;
+				Ok((
+					RuleKind::AcceptFilesByAttributes,
+					attributes
+						.iter()
+						.any(|attribute| attribute.is_set(source, &metadata)),
+				))
+			}
+			RulePerKind::RejectFilesByAttributes(attributes) => {
+				let source = source.as_ref();
+				let metadata = rule_metadata(source).await?;
+				Ok((
+					RuleKind::RejectFilesByAttributes,
+					!attributes
+						.iter()
+						.any(|attribute| attribute.is_set(source, &metadata)),
+				))
+			}
+			RulePerKind::AcceptFilesByExtendedAttributes(names) => {
+				has_any_extended_attribute(source, names)
+					.await
+					.map(|accepted| (RuleKind::AcceptFilesByExtendedAttributes, accepted))
+			}
+			RulePerKind::RejectFilesByExtendedAttributes(names) => {
+				has_any_extended_attribute(source, names)
+					.await
+					.map(|rejected| (RuleKind::RejectFilesByExtendedAttributes, !rejected))
+			}
 		}
 	}
 }
@@ -491,6 +1026,40 @@ impl IndexerRule {
 		Ok(())
 	}
 
+	/// Names of the ignore files that the walker must look for, in order of precedence
+	pub fn ignore_files_names(rules: &[IndexerRule]) -> Vec<String> {
+		rules
+			.iter()
+			.flat_map(|rule| &rule.rules)
+			.filter_map(|rule| match rule {
+				RulePerKind::RejectByIgnoreFiles(file_names) => Some(file_names),
+				_ => None,
+			})
+			.flatten()
+			.fold(Vec::new(), |mut file_names, file_name| {
+				if !file_names.contains(file_name) {
+					file_names.push(file_name.clone());
+				}
+				file_names
+			})
+	}
+
+	/// If these rules decide the same for the entries of a directory while its modification time
+	/// doesn't change, so the indexer can skip reading unchanged directories. Sizes, ages and
+	/// attributes change without touching the parent directory, and a directory accepted by its
+	/// children isn't indexed until one of them is, so we can't know about it from the database
+	pub fn allow_skipping_unchanged_dirs(rules: &[IndexerRule]) -> bool {
+		rules.iter().flat_map(|rule| &rule.rules).all(|rule| {
+			matches!(
+				rule,
+				RulePerKind::AcceptFilesByGlob(_, _)
+					| RulePerKind::RejectFilesByGlob(_, _)
+					| RulePerKind::RejectIfChildrenDirectoriesArePresent(_)
+					| RulePerKind::RejectByIgnoreFiles(_)
+			)
+		})
+	}
+
 	pub async fn apply_all(
 		rules: &[IndexerRule],
 		source: impl AsRef<Path>,
@@ -540,6 +1109,58 @@ fn reject_by_glob(source: impl AsRef<Path>, reject_glob_set: &GlobSet) -> bool {
 	!accept_by_glob(source.as_ref(), reject_glob_set)
 }
 
+async fn rule_metadata(source: impl AsRef<Path>) -> Result<Metadata, IndexerRuleError> {
+	let source = source.as_ref();
+
+	fs::symlink_metadata(source)
+		.await
+		.map_err(|e| IndexerRuleError::FileMetadataIO(FileIOError::from((source, e))))
+}
+
+/// Files modified in the future are considered to be just modified
+fn age_in_seconds(metadata: &Metadata) -> u64 {
+	SystemTime::now()
+		.duration_since(metadata.modified_or_now())
+		.map(|age| age.as_secs())
+		.unwrap_or(0)
+}
+
+/// Extended attributes are read with blocking syscalls, so they're read on a blocking thread to not
+/// stall the runtime for every file walked
+async fn has_any_extended_attribute(
+	source: impl AsRef<Path>,
+	names: &HashSet<String>,
+) -> Result<bool, IndexerRuleError> {
+	#[cfg(target_family = "unix")]
+	{
+		let source = source.as_ref().to_path_buf();
+		let names = names.clone();
+
+		task::spawn_blocking(move || {
+			for name in &names {
+				if xattr::get(&source, name)
+					.map_err(|e| {
+						IndexerRuleError::ExtendedAttributesIO(FileIOError::from((&source, e)))
+					})?
+					.is_some()
+				{
+					return Ok(true);
+				}
+			}
+
+			Ok(false)
+		})
+		.await?
+	}
+
+	#[cfg(not(target_family = "unix"))]
+	{
+		// TODO: Windows keeps extended attributes on NTFS alternate data streams
+		let _ = (source, names); // To avoid unused variable warning
+		Ok(false)
+	}
+}
+
 async fn accept_dir_for_its_children(
 	source: impl AsRef<Path>,
 	children: &HashSet<String>,
@@ -809,6 +1430,42 @@ mod tests {
 					RulePerKind::RejectIfChildrenDirectoriesArePresent(self_childrens),
 					RulePerKind::RejectIfChildrenDirectoriesArePresent(other_childrens),
 				) => self_childrens == other_childrens,
+				(
+					RulePerKind::RejectByIgnoreFiles(self_file_names),
+					RulePerKind::RejectByIgnoreFiles(other_file_names),
+				) => self_file_names == other_file_names,
+				(
+					RulePerKind::AcceptFilesBySize(self_range),
+					RulePerKind::AcceptFilesBySize(other_range),
+				)
+				| (
+					RulePerKind::RejectFilesBySize(self_range),
+					RulePerKind::RejectFilesBySize(other_range),
+				)
+				| (
+					RulePerKind::AcceptFilesByAge(self_range),
+					RulePerKind::AcceptFilesByAge(other_range),
+				)
+				| (
+					RulePerKind::RejectFilesByAge(self_range),
+					RulePerKind::RejectFilesByAge(other_range),
+				) => self_range == other_range,
+				(
+					RulePerKind::AcceptFilesByAttributes(self_attributes),
+					RulePerKind::AcceptFilesByAttributes(other_attributes),
+				)
+				| (
+					RulePerKind::RejectFilesByAttributes(self_attributes),
+					RulePerKind::RejectFilesByAttributes(other_attributes),
+				) => self_attributes == other_attributes,
+				(
+					RulePerKind::AcceptFilesByExtendedAttributes(self_names),
+					RulePerKind::AcceptFilesByExtendedAttributes(other_names),
+				)
+				| (
+					RulePerKind::RejectFilesByExtendedAttributes(self_names),
+					RulePerKind::RejectFilesByExtendedAttributes(other_names),
+				) => self_names == other_names,
 				_ => false,
 			}
 		}
@@ -848,4 +1505,265 @@ mod tests {
 
 		assert_eq!(actual, expected);
 	}
+
+	#[test]
+	fn serde_ignore_files_rule() {
+		let actual = IndexerRule::new(
+			"Respect Ignore Files".to_string(),
+			false,
+			vec![RulePerKind::RejectByIgnoreFiles(vec![
+				".gitignore".to_string(),
+				SD_IGNORE_FILE_NAME.to_string(),
+			])],
+		);
+
+		let expected =
+			rmp_serde::from_slice::<IndexerRule>(&rmp_serde::to_vec_named(&actual).unwrap())
+				.unwrap();
+
+		assert_eq!(actual, expected);
+		assert_eq!(
+			IndexerRule::ignore_files_names(&[actual, expected]),
+			vec![".gitignore".to_string(), SD_IGNORE_FILE_NAME.to_string()]
+		);
+	}
+
+	#[tokio::test]
+	async fn test_files_by_size() {
+		let root = tempdir().unwrap();
+
+		let empty = root.path().join("empty.txt");
+		let small = root.path().join("small.txt");
+		let big = root.path().join("big.bin");
+		let dir = root.path().join("dir");
+
+		fs::write(&empty, b"").await.unwrap();
+		fs::write(&small, [0; 10]).await.unwrap();
+		fs::write(&big, [0; 4096]).await.unwrap();
+		fs::create_dir(&dir).await.unwrap();
+
+		let range = RuleRange {
+			min: Some(1),
+			max: Some(1024),
+		};
+
+		let accept = IndexerRule::new(
+			"only small files".to_string(),
+			false,
+			vec![RulePerKind::AcceptFilesBySize(range)],
+		);
+		let reject = IndexerRule::new(
+			"no small files".to_string(),
+			false,
+			vec![RulePerKind::RejectFilesBySize(range)],
+		);
+
+		assert!(!check_rule(&accept, &empty).await);
+		assert!(check_rule(&accept, &small).await);
+		assert!(!check_rule(&accept, &big).await);
+		assert!(check_rule(&accept, &dir).await);
+
+		assert!(check_rule(&reject, &empty).await);
+		assert!(!check_rule(&reject, &small).await);
+		assert!(check_rule(&reject, &big).await);
+		assert!(check_rule(&reject, &dir).await);
+	}
+
+	#[tokio::test]
+	async fn test_files_by_age() {
+		let root = tempdir().unwrap();
+
+		let fresh = root.path().join("fresh.txt");
+		fs::write(&fresh, b"just created").await.unwrap();
+
+		let last_hour = RuleRange {
+			min: None,
+			max: Some(3600),
+		};
+		let older_than_an_hour = RuleRange {
+			min: Some(3600),
+			max: None,
+		};
+
+		assert!(
+			check_rule(
+				&IndexerRule::new(
+					"recent files".to_string(),
+					false,
+					vec![RulePerKind::AcceptFilesByAge(last_hour)],
+				),
+				&fresh
+			)
+			.await
+		);
+		assert!(
+			!check_rule(
+				&IndexerRule::new(
+					"old files".to_string(),
+					false,
+					vec![RulePerKind::AcceptFilesByAge(older_than_an_hour)],
+				),
+				&fresh
+			)
+			.await
+		);
+		assert!(
+			!check_rule(
+				&IndexerRule::new(
+					"no recent files".to_string(),
+					false,
+					vec![RulePerKind::RejectFilesByAge(last_hour)],
+				),
+				&fresh
+			)
+			.await
+		);
+	}
+
+	#[tokio::test]
+	#[cfg(target_family = "unix")]
+	async fn test_reject_hidden_files_by_attributes() {
+		let root = tempdir().unwrap();
+
+		let hidden = root.path().join(".hidden.txt");
+		let hidden_dir = root.path().join(".hidden");
+		let normal = root.path().join("normal.txt");
+
+		fs::write(&hidden, b"").await.unwrap();
+		fs::create_dir(&hidden_dir).await.unwrap();
+		fs::write(&normal, b"").await.unwrap();
+
+		let rule = IndexerRule::new(
+			"no hidden files".to_string(),
+			false,
+			vec![RulePerKind::RejectFilesByAttributes(
+				[FileAttribute::Hidden].into_iter().collect(),
+			)],
+		);
+
+		assert!(!check_rule(&rule, &hidden).await);
+		assert!(!check_rule(&rule, &hidden_dir).await);
+		assert!(check_rule(&rule, &normal).await);
+	}
+
+	#[tokio::test]
+	#[cfg(target_family = "unix")]
+	async fn test_files_by_extended_attributes() {
+		let root = tempdir().unwrap();
+
+		let tagged = root.path().join("tagged.txt");
+		let untagged = root.path().join("untagged.txt");
+
+		fs::write(&tagged, b"").await.unwrap();
+		fs::write(&untagged, b"").await.unwrap();
+
+		if xattr::set(&tagged, "user.sd.ignore", b"1").is_err() {
+			// The filesystem backing the temporary directory doesn't support user extended attributes
+			return;
+		}
+
+		let names = ["user.sd.ignore".to_string()]
+			.into_iter()
+			.collect::<HashSet<_>>();
+
+		let accept = IndexerRule::new(
+			"only tagged files".to_string(),
+			false,
+			vec![RulePerKind::AcceptFilesByExtendedAttributes(names.clone())],
+		);
+		let reject = IndexerRule::new(
+			"no tagged files".to_string(),
+			false,
+			vec![RulePerKind::RejectFilesByExtendedAttributes(names)],
+		);
+
+		assert!(check_rule(&accept, &tagged).await);
+		assert!(!check_rule(&accept, &untagged).await);
+		assert!(!check_rule(&reject, &tagged).await);
+		assert!(check_rule(&reject, &untagged).await);
+	}
+
+	#[test]
+	fn rule_parameters_parsing() {
+		let kind = RuleKind::AcceptFilesBySize;
+
+		assert_eq!(
+			RuleRange::from_parameters(kind, &["".to_string(), "1024".to_string()]).unwrap(),
+			RuleRange {
+				min: None,
+				max: Some(1024)
+			}
+		);
+		assert!(RuleRange::from_parameters(kind, &[]).is_err());
+		assert!(RuleRange::from_parameters(kind, &["10".to_string(), "1".to_string()]).is_err());
+		assert!(RuleRange::from_parameters(kind, &["ten".to_string()]).is_err());
+
+		let kind = RuleKind::RejectFilesByAttributes;
+
+		assert_eq!(
+			FileAttribute::from_parameters(kind, &["Hidden".to_string(), "system".to_string()])
+				.unwrap(),
+			[FileAttribute::Hidden, FileAttribute::System]
+				.into_iter()
+				.collect::<HashSet<_>>()
+		);
+		assert!(FileAttribute::from_parameters(kind, &["archive".to_string()]).is_err());
+	}
+
+	#[test]
+	fn serde_file_property_rules() {
+		let actual = IndexerRule::new(
+			"File properties".to_string(),
+			false,
+			vec![
+				RulePerKind::AcceptFilesBySize(RuleRange {
+					min: Some(1),
+					max: None,
+				}),
+				RulePerKind::RejectFilesByAge(RuleRange {
+					min: None,
+					max: Some(60),
+				}),
+				RulePerKind::RejectFilesByAttributes(
+					[FileAttribute::Hidden, FileAttribute::System]
+						.into_iter()
+						.collect(),
+				),
+				RulePerKind::RejectFilesByExtendedAttributes(
+					["user.sd.ignore".to_string()].into_iter().collect(),
+				),
+			],
+		);
+
+		let expected =
+			rmp_serde::from_slice::<IndexerRule>(&rmp_serde::to_vec_named(&actual).unwrap())
+				.unwrap();
+
+		assert_eq!(actual, expected);
+	}
+
+	#[test]
+	fn skipping_unchanged_dirs_depends_on_rule_kinds() {
+		let globs_and_ignore_files = [IndexerRule::new(
+			"no build artifacts".to_string(),
+			false,
+			vec![
+				RulePerKind::new_reject_files_by_globs_str(["**/target"]).unwrap(),
+				RulePerKind::RejectByIgnoreFiles(vec![".gitignore".to_string()]),
+			],
+		)];
+		assert!(IndexerRule::allow_skipping_unchanged_dirs(
+			&globs_and_ignore_files
+		));
+
+		let recent_files = [IndexerRule::new(
+			"recent files".to_string(),
+			false,
+			vec![RulePerKind::AcceptFilesByAge(RuleRange {
+				min: None,
+				max: Some(60 * 60 * 24),
+			})],
+		)];
+		assert!(!IndexerRule::allow_skipping_unchanged_dirs(&recent_files));
+	}
 }
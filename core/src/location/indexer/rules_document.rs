@@ -0,0 +1,277 @@
+use crate::{
+	library::Library,
+	prisma::{indexer_rule, PrismaClient},
+};
+
+use std::collections::HashSet;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tracing::debug;
+
+use super::rules::{IndexerRule, IndexerRuleError, RulePerKind};
+
+/// Bumped when the document changes in a way that older versions can't read
+pub const RULES_DOCUMENT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Type)]
+pub enum RulesDocumentFormat {
+	Json,
+	Toml,
+}
+
+/// What to do when an imported indexer rule has the same name of one already in the library
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Type)]
+pub enum RulesImportConflict {
+	/// Keep the existing rule and ignore the imported one
+	Skip,
+	/// Replace the existing rule's rules with the imported ones, default rules are never replaced
+	Replace,
+	/// Import the rule with a new name, like `name (1)`
+	Rename,
+}
+
+/// A portable rule set, to share indexer rules between libraries. Ids, dates and the default flag
+/// only make sense inside a library, so they're left out
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexerRulesDocument {
+	pub version: u32,
+	pub indexer_rules: Vec<PortableIndexerRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PortableIndexerRule {
+	pub name: String,
+	pub rules: Vec<RulePerKind>,
+}
+
+#[derive(Debug, Default, Serialize, Type)]
+pub struct RulesImportReport {
+	pub created: Vec<String>,
+	pub replaced: Vec<String>,
+	/// The ids of the replaced rules, whose locations must be reevaluated with their new rules
+	pub replaced_ids: Vec<i32>,
+	pub skipped: Vec<String>,
+}
+
+impl From<IndexerRule> for PortableIndexerRule {
+	fn from(IndexerRule { name, rules, .. }: IndexerRule) -> Self {
+		Self { name, rules }
+	}
+}
+
+impl IndexerRulesDocument {
+	pub fn new(indexer_rules: impl IntoIterator<Item = IndexerRule>) -> Self {
+		Self {
+			version: RULES_DOCUMENT_VERSION,
+			indexer_rules: indexer_rules.into_iter().map(Into::into).collect(),
+		}
+	}
+
+	pub fn parse(format: RulesDocumentFormat, document: &str) -> Result<Self, IndexerRuleError> {
+		let document: Self = match format {
+			RulesDocumentFormat::Json => serde_json::from_str(document)?,
+			RulesDocumentFormat::Toml => toml::from_str(document)?,
+		};
+
+		if document.version > RULES_DOCUMENT_VERSION {
+			return Err(IndexerRuleError::UnsupportedRulesDocumentVersion(
+				document.version,
+			));
+		}
+
+		Ok(document)
+	}
+
+	pub fn render(&self, format: RulesDocumentFormat) -> Result<String, IndexerRuleError> {
+		match format {
+			RulesDocumentFormat::Json => serde_json::to_string_pretty(self).map_err(Into::into),
+			RulesDocumentFormat::Toml => toml::to_string_pretty(self).map_err(Into::into),
+		}
+	}
+
+	pub async fn export(
+		indexer_rules_ids: Vec<i32>,
+		db: &PrismaClient,
+	) -> Result<Self, IndexerRuleError> {
+		let indexer_rules = db
+			.indexer_rule()
+			.find_many(vec![indexer_rule::id::in_vec(indexer_rules_ids.clone())])
+			.exec()
+			.await?;
+
+		if let Some(missing_id) = indexer_rules_ids
+			.iter()
+			.find(|id| !indexer_rules.iter().any(|rule| rule.id == **id))
+		{
+			return Err(IndexerRuleError::NotFound(*missing_id));
+		}
+
+		indexer_rules
+			.iter()
+			.map(IndexerRule::try_from)
+			.collect::<Result<Vec<_>, _>>()
+			.map(Self::new)
+	}
+
+	pub async fn import(
+		self,
+		conflict: RulesImportConflict,
+		library: &Library,
+	) -> Result<RulesImportReport, IndexerRuleError> {
+		let Library { db, .. } = library;
+
+		let existing = db
+			.indexer_rule()
+			.find_many(vec![])
+			.select(indexer_rule::select!({ id name default }))
+			.exec()
+			.await?;
+
+		let mut names = existing
+			.iter()
+			.map(|rule| rule.name.clone())
+			.collect::<HashSet<_>>();
+
+		let mut report = RulesImportReport::default();
+
+		for PortableIndexerRule { name, rules } in self.indexer_rules {
+			let rules_data = rmp_serde::to_vec_named(&rules)?;
+
+			let Some(existing_rule) = existing.iter().find(|rule| rule.name == name) else {
+				db.indexer_rule()
+					.create(name.clone(), rules_data, vec![])
+					.exec()
+					.await?;
+				names.insert(name.clone());
+				report.created.push(name);
+				continue;
+			};
+
+			match conflict {
+				RulesImportConflict::Replace if !existing_rule.default => {
+					db.indexer_rule()
+						.update(
+							indexer_rule::id::equals(existing_rule.id),
+							vec![
+								indexer_rule::rules_per_kind::set(rules_data),
+								indexer_rule::date_modified::set(Utc::now().into()),
+							],
+						)
+						.exec()
+						.await?;
+					report.replaced.push(name);
+					report.replaced_ids.push(existing_rule.id);
+				}
+				RulesImportConflict::Rename => {
+					let new_name = (1..)
+						.map(|i| format!("{name} ({i})"))
+						.find(|new_name| !names.contains(new_name))
+						.expect("infinite iterator always finds an unused name");
+
+					db.indexer_rule()
+						.create(new_name.clone(), rules_data, vec![])
+						.exec()
+						.await?;
+					names.insert(new_name.clone());
+					report.created.push(new_name);
+				}
+				RulesImportConflict::Skip | RulesImportConflict::Replace => {
+					debug!("Skipping imported indexer rule <name='{name}'> as it already exists");
+					report.skipped.push(name);
+				}
+			}
+		}
+
+		Ok(report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::rules::{FileAttribute, RuleRange};
+	use super::*;
+
+	fn rule_set() -> IndexerRulesDocument {
+		IndexerRulesDocument::new([
+			IndexerRule::new(
+				"No build artifacts".to_string(),
+				false,
+				vec![
+					RulePerKind::new_reject_files_by_globs_str(["**/node_modules", "**/target"])
+						.unwrap(),
+					RulePerKind::RejectIfChildrenDirectoriesArePresent(
+						[".venv".to_string()].into_iter().collect(),
+					),
+				],
+			),
+			IndexerRule::new(
+				"Small visible files".to_string(),
+				false,
+				vec![
+					RulePerKind::AcceptFilesBySize(RuleRange {
+						min: None,
+						max: Some(1024 * 1024),
+					}),
+					RulePerKind::RejectFilesByAttributes(
+						[FileAttribute::Hidden].into_iter().collect(),
+					),
+				],
+			),
+		])
+	}
+
+	fn assert_same_rules(actual: &IndexerRulesDocument, expected: &IndexerRulesDocument) {
+		assert_eq!(actual.version, expected.version);
+		assert_eq!(actual.indexer_rules.len(), expected.indexer_rules.len());
+
+		for (actual, expected) in actual.indexer_rules.iter().zip(&expected.indexer_rules) {
+			assert_eq!(actual.name, expected.name);
+			// RulePerKind has no PartialEq outside of tests in rules.rs, so we compare encodings
+			assert_eq!(
+				rmp_serde::to_vec_named(&actual.rules).unwrap(),
+				rmp_serde::to_vec_named(&expected.rules).unwrap()
+			);
+		}
+	}
+
+	#[test]
+	fn json_round_trip() {
+		let expected = rule_set();
+
+		let document = expected.render(RulesDocumentFormat::Json).unwrap();
+		let actual = IndexerRulesDocument::parse(RulesDocumentFormat::Json, &document).unwrap();
+
+		assert_same_rules(&actual, &expected);
+	}
+
+	#[test]
+	fn toml_round_trip() {
+		let expected = rule_set();
+
+		let document = expected.render(RulesDocumentFormat::Toml).unwrap();
+		let actual = IndexerRulesDocument::parse(RulesDocumentFormat::Toml, &document).unwrap();
+
+		assert_same_rules(&actual, &expected);
+	}
+
+	#[test]
+	fn rejects_invalid_documents() {
+		let invalid_glob = r#"{
+			"version": 1,
+			"indexer_rules": [{ "name": "Broken", "rules": [{ "RejectFilesByGlob": ["a/**b/[c"] }] }]
+		}"#;
+
+		let err = IndexerRulesDocument::parse(RulesDocumentFormat::Json, invalid_glob).unwrap_err();
+		assert!(matches!(err, IndexerRuleError::RulesDocumentJson(_)));
+		assert!(err.to_string().contains("glob"), "{err}");
+
+		let from_the_future = "version = 2\nindexer_rules = []\n";
+
+		assert!(matches!(
+			IndexerRulesDocument::parse(RulesDocumentFormat::Toml, from_the_future),
+			Err(IndexerRuleError::UnsupportedRulesDocumentVersion(2))
+		));
+	}
+}
@@ -2,9 +2,12 @@ use crate::{
 	file_paths_db_fetcher_fn,
 	job::JobError,
 	library::Library,
-	location::file_path_helper::{
-		check_file_path_exists, ensure_sub_path_is_directory, ensure_sub_path_is_in_location,
-		IsolatedFilePathData,
+	location::{
+		file_path_helper::{
+			check_file_path_exists, ensure_sub_path_is_directory, ensure_sub_path_is_in_location,
+			IsolatedFilePathData,
+		},
+		size_tree::invalidate_size_tree,
 	},
 	to_remove_db_fetcher_fn,
 };
@@ -16,8 +19,8 @@ use itertools::Itertools;
 
 use super::{
 	execute_indexer_save_step, iso_file_path_factory, location_with_indexer_rules,
-	remove_non_existing_file_paths, rules::IndexerRule, walk::walk_single_dir, IndexerError,
-	IndexerJobSaveStep,
+	remove_non_existing_file_paths, rules::IndexerRule, sort_parents_first, walk::walk_single_dir,
+	IndexerError, IndexerJobSaveStep,
 };
 
 /// BATCH_SIZE is the number of files to index at each step, writing the chunk of files metadata in the database.
@@ -65,6 +68,7 @@ pub async fn shallow(
 		walk_single_dir(
 			&to_walk_path,
 			&indexer_rules,
+			location.follow_symlinks,
 			|_, _| {},
 			file_paths_db_fetcher_fn!(&db),
 			to_remove_db_fetcher_fn!(location_id, location_path, &db),
@@ -77,11 +81,11 @@ pub async fn shallow(
 	errors.into_iter().for_each(|e| error!("{e}"));
 
 	// TODO pass these uuids to sync system
-	remove_non_existing_file_paths(to_remove, &db).await?;
+	remove_non_existing_file_paths(location_id, to_remove, &db).await?;
 
 	let total_paths = &mut 0;
 
-	let steps = walked
+	let steps = sort_parents_first(walked)
 		.chunks(BATCH_SIZE)
 		.into_iter()
 		.enumerate()
@@ -101,6 +105,8 @@ pub async fn shallow(
 		execute_indexer_save_step(&location, &step, &library).await?;
 	}
 
+	invalidate_size_tree(library, location_id).await;
+
 	library.orphan_remover.invoke().await;
 
 	Ok(())
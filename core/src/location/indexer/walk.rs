@@ -1,7 +1,7 @@
 use crate::{
 	location::file_path_helper::{
-		file_path_just_pub_id, file_path_to_isolate, FilePathMetadata, IsolatedFilePathData,
-		MetadataExt,
+		file_path_just_pub_id, file_path_to_isolate, FilePathMetadata, FilePathPermissions,
+		IsolatedFilePathData, MetadataExt,
 	},
 	prisma::file_path,
 	util::error::FileIOError,
@@ -18,8 +18,11 @@ use std::{
 	future::Future,
 	hash::{Hash, Hasher},
 	path::{Path, PathBuf},
+	sync::Mutex,
 };
 
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
 use prisma_client_rust::operator;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
@@ -27,6 +30,7 @@ use tracing::trace;
 use uuid::Uuid;
 
 use super::{
+	ignore_files::{find_ignore_files, IgnoreFiles, IgnoreFilesCache},
 	rules::{IndexerRule, RuleKind},
 	IndexerError,
 };
@@ -35,6 +39,24 @@ const TO_WALK_QUEUE_INITIAL_CAPACITY: usize = 32;
 const WALKER_PATHS_BUFFER_INITIAL_CAPACITY: usize = 256;
 const WALK_SINGLE_DIR_PATHS_BUFFER_INITIAL_CAPACITY: usize = 32;
 
+/// Rejecting a directory with any of these rules also skips its children
+const REJECT_FILES_RULE_KINDS: [RuleKind; 5] = [
+	RuleKind::RejectFilesByGlob,
+	RuleKind::RejectFilesBySize,
+	RuleKind::RejectFilesByAge,
+	RuleKind::RejectFilesByAttributes,
+	RuleKind::RejectFilesByExtendedAttributes,
+];
+
+/// An entry must pass at least one rule of each of these kinds, if any, to be indexed
+const ACCEPT_FILES_RULE_KINDS: [RuleKind; 5] = [
+	RuleKind::AcceptFilesByGlob,
+	RuleKind::AcceptFilesBySize,
+	RuleKind::AcceptFilesByAge,
+	RuleKind::AcceptFilesByAttributes,
+	RuleKind::AcceptFilesByExtendedAttributes,
+];
+
 /// `WalkEntry` represents a single path in the filesystem, for any comparison purposes, we only
 /// consider the path itself, not the metadata.
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,17 +64,71 @@ pub struct WalkedEntry {
 	pub pub_id: Uuid,
 	pub iso_file_path: IsolatedFilePathData<'static>,
 	pub metadata: FilePathMetadata,
+	#[serde(default)]
+	pub symlink_target: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ToWalkEntry {
 	path: PathBuf,
 	parent_dir_accepted_by_its_children: Option<bool>,
+	/// Ignore files found in the directories above this one, from the outermost to the innermost
+	#[serde(default)]
+	ignore_files: Vec<PathBuf>,
+	/// If symbolic links to directories are walked into, instead of only being indexed as aliases
+	#[serde(default)]
+	follow_symlinks: bool,
+	/// Canonical targets of the symbolic links followed to reach this directory, to detect loops
+	#[serde(default)]
+	followed_symlinks: Vec<PathBuf>,
+}
+
+impl ToWalkEntry {
+	/// The entry where a walk starts, which can be below the location root, so we also look
+	/// for ignore files in the directories between the location root and it
+	async fn new_root(
+		root: &Path,
+		indexer_rules: &[IndexerRule],
+		iso_file_path_factory: &impl Fn(
+			&Path,
+			bool,
+		) -> Result<IsolatedFilePathData<'static>, IndexerError>,
+		follow_symlinks: bool,
+		errors: &mut Vec<IndexerError>,
+	) -> Self {
+		let ignore_files_names = IndexerRule::ignore_files_names(indexer_rules);
+
+		let mut ignore_files = vec![];
+		if !ignore_files_names.is_empty() {
+			// Only directories inside the location have a materialized path
+			let ancestors = root
+				.ancestors()
+				.skip(1)
+				.take_while(|&ancestor| iso_file_path_factory(ancestor, true).is_ok())
+				.collect::<Vec<_>>();
+
+			for ancestor in ancestors.into_iter().rev() {
+				match find_ignore_files(ancestor, &ignore_files_names).await {
+					Ok(found) => ignore_files.extend(found),
+					Err(e) => errors.push(e.into()),
+				}
+			}
+		}
+
+		Self {
+			path: root.to_path_buf(),
+			parent_dir_accepted_by_its_children: None,
+			ignore_files,
+			follow_symlinks,
+			followed_symlinks: vec![],
+		}
+	}
 }
 
 struct WalkingEntry {
 	iso_file_path: IsolatedFilePathData<'static>,
 	maybe_metadata: Option<FilePathMetadata>,
+	symlink_target: Option<String>,
 }
 
 impl PartialEq for WalkingEntry {
@@ -69,6 +145,39 @@ impl Hash for WalkingEntry {
 	}
 }
 
+/// A directory already in the database, with what the walker needs to know if it changed since it
+/// was indexed
+#[derive(Debug, Clone)]
+pub struct IndexedDirectory {
+	pub pub_id: Vec<u8>,
+	pub modified_at: DateTime<Utc>,
+	pub indexed_at: DateTime<Utc>,
+	pub children_dirs_names: Vec<String>,
+}
+
+/// An indexed directory whose entries were read again because it changed. Its modification time
+/// must only be updated after its new entries are saved, otherwise a later scan would skip them
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshedDirectory {
+	pub pub_id: Vec<u8>,
+	pub modified_at: DateTime<Utc>,
+}
+
+/// A path left out by the walker and the kind of rule responsible for it
+#[derive(Debug)]
+pub struct RejectedEntry {
+	pub path: PathBuf,
+	pub kind: RuleKind,
+}
+
+pub struct PreviewWalkResult {
+	pub accepted: Vec<IsolatedFilePathData<'static>>,
+	pub rejected: Vec<RejectedEntry>,
+	/// If we reached the limit before walking all directories
+	pub truncated: bool,
+	pub errors: Vec<IndexerError>,
+}
+
 pub struct WalkResult<Walked, ToRemove>
 where
 	Walked: Iterator<Item = WalkedEntry>,
@@ -77,23 +186,59 @@ where
 	pub walked: Walked,
 	pub to_walk: VecDeque<ToWalkEntry>,
 	pub to_remove: ToRemove,
+	pub to_refresh: Vec<RefreshedDirectory>,
 	pub errors: Vec<IndexerError>,
 }
 
+/// The state of one of the concurrent directory walks in [`walk`], reused between batches
+#[derive(Default)]
+struct WalkerSlot {
+	paths_buffer: Vec<WalkingEntry>,
+	to_walk: VecDeque<ToWalkEntry>,
+	ignore_files_cache: IgnoreFilesCache,
+	to_refresh: Vec<RefreshedDirectory>,
+	errors: Vec<IndexerError>,
+}
+
+impl WalkerSlot {
+	/// Drops the results of a walk that won't be merged, the ignore files cache is kept
+	fn clear(&mut self) {
+		self.paths_buffer.clear();
+		self.to_walk.clear();
+		self.to_refresh.clear();
+		self.errors.clear();
+	}
+}
+
 /// This function walks through the filesystem, applying the rules to each entry and then returning
 /// a list of accepted entries. There are some useful comments in the implementation of this function
 /// in case of doubts.
-pub(super) async fn walk<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
+///
+/// Up to `max_concurrent_dirs` directories from the queue are walked at the same time, their
+/// results are merged in queue order after each batch, so the walk is still breadth first and
+/// `limit` is checked after each directory, as if they were walked one at a time. Directories of
+/// a batch after the one reaching the limit are queued again. Using 1 walks one directory at a time.
+///
+/// Directories found by `indexed_dir_db_fetcher` whose modification time didn't change aren't
+/// read again, we just keep walking their children directories.
+///
+/// Symbolic links are indexed as aliases, with `follow_symlinks` the ones pointing to directories
+/// are also walked into, unless that would loop back to a directory we're already walking.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn walk<FilePathDBFetcherFut, ToRemoveDbFetcherFut, IndexedDirDBFetcherFut>(
 	root: impl AsRef<Path>,
 	indexer_rules: &[IndexerRule],
-	mut update_notifier: impl FnMut(&Path, usize),
+	follow_symlinks: bool,
+	update_notifier: impl FnMut(&Path, usize),
 	file_paths_db_fetcher: impl Fn(Vec<file_path::WhereParam>) -> FilePathDBFetcherFut,
 	to_remove_db_fetcher: impl Fn(
 		IsolatedFilePathData<'static>,
 		Vec<file_path::WhereParam>,
 	) -> ToRemoveDbFetcherFut,
+	indexed_dir_db_fetcher: impl Fn(IsolatedFilePathData<'static>) -> IndexedDirDBFetcherFut,
 	iso_file_path_factory: impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
 	limit: u64,
+	max_concurrent_dirs: usize,
 ) -> Result<
 	WalkResult<
 		impl Iterator<Item = WalkedEntry>,
@@ -104,51 +249,190 @@ pub(super) async fn walk<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
 where
 	FilePathDBFetcherFut: Future<Output = Result<Vec<file_path_to_isolate::Data>, IndexerError>>,
 	ToRemoveDbFetcherFut: Future<Output = Result<Vec<file_path_just_pub_id::Data>, IndexerError>>,
+	IndexedDirDBFetcherFut: Future<Output = Result<Option<IndexedDirectory>, IndexerError>>,
 {
 	let root = root.as_ref();
 
+	let mut errors = vec![];
 	let mut to_walk = VecDeque::with_capacity(TO_WALK_QUEUE_INITIAL_CAPACITY);
-	to_walk.push_back(ToWalkEntry {
-		path: root.to_path_buf(),
-		parent_dir_accepted_by_its_children: None,
-	});
+	to_walk.push_back(
+		ToWalkEntry::new_root(
+			root,
+			indexer_rules,
+			&iso_file_path_factory,
+			follow_symlinks,
+			&mut errors,
+		)
+		.await,
+	);
 	let mut indexed_paths = HashSet::with_capacity(WALKER_PATHS_BUFFER_INITIAL_CAPACITY);
+	let mut to_refresh = vec![];
+	let mut slots = (0..max_concurrent_dirs.max(1))
+		.map(|_| WalkerSlot::default())
+		.collect::<Vec<_>>();
+	let mut to_remove = vec![];
+
+	// The walks of a batch share the notifier, they never hold the lock across an await point
+	let update_notifier = Mutex::new(update_notifier);
+
+	while !to_walk.is_empty() {
+		let batch = to_walk
+			.drain(..to_walk.len().min(slots.len()))
+			.collect::<Vec<_>>();
+
+		let indexed_paths_ref = &indexed_paths;
+		let update_notifier_ref = &update_notifier;
+		let iso_file_path_factory_ref = &iso_file_path_factory;
+		let to_remove_db_fetcher_ref = &to_remove_db_fetcher;
+		let indexed_dir_db_fetcher_ref = &indexed_dir_db_fetcher;
+
+		let batch_to_remove = join_all(batch.iter().zip(slots.iter_mut()).map(
+			|(entry, slot)| async move {
+				inner_walk_single_dir(
+					root,
+					entry,
+					indexer_rules,
+					&mut |path, count| {
+						(*update_notifier_ref
+							.lock()
+							.expect("Failed to lock the walker update notifier"))(path, count)
+					},
+					to_remove_db_fetcher_ref,
+					indexed_dir_db_fetcher_ref,
+					iso_file_path_factory_ref,
+					WorkingTable {
+						indexed_paths: indexed_paths_ref,
+						paths_buffer: &mut slot.paths_buffer,
+						maybe_to_walk: Some(&mut slot.to_walk),
+						ignore_files_cache: &mut slot.ignore_files_cache,
+						maybe_rejected: None,
+						maybe_to_refresh: Some(&mut slot.to_refresh),
+						errors: &mut slot.errors,
+					},
+				)
+				.await
+			},
+		))
+		.await;
+
+		// Merging in the same order the directories were queued, to keep the walk breadth first
+		let mut reached_limit = false;
+		let mut not_merged = vec![];
+		for ((entry, slot), dir_to_remove) in batch.into_iter().zip(&mut slots).zip(batch_to_remove)
+		{
+			if reached_limit {
+				slot.clear();
+				not_merged.push(entry);
+				continue;
+			}
+
+			indexed_paths.extend(slot.paths_buffer.drain(..));
+			to_walk.append(&mut slot.to_walk);
+			to_refresh.append(&mut slot.to_refresh);
+			errors.append(&mut slot.errors);
+			to_remove.push(dir_to_remove);
+
+			reached_limit = indexed_paths.len() >= limit as usize;
+		}
+
+		if reached_limit {
+			// They were queued before the rest of the queue
+			for entry in not_merged.into_iter().rev() {
+				to_walk.push_front(entry);
+			}
+			break;
+		}
+	}
+
+	Ok(WalkResult {
+		walked: filter_existing_paths(indexed_paths, file_paths_db_fetcher).await?,
+		to_walk,
+		to_remove: to_remove.into_iter().flatten(),
+		to_refresh,
+		errors,
+	})
+}
+
+/// Walks like [`walk`] but without touching the database, so every accepted path is returned even
+/// if it is already indexed, along with the rejected ones. The children of a rejected directory
+/// aren't walked, so they aren't reported.
+pub(super) async fn walk_preview(
+	root: impl AsRef<Path>,
+	indexer_rules: &[IndexerRule],
+	follow_symlinks: bool,
+	iso_file_path_factory: impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
+	limit: u64,
+) -> PreviewWalkResult {
+	let root = root.as_ref();
+
 	let mut errors = vec![];
+	let mut to_walk = VecDeque::with_capacity(TO_WALK_QUEUE_INITIAL_CAPACITY);
+	to_walk.push_back(
+		ToWalkEntry::new_root(
+			root,
+			indexer_rules,
+			&iso_file_path_factory,
+			follow_symlinks,
+			&mut errors,
+		)
+		.await,
+	);
+	let mut indexed_paths = HashSet::with_capacity(WALKER_PATHS_BUFFER_INITIAL_CAPACITY);
 	let mut paths_buffer = Vec::with_capacity(WALKER_PATHS_BUFFER_INITIAL_CAPACITY);
-	let mut to_remove = vec![];
+	let mut ignore_files_cache = IgnoreFilesCache::new();
+	let mut rejected = vec![];
 
 	while let Some(ref entry) = to_walk.pop_front() {
-		let current_to_remove = inner_walk_single_dir(
+		inner_walk_single_dir(
 			root,
 			entry,
 			indexer_rules,
-			&mut update_notifier,
-			&to_remove_db_fetcher,
+			&mut |_, _| {},
+			&|_, _| async { Ok(vec![]) },
+			&|_| async { Ok(None) },
 			&iso_file_path_factory,
 			WorkingTable {
-				indexed_paths: &mut indexed_paths,
+				indexed_paths: &indexed_paths,
 				paths_buffer: &mut paths_buffer,
 				maybe_to_walk: Some(&mut to_walk),
+				ignore_files_cache: &mut ignore_files_cache,
+				maybe_rejected: Some(&mut rejected),
+				maybe_to_refresh: None,
 				errors: &mut errors,
 			},
 		)
 		.await;
-		to_remove.push(current_to_remove);
+		indexed_paths.extend(paths_buffer.drain(..));
 
-		if indexed_paths.len() >= limit as usize {
+		if indexed_paths.len() + rejected.len() >= limit as usize {
 			break;
 		}
 	}
 
-	Ok(WalkResult {
-		walked: filter_existing_paths(indexed_paths, file_paths_db_fetcher).await?,
-		to_walk,
-		to_remove: to_remove.into_iter().flatten(),
+	let accepted = indexed_paths
+		.into_iter()
+		.map(|entry| entry.iso_file_path)
+		.collect::<HashSet<_>>();
+
+	// Directories rejected by accept rules are still indexed if any of their children is accepted
+	rejected.retain(|RejectedEntry { path, .. }| {
+		iso_file_path_factory(path, true)
+			.map_or(true, |iso_file_path| !accepted.contains(&iso_file_path))
+	});
+
+	PreviewWalkResult {
+		accepted: accepted.into_iter().collect(),
+		rejected,
+		truncated: !to_walk.is_empty(),
 		errors,
-	})
+	}
 }
 
-pub(super) async fn keep_walking<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
+pub(super) async fn keep_walking<
+	FilePathDBFetcherFut,
+	ToRemoveDbFetcherFut,
+	IndexedDirDBFetcherFut,
+>(
 	to_walk_entry: &ToWalkEntry,
 	indexer_rules: &[IndexerRule],
 	mut update_notifier: impl FnMut(&Path, usize),
@@ -157,6 +441,7 @@ pub(super) async fn keep_walking<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
 		IsolatedFilePathData<'static>,
 		Vec<file_path::WhereParam>,
 	) -> ToRemoveDbFetcherFut,
+	indexed_dir_db_fetcher: impl Fn(IsolatedFilePathData<'static>) -> IndexedDirDBFetcherFut,
 	iso_file_path_factory: impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
 ) -> Result<
 	WalkResult<
@@ -168,8 +453,10 @@ pub(super) async fn keep_walking<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
 where
 	FilePathDBFetcherFut: Future<Output = Result<Vec<file_path_to_isolate::Data>, IndexerError>>,
 	ToRemoveDbFetcherFut: Future<Output = Result<Vec<file_path_just_pub_id::Data>, IndexerError>>,
+	IndexedDirDBFetcherFut: Future<Output = Result<Option<IndexedDirectory>, IndexerError>>,
 {
 	let mut to_keep_walking = VecDeque::with_capacity(TO_WALK_QUEUE_INITIAL_CAPACITY);
+	let mut to_refresh = vec![];
 	let mut indexed_paths = HashSet::with_capacity(WALK_SINGLE_DIR_PATHS_BUFFER_INITIAL_CAPACITY);
 	let mut paths_buffer = Vec::with_capacity(WALK_SINGLE_DIR_PATHS_BUFFER_INITIAL_CAPACITY);
 	let mut errors = vec![];
@@ -180,27 +467,35 @@ where
 		indexer_rules,
 		&mut update_notifier,
 		&to_remove_db_fetcher,
+		&indexed_dir_db_fetcher,
 		&iso_file_path_factory,
 		WorkingTable {
-			indexed_paths: &mut indexed_paths,
+			indexed_paths: &indexed_paths,
 			paths_buffer: &mut paths_buffer,
 			maybe_to_walk: Some(&mut to_keep_walking),
+			ignore_files_cache: &mut IgnoreFilesCache::new(),
+			maybe_rejected: None,
+			maybe_to_refresh: Some(&mut to_refresh),
 			errors: &mut errors,
 		},
 	)
 	.await;
+	indexed_paths.extend(paths_buffer);
 
 	Ok(WalkResult {
 		walked: filter_existing_paths(indexed_paths, file_paths_db_fetcher).await?,
 		to_walk: to_keep_walking,
 		to_remove: to_remove.into_iter(),
+		to_refresh,
 		errors,
 	})
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(super) async fn walk_single_dir<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
 	root: impl AsRef<Path>,
 	indexer_rules: &[IndexerRule],
+	follow_symlinks: bool,
 	mut update_notifier: impl FnMut(&Path, usize) + '_,
 	file_paths_db_fetcher: impl Fn(Vec<file_path::WhereParam>) -> FilePathDBFetcherFut,
 	to_remove_db_fetcher: impl Fn(
@@ -250,31 +545,44 @@ where
 				size_in_bytes: metadata.len(),
 				created_at: metadata.created_or_now().into(),
 				modified_at: metadata.modified_or_now().into(),
+				permissions: FilePathPermissions::from_metadata(&metadata),
 			}),
+			symlink_target: None,
 		});
 	}
 
 	let mut paths_buffer = Vec::with_capacity(WALK_SINGLE_DIR_PATHS_BUFFER_INITIAL_CAPACITY);
 	let mut errors = vec![];
 
+	let to_walk_entry = ToWalkEntry::new_root(
+		root,
+		indexer_rules,
+		&iso_file_path_factory,
+		follow_symlinks,
+		&mut errors,
+	)
+	.await;
+
 	let to_remove = inner_walk_single_dir(
 		root,
-		&ToWalkEntry {
-			path: root.to_path_buf(),
-			parent_dir_accepted_by_its_children: None,
-		},
+		&to_walk_entry,
 		indexer_rules,
 		&mut update_notifier,
 		&to_remove_db_fetcher,
+		&|_| async { Ok(None) },
 		&iso_file_path_factory,
 		WorkingTable {
-			indexed_paths: &mut indexed_paths,
+			indexed_paths: &indexed_paths,
 			paths_buffer: &mut paths_buffer,
 			maybe_to_walk: None,
+			ignore_files_cache: &mut IgnoreFilesCache::new(),
+			maybe_rejected: None,
+			maybe_to_refresh: None,
 			errors: &mut errors,
 		},
 	)
 	.await;
+	indexed_paths.extend(paths_buffer);
 
 	Ok((
 		filter_existing_paths(indexed_paths, file_paths_db_fetcher).await?,
@@ -315,46 +623,217 @@ where
 				metadata: entry
 					.maybe_metadata
 					.expect("we always use Some in `the inner_walk_single_dir` function"),
+				symlink_target: entry.symlink_target,
 			})
 		})
 	})
 }
 
+/// The state borrowed by a single directory walk. `indexed_paths` is only read, the paths found
+/// go to `paths_buffer` and the caller merges them, so many directories can be walked at once
 struct WorkingTable<'a> {
-	indexed_paths: &'a mut HashSet<WalkingEntry>,
+	indexed_paths: &'a HashSet<WalkingEntry>,
 	paths_buffer: &'a mut Vec<WalkingEntry>,
 	maybe_to_walk: Option<&'a mut VecDeque<ToWalkEntry>>,
+	ignore_files_cache: &'a mut IgnoreFilesCache,
+	maybe_rejected: Option<&'a mut Vec<RejectedEntry>>,
+	maybe_to_refresh: Option<&'a mut Vec<RefreshedDirectory>>,
 	errors: &'a mut Vec<IndexerError>,
 }
 
-async fn inner_walk_single_dir<ToRemoveDbFetcherFut>(
+/// A directory didn't change since it was indexed if its modification time is the same and none of
+/// the ignore files that apply to it changed after it was indexed
+async fn is_dir_unchanged(
+	indexed_dir: &IndexedDirectory,
+	modified_at: DateTime<Utc>,
+	ignore_files: &[PathBuf],
+	errors: &mut Vec<IndexerError>,
+) -> bool {
+	// The database doesn't keep sub millisecond precision
+	if indexed_dir.modified_at.timestamp_millis() != modified_at.timestamp_millis() {
+		return false;
+	}
+
+	for ignore_file in ignore_files {
+		match fs::metadata(ignore_file).await {
+			Ok(metadata)
+				if DateTime::<Utc>::from(metadata.modified_or_now()) <= indexed_dir.indexed_at => {}
+			Ok(_) => return false,
+			Err(e) => {
+				errors.push(FileIOError::from((ignore_file, e)).into());
+				return false;
+			}
+		}
+	}
+
+	true
+}
+
+/// Queues the children directories of an unchanged directory without reading its entries. If any
+/// of them is gone or is rejected now, nothing is queued and we return false, so the directory is
+/// read again and the rejected ones are removed
+async fn queue_unchanged_dir_children(
+	parent: &ToWalkEntry,
+	children_dirs_names: &[String],
+	indexer_rules: &[IndexerRule],
+	ignore_files: &[PathBuf],
+	maybe_to_walk: &mut Option<&mut VecDeque<ToWalkEntry>>,
+	errors: &mut Vec<IndexerError>,
+) -> bool {
+	let mut children = Vec::with_capacity(children_dirs_names.len());
+
+	for name in children_dirs_names {
+		let child_path = parent.path.join(name);
+
+		if !fs::symlink_metadata(&child_path)
+			.await
+			.map_or(false, |metadata| metadata.is_dir())
+		{
+			return false;
+		}
+
+		let Ok(rules_per_kind) = IndexerRule::apply_all(indexer_rules, &child_path)
+			.await
+			.map_err(|e| errors.push(e.into()))
+		else {
+			return false;
+		};
+
+		if REJECT_FILES_RULE_KINDS
+			.iter()
+			.chain([&RuleKind::RejectIfChildrenDirectoriesArePresent])
+			.any(|kind| {
+				rules_per_kind.get(kind).map_or(false, |reject_results| {
+					reject_results.iter().any(|reject| !reject)
+				})
+			}) {
+			return false;
+		}
+
+		children.push(ToWalkEntry {
+			path: child_path,
+			parent_dir_accepted_by_its_children: parent.parent_dir_accepted_by_its_children,
+			ignore_files: ignore_files.to_vec(),
+			follow_symlinks: parent.follow_symlinks,
+			followed_symlinks: parent.followed_symlinks.clone(),
+		});
+	}
+
+	if let Some(to_walk) = maybe_to_walk {
+		to_walk.extend(children);
+	}
+
+	true
+}
+
+/// Returns the canonical target of a symbolic link if it's a directory we can walk into, which isn't
+/// the case if it is an ancestor of the directory being walked or of a link followed to reach it,
+/// as the walk would never end
+pub(crate) async fn followable_symlink_dir(
+	link: &Path,
+	walked_dir: &Path,
+	followed_symlinks: &[PathBuf],
+) -> Option<PathBuf> {
+	// Broken links are still indexed as aliases, they just can't be followed
+	let target = fs::canonicalize(link).await.ok()?;
+
+	if !fs::metadata(&target)
+		.await
+		.map_or(false, |metadata| metadata.is_dir())
+	{
+		return None;
+	}
+
+	let walked_dir = fs::canonicalize(walked_dir).await.ok()?;
+
+	if walked_dir.starts_with(&target)
+		|| followed_symlinks
+			.iter()
+			.any(|followed| followed.starts_with(&target))
+	{
+		trace!(
+			"Not following symbolic link {} to {}, as it would loop",
+			link.display(),
+			target.display()
+		);
+		return None;
+	}
+
+	Some(target)
+}
+
+fn push_rejected(
+	maybe_rejected: &mut Option<&mut Vec<RejectedEntry>>,
+	path: impl AsRef<Path>,
+	kind: RuleKind,
+) {
+	if let Some(rejected) = maybe_rejected {
+		rejected.push(RejectedEntry {
+			path: path.as_ref().to_path_buf(),
+			kind,
+		});
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn inner_walk_single_dir<ToRemoveDbFetcherFut, IndexedDirDBFetcherFut>(
 	root: impl AsRef<Path>,
-	ToWalkEntry {
-		path,
-		parent_dir_accepted_by_its_children,
-	}: &ToWalkEntry,
+	to_walk_entry: &ToWalkEntry,
 	indexer_rules: &[IndexerRule],
 	update_notifier: &mut impl FnMut(&Path, usize),
 	to_remove_db_fetcher: &impl Fn(
 		IsolatedFilePathData<'static>,
 		Vec<file_path::WhereParam>,
 	) -> ToRemoveDbFetcherFut,
+	indexed_dir_db_fetcher: &impl Fn(IsolatedFilePathData<'static>) -> IndexedDirDBFetcherFut,
 	iso_file_path_factory: &impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
 	WorkingTable {
 		indexed_paths,
 		paths_buffer,
 		mut maybe_to_walk,
+		ignore_files_cache,
+		mut maybe_rejected,
+		maybe_to_refresh,
 		errors,
 	}: WorkingTable<'_>,
 ) -> Vec<file_path_just_pub_id::Data>
 where
 	ToRemoveDbFetcherFut: Future<Output = Result<Vec<file_path_just_pub_id::Data>, IndexerError>>,
+	IndexedDirDBFetcherFut: Future<Output = Result<Option<IndexedDirectory>, IndexerError>>,
 {
+	let ToWalkEntry {
+		path,
+		parent_dir_accepted_by_its_children,
+		ignore_files,
+		follow_symlinks,
+		followed_symlinks,
+	} = to_walk_entry;
+
 	let Ok(iso_file_path_to_walk) = iso_file_path_factory(path, true).map_err(|e| errors.push(e))
 	else {
 		return vec![];
 	};
 
+	let maybe_indexed_dir = match iso_file_path_factory(path, true) {
+		Ok(iso_file_path) => indexed_dir_db_fetcher(iso_file_path).await,
+		Err(e) => Err(e),
+	}
+	.unwrap_or_else(|e| {
+		errors.push(e);
+		None
+	});
+
+	// Taken before reading the directory, so changes made while we read it are seen by the next scan
+	let maybe_modified_at = if maybe_indexed_dir.is_some() {
+		fs::metadata(path)
+			.await
+			.map(|metadata| DateTime::<Utc>::from(metadata.modified_or_now()))
+			.map_err(|e| errors.push(FileIOError::from((path.clone(), e)).into()))
+			.ok()
+	} else {
+		None
+	};
+
 	let Ok(mut read_dir) = fs::read_dir(path).await
 		.map_err(|e| errors.push(FileIOError::from((path.clone(), e)).into()))
 		else {
@@ -363,6 +842,41 @@ where
 
 	let root = root.as_ref();
 
+	// The ignore files of this directory also apply to its children directories
+	let ignore_files_names = IndexerRule::ignore_files_names(indexer_rules);
+	let mut ignore_files = ignore_files.clone();
+	if !ignore_files_names.is_empty() {
+		match find_ignore_files(path, &ignore_files_names).await {
+			Ok(found) => ignore_files.extend(found),
+			Err(e) => errors.push(e.into()),
+		}
+	}
+	let ignore_matchers = IgnoreFiles::load(&ignore_files, ignore_files_cache, errors).await;
+
+	if let (Some(indexed_dir), Some(modified_at)) = (maybe_indexed_dir, maybe_modified_at) {
+		if is_dir_unchanged(&indexed_dir, modified_at, &ignore_files, errors).await {
+			if queue_unchanged_dir_children(
+				to_walk_entry,
+				&indexed_dir.children_dirs_names,
+				indexer_rules,
+				&ignore_files,
+				&mut maybe_to_walk,
+				errors,
+			)
+			.await
+			{
+				trace!("Skipping unchanged directory {}", path.display());
+				update_notifier(path, indexed_paths.len());
+				return vec![];
+			}
+		} else if let Some(to_refresh) = maybe_to_refresh {
+			to_refresh.push(RefreshedDirectory {
+				pub_id: indexed_dir.pub_id,
+				modified_at,
+			});
+		}
+	}
+
 	// Just to make sure...
 	paths_buffer.clear();
 
@@ -410,15 +924,16 @@ where
 			continue 'entries;
 		};
 
-		if rules_per_kind
-			.get(&RuleKind::RejectFilesByGlob)
-			.map_or(false, |reject_results| {
+		if let Some(kind) = REJECT_FILES_RULE_KINDS.iter().find(|kind| {
+			rules_per_kind.get(kind).map_or(false, |reject_results| {
 				reject_results.iter().any(|reject| !reject)
-			}) {
+			})
+		}) {
 			trace!(
-				"Path {} rejected by `RuleKind::RejectFilesByGlob`",
+				"Path {} rejected by `RuleKind::{kind:?}`",
 				current_path.display()
 			);
+			push_rejected(&mut maybe_rejected, &current_path, *kind);
 			continue 'entries;
 		}
 
@@ -430,12 +945,40 @@ where
 				continue 'entries;
 		};
 
-		// TODO: Hard ignoring symlinks for now, but this should be configurable
+		let mut is_dir = metadata.is_dir();
+		let mut symlink_target = None;
+		let mut followed_symlink = None;
+
+		// Symbolic links are indexed as aliases, unless we follow the ones pointing to directories
 		if metadata.is_symlink() {
-			continue 'entries;
+			let Ok(target) = fs::read_link(&current_path)
+				.await
+				.map_err(|e| errors.push(FileIOError::from((&current_path, e)).into()))
+			else {
+				continue 'entries;
+			};
+
+			if *follow_symlinks {
+				followed_symlink =
+					followable_symlink_dir(&current_path, path, followed_symlinks).await;
+				is_dir = followed_symlink.is_some();
+			}
+
+			symlink_target = Some(target.to_string_lossy().to_string());
 		}
 
-		let is_dir = metadata.is_dir();
+		if ignore_matchers.is_ignored(&current_path, is_dir) {
+			trace!(
+				"Path {} rejected by `RuleKind::RejectByIgnoreFiles`",
+				current_path.display()
+			);
+			push_rejected(
+				&mut maybe_rejected,
+				&current_path,
+				RuleKind::RejectByIgnoreFiles,
+			);
+			continue 'entries;
+		}
 
 		let Ok((inode, device)) = {
 			#[cfg(target_family = "unix")]
@@ -463,6 +1006,11 @@ where
 					"Path {} rejected by rule `RuleKind::RejectIfChildrenDirectoriesArePresent`",
 					current_path.display(),
 				);
+				push_rejected(
+					&mut maybe_rejected,
+					&current_path,
+					RuleKind::RejectIfChildrenDirectoriesArePresent,
+				);
 				continue 'entries;
 			}
 
@@ -489,19 +1037,27 @@ where
 				to_walk.push_back(ToWalkEntry {
 					path: entry.path(),
 					parent_dir_accepted_by_its_children: accept_by_children_dir,
+					ignore_files: ignore_files.clone(),
+					follow_symlinks: *follow_symlinks,
+					followed_symlinks: followed_symlinks
+						.iter()
+						.cloned()
+						.chain(followed_symlink.take())
+						.collect(),
 				});
 			}
 		}
 
-		if rules_per_kind
-			.get(&RuleKind::AcceptFilesByGlob)
-			.map_or(false, |accept_rules| {
+		if let Some(kind) = ACCEPT_FILES_RULE_KINDS.iter().find(|kind| {
+			rules_per_kind.get(kind).map_or(false, |accept_rules| {
 				accept_rules.iter().all(|accept| !accept)
-			}) {
+			})
+		}) {
 			trace!(
-				"Path {} reject because it didn't passed in any AcceptFilesByGlob rules",
+				"Path {} reject because it didn't passed in any {kind:?} rules",
 				current_path.display()
 			);
+			push_rejected(&mut maybe_rejected, &current_path, *kind);
 			continue 'entries;
 		}
 
@@ -519,7 +1075,9 @@ where
 					size_in_bytes: metadata.len(),
 					created_at: metadata.created_or_now().into(),
 					modified_at: metadata.modified_or_now().into(),
+					permissions: FilePathPermissions::from_metadata(&metadata),
 				}),
+				symlink_target,
 			});
 
 			// If the ancestors directories wasn't indexed before, now we do
@@ -538,6 +1096,7 @@ where
 				let mut ancestor_iso_walking_entry = WalkingEntry {
 					iso_file_path,
 					maybe_metadata: None,
+					symlink_target: None,
 				};
 				trace!("Indexing ancestor {}", ancestor.display());
 				if !indexed_paths.contains(&ancestor_iso_walking_entry) {
@@ -569,6 +1128,7 @@ where
 						size_in_bytes: metadata.len(),
 						created_at: metadata.created_or_now().into(),
 						modified_at: metadata.modified_or_now().into(),
+						permissions: FilePathPermissions::from_metadata(&metadata),
 					});
 
 					paths_buffer.push(ancestor_iso_walking_entry);
@@ -578,6 +1138,12 @@ where
 					break;
 				}
 			}
+		} else {
+			push_rejected(
+				&mut maybe_rejected,
+				&current_path,
+				RuleKind::AcceptIfChildrenDirectoriesArePresent,
+			);
 		}
 	}
 
@@ -600,23 +1166,22 @@ where
 		vec![]
 	});
 
-	// Just merging the `found_paths` with `indexed_paths` here in the end to avoid possibly
-	// multiple rehashes during function execution
-	indexed_paths.extend(paths_buffer.drain(..));
-
 	to_remove
 }
 
 #[cfg(test)]
 mod tests {
-	use super::super::rules::RulePerKind;
+	use super::super::rules::{RulePerKind, SD_IGNORE_FILE_NAME};
 	use super::*;
 	use chrono::Utc;
 	use globset::{Glob, GlobSetBuilder};
+	use std::collections::HashMap;
 	use tempfile::{tempdir, TempDir};
 	use tokio::fs;
 	// use tracing_test::traced_test;
 
+	const MAX_CONCURRENT_DIRS: usize = 16;
+
 	impl PartialEq for WalkedEntry {
 		fn eq(&self, other: &Self) -> bool {
 			self.iso_file_path == other.iso_file_path
@@ -699,6 +1264,7 @@ mod tests {
 			size_in_bytes: 0,
 			created_at: Utc::now(),
 			modified_at: Utc::now(),
+			permissions: None,
 		};
 
 		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
@@ -706,28 +1272,28 @@ mod tests {
 
 		#[rustfmt::skip]
 		let expected = [
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/target"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/target/debug"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/target/debug/main"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/text.txt"), false), metadata },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/target"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/target/debug"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/target/debug/main"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/text.txt"), false), metadata, symlink_target: None },
 		]
 		.into_iter()
 		.collect::<HashSet<_>>();
@@ -735,13 +1301,16 @@ mod tests {
 		let walk_result = walk(
 			root_path.to_path_buf(),
 			&[],
+			false,
 			|_, _| {},
 			|_| async { Ok(vec![]) },
 			|_, _| async { Ok(vec![]) },
+			|_| async { Ok(None) },
 			|path, is_dir| {
 				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
 			},
 			420,
+			MAX_CONCURRENT_DIRS,
 		)
 		.await
 		.unwrap();
@@ -769,6 +1338,7 @@ mod tests {
 			size_in_bytes: 0,
 			created_at: Utc::now(),
 			modified_at: Utc::now(),
+			permissions: None,
 		};
 
 		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
@@ -776,10 +1346,10 @@ mod tests {
 
 		#[rustfmt::skip]
 		let expected = [
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata, symlink_target: None },
 		]
 		.into_iter()
 		.collect::<HashSet<_>>();
@@ -799,13 +1369,16 @@ mod tests {
 		let walk_result = walk(
 			root_path.to_path_buf(),
 			only_photos_rule,
+			false,
 			|_, _| {},
 			|_| async { Ok(vec![]) },
 			|_, _| async { Ok(vec![]) },
+			|_| async { Ok(None) },
 			|path, is_dir| {
 				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
 			},
 			420,
+			MAX_CONCURRENT_DIRS,
 		)
 		.await
 		.unwrap();
@@ -821,6 +1394,68 @@ mod tests {
 		}
 	}
 
+	#[tokio::test]
+	async fn test_walk_preview() {
+		let root = prepare_location().await;
+		let root_path = root.path();
+
+		let no_build_artifacts_rule = &[IndexerRule::new(
+			"no build artifacts".to_string(),
+			false,
+			vec![RulePerKind::RejectFilesByGlob(
+				vec![],
+				GlobSetBuilder::new()
+					.add(Glob::new("**/{target,node_modules}").unwrap())
+					.build()
+					.unwrap(),
+			)],
+		)];
+
+		let preview = walk_preview(
+			root_path,
+			no_build_artifacts_rule,
+			false,
+			|path, is_dir| {
+				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
+			},
+			420,
+		)
+		.await;
+
+		if !preview.errors.is_empty() {
+			panic!("errors: {:#?}", preview.errors);
+		}
+
+		assert!(!preview.truncated);
+		assert_eq!(preview.accepted.len(), 16);
+		assert!(preview.accepted.iter().all(|iso_file_path| {
+			let relative_path = iso_file_path.to_string();
+			!relative_path.contains("target") && !relative_path.contains("node_modules")
+		}));
+
+		let rejected = preview
+			.rejected
+			.into_iter()
+			.map(|RejectedEntry { path, kind }| (path, kind))
+			.collect::<HashSet<_>>();
+
+		assert_eq!(
+			rejected,
+			[
+				(
+					root_path.join("rust_project/target"),
+					RuleKind::RejectFilesByGlob
+				),
+				(
+					root_path.join("inner/node_project/node_modules"),
+					RuleKind::RejectFilesByGlob
+				),
+			]
+			.into_iter()
+			.collect()
+		);
+	}
+
 	#[tokio::test]
 	// #[traced_test]
 	async fn test_git_repos() {
@@ -833,6 +1468,7 @@ mod tests {
 			size_in_bytes: 0,
 			created_at: Utc::now(),
 			modified_at: Utc::now(),
+			permissions: None,
 		};
 
 		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
@@ -840,23 +1476,23 @@ mod tests {
 
 		#[rustfmt::skip]
 		let expected = [
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/target"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/target/debug"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/target/debug/main"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/target"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/target/debug"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/target/debug/main"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata, symlink_target: None },
 		]
 		.into_iter()
 		.collect::<HashSet<_>>();
@@ -872,13 +1508,16 @@ mod tests {
 		let walk_result = walk(
 			root_path.to_path_buf(),
 			git_repos,
+			false,
 			|_, _| {},
 			|_| async { Ok(vec![]) },
 			|_, _| async { Ok(vec![]) },
+			|_| async { Ok(None) },
 			|path, is_dir| {
 				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
 			},
 			420,
+			MAX_CONCURRENT_DIRS,
 		)
 		.await
 		.unwrap();
@@ -906,6 +1545,7 @@ mod tests {
 			size_in_bytes: 0,
 			created_at: Utc::now(),
 			modified_at: Utc::now(),
+			permissions: None,
 		};
 
 		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
@@ -913,17 +1553,17 @@ mod tests {
 
 		#[rustfmt::skip]
 		let expected = [
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata },
-			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata, symlink_target: None },
 		]
 		.into_iter()
 		.collect::<HashSet<_>>();
@@ -963,13 +1603,16 @@ mod tests {
 		let walk_result = walk(
 			root_path.to_path_buf(),
 			git_repos_no_deps_no_build_dirs,
+			false,
 			|_, _| {},
 			|_| async { Ok(vec![]) },
 			|_, _| async { Ok(vec![]) },
+			|_| async { Ok(None) },
 			|path, is_dir| {
 				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
 			},
 			420,
+			MAX_CONCURRENT_DIRS,
 		)
 		.await
 		.unwrap();
@@ -984,4 +1627,329 @@ mod tests {
 			panic!("difference: {:#?}", expected.difference(&actual));
 		}
 	}
+
+	#[tokio::test]
+	// #[traced_test]
+	async fn test_ignore_files() {
+		let root = prepare_location().await;
+		let root_path = root.path();
+
+		// Ignoring the build dir on the rust project, and everything from node_modules
+		// but react on the node project, while keeping the photos
+		fs::write(root_path.join("rust_project/.gitignore"), "target/\n")
+			.await
+			.unwrap();
+		fs::write(
+			root_path.join("inner/node_project/.gitignore"),
+			"node_modules/*\n!node_modules/react\n",
+		)
+		.await
+		.unwrap();
+		fs::write(root_path.join(SD_IGNORE_FILE_NAME), "*.txt\n")
+			.await
+			.unwrap();
+
+		let metadata = FilePathMetadata {
+			inode: 0,
+			device: 0,
+			size_in_bytes: 0,
+			created_at: Utc::now(),
+			modified_at: Utc::now(),
+			permissions: None,
+		};
+
+		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
+		let pub_id = Uuid::new_v4();
+
+		#[rustfmt::skip]
+		let expected = [
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join(SD_IGNORE_FILE_NAME), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/.gitignore"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/.gitignore"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos"), true), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata, symlink_target: None },
+			WalkedEntry { pub_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata, symlink_target: None },
+		]
+		.into_iter()
+		.collect::<HashSet<_>>();
+
+		let ignore_files_rule = &[IndexerRule::new(
+			"ignore files".to_string(),
+			false,
+			vec![RulePerKind::RejectByIgnoreFiles(vec![
+				".gitignore".to_string(),
+				SD_IGNORE_FILE_NAME.to_string(),
+			])],
+		)];
+
+		let walk_result = walk(
+			root_path.to_path_buf(),
+			ignore_files_rule,
+			false,
+			|_, _| {},
+			|_| async { Ok(vec![]) },
+			|_, _| async { Ok(vec![]) },
+			|_| async { Ok(None) },
+			|path, is_dir| {
+				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
+			},
+			420,
+			MAX_CONCURRENT_DIRS,
+		)
+		.await
+		.unwrap();
+
+		if !walk_result.errors.is_empty() {
+			panic!("errors: {:#?}", walk_result.errors);
+		}
+
+		let actual = walk_result.walked.collect::<HashSet<_>>();
+
+		if actual != expected {
+			panic!("difference: {:#?}", expected.difference(&actual));
+		}
+	}
+
+	#[tokio::test]
+	async fn test_incremental_walk_skips_unchanged_dirs() {
+		let root = prepare_location().await;
+		let root_path = root.path();
+
+		let iso_file_path_factory = |path: &Path, is_dir| {
+			IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
+		};
+
+		let first_walk = walk(
+			root_path.to_path_buf(),
+			&[],
+			false,
+			|_, _| {},
+			|_| async { Ok(vec![]) },
+			|_, _| async { Ok(vec![]) },
+			|_| async { Ok(None) },
+			iso_file_path_factory,
+			420,
+			MAX_CONCURRENT_DIRS,
+		)
+		.await
+		.unwrap()
+		.walked
+		.collect::<Vec<_>>();
+
+		// What the database would have after saving the first walk, keyed by relative path
+		let indexed_at = Utc::now();
+		let indexed_dirs = first_walk
+			.iter()
+			.filter(|entry| entry.iso_file_path.is_dir)
+			.map(|dir| {
+				let children_materialized_path =
+					dir.iso_file_path.materialized_path_for_children().unwrap();
+
+				(
+					dir.iso_file_path.to_string(),
+					IndexedDirectory {
+						pub_id: dir.pub_id.as_bytes().to_vec(),
+						modified_at: dir.metadata.modified_at,
+						indexed_at,
+						children_dirs_names: first_walk
+							.iter()
+							.filter(|entry| {
+								entry.iso_file_path.is_dir
+									&& entry.iso_file_path.materialized_path
+										== children_materialized_path
+							})
+							.map(|entry| entry.iso_file_path.name.to_string())
+							.collect(),
+					},
+				)
+			})
+			.collect::<HashMap<_, _>>();
+
+		let walk_again = || {
+			walk(
+				root_path.to_path_buf(),
+				&[],
+				false,
+				|_, _| {},
+				|_| async { Ok(vec![]) },
+				|_, _| async { Ok(vec![]) },
+				|iso_file_path: IsolatedFilePathData<'static>| {
+					let maybe_indexed_dir = indexed_dirs.get(&iso_file_path.to_string()).cloned();
+					async move { Ok(maybe_indexed_dir) }
+				},
+				iso_file_path_factory,
+				420,
+				MAX_CONCURRENT_DIRS,
+			)
+		};
+
+		// The location root isn't a file path, so it's always read
+		let walk_result = walk_again().await.unwrap();
+		assert!(walk_result.errors.is_empty(), "{:#?}", walk_result.errors);
+		assert!(walk_result.to_refresh.is_empty());
+		assert_eq!(
+			walk_result
+				.walked
+				.map(|entry| entry.iso_file_path.to_string())
+				.collect::<HashSet<_>>(),
+			first_walk
+				.iter()
+				.filter(|entry| entry.iso_file_path.materialized_path == "/")
+				.map(|entry| entry.iso_file_path.to_string())
+				.collect::<HashSet<_>>()
+		);
+
+		// Making sure the new modification time is in another millisecond
+		tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+		fs::File::create(root_path.join("photos/photo4.png"))
+			.await
+			.unwrap();
+
+		let walk_result = walk_again().await.unwrap();
+		assert!(walk_result.errors.is_empty(), "{:#?}", walk_result.errors);
+		assert_eq!(walk_result.to_refresh.len(), 1);
+		assert_eq!(
+			walk_result.to_refresh[0].pub_id,
+			indexed_dirs["photos"].pub_id
+		);
+		assert!(walk_result
+			.walked
+			.any(|entry| entry.iso_file_path.to_string() == "photos/photo4.png"));
+	}
+
+	#[cfg(target_family = "unix")]
+	#[tokio::test]
+	async fn test_symlinks_are_aliases_unless_followed() {
+		use std::os::unix::fs::symlink;
+
+		let root = prepare_location().await;
+		let root_path = root.path();
+		let outside = tempdir().unwrap();
+		fs::File::create(outside.path().join("song.mp3"))
+			.await
+			.unwrap();
+
+		symlink("photos", root_path.join("photos_link")).unwrap();
+		symlink(outside.path(), root_path.join("outside")).unwrap();
+		// Following this one would walk the whole location again, forever
+		symlink(root_path, root_path.join("photos/loop")).unwrap();
+
+		let walk_symlinks = |follow_symlinks| async move {
+			let walk_result = walk(
+				root_path.to_path_buf(),
+				&[],
+				follow_symlinks,
+				|_, _| {},
+				|_| async { Ok(vec![]) },
+				|_, _| async { Ok(vec![]) },
+				|_| async { Ok(None) },
+				|path, is_dir| {
+					IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
+				},
+				420,
+				MAX_CONCURRENT_DIRS,
+			)
+			.await
+			.unwrap();
+			assert!(walk_result.errors.is_empty(), "{:#?}", walk_result.errors);
+
+			walk_result
+				.walked
+				.map(|entry| {
+					(
+						entry.iso_file_path.to_string(),
+						(entry.iso_file_path.is_dir, entry.symlink_target),
+					)
+				})
+				.collect::<HashMap<_, _>>()
+		};
+
+		let root_target = Some(root_path.to_string_lossy().to_string());
+		let outside_target = Some(outside.path().to_string_lossy().to_string());
+
+		let not_followed = walk_symlinks(false).await;
+		assert_eq!(
+			not_followed["photos_link"],
+			(false, Some("photos".to_string()))
+		);
+		assert_eq!(not_followed["outside"], (false, outside_target.clone()));
+		assert_eq!(not_followed["photos/loop"], (false, root_target.clone()));
+		assert!(!not_followed.contains_key("outside/song.mp3"));
+
+		let followed = walk_symlinks(true).await;
+		assert_eq!(followed["photos_link"], (true, Some("photos".to_string())));
+		assert_eq!(followed["photos_link/photo1.png"], (false, None));
+		assert_eq!(followed["photos_link/loop"], (false, root_target.clone()));
+		assert_eq!(followed["outside"], (true, outside_target));
+		assert_eq!(followed["outside/song.mp3"], (false, None));
+		assert_eq!(followed["photos/loop"], (false, root_target));
+		assert!(!followed.contains_key("photos/loop/photos"));
+	}
+
+	#[tokio::test]
+	async fn test_concurrent_walk_matches_sequential() {
+		let root = prepare_location().await;
+		let root_path = root.path();
+
+		let git_repos = [IndexerRule::new(
+			"git repos".to_string(),
+			false,
+			vec![RulePerKind::AcceptIfChildrenDirectoriesArePresent(
+				[".git".to_string()].into_iter().collect(),
+			)],
+		)];
+
+		// With a small limit the walk stops halfway, at the same directory either way
+		for (indexer_rules, limit) in [(&[][..], 3), (&[][..], 420), (&git_repos[..], 420)] {
+			let mut results = vec![];
+			for max_concurrent_dirs in [1, MAX_CONCURRENT_DIRS] {
+				let walk_result = walk(
+					root_path.to_path_buf(),
+					indexer_rules,
+					false,
+					|_, _| {},
+					|_| async { Ok(vec![]) },
+					|_, _| async { Ok(vec![]) },
+					|_| async { Ok(None) },
+					|path, is_dir| {
+						IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
+					},
+					limit,
+					max_concurrent_dirs,
+				)
+				.await
+				.unwrap();
+
+				if !walk_result.errors.is_empty() {
+					panic!("errors: {:#?}", walk_result.errors);
+				}
+
+				results.push((
+					walk_result.walked.collect::<HashSet<_>>(),
+					walk_result
+						.to_walk
+						.into_iter()
+						.map(|entry| entry.path)
+						.collect::<Vec<_>>(),
+				));
+			}
+
+			assert_eq!(results[0], results[1]);
+		}
+	}
 }
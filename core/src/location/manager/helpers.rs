@@ -1,21 +1,39 @@
-use crate::{library::Library, prisma::location};
+use crate::{
+	api::CoreEvent,
+	invalidate_query,
+	library::Library,
+	location::{metadata::SpacedriveLocationMetadataFile, relink_location},
+	prisma::location,
+	volume::get_mount_points,
+};
 
 use std::{
-	collections::{HashMap, HashSet},
-	path::PathBuf,
+	collections::{BTreeSet, HashMap, HashSet},
+	path::{Component, Path, PathBuf},
 	time::Duration,
 };
 
-use tokio::{fs, io::ErrorKind, sync::oneshot, time::sleep};
-use tracing::{error, warn};
+use tokio::{
+	fs,
+	io::ErrorKind,
+	sync::oneshot,
+	task::{self, JoinHandle},
+	time::{sleep, Instant},
+};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
-use super::{watcher::LocationWatcher, LocationId, LocationManagerError};
+use super::{
+	watcher::{reconcile_location, save_checkpoint, LocationWatcher},
+	LocationId, LocationManagerError, LocationRelinkedEvent,
+};
 
 type LibraryId = Uuid;
 type LocationAndLibraryKey = (LocationId, LibraryId);
 
 const LOCATION_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Changes made in this interval before the node stops are reconciled again on the next startup
+const WATCHER_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
 
 pub(super) async fn check_online(
 	location: &location::Data,
@@ -45,6 +63,87 @@ pub(super) async fn check_online(
 	}
 }
 
+/// Looks for the `.spacedrive` file of an offline location in the mount points that appeared since
+/// we last looked, as its drive may have been mounted somewhere else (e.g. `/media/usb1` coming back
+/// as `/media/usb2`). The location keeps its path relative to the drive, so we try every suffix of
+/// its old path under each new mount point, relinking it to the first one that is this location
+pub(super) async fn relink_if_remounted(
+	location: &location::Data,
+	library: &Library,
+	known_mount_points: &mut BTreeSet<PathBuf>,
+) -> Option<PathBuf> {
+	let mount_points = task::spawn_blocking(get_mount_points).await.ok()?;
+	let new_mount_points = mount_points
+		.difference(known_mount_points)
+		.cloned()
+		.collect::<Vec<_>>();
+	*known_mount_points = mount_points;
+
+	relink_to_mount_points(location, library, new_mount_points).await
+}
+
+/// Relinks a location to the first of the `mount_points` its `.spacedrive` file is found under
+async fn relink_to_mount_points(
+	location: &location::Data,
+	library: &Library,
+	mount_points: Vec<PathBuf>,
+) -> Option<PathBuf> {
+	let old_path = Path::new(&location.path);
+	let components = old_path
+		.components()
+		.filter(|component| matches!(component, Component::Normal(_)))
+		.collect::<Vec<_>>();
+
+	for mount_point in mount_points {
+		for skip in 0..=components.len() {
+			let candidate = mount_point.join(components[skip..].iter().collect::<PathBuf>());
+			if candidate == old_path {
+				continue;
+			}
+
+			let Ok(Some(metadata)) = SpacedriveLocationMetadataFile::try_load(&candidate).await
+			else {
+				continue;
+			};
+
+			if !matches!(
+				metadata.location_pub_id(library.id),
+				Ok(pub_id) if pub_id.as_bytes()[..] == location.pub_id[..]
+			) {
+				continue;
+			}
+
+			if let Err(e) = relink_location(library, &candidate).await {
+				error!(
+					"Failed to relink location <id='{}'> to {}: {e}",
+					location.id,
+					candidate.display()
+				);
+				return None;
+			}
+
+			let new_path = candidate.to_string_lossy().to_string();
+
+			info!(
+				"Relinked location <id='{}'> from {} to {new_path}, as its drive was mounted there",
+				location.id, location.path
+			);
+
+			library.emit(CoreEvent::LocationRelinked(LocationRelinkedEvent {
+				library_id: library.id,
+				location_id: location.id,
+				old_path: location.path.clone(),
+				new_path,
+			}));
+			invalidate_query!(library, "locations.list");
+
+			return Some(candidate);
+		}
+	}
+
+	None
+}
+
 pub(super) async fn location_check_sleep(
 	location_id: LocationId,
 	library: Library,
@@ -61,7 +160,7 @@ pub(super) fn watch_location(
 ) {
 	let location_id = location.id;
 	if let Some(mut watcher) = locations_unwatched.remove(&(location_id, library_id)) {
-		if watcher.check_path(&location.path) {
+		if watcher.check_location(&location) {
 			watcher.watch();
 		} else {
 			watcher.update_data(location, true);
@@ -71,6 +170,48 @@ pub(super) fn watch_location(
 	}
 }
 
+/// Replays the changes made to a location while it wasn't watched, in the background as a big
+/// location can take a while
+pub(super) fn spawn_reconciliation(location_id: LocationId, library: Library) -> JoinHandle<()> {
+	tokio::spawn(async move {
+		if let Err(e) = reconcile_location(location_id, &library).await {
+			error!("Failed to reconcile location: <id='{location_id}', error='{e:#?}'>");
+		}
+	})
+}
+
+/// Moves the checkpoint of a watched location forward, every once in a while. It stays put while the
+/// location is reconciled, as the changes from before it may not have been replayed yet
+pub(super) async fn update_watcher_checkpoint(
+	location_id: LocationId,
+	library: &Library,
+	reconciliations: &mut HashMap<LocationAndLibraryKey, JoinHandle<()>>,
+	checkpoints_saved_at: &mut HashMap<LocationAndLibraryKey, Instant>,
+) {
+	let key = (location_id, library.id);
+
+	if reconciliations
+		.get(&key)
+		.map_or(false, |reconciliation| !reconciliation.is_finished())
+	{
+		return;
+	}
+	reconciliations.remove(&key);
+
+	if checkpoints_saved_at.get(&key).map_or(false, |saved_at| {
+		saved_at.elapsed() < WATCHER_CHECKPOINT_INTERVAL
+	}) {
+		return;
+	}
+
+	match save_checkpoint(location_id, library).await {
+		Ok(()) => {
+			checkpoints_saved_at.insert(key, Instant::now());
+		}
+		Err(e) => error!("Failed to save watcher checkpoint: <id='{location_id}', error='{e:#?}'>"),
+	}
+}
+
 pub(super) fn unwatch_location(
 	location: location::Data,
 	library_id: LibraryId,
@@ -79,7 +220,7 @@ pub(super) fn unwatch_location(
 ) {
 	let location_id = location.id;
 	if let Some(mut watcher) = locations_watched.remove(&(location_id, library_id)) {
-		if watcher.check_path(&location.path) {
+		if watcher.check_location(&location) {
 			watcher.unwatch();
 		} else {
 			watcher.update_data(location, false)
@@ -259,3 +400,74 @@ pub(super) fn handle_ignore_path_request(
 		},
 	); // ignore errors, we handle errors on receiver
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::{library::test_library, util::db::create_test_location};
+
+	use tempfile::tempdir;
+
+	#[tokio::test]
+	async fn locations_are_relinked_where_their_drive_was_mounted_again() {
+		let (library, _data_dir) = test_library().await;
+		let drives = tempdir().unwrap();
+
+		let old_path = drives.path().join("usb1/photos");
+		fs::create_dir_all(&old_path).await.unwrap();
+
+		let location = create_test_location(&library.db, &old_path).await;
+		SpacedriveLocationMetadataFile::create_and_save(
+			library.id,
+			Uuid::from_slice(&location.pub_id).unwrap(),
+			&old_path,
+			location.name.clone(),
+		)
+		.await
+		.unwrap();
+
+		// The drive comes back somewhere else, along with one that isn't it
+		fs::rename(drives.path().join("usb1"), drives.path().join("usb2"))
+			.await
+			.unwrap();
+		let other_drive = drives.path().join("usb3/photos");
+		fs::create_dir_all(&other_drive).await.unwrap();
+
+		let new_path = drives.path().join("usb2/photos");
+		assert_eq!(
+			relink_to_mount_points(
+				&location,
+				&library,
+				vec![drives.path().join("usb3"), drives.path().join("usb2")],
+			)
+			.await,
+			Some(new_path.clone())
+		);
+
+		let relinked = library
+			.db
+			.location()
+			.find_unique(location::id::equals(location.id))
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(Path::new(&relinked.path), new_path);
+
+		let metadata = SpacedriveLocationMetadataFile::try_load(&new_path)
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(
+			metadata.location_pub_id(library.id).unwrap().as_bytes()[..],
+			location.pub_id[..]
+		);
+
+		// Nothing to relink to in a drive without the location
+		assert_eq!(
+			relink_to_mount_points(&relinked, &library, vec![drives.path().join("usb3")]).await,
+			None
+		);
+	}
+}
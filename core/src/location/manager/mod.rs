@@ -7,6 +7,8 @@ use std::{
 };
 
 use futures::executor::block_on;
+use serde::{Deserialize, Serialize};
+use specta::Type;
 use thiserror::Error;
 use tokio::sync::{
 	broadcast::{self, Receiver},
@@ -80,6 +82,9 @@ pub enum LocationManagerError {
 	#[error("Watcher error: (error: {0})")]
 	WatcherError(#[from] notify::Error),
 
+	#[error("Reached the system limit of watched directories while watching location: <id='{0}'>")]
+	WatchLimitReached(LocationId),
+
 	#[error("Failed to stop or reinit a watcher: {reason}")]
 	FailedToStopOrReinitWatcher { reason: String },
 
@@ -94,6 +99,8 @@ pub enum LocationManagerError {
 
 	#[error("Tried to update a non-existing file: <path='{0}'>")]
 	UpdateNonExistingFile(PathBuf),
+	#[error("Invalid watcher mode on database: <mode='{0}'>")]
+	InvalidWatcherMode(i32),
 	#[error("Database error: {0}")]
 	DatabaseError(#[from] prisma_client_rust::QueryError),
 	#[error("File path related error (error: {0})")]
@@ -103,15 +110,55 @@ pub enum LocationManagerError {
 	#[error("Job Manager error: (error: {0})")]
 	JobManager(#[from] JobManagerError),
 
-	#[error("invalid inode")]
-	InvalidInode,
-	#[error("invalid device")]
-	InvalidDevice,
-
 	#[error(transparent)]
 	FileIO(#[from] FileIOError),
 }
 
+/// How the changes to a location are picked up. Native watchers are notified by the operating
+/// system, but miss the changes made to network and FUSE filesystems by other machines or processes,
+/// whose locations are polled instead. `Auto` chooses by the location's filesystem
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Type, Eq, PartialEq)]
+pub enum WatcherMode {
+	#[default]
+	Auto = 0,
+	Native = 1,
+	Polling = 2,
+}
+
+impl TryFrom<i32> for WatcherMode {
+	type Error = LocationManagerError;
+
+	fn try_from(value: i32) -> Result<Self, Self::Error> {
+		Ok(match value {
+			0 => Self::Auto,
+			1 => Self::Native,
+			2 => Self::Polling,
+			_ => return Err(LocationManagerError::InvalidWatcherMode(value)),
+		})
+	}
+}
+
+/// Sent when the drive of an offline location was found mounted somewhere else, and the location
+/// was relinked to where it is now
+#[derive(Serialize, Debug, Clone, Type)]
+pub struct LocationRelinkedEvent {
+	pub library_id: Uuid,
+	pub location_id: LocationId,
+	pub old_path: String,
+	pub new_path: String,
+}
+
+/// Sent when a location has more directories than the system allows us to watch, so only some of
+/// them are watched and the rest of the location is polled for changes
+#[derive(Serialize, Debug, Clone, Type)]
+pub struct LocationWatchLimitedEvent {
+	pub library_id: Uuid,
+	pub location_id: LocationId,
+	/// The limit of watched directories per user (`fs.inotify.max_user_watches`), if known
+	pub max_user_watches: Option<u64>,
+}
+
 type OnlineLocations = BTreeSet<Vec<u8>>;
 
 #[derive(Debug)]
@@ -315,7 +362,8 @@ impl LocationManager {
 		use helpers::{
 			check_online, drop_location, get_location, handle_ignore_path_request,
 			handle_reinit_watcher_request, handle_remove_location_request,
-			handle_stop_watcher_request, location_check_sleep, unwatch_location, watch_location,
+			handle_stop_watcher_request, location_check_sleep, relink_if_remounted,
+			spawn_reconciliation, unwatch_location, update_watcher_checkpoint, watch_location,
 		};
 		use watcher::LocationWatcher;
 
@@ -324,6 +372,11 @@ impl LocationManager {
 		let mut locations_watched = HashMap::new();
 		let mut locations_unwatched = HashMap::new();
 		let mut forced_unwatch = HashSet::new();
+		// Mount points already searched for each offline location, so we only look into new ones
+		let mut known_mount_points = HashMap::new();
+		// Watcher checkpoints can't move forward while their locations are being reconciled
+		let mut reconciliations = HashMap::new();
+		let mut checkpoints_saved_at = HashMap::new();
 
 		loop {
 			select! {
@@ -356,6 +409,10 @@ impl LocationManager {
 													(location_id, library.id),
 													watcher
 												);
+												reconciliations.insert(
+													(location_id, library.id),
+													spawn_reconciliation(location_id, library.clone())
+												);
 											} else {
 												locations_unwatched.insert(
 													(location_id, library.id),
@@ -445,9 +502,11 @@ impl LocationManager {
 					if to_remove.contains(&key) {
 						// The time to check came for an already removed library, so we just ignore it
 						to_remove.remove(&key);
-					} else if let Some(location) = get_location(location_id, &library).await {
+						reconciliations.remove(&key);
+						checkpoints_saved_at.remove(&key);
+					} else if let Some(mut location) = get_location(location_id, &library).await {
 						if location.node_id == library.node_local_id {
-							let is_online = match check_online(&location, &library).await {
+							let mut is_online = match check_online(&location, &library).await {
 								Ok(is_online) => is_online,
 								Err(e) => {
 									error!("Error while checking online status of location {location_id}: {e}");
@@ -455,15 +514,43 @@ impl LocationManager {
 								}
 							};
 
+							if is_online {
+								known_mount_points.remove(&key);
+							} else if let Some(new_path) = relink_if_remounted(
+								&location,
+								&library,
+								known_mount_points.entry(key).or_default(),
+							).await {
+								location.path = new_path.to_string_lossy().to_string();
+								is_online = matches!(check_online(&location, &library).await, Ok(true));
+							}
+
 							if is_online
 								&& !forced_unwatch.contains(&key)
 							{
+								let was_unwatched = locations_unwatched.contains_key(&key);
+
 								watch_location(
 									location,
 									library.id,
 									&mut locations_watched,
 									&mut locations_unwatched,
 								);
+
+								// Back online, so whatever changed while it was offline is replayed
+								if was_unwatched && locations_watched.contains_key(&key) {
+									reconciliations.insert(
+										key,
+										spawn_reconciliation(location_id, library.clone())
+									);
+								}
+
+								update_watcher_checkpoint(
+									location_id,
+									&library,
+									&mut reconciliations,
+									&mut checkpoints_saved_at,
+								).await;
 							} else {
 								unwatch_location(
 									location,
@@ -483,6 +570,9 @@ impl LocationManager {
 								&mut locations_unwatched
 							);
 							forced_unwatch.remove(&key);
+							known_mount_points.remove(&key);
+							reconciliations.remove(&key);
+							checkpoints_saved_at.remove(&key);
 						}
 					} else {
 						drop_location(
@@ -493,6 +583,9 @@ impl LocationManager {
 							&mut locations_unwatched,
 						);
 						forced_unwatch.remove(&key);
+						known_mount_points.remove(&key);
+						reconciliations.remove(&key);
+						checkpoints_saved_at.remove(&key);
 					}
 				}
 
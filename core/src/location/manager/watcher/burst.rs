@@ -0,0 +1,213 @@
+//! Bulk operations, like extracting an archive into a location, create thousands of paths in a
+//! couple of seconds. Handling each creation as it comes takes some database queries, a cas_id and a
+//! thumbnail for every file, so once they come faster than `BURST_THRESHOLD` per `BURST_WINDOW`
+//! they're held back instead. When the burst is over, the directories they were created in are
+//! scanned, saving the new paths in batches like the indexer does and leaving their identification
+//! and thumbnails to the jobs queued after it.
+
+use crate::{
+	invalidate_query,
+	library::Library,
+	location::{
+		find_location, location_with_indexer_rules, manager::LocationManagerError, scan_location,
+		scan_location_sub_path, LocationId,
+	},
+};
+
+use std::{
+	collections::BTreeSet,
+	mem,
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+use tokio::time::Instant;
+use tracing::{debug, error};
+
+const BURST_WINDOW: Duration = Duration::from_secs(1);
+const BURST_THRESHOLD: usize = 100;
+/// Creations held back at most, so a never ending burst still gets indexed as it goes
+const MAX_COALESCED: usize = 10_000;
+
+#[derive(Debug)]
+pub(super) struct BurstCoalescer {
+	location_id: LocationId,
+	window_started_at: Instant,
+	creations_in_window: usize,
+	coalesced: BTreeSet<PathBuf>,
+	last_coalesced_at: Instant,
+}
+
+impl BurstCoalescer {
+	pub(super) fn new(location_id: LocationId) -> Self {
+		Self {
+			location_id,
+			window_started_at: Instant::now(),
+			creations_in_window: 0,
+			coalesced: BTreeSet::new(),
+			last_coalesced_at: Instant::now(),
+		}
+	}
+
+	/// Counts the creation of `path`, returning if it was held back as part of a burst, in which
+	/// case it mustn't be handled now
+	pub(super) fn coalesce(&mut self, path: &Path) -> bool {
+		if self.window_started_at.elapsed() > BURST_WINDOW {
+			self.window_started_at = Instant::now();
+			self.creations_in_window = 0;
+		}
+
+		self.creations_in_window += 1;
+
+		if self.coalesced.is_empty() && self.creations_in_window <= BURST_THRESHOLD {
+			return false;
+		}
+
+		self.coalesced.insert(path.to_path_buf());
+		self.last_coalesced_at = Instant::now();
+
+		true
+	}
+
+	/// If `path`, or a directory it's in, was created in the burst being coalesced, so the events
+	/// about it can be ignored as it'll be indexed when the burst is over
+	pub(super) fn is_coalesced(&self, path: &Path) -> bool {
+		!self.coalesced.is_empty()
+			&& path
+				.ancestors()
+				.any(|ancestor| self.coalesced.contains(ancestor))
+	}
+
+	pub(super) async fn tick(&mut self, library: &Library) {
+		if !self.coalesced.is_empty()
+			&& (self.last_coalesced_at.elapsed() > BURST_WINDOW
+				|| self.coalesced.len() >= MAX_COALESCED)
+		{
+			if let Err(e) = self.flush(library).await {
+				error!(
+					"Failed to index paths created in a burst: <id='{}', error='{e:#?}'>",
+					self.location_id
+				);
+			}
+		}
+	}
+
+	async fn flush(&mut self, library: &Library) -> Result<(), LocationManagerError> {
+		let coalesced = mem::take(&mut self.coalesced);
+		let mut scanned = Vec::new();
+
+		let res = self.scan(library, &coalesced, &mut scanned).await;
+		match res {
+			// Without the location, there's nowhere to index them anymore
+			Err(LocationManagerError::MissingLocation(_)) | Ok(()) => {}
+			Err(_) => self.restore(coalesced, &scanned),
+		}
+
+		res
+	}
+
+	/// Holds back again the paths of a failed flush that weren't in the directories already
+	/// scanned, so they're retried on a later tick instead of being lost
+	fn restore(&mut self, coalesced: BTreeSet<PathBuf>, scanned: &[PathBuf]) {
+		self.coalesced.extend(
+			coalesced
+				.into_iter()
+				.filter(|path| !scanned.iter().any(|dir| path.starts_with(dir))),
+		);
+		self.last_coalesced_at = Instant::now();
+	}
+
+	async fn scan(
+		&self,
+		library: &Library,
+		coalesced: &BTreeSet<PathBuf>,
+		scanned: &mut Vec<PathBuf>,
+	) -> Result<(), LocationManagerError> {
+		let location = find_location(library, self.location_id)
+			.include(location_with_indexer_rules::include())
+			.exec()
+			.await?
+			.ok_or(LocationManagerError::MissingLocation(self.location_id))?;
+
+		let location_path = PathBuf::from(&location.path);
+
+		let parents = coalesced
+			.iter()
+			.filter_map(|path| path.parent())
+			.collect::<BTreeSet<_>>();
+
+		debug!(
+			"Indexing {} paths created in a burst, from {} directories: <id='{}'>",
+			coalesced.len(),
+			parents.len(),
+			self.location_id
+		);
+
+		if parents.contains(location_path.as_path()) {
+			// The whole location has to be scanned anyway
+			scan_location(library, location).await?;
+			scanned.push(location_path);
+		} else {
+			// Scanning a directory indexes everything in it, so the ones inside others are skipped
+			for parent in parents {
+				if scanned.iter().any(|dir| parent.starts_with(dir)) {
+					continue;
+				}
+
+				scan_location_sub_path(library, location.clone(), parent).await?;
+				scanned.push(parent.to_path_buf());
+			}
+		}
+
+		invalidate_query!(library, "search.paths");
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn creations_are_coalesced_past_the_threshold() {
+		let mut burst = BurstCoalescer::new(1);
+		let location_path = Path::new("/location");
+
+		for i in 0..BURST_THRESHOLD {
+			assert!(!burst.coalesce(&location_path.join(format!("file_{i}.txt"))));
+		}
+
+		let extracted_dir = location_path.join("extracted");
+		assert!(burst.coalesce(&extracted_dir));
+		assert!(burst.coalesce(&location_path.join("another.txt")));
+
+		assert!(burst.is_coalesced(&extracted_dir.join("nested/file.txt")));
+		assert!(!burst.is_coalesced(&location_path.join("file_0.txt")));
+	}
+
+	#[test]
+	fn paths_of_a_failed_flush_are_restored() {
+		let mut burst = BurstCoalescer::new(1);
+		let location_path = Path::new("/location");
+
+		let coalesced = BTreeSet::from([
+			location_path.join("scanned/file.txt"),
+			location_path.join("scanned/nested/file.txt"),
+			location_path.join("failed/file.txt"),
+			location_path.join("pending/file.txt"),
+		]);
+
+		burst.restore(coalesced, &[location_path.join("scanned")]);
+
+		assert_eq!(
+			burst.coalesced,
+			BTreeSet::from([
+				location_path.join("failed/file.txt"),
+				location_path.join("pending/file.txt"),
+			])
+		);
+		assert!(burst.is_coalesced(&location_path.join("failed/file.txt")));
+		assert!(!burst.is_coalesced(&location_path.join("scanned/file.txt")));
+	}
+}
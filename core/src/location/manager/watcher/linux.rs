@@ -25,7 +25,8 @@ use tokio::{fs, time::Instant};
 use tracing::{error, trace};
 
 use super::{
-	utils::{create_dir, file_creation_or_update, remove, rename},
+	burst::BurstCoalescer,
+	utils::{create_dir, file_creation_or_update, remove, rename, update_posix_metadata},
 	EventHandler, LocationId, HUNDRED_MILLIS,
 };
 
@@ -37,6 +38,7 @@ pub(super) struct LinuxEventHandler<'lib> {
 	rename_from: HashMap<PathBuf, Instant>,
 	rename_from_buffer: Vec<(PathBuf, Instant)>,
 	recently_renamed_from: BTreeMap<PathBuf, Instant>,
+	burst: BurstCoalescer,
 }
 
 #[async_trait]
@@ -49,6 +51,7 @@ impl<'lib> EventHandler<'lib> for LinuxEventHandler<'lib> {
 			rename_from: HashMap::new(),
 			rename_from_buffer: Vec::new(),
 			recently_renamed_from: BTreeMap::new(),
+			burst: BurstCoalescer::new(location_id),
 		}
 	}
 
@@ -60,13 +63,23 @@ impl<'lib> EventHandler<'lib> for LinuxEventHandler<'lib> {
 		} = event;
 
 		match kind {
+			EventKind::Create(CreateKind::File) => {
+				// Files are only created when they're closed, we just count them to spot bursts
+				self.burst.coalesce(&paths[0]);
+			}
 			EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
 				// If a file was closed with write mode, then it was updated or created
-				file_creation_or_update(self.location_id, &paths[0], self.library).await?;
+				if !self.burst.is_coalesced(&paths[0]) {
+					file_creation_or_update(self.location_id, &paths[0], self.library).await?;
+				}
 			}
 			EventKind::Create(CreateKind::Folder) => {
 				let path = &paths[0];
 
+				if self.burst.coalesce(path) {
+					return Ok(());
+				}
+
 				create_dir(
 					self.location_id,
 					path,
@@ -92,6 +105,10 @@ impl<'lib> EventHandler<'lib> for LinuxEventHandler<'lib> {
 				self.recently_renamed_from
 					.insert(paths.swap_remove(0), Instant::now());
 			}
+			EventKind::Modify(ModifyKind::Metadata(_)) => {
+				// Permissions, owner or extended attributes changed
+				update_posix_metadata(self.location_id, &paths[0], self.library).await?;
+			}
 			EventKind::Remove(_) => {
 				remove(self.location_id, &paths[0], self.library).await?;
 			}
@@ -104,6 +121,8 @@ impl<'lib> EventHandler<'lib> for LinuxEventHandler<'lib> {
 	}
 
 	async fn tick(&mut self) {
+		self.burst.tick(self.library).await;
+
 		if self.last_check_rename.elapsed() > HUNDRED_MILLIS {
 			self.last_check_rename = Instant::now();
 			self.handle_rename_from_eviction().await;
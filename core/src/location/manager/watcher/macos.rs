@@ -34,9 +34,10 @@ use tokio::{fs, io, time::Instant};
 use tracing::{error, trace, warn};
 
 use super::{
+	burst::BurstCoalescer,
 	utils::{
 		create_dir, create_dir_or_file, create_file, extract_inode_and_device_from_path,
-		extract_location_path, remove, rename, update_file,
+		extract_location_path, remove, rename, update_file, update_posix_metadata,
 	},
 	EventHandler, INodeAndDevice, InstantAndPath, HUNDRED_MILLIS, ONE_SECOND,
 };
@@ -52,6 +53,7 @@ pub(super) struct MacOsEventHandler<'lib> {
 	old_paths_map: HashMap<INodeAndDevice, InstantAndPath>,
 	new_paths_map: HashMap<INodeAndDevice, InstantAndPath>,
 	paths_map_buffer: Vec<(INodeAndDevice, InstantAndPath)>,
+	burst: BurstCoalescer,
 }
 
 #[async_trait]
@@ -70,6 +72,7 @@ impl<'lib> EventHandler<'lib> for MacOsEventHandler<'lib> {
 			old_paths_map: HashMap::new(),
 			new_paths_map: HashMap::new(),
 			paths_map_buffer: Vec::new(),
+			burst: BurstCoalescer::new(location_id),
 		}
 	}
 
@@ -93,6 +96,10 @@ impl<'lib> EventHandler<'lib> for MacOsEventHandler<'lib> {
 					}
 				}
 
+				if self.burst.coalesce(path) {
+					return Ok(());
+				}
+
 				create_dir(
 					self.location_id,
 					path,
@@ -106,6 +113,10 @@ impl<'lib> EventHandler<'lib> for MacOsEventHandler<'lib> {
 			}
 			EventKind::Create(CreateKind::File) => {
 				let path = &paths[0];
+				if self.burst.coalesce(path) {
+					return Ok(());
+				}
+
 				create_file(
 					self.location_id,
 					path,
@@ -122,13 +133,19 @@ impl<'lib> EventHandler<'lib> for MacOsEventHandler<'lib> {
 				// NOTE: MacOS emits a Create File and then an Update Content event
 				// when a file is created. So we need to check if the file was recently
 				// created to avoid unecessary updates
-				if !self.recently_created_files.contains_key(&paths[0]) {
+				if !self.recently_created_files.contains_key(&paths[0])
+					&& !self.burst.is_coalesced(&paths[0])
+				{
 					update_file(self.location_id, &paths[0], self.library).await?;
 				}
 			}
 			EventKind::Modify(ModifyKind::Name(RenameMode::Any)) => {
 				self.handle_single_rename_event(paths.remove(0)).await?;
 			}
+			EventKind::Modify(ModifyKind::Metadata(_)) => {
+				// Permissions, owner or extended attributes changed
+				update_posix_metadata(self.location_id, &paths[0], self.library).await?;
+			}
 			EventKind::Remove(_) => {
 				remove(self.location_id, &paths[0], self.library).await?;
 			}
@@ -141,6 +158,8 @@ impl<'lib> EventHandler<'lib> for MacOsEventHandler<'lib> {
 	}
 
 	async fn tick(&mut self) {
+		self.burst.tick(self.library).await;
+
 		// Cleaning out recently created files that are older than 1 second
 		if self.last_check_created_files.elapsed() > ONE_SECOND {
 			self.last_check_created_files = Instant::now();
@@ -1,4 +1,10 @@
-use crate::{library::Library, location::LocationId, prisma::location};
+use crate::{
+	api::CoreEvent,
+	library::Library,
+	location::{LocationId, LocationWatchLimitedEvent, WatcherMode},
+	prisma::{file_path, location, SortOrder},
+	volume::is_network_file_system,
+};
 
 use std::{
 	collections::HashSet,
@@ -22,10 +28,15 @@ use super::LocationManagerError;
 
 mod linux;
 mod macos;
+mod polling;
 mod windows;
 
+mod burst;
+mod reconcile;
 mod utils;
 
+use polling::PollingWatcher;
+pub(super) use reconcile::{reconcile_location, save_checkpoint};
 use utils::check_event;
 
 #[cfg(target_os = "linux")]
@@ -45,6 +56,9 @@ type InstantAndPath = (Instant, PathBuf);
 const ONE_SECOND: Duration = Duration::from_secs(1);
 const HUNDRED_MILLIS: Duration = Duration::from_millis(100);
 
+/// How many directories are watched at most when a location can't be watched whole
+const LIMITED_WATCHES: usize = 256;
+
 #[async_trait]
 trait EventHandler<'lib> {
 	fn new(location_id: LocationId, library: &'lib Library) -> Self
@@ -59,10 +73,25 @@ trait EventHandler<'lib> {
 	async fn tick(&mut self);
 }
 
+#[derive(Debug)]
+enum WatcherBackend {
+	Native(RecommendedWatcher),
+	Polling(PollingWatcher),
+	/// Some directories watched natively and the whole location polled, for locations with more
+	/// directories than the system allows us to watch
+	Limited {
+		native: RecommendedWatcher,
+		watched_dirs: Vec<PathBuf>,
+		polling: PollingWatcher,
+	},
+}
+
 #[derive(Debug)]
 pub(super) struct LocationWatcher {
 	location: location::Data,
-	watcher: RecommendedWatcher,
+	library: Library,
+	backend: WatcherBackend,
+	events_tx: mpsc::UnboundedSender<notify::Result<Event>>,
 	ignore_path_tx: mpsc::UnboundedSender<IgnorePath>,
 	handle: Option<JoinHandle<()>>,
 	stop_tx: Option<oneshot::Sender<()>>,
@@ -77,29 +106,12 @@ impl LocationWatcher {
 		let (ignore_path_tx, ignore_path_rx) = mpsc::unbounded_channel();
 		let (stop_tx, stop_rx) = oneshot::channel();
 
-		let watcher = RecommendedWatcher::new(
-			move |result| {
-				if !events_tx.is_closed() {
-					if events_tx.send(result).is_err() {
-						error!(
-						"Unable to send watcher event to location manager for location: <id='{}'>",
-						location.id
-					);
-					}
-				} else {
-					error!(
-						"Tried to send location file system events to a closed channel: <id='{}'",
-						location.id
-					);
-				}
-			},
-			Config::default(),
-		)?;
+		let backend = Self::new_backend(&location, &library, &events_tx)?;
 
 		let handle = tokio::spawn(Self::handle_watch_events(
 			location.id,
 			Uuid::from_slice(&location.pub_id)?,
-			library,
+			library.clone(),
 			events_rx,
 			ignore_path_rx,
 			stop_rx,
@@ -107,13 +119,67 @@ impl LocationWatcher {
 
 		Ok(Self {
 			location,
-			watcher,
+			library,
+			backend,
+			events_tx,
 			ignore_path_tx,
 			handle: Some(handle),
 			stop_tx: Some(stop_tx),
 		})
 	}
 
+	fn new_backend(
+		location: &location::Data,
+		library: &Library,
+		events_tx: &mpsc::UnboundedSender<notify::Result<Event>>,
+	) -> Result<WatcherBackend, LocationManagerError> {
+		let location_id = location.id;
+
+		let polling = match WatcherMode::try_from(location.watcher_mode)? {
+			WatcherMode::Auto => is_network_file_system(&location.path),
+			WatcherMode::Native => false,
+			WatcherMode::Polling => true,
+		};
+
+		if polling {
+			debug!("Location will be polled for changes: <id='{location_id}'>");
+
+			return Ok(WatcherBackend::Polling(PollingWatcher::new(
+				location_id,
+				library.clone(),
+			)));
+		}
+
+		Self::new_native_watcher(location_id, events_tx).map(WatcherBackend::Native)
+	}
+
+	fn new_native_watcher(
+		location_id: LocationId,
+		events_tx: &mpsc::UnboundedSender<notify::Result<Event>>,
+	) -> Result<RecommendedWatcher, LocationManagerError> {
+		let events_tx = events_tx.clone();
+
+		RecommendedWatcher::new(
+			move |result| {
+				if !events_tx.is_closed() {
+					if events_tx.send(result).is_err() {
+						error!(
+						"Unable to send watcher event to location manager for location: <id='{}'>",
+						location_id
+					);
+					}
+				} else {
+					error!(
+						"Tried to send location file system events to a closed channel: <id='{}'",
+						location_id
+					);
+				}
+			},
+			Config::default(),
+		)
+		.map_err(Into::into)
+	}
+
 	async fn handle_watch_events(
 		location_id: LocationId,
 		location_pub_id: Uuid,
@@ -211,22 +277,180 @@ impl LocationWatcher {
 		self.ignore_path_tx.send((path, ignore)).map_err(Into::into)
 	}
 
-	pub(super) fn check_path(&self, path: impl AsRef<Path>) -> bool {
-		(self.location.path.as_ref() as &Path) == path.as_ref()
+	/// If the watcher is still watching the location the way it's described by `location`
+	pub(super) fn check_location(&self, location: &location::Data) -> bool {
+		(self.location.path.as_ref() as &Path) == Path::new(&location.path)
+			&& self.location.watcher_mode == location.watcher_mode
 	}
 
 	pub(super) fn watch(&mut self) {
+		if matches!(self.backend, WatcherBackend::Limited { .. }) {
+			// The limit may have been raised since, so we try to watch the whole location again
+			match Self::new_native_watcher(self.location.id, &self.events_tx) {
+				Ok(watcher) => self.backend = WatcherBackend::Native(watcher),
+				Err(e) => {
+					error!(
+						"Unable to create watcher for location: <id='{}', error='{e:#?}'>",
+						self.location.id
+					);
+					return;
+				}
+			}
+		}
+
 		let path = &self.location.path;
-		if let Err(e) = self.watcher.watch(path.as_ref(), RecursiveMode::Recursive) {
-			error!("Unable to watch location: (path: {path}, error: {e:#?})");
-		} else {
-			debug!("Now watching location: (path: {path})");
+
+		let watcher = match &mut self.backend {
+			WatcherBackend::Native(watcher) => watcher,
+			WatcherBackend::Polling(watcher) => {
+				watcher.watch();
+				debug!("Now polling location: (path: {path})");
+				return;
+			}
+			WatcherBackend::Limited { .. } => unreachable!("limited watchers were just replaced"),
+		};
+
+		match watch_recursively(watcher, &self.location) {
+			Ok(()) => debug!("Now watching location: (path: {path})"),
+			Err(LocationManagerError::WatchLimitReached(_)) => self.watch_limited(),
+			Err(e) => error!("Unable to watch location: (path: {path}, error: {e:#?})"),
 		}
 	}
 
+	/// Watching every directory of the location would take more inotify watches than the system
+	/// allows, so only its root, top level directories and most recently modified directories are
+	/// watched, and the whole location is polled now and then to find the changes made anywhere
+	/// else. fanotify could watch the whole filesystem instead, but only with `CAP_SYS_ADMIN`, which
+	/// the app doesn't run with, so it isn't used
+	fn watch_limited(&mut self) {
+		let location_id = self.location.id;
+
+		// Dropping the watcher releases the watches it got before reaching the limit
+		let mut native = match Self::new_native_watcher(location_id, &self.events_tx) {
+			Ok(native) => native,
+			Err(e) => {
+				error!(
+					"Unable to create watcher for location: <id='{location_id}', error='{e:#?}'>"
+				);
+				return;
+			}
+		};
+
+		let mut watched_dirs = vec![];
+		for dir in self.limited_watch_candidates() {
+			match native.watch(&dir, RecursiveMode::NonRecursive) {
+				Ok(()) => watched_dirs.push(dir),
+				Err(e) if matches!(e.kind, notify::ErrorKind::MaxFilesWatch) => break,
+				Err(e) => debug!(
+					"Unable to watch directory: (path: {}, error: {e:#?})",
+					dir.display()
+				),
+			}
+		}
+
+		let mut polling = PollingWatcher::new_limited(location_id, self.library.clone());
+		polling.watch();
+
+		warn!(
+			"Location has too many directories to be watched, watching {} of them and polling \
+			the rest: <id='{location_id}'>",
+			watched_dirs.len()
+		);
+
+		self.backend = WatcherBackend::Limited {
+			native,
+			watched_dirs,
+			polling,
+		};
+
+		self.library
+			.emit(CoreEvent::LocationWatchLimited(LocationWatchLimitedEvent {
+				library_id: self.library.id,
+				location_id,
+				max_user_watches: max_user_watches(),
+			}));
+	}
+
+	fn limited_watch_candidates(&self) -> Vec<PathBuf> {
+		let location_path = Path::new(&self.location.path);
+
+		let mut candidates = vec![location_path.to_path_buf()];
+
+		match std::fs::read_dir(location_path) {
+			Ok(entries) => candidates.extend(
+				entries
+					.filter_map(Result::ok)
+					.filter(|entry| entry.file_type().map_or(false, |kind| kind.is_dir()))
+					.map(|entry| entry.path()),
+			),
+			Err(e) => error!(
+				"Unable to read location directory: (path: {}, error: {e:#?})",
+				location_path.display()
+			),
+		}
+
+		// FIXME: change this to an async function when watching becomes async
+		let recently_modified = block_in_place(|| {
+			Handle::current().block_on(
+				self.library
+					.db
+					.file_path()
+					.find_many(vec![
+						file_path::location_id::equals(self.location.id),
+						file_path::is_dir::equals(true),
+					])
+					.order_by(file_path::date_modified::order(SortOrder::Desc))
+					.take(LIMITED_WATCHES as i64)
+					.select(file_path::select!({ materialized_path name }))
+					.exec(),
+			)
+		});
+
+		match recently_modified {
+			Ok(dirs) => candidates.extend(dirs.into_iter().map(|dir| {
+				location_path
+					.join(dir.materialized_path.trim_start_matches('/'))
+					.join(dir.name)
+			})),
+			Err(e) => error!(
+				"Unable to fetch recently modified directories of location: <id='{}', error='{e:#?}'>",
+				self.location.id
+			),
+		}
+
+		let mut seen = HashSet::new();
+		candidates.retain(|dir| seen.insert(dir.clone()));
+		candidates.truncate(LIMITED_WATCHES);
+
+		candidates
+	}
+
 	pub(super) fn unwatch(&mut self) {
 		let path = &self.location.path;
-		if let Err(e) = self.watcher.unwatch(path.as_ref()) {
+
+		let watcher = match &mut self.backend {
+			WatcherBackend::Native(watcher) => watcher,
+			WatcherBackend::Polling(watcher) => {
+				watcher.unwatch();
+				debug!("Stop polling location: (path: {path})");
+				return;
+			}
+			WatcherBackend::Limited {
+				native,
+				watched_dirs,
+				polling,
+			} => {
+				for dir in watched_dirs.drain(..) {
+					// Removed directories already lost their watches
+					native.unwatch(&dir).ok();
+				}
+				polling.unwatch();
+				debug!("Stop watching and polling location: (path: {path})");
+				return;
+			}
+		};
+
+		if let Err(e) = watcher.unwatch(path.as_ref()) {
 			/**************************************** TODO: ****************************************
 			 * According to an unit test, this error may occur when a subdirectory is removed	   *
 			 * and we try to unwatch the parent directory then we have to check the implications   *
@@ -245,19 +469,59 @@ impl LocationWatcher {
 		);
 
 		let new_path = self.location.path != new_location.path;
+		let new_mode = self.location.watcher_mode != new_location.watcher_mode;
 
-		if new_path {
+		if new_path || new_mode {
 			self.unwatch();
 		}
 
 		self.location = new_location;
 
-		if new_path && to_watch {
+		if new_mode {
+			match Self::new_backend(&self.location, &self.library, &self.events_tx) {
+				Ok(backend) => self.backend = backend,
+				Err(e) => error!(
+					"Failed to change the watcher mode of location: <id='{}', error='{e:#?}'>",
+					self.location.id
+				),
+			}
+		}
+
+		if (new_path || new_mode) && to_watch {
 			self.watch();
 		}
 	}
 }
 
+fn watch_recursively(
+	watcher: &mut RecommendedWatcher,
+	location: &location::Data,
+) -> Result<(), LocationManagerError> {
+	watcher
+		.watch(location.path.as_ref(), RecursiveMode::Recursive)
+		.map_err(|e| match e.kind {
+			notify::ErrorKind::MaxFilesWatch => {
+				LocationManagerError::WatchLimitReached(location.id)
+			}
+			_ => e.into(),
+		})
+}
+
+/// The limit of inotify watches of each user, only known on Linux
+fn max_user_watches() -> Option<u64> {
+	#[cfg(target_os = "linux")]
+	{
+		std::fs::read_to_string("/proc/sys/fs/inotify/max_user_watches")
+			.ok()
+			.and_then(|max| max.trim().parse().ok())
+	}
+
+	#[cfg(not(target_os = "linux"))]
+	{
+		None
+	}
+}
+
 impl Drop for LocationWatcher {
 	fn drop(&mut self) {
 		if let Some(stop_tx) = self.stop_tx.take() {
@@ -354,7 +618,16 @@ impl Drop for LocationWatcher {
 ***************************************************************************************************/
 #[cfg(test)]
 mod tests {
+	use super::{LocationWatcher, WatcherBackend, LIMITED_WATCHES};
+	use crate::{
+		api::CoreEvent,
+		library::test_library,
+		location::{find_location, location_with_indexer_rules, poll_location},
+		util::db::create_test_location,
+	};
+
 	use std::{
+		collections::HashSet,
 		io::ErrorKind,
 		path::{Path, PathBuf},
 		time::Duration,
@@ -729,4 +1002,112 @@ mod tests {
 			error!("Failed to unwatch root directory: {e:#?}");
 		}
 	}
+
+	// Limited watchers are tested directly, as reaching the real watches limit would take too many
+	// directories. Dropping a watcher blocks in place, so these need a multi threaded runtime
+	#[tokio::test(flavor = "multi_thread")]
+	async fn limited_watchers_watch_the_root_top_level_and_recently_modified_directories() {
+		let (library, data_dir) = test_library().await;
+		let location_path = data_dir.path().join("location");
+
+		fs::create_dir_all(location_path.join("music/albums/live"))
+			.await
+			.unwrap();
+		fs::create_dir(location_path.join("photos")).await.unwrap();
+
+		let location = create_test_location(&library.db, &location_path).await;
+		poll_location(
+			&library,
+			find_location(&library, location.id)
+				.include(location_with_indexer_rules::include())
+				.exec()
+				.await
+				.unwrap()
+				.unwrap(),
+			false,
+		)
+		.await
+		.unwrap();
+
+		let mut events_rx = library.subscribe();
+		let mut watcher = LocationWatcher::new(location.clone(), library.clone())
+			.await
+			.unwrap();
+		watcher.watch_limited();
+
+		let WatcherBackend::Limited { watched_dirs, .. } = &watcher.backend else {
+			panic!("the watcher should be limited: {:#?}", watcher.backend);
+		};
+		let mut watched_dirs = watched_dirs.clone();
+		watched_dirs.sort();
+		assert_eq!(
+			watched_dirs,
+			[
+				location_path.clone(),
+				location_path.join("music"),
+				location_path.join("music/albums"),
+				location_path.join("music/albums/live"),
+				location_path.join("photos"),
+			]
+		);
+
+		match events_rx.try_recv() {
+			Ok(CoreEvent::LocationWatchLimited(event)) => {
+				assert_eq!(event.library_id, library.id);
+				assert_eq!(event.location_id, location.id);
+			}
+			other => panic!("expected a watch limited event: {other:#?}"),
+		}
+
+		watcher.unwatch();
+		let WatcherBackend::Limited { watched_dirs, .. } = &watcher.backend else {
+			panic!(
+				"unwatching shouldn't change the backend: {:#?}",
+				watcher.backend
+			);
+		};
+		assert!(watched_dirs.is_empty());
+
+		// The limit isn't reached in tests, so the whole location is watched again
+		watcher.watch();
+		assert!(matches!(watcher.backend, WatcherBackend::Native(_)));
+	}
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn limited_watch_candidates_are_capped_and_unique() {
+		let (library, data_dir) = test_library().await;
+		let location_path = data_dir.path().join("location");
+
+		for i in 0..LIMITED_WATCHES + 10 {
+			fs::create_dir_all(location_path.join(format!("dir{i}")))
+				.await
+				.unwrap();
+		}
+
+		let location = create_test_location(&library.db, &location_path).await;
+		poll_location(
+			&library,
+			find_location(&library, location.id)
+				.include(location_with_indexer_rules::include())
+				.exec()
+				.await
+				.unwrap()
+				.unwrap(),
+			false,
+		)
+		.await
+		.unwrap();
+
+		let watcher = LocationWatcher::new(location, library.clone())
+			.await
+			.unwrap();
+		let candidates = watcher.limited_watch_candidates();
+
+		assert_eq!(candidates.len(), LIMITED_WATCHES);
+		assert_eq!(candidates[0], location_path);
+		assert_eq!(
+			candidates.iter().collect::<HashSet<_>>().len(),
+			LIMITED_WATCHES
+		);
+	}
 }
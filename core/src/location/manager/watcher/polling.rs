@@ -0,0 +1,289 @@
+//! Native watchers aren't notified of the changes made to network and FUSE filesystems by other
+//! machines or processes, so the locations in them are polled instead. Each poll walks the whole
+//! location for created and removed paths, and checks the indexed files for content changes.
+//!
+//! Locations with more directories than can be watched are polled too, for the changes made in
+//! the directories left unwatched. Those are usually local, so their polls are less frequent and
+//! skip the directories that didn't change, where their modification times can be trusted.
+
+use crate::{
+	library::Library,
+	location::{
+		file_path_helper::{IsolatedFilePathData, MetadataExt},
+		find_location, location_with_indexer_rules, poll_location, LocationId,
+	},
+	prisma::{file_path, SortOrder},
+	util::{db::size_to_db_int, error::FileIOError},
+};
+
+use std::{
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use tokio::{
+	fs,
+	io::ErrorKind,
+	select,
+	sync::oneshot,
+	time::{interval_at, Instant, MissedTickBehavior},
+};
+use tracing::{debug, error};
+use uuid::Uuid;
+
+use super::{utils::update_file, LocationManagerError};
+
+/// Polls walk the whole location, so they can't be too frequent
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Most of the changes to locations too big to be watched whole are seen by the directories that
+/// are watched, and their polls still check every indexed file for content changes
+const LIMITED_POLL_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// How many file paths are checked for content changes at once
+const FILE_PATHS_CHUNK_SIZE: i64 = 1000;
+
+#[derive(Debug)]
+pub(super) struct PollingWatcher {
+	location_id: LocationId,
+	library: Library,
+	poll_interval: Duration,
+	/// If polls skip the directories that didn't change, see [`poll_location`]
+	incremental: bool,
+	stop_tx: Option<oneshot::Sender<()>>,
+}
+
+impl PollingWatcher {
+	pub(super) fn new(location_id: LocationId, library: Library) -> Self {
+		Self {
+			location_id,
+			library,
+			poll_interval: POLL_INTERVAL,
+			incremental: false,
+			stop_tx: None,
+		}
+	}
+
+	/// Polls the directories of a location that aren't watched, as it has too many of them
+	pub(super) fn new_limited(location_id: LocationId, library: Library) -> Self {
+		Self {
+			location_id,
+			library,
+			poll_interval: LIMITED_POLL_INTERVAL,
+			incremental: true,
+			stop_tx: None,
+		}
+	}
+
+	pub(super) fn watch(&mut self) {
+		if self.stop_tx.is_some() {
+			return;
+		}
+
+		let (stop_tx, stop_rx) = oneshot::channel();
+
+		tokio::spawn(Self::poll_periodically(
+			self.location_id,
+			self.library.clone(),
+			self.poll_interval,
+			self.incremental,
+			stop_rx,
+		));
+
+		self.stop_tx = Some(stop_tx);
+	}
+
+	pub(super) fn unwatch(&mut self) {
+		if let Some(stop_tx) = self.stop_tx.take() {
+			// The polling task may have already finished, nothing to stop then
+			stop_tx.send(()).ok();
+		}
+	}
+
+	async fn poll_periodically(
+		location_id: LocationId,
+		library: Library,
+		period: Duration,
+		incremental: bool,
+		mut stop_rx: oneshot::Receiver<()>,
+	) {
+		let mut poll_interval = interval_at(Instant::now() + period, period);
+		// A poll can take longer than the interval on big locations, so we don't try to catch up
+		poll_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+		loop {
+			select! {
+				_ = poll_interval.tick() => {
+					if let Err(e) = poll(location_id, &library, incremental).await {
+						error!("Failed to poll location: <id='{location_id}', error='{e:#?}'>");
+					}
+				}
+
+				_ = &mut stop_rx => {
+					debug!("Stop polling location: <id='{location_id}'>");
+					break
+				}
+			}
+		}
+	}
+}
+
+impl Drop for PollingWatcher {
+	fn drop(&mut self) {
+		self.unwatch();
+	}
+}
+
+async fn poll(
+	location_id: LocationId,
+	library: &Library,
+	incremental: bool,
+) -> Result<(), LocationManagerError> {
+	let Some(location) = find_location(library, location_id)
+		.include(location_with_indexer_rules::include())
+		.exec()
+		.await?
+	else {
+		return Ok(());
+	};
+
+	if !library
+		.location_manager()
+		.is_online(&Uuid::from_slice(&location.pub_id)?)
+		.await
+	{
+		return Ok(());
+	}
+
+	let location_path = PathBuf::from(&location.path);
+
+	poll_location(library, location, incremental).await?;
+
+	update_changed_files(location_id, &location_path, library).await
+}
+
+/// Files changed in place keep their paths, so they're found by their sizes and modification times
+/// not matching the indexed ones anymore, and updated as if the watcher had seen them change
+pub(super) async fn update_changed_files(
+	location_id: LocationId,
+	location_path: &Path,
+	library: &Library,
+) -> Result<(), LocationManagerError> {
+	let mut cursor = 0;
+
+	loop {
+		let file_paths = library
+			.db
+			.file_path()
+			.find_many(vec![
+				file_path::location_id::equals(location_id),
+				file_path::is_dir::equals(false),
+				file_path::id::gt(cursor),
+			])
+			.order_by(file_path::id::order(SortOrder::Asc))
+			.take(FILE_PATHS_CHUNK_SIZE)
+			.exec()
+			.await?;
+
+		let Some(last) = file_paths.last() else {
+			return Ok(());
+		};
+		cursor = last.id;
+
+		for file_path in &file_paths {
+			let full_path = location_path.join(IsolatedFilePathData::from(file_path));
+
+			let metadata = match fs::symlink_metadata(&full_path).await {
+				Ok(metadata) => metadata,
+				// Removed after this poll's walk, the next one will take care of it
+				Err(e) if e.kind() == ErrorKind::NotFound => continue,
+				Err(e) => return Err(FileIOError::from((full_path, e)).into()),
+			};
+
+			if file_path.size_in_bytes != size_to_db_int(metadata.len())
+				|| file_path.date_modified.timestamp()
+					!= DateTime::<Utc>::from(metadata.modified_or_now()).timestamp()
+			{
+				if let Err(e) = update_file(location_id, &full_path, library).await {
+					error!(
+						"Failed to update polled file: <path='{}', error='{e:#?}'>",
+						full_path.display()
+					);
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{library::test_library, util::db::create_test_location};
+
+	async fn indexed_file(
+		location_id: LocationId,
+		name: &str,
+		library: &Library,
+	) -> Option<file_path::Data> {
+		library
+			.db
+			.file_path()
+			.find_first(vec![
+				file_path::location_id::equals(location_id),
+				file_path::name::equals(name.to_string()),
+			])
+			.exec()
+			.await
+			.unwrap()
+	}
+
+	#[tokio::test]
+	async fn polls_find_files_changed_in_place() {
+		let (library, data_dir) = test_library().await;
+		let location_path = data_dir.path().join("location");
+		fs::create_dir_all(&location_path).await.unwrap();
+		fs::write(location_path.join("notes.txt"), [1u8; 10])
+			.await
+			.unwrap();
+
+		let location = create_test_location(&library.db, &location_path).await;
+		library
+			.location_manager()
+			.add_online(Uuid::from_slice(&location.pub_id).unwrap())
+			.await;
+
+		poll(location.id, &library, false).await.unwrap();
+
+		let indexed = indexed_file(location.id, "notes", &library).await.unwrap();
+		assert_eq!(indexed.size_in_bytes, 10);
+		assert!(indexed.cas_id.is_some());
+
+		fs::write(location_path.join("notes.txt"), [2u8; 30])
+			.await
+			.unwrap();
+
+		poll(location.id, &library, false).await.unwrap();
+
+		let updated = indexed_file(location.id, "notes", &library).await.unwrap();
+		assert_eq!(updated.pub_id, indexed.pub_id);
+		assert_eq!(updated.size_in_bytes, 30);
+		assert_ne!(updated.cas_id, indexed.cas_id);
+	}
+
+	#[tokio::test]
+	async fn offline_locations_arent_polled() {
+		let (library, data_dir) = test_library().await;
+		let location_path = data_dir.path().join("location");
+		fs::create_dir_all(&location_path).await.unwrap();
+		fs::write(location_path.join("notes.txt"), [1u8; 10])
+			.await
+			.unwrap();
+
+		let location = create_test_location(&library.db, &location_path).await;
+
+		poll(location.id, &library, false).await.unwrap();
+
+		assert!(indexed_file(location.id, "notes", &library).await.is_none());
+	}
+}
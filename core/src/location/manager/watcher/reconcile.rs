@@ -0,0 +1,588 @@
+//! The watcher only sees the changes made while the node is running. Its checkpoint marks until when
+//! every change to a location was handled, so when the location comes online again the directories
+//! modified after it are read and compared with the database, replaying the changes the watcher
+//! missed with the same functions that handle its events. Files edited in place don't change their
+//! directories' modification times, so every indexed file is then checked like polls do.
+
+use crate::{
+	library::Library,
+	location::{
+		file_path_helper::{get_inode_and_device_from_path, IsolatedFilePathData, MetadataExt},
+		find_location, location_with_indexer_rules, poll_location, LocationId,
+	},
+	prisma::{file_path, location, watcher_checkpoint, SortOrder},
+	util::{
+		db::{db_int_to_u64, size_to_db_int},
+		error::FileIOError,
+	},
+	volume::directory_mtimes_are_reliable,
+};
+
+use std::{
+	collections::{hash_map::Entry, HashMap},
+	fs::Metadata,
+	path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use tokio::{fs, io::ErrorKind};
+use tracing::{debug, error};
+
+use super::{
+	polling::update_changed_files,
+	utils::{create_dir, create_file, remove, rename, update_file},
+	INodeAndDevice, LocationManagerError,
+};
+
+/// Some filesystems, like FAT, only store modification times with a 2 seconds resolution
+const CHECKPOINT_SLACK_SECONDS: i64 = 2;
+
+/// How many directories are checked for modifications at once
+const DIRECTORIES_CHUNK_SIZE: i64 = 1000;
+
+/// The changes found in the modified directories, applied only after all of them were read, so
+/// paths moved between two directories are found as renames
+struct Reconciliation {
+	location_id: LocationId,
+	location_path: PathBuf,
+	since: DateTime<Utc>,
+	created: Vec<(PathBuf, Metadata)>,
+	removed: Vec<(PathBuf, file_path::Data)>,
+	updated: Vec<PathBuf>,
+}
+
+pub(in crate::location::manager) async fn save_checkpoint(
+	location_id: LocationId,
+	library: &Library,
+) -> Result<(), LocationManagerError> {
+	let checkpoint = Utc::now().into();
+
+	library
+		.db
+		.watcher_checkpoint()
+		.upsert(
+			watcher_checkpoint::location_id::equals(location_id),
+			watcher_checkpoint::create(
+				location::id::equals(location_id),
+				vec![watcher_checkpoint::checkpoint::set(checkpoint)],
+			),
+			vec![watcher_checkpoint::checkpoint::set(checkpoint)],
+		)
+		.exec()
+		.await?;
+
+	Ok(())
+}
+
+pub(in crate::location::manager) async fn reconcile_location(
+	location_id: LocationId,
+	library: &Library,
+) -> Result<(), LocationManagerError> {
+	let Some(location) = find_location(library, location_id)
+		.include(location_with_indexer_rules::include())
+		.exec()
+		.await?
+	else {
+		return Ok(());
+	};
+
+	// Never watched before, the indexer job takes care of it
+	let Some(checkpoint) = library
+		.db
+		.watcher_checkpoint()
+		.find_unique(watcher_checkpoint::location_id::equals(location_id))
+		.exec()
+		.await?
+	else {
+		return Ok(());
+	};
+
+	let location_path = PathBuf::from(&location.path);
+
+	if !directory_mtimes_are_reliable(&location.path) {
+		debug!(
+			"Polling location to reconcile it, as its directories can't tell if they changed: \
+			<id='{location_id}'>"
+		);
+		poll_location(library, location, false).await?;
+
+		return update_changed_files(location_id, &location_path, library).await;
+	}
+
+	let mut reconciliation = Reconciliation {
+		location_id,
+		location_path: location_path.clone(),
+		since: DateTime::<Utc>::from(checkpoint.checkpoint)
+			- Duration::seconds(CHECKPOINT_SLACK_SECONDS),
+		created: vec![],
+		removed: vec![],
+		updated: vec![],
+	};
+
+	for (dir_path, materialized_path) in find_modified_directories(
+		location_id,
+		&reconciliation.location_path,
+		reconciliation.since,
+		library,
+	)
+	.await?
+	{
+		if let Err(e) = reconciliation
+			.read_directory(&dir_path, &materialized_path, library)
+			.await
+		{
+			error!(
+				"Failed to reconcile directory: <path='{}', error='{e:#?}'>",
+				dir_path.display()
+			);
+		}
+	}
+
+	reconciliation.apply(library).await;
+
+	update_changed_files(location_id, &location_path, library).await
+}
+
+/// The directories of the location, the root included, modified since the checkpoint, with the
+/// materialized paths of their children
+async fn find_modified_directories(
+	location_id: LocationId,
+	location_path: &Path,
+	since: DateTime<Utc>,
+	library: &Library,
+) -> Result<Vec<(PathBuf, String)>, LocationManagerError> {
+	let mut modified = vec![];
+
+	if was_modified_since(location_path, since).await? {
+		modified.push((location_path.to_path_buf(), "/".to_string()));
+	}
+
+	let mut cursor = 0;
+
+	loop {
+		let directories = library
+			.db
+			.file_path()
+			.find_many(vec![
+				file_path::location_id::equals(location_id),
+				file_path::is_dir::equals(true),
+				file_path::id::gt(cursor),
+			])
+			.order_by(file_path::id::order(SortOrder::Asc))
+			.take(DIRECTORIES_CHUNK_SIZE)
+			.exec()
+			.await?;
+
+		let Some(last) = directories.last() else {
+			return Ok(modified);
+		};
+		cursor = last.id;
+
+		for directory in &directories {
+			let iso_file_path = IsolatedFilePathData::from(directory);
+			let full_path = location_path.join(&iso_file_path);
+
+			if was_modified_since(&full_path, since).await? {
+				modified.push((
+					full_path,
+					iso_file_path
+						.materialized_path_for_children()
+						.expect("directories always have a materialized path for children"),
+				));
+			}
+		}
+	}
+}
+
+/// Paths that don't exist anymore weren't modified, their removal shows up in their parent
+async fn was_modified_since(
+	path: impl AsRef<Path>,
+	since: DateTime<Utc>,
+) -> Result<bool, LocationManagerError> {
+	let path = path.as_ref();
+
+	match fs::symlink_metadata(path).await {
+		Ok(metadata) => Ok(DateTime::<Utc>::from(metadata.modified_or_now()) > since),
+		Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+		Err(e) => Err(FileIOError::from((path, e)).into()),
+	}
+}
+
+impl Reconciliation {
+	async fn read_directory(
+		&mut self,
+		dir_path: &Path,
+		materialized_path: &str,
+		library: &Library,
+	) -> Result<(), LocationManagerError> {
+		let mut indexed = library
+			.db
+			.file_path()
+			.find_many(vec![
+				file_path::location_id::equals(self.location_id),
+				file_path::materialized_path::equals(materialized_path.to_string()),
+			])
+			.exec()
+			.await?
+			.into_iter()
+			.map(|file_path| {
+				(
+					self.location_path
+						.join(IsolatedFilePathData::from(&file_path)),
+					file_path,
+				)
+			})
+			.collect::<HashMap<_, _>>();
+
+		let mut read_dir = fs::read_dir(dir_path)
+			.await
+			.map_err(|e| FileIOError::from((dir_path, e)))?;
+
+		while let Some(entry) = read_dir
+			.next_entry()
+			.await
+			.map_err(|e| FileIOError::from((dir_path, e)))?
+		{
+			let path = entry.path();
+
+			// The same paths the watcher ignores
+			if path.ends_with(".DS_Store") || path.ends_with(".spacedrive") {
+				continue;
+			}
+
+			let metadata = match fs::symlink_metadata(&path).await {
+				Ok(metadata) => metadata,
+				// Removed while we were reading its directory
+				Err(e) if e.kind() == ErrorKind::NotFound => continue,
+				Err(e) => return Err(FileIOError::from((path, e)).into()),
+			};
+
+			match indexed.remove(&path) {
+				// Symbolic links are indexed as directories when they're followed into them
+				Some(file_path)
+					if !metadata.is_symlink() && file_path.is_dir != metadata.is_dir() =>
+				{
+					self.removed.push((path.clone(), file_path));
+					self.created.push((path, metadata));
+				}
+				Some(file_path) => {
+					if !file_path.is_dir
+						&& (file_path.size_in_bytes != size_to_db_int(metadata.len())
+							|| DateTime::<Utc>::from(metadata.modified_or_now()) > self.since)
+					{
+						self.updated.push(path);
+					}
+				}
+				None => self.created.push((path, metadata)),
+			}
+		}
+
+		// Whatever is left in the database isn't in the directory anymore
+		self.removed.extend(indexed);
+
+		Ok(())
+	}
+
+	async fn apply(self, library: &Library) {
+		let Self {
+			location_id,
+			created,
+			removed,
+			updated,
+			..
+		} = self;
+
+		// Hardlinks share their inode and device, so several removed paths may have the same ones
+		let mut removed_by_inode = HashMap::<INodeAndDevice, Vec<_>>::new();
+		for (path, file_path) in removed {
+			removed_by_inode
+				.entry((
+					db_int_to_u64(file_path.inode),
+					db_int_to_u64(file_path.device),
+				))
+				.or_default()
+				.push(path);
+		}
+
+		let mut moved = vec![];
+		let mut new = vec![];
+		for (path, metadata) in created {
+			match get_inode_and_device_from_path(&path).await {
+				Ok(inode_and_device) => match removed_by_inode.entry(inode_and_device) {
+					Entry::Occupied(mut entry) => {
+						let old_path = entry.get_mut().pop().expect("entries are never empty");
+						if entry.get().is_empty() {
+							entry.remove();
+						}
+						moved.push((path, old_path, metadata));
+					}
+					Entry::Vacant(_) => new.push((path, metadata)),
+				},
+				Err(e) => error!(
+					"Failed to get inode and device of a reconciled path: <path='{}', error='{e:#?}'>",
+					path.display()
+				),
+			}
+		}
+
+		let mut moved_count = moved.len();
+		let mut removed = vec![];
+		for (path, old_path, metadata) in moved {
+			if let Err(e) = rename(location_id, &path, &old_path, library).await {
+				// Usually moved into a new directory, so it's indexed along with it
+				debug!(
+					"Failed to reconcile a moved path, removing and creating it instead: \
+					<path='{}', error='{e:#?}'>",
+					path.display()
+				);
+				moved_count -= 1;
+				removed.push(old_path);
+				new.push((path, metadata));
+			}
+		}
+
+		removed.extend(removed_by_inode.into_values().flatten());
+
+		let removed_count = removed.len();
+		for path in removed {
+			if let Err(e) = remove(location_id, &path, library).await {
+				error!(
+					"Failed to reconcile a removed path: <path='{}', error='{e:#?}'>",
+					path.display()
+				);
+			}
+		}
+
+		// Parents first, so their children have somewhere to be created in
+		new.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+		let mut new_dirs = Vec::<PathBuf>::new();
+		let mut created_count = 0;
+		for (path, metadata) in new {
+			// Directories are created with their contents
+			if new_dirs.iter().any(|dir| path.starts_with(dir)) {
+				continue;
+			}
+
+			created_count += 1;
+
+			let result = if metadata.is_dir() {
+				new_dirs.push(path.clone());
+				create_dir(location_id, &path, &metadata, library).await
+			} else {
+				create_file(location_id, &path, &metadata, library).await
+			};
+
+			if let Err(e) = result {
+				error!(
+					"Failed to reconcile a new path: <path='{}', error='{e:#?}'>",
+					path.display()
+				);
+			}
+		}
+
+		let updated_count = updated.len();
+		for path in updated {
+			if let Err(e) = update_file(location_id, &path, library).await {
+				error!(
+					"Failed to reconcile an updated file: <path='{}', error='{e:#?}'>",
+					path.display()
+				);
+			}
+		}
+
+		debug!(
+			"Reconciled location <id='{location_id}'>: {created_count} new paths, \
+			{moved_count} moved, {removed_count} removed and {updated_count} updated"
+		);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		library::test_library, location::file_path_helper::file_path_to_isolate,
+		util::db::create_test_location,
+	};
+
+	async fn indexed_paths(location_id: LocationId, library: &Library) -> Vec<String> {
+		let mut paths = library
+			.db
+			.file_path()
+			.find_many(vec![file_path::location_id::equals(location_id)])
+			.select(file_path_to_isolate::select())
+			.exec()
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|file_path| IsolatedFilePathData::from(file_path).to_string())
+			.collect::<Vec<_>>();
+		paths.sort();
+		paths
+	}
+
+	async fn pub_id_of(location_id: LocationId, name: &str, library: &Library) -> Vec<u8> {
+		library
+			.db
+			.file_path()
+			.find_first(vec![
+				file_path::location_id::equals(location_id),
+				file_path::name::equals(name.to_string()),
+			])
+			.exec()
+			.await
+			.unwrap()
+			.unwrap()
+			.pub_id
+	}
+
+	#[tokio::test]
+	async fn changes_made_while_unwatched_are_replayed() {
+		let (library, data_dir) = test_library().await;
+		let location_path = data_dir.path().join("location");
+
+		fs::create_dir_all(location_path.join("docs"))
+			.await
+			.unwrap();
+		fs::write(location_path.join("docs/report.pdf"), [0u8; 10])
+			.await
+			.unwrap();
+		fs::write(location_path.join("notes.txt"), [0u8; 20])
+			.await
+			.unwrap();
+		fs::write(location_path.join("old.txt"), [0u8; 30])
+			.await
+			.unwrap();
+
+		let location_id = create_test_location(&library.db, &location_path).await.id;
+		let location = find_location(&library, location_id)
+			.include(location_with_indexer_rules::include())
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+		poll_location(&library, location, false).await.unwrap();
+		save_checkpoint(location_id, &library).await.unwrap();
+
+		let notes_pub_id = pub_id_of(location_id, "notes", &library).await;
+
+		// Changed while the location wasn't watched
+		fs::rename(
+			location_path.join("notes.txt"),
+			location_path.join("docs/notes.txt"),
+		)
+		.await
+		.unwrap();
+		fs::remove_file(location_path.join("old.txt"))
+			.await
+			.unwrap();
+		fs::write(location_path.join("new.txt"), [0u8; 40])
+			.await
+			.unwrap();
+
+		reconcile_location(location_id, &library).await.unwrap();
+
+		assert_eq!(
+			indexed_paths(location_id, &library).await,
+			["docs", "docs/notes.txt", "docs/report.pdf", "new.txt"]
+		);
+
+		// Locations whose directories can't tell if they changed are polled instead, which sees
+		// moves as a removal and a creation
+		if directory_mtimes_are_reliable(&location_path) {
+			assert_eq!(
+				pub_id_of(location_id, "notes", &library).await,
+				notes_pub_id
+			);
+		}
+	}
+
+	async fn index(location_id: LocationId, library: &Library) {
+		let location = find_location(library, location_id)
+			.include(location_with_indexer_rules::include())
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+		poll_location(library, location, false).await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn files_edited_in_place_are_updated() {
+		let (library, data_dir) = test_library().await;
+		let location_path = data_dir.path().join("location");
+
+		fs::create_dir_all(location_path.join("docs"))
+			.await
+			.unwrap();
+		fs::write(location_path.join("docs/report.pdf"), [0u8; 10])
+			.await
+			.unwrap();
+
+		let location_id = create_test_location(&library.db, &location_path).await.id;
+		index(location_id, &library).await;
+
+		// No directory looks modified after a checkpoint in the future, as if the file was edited
+		// long after its directory last changed
+		library
+			.db
+			.watcher_checkpoint()
+			.create(
+				location::id::equals(location_id),
+				vec![watcher_checkpoint::checkpoint::set(
+					(Utc::now() + Duration::hours(1)).into(),
+				)],
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		fs::write(location_path.join("docs/report.pdf"), [1u8; 25])
+			.await
+			.unwrap();
+
+		reconcile_location(location_id, &library).await.unwrap();
+
+		let report = library
+			.db
+			.file_path()
+			.find_first(vec![
+				file_path::location_id::equals(location_id),
+				file_path::name::equals("report".to_string()),
+			])
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(report.size_in_bytes, 25);
+	}
+
+	#[tokio::test]
+	async fn all_removed_hardlinks_are_removed() {
+		let (library, data_dir) = test_library().await;
+		let location_path = data_dir.path().join("location");
+
+		fs::create_dir_all(&location_path).await.unwrap();
+		fs::write(location_path.join("original.txt"), [0u8; 10])
+			.await
+			.unwrap();
+		for link in ["first.txt", "second.txt"] {
+			fs::hard_link(location_path.join("original.txt"), location_path.join(link))
+				.await
+				.unwrap();
+		}
+
+		let location_id = create_test_location(&library.db, &location_path).await.id;
+		index(location_id, &library).await;
+		save_checkpoint(location_id, &library).await.unwrap();
+
+		for link in ["first.txt", "second.txt"] {
+			fs::remove_file(location_path.join(link)).await.unwrap();
+		}
+
+		reconcile_location(location_id, &library).await.unwrap();
+
+		assert_eq!(indexed_paths(location_id, &library).await, ["original.txt"]);
+	}
+}
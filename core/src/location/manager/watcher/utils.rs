@@ -1,18 +1,25 @@
 use crate::{
 	invalidate_query,
-	library::Library,
+	library::{
+		update_kind_statistics, update_location_statistics, FilePathsStatisticsDelta, Library,
+	},
 	location::{
 		delete_directory,
 		file_path_helper::{
 			check_existing_file_path, create_file_path, file_path_with_object,
 			filter_existing_file_path_params, get_parent_dir,
 			isolated_file_path_data::extract_normalized_materialized_path_str,
-			loose_find_existing_file_path_params, FilePathError, FilePathMetadata,
+			loose_find_existing_file_path_params, posix_metadata, DirectorySizeDelta,
+			DirectorySizesDeltas, FilePathError, FilePathMetadata, FilePathPermissions,
 			IsolatedFilePathData, MetadataExt,
 		},
-		find_location, location_with_indexer_rules,
+		find_location,
+		indexer::{followable_symlink_dir, promote_hardlinks},
+		location_with_indexer_rules,
 		manager::LocationManagerError,
-		scan_location_sub_path, LocationId,
+		scan_location_sub_path,
+		size_tree::invalidate_size_tree,
+		LocationId,
 	},
 	object::{
 		file_identifier::FileMetadata,
@@ -22,7 +29,10 @@ use crate::{
 	},
 	prisma::{file_path, location, object},
 	sync,
-	util::error::FileIOError,
+	util::{
+		db::{db_int_to_u64, size_to_db_int},
+		error::FileIOError,
+	},
 };
 
 #[cfg(target_family = "unix")]
@@ -40,7 +50,7 @@ use std::{
 
 use sd_file_ext::extensions::ImageExtension;
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, FixedOffset, Local};
 use notify::{Event, EventKind};
 use prisma_client_rust::{raw, PrismaValue};
 use serde_json::json;
@@ -61,11 +71,66 @@ pub(super) fn check_event(event: &Event, ignore_paths: &HashSet<PathBuf>) -> boo
 	})
 }
 
+/// A symbolic link, indexed like the walker does: as an alias, unless the location follows
+/// symbolic links and this one points to a directory, which is then indexed as a directory
+struct Symlink {
+	target: String,
+	followed: bool,
+	/// Of the link itself, not of what it points to
+	metadata: Metadata,
+}
+
+async fn read_symlink(path: &Path, follow_symlinks: bool) -> Result<Option<Symlink>, FileIOError> {
+	let metadata = fs::symlink_metadata(path)
+		.await
+		.map_err(|e| FileIOError::from((path, e)))?;
+
+	if !metadata.is_symlink() {
+		return Ok(None);
+	}
+
+	let target = fs::read_link(path)
+		.await
+		.map_err(|e| FileIOError::from((path, e)))?;
+
+	let followed = match path.parent() {
+		Some(parent) if follow_symlinks => {
+			followable_symlink_dir(path, parent, &[]).await.is_some()
+		}
+		_ => false,
+	};
+
+	Ok(Some(Symlink {
+		target: target.to_string_lossy().to_string(),
+		followed,
+		metadata,
+	}))
+}
+
 pub(super) async fn create_dir(
 	location_id: LocationId,
 	path: impl AsRef<Path>,
 	metadata: &Metadata,
 	library: &Library,
+) -> Result<(), LocationManagerError> {
+	create_dir_or_file_with_metadata(location_id, path.as_ref(), metadata, true, library).await
+}
+
+pub(super) async fn create_file(
+	location_id: LocationId,
+	path: impl AsRef<Path>,
+	metadata: &Metadata,
+	library: &Library,
+) -> Result<(), LocationManagerError> {
+	create_dir_or_file_with_metadata(location_id, path.as_ref(), metadata, false, library).await
+}
+
+async fn create_dir_or_file_with_metadata(
+	location_id: LocationId,
+	path: &Path,
+	metadata: &Metadata,
+	is_dir: bool,
+	library: &Library,
 ) -> Result<(), LocationManagerError> {
 	let location = find_location(library, location_id)
 		.include(location_with_indexer_rules::include())
@@ -73,8 +138,38 @@ pub(super) async fn create_dir(
 		.await?
 		.ok_or(LocationManagerError::MissingLocation(location_id))?;
 
-	let path = path.as_ref();
+	let symlink = read_symlink(path, location.follow_symlinks).await?;
+	let (is_dir, metadata, symlink_target) = match &symlink {
+		Some(symlink) => (
+			symlink.followed,
+			&symlink.metadata,
+			Some(symlink.target.clone()),
+		),
+		None => (is_dir, metadata, None),
+	};
+
+	if is_dir {
+		inner_create_dir(location, path, metadata, symlink_target, library).await
+	} else {
+		inner_create_file(
+			location_id,
+			PathBuf::from(location.path),
+			path,
+			metadata,
+			symlink_target,
+			library,
+		)
+		.await
+	}
+}
 
+async fn inner_create_dir(
+	location: location_with_indexer_rules::Data,
+	path: &Path,
+	metadata: &Metadata,
+	symlink_target: Option<String>,
+	library: &Library,
+) -> Result<(), LocationManagerError> {
 	trace!(
 		"Location: <root_path ='{}'> creating directory: {}",
 		location.path,
@@ -113,15 +208,28 @@ pub(super) async fn create_dir(
 		FilePathMetadata {
 			inode,
 			device,
-			size_in_bytes: metadata.len(),
+			// The directory's size will be the sum of its children, added as they're indexed
+			size_in_bytes: 0,
 			created_at: metadata.created_or_now().into(),
 			modified_at: metadata.modified_or_now().into(),
+			permissions: FilePathPermissions::from_metadata(metadata),
 		},
+		posix_metadata::read_extended_attributes_async(path).await?,
+		symlink_target,
 	)
 	.await?;
 
 	info!("Created path: {}", created_path.materialized_path);
 
+	let mut directory_sizes = DirectorySizesDeltas::default();
+	directory_sizes.add(
+		&created_path.materialized_path,
+		&created_path.name,
+		DirectorySizeDelta::added(0, 0),
+	);
+	directory_sizes.apply(location.id, &library.db).await?;
+	invalidate_size_tree(library, location.id).await;
+
 	// scan the new directory
 	scan_location_sub_path(library, location, &created_path.materialized_path).await?;
 
@@ -130,15 +238,14 @@ pub(super) async fn create_dir(
 	Ok(())
 }
 
-pub(super) async fn create_file(
+async fn inner_create_file(
 	location_id: LocationId,
-	path: impl AsRef<Path>,
+	location_path: PathBuf,
+	path: &Path,
 	metadata: &Metadata,
+	symlink_target: Option<String>,
 	library: &Library,
 ) -> Result<(), LocationManagerError> {
-	let path = path.as_ref();
-	let location_path = extract_location_path(location_id, library).await?;
-
 	trace!(
 		"Location: <root_path ='{}'> creating file: {}",
 		location_path.display(),
@@ -187,12 +294,37 @@ pub(super) async fn create_file(
 			size_in_bytes: metadata.len(),
 			created_at: metadata.created_or_now().into(),
 			modified_at: metadata.modified_or_now().into(),
+			permissions: FilePathPermissions::from_metadata(metadata),
 		},
+		posix_metadata::read_extended_attributes_async(path).await?,
+		symlink_target,
 	)
 	.await?;
 
 	info!("Created path: {}", created_file.materialized_path);
 
+	let size_in_bytes = if created_file.is_hardlink {
+		0
+	} else {
+		size_to_db_int(metadata.len())
+	};
+
+	update_location_statistics(
+		db,
+		location_id,
+		FilePathsStatisticsDelta::added(size_in_bytes),
+	)
+	.await?;
+
+	let mut directory_sizes = DirectorySizesDeltas::default();
+	directory_sizes.add(
+		&created_file.materialized_path,
+		&created_file.name,
+		DirectorySizeDelta::added(size_in_bytes, 0),
+	);
+	directory_sizes.apply(location_id, db).await?;
+	invalidate_size_tree(library, location_id).await;
+
 	let existing_object = db
 		.object()
 		.find_first(vec![object::file_paths::some(vec![
@@ -206,7 +338,8 @@ pub(super) async fn create_file(
 	let object = if let Some(object) = existing_object {
 		object
 	} else {
-		db.object()
+		let object = db
+			.object()
 			.create(
 				Uuid::new_v4().as_bytes().to_vec(),
 				vec![
@@ -218,7 +351,11 @@ pub(super) async fn create_file(
 			)
 			.select(object_just_id_has_thumbnail::select())
 			.exec()
-			.await?
+			.await?;
+
+		update_kind_statistics(db, kind as i32, 1, size_in_bytes).await?;
+
+		object
 	};
 
 	db.file_path()
@@ -363,13 +500,14 @@ async fn inner_update_file(
 
 				[
 					(
-						(cas_id::NAME, json!(old_cas_id)),
-						cas_id::set(Some(old_cas_id.clone())),
-					),
-					(
-						(size_in_bytes::NAME, json!(fs_metadata.len().to_string())),
-						size_in_bytes::set(fs_metadata.len().to_string()),
+						(cas_id::NAME, json!(cas_id)),
+						cas_id::set(Some(cas_id.clone())),
 					),
+					{
+						let size = size_to_db_int(fs_metadata.len());
+
+						((size_in_bytes::NAME, json!(size)), size_in_bytes::set(size))
+					},
 					{
 						let date = DateTime::<Local>::from(fs_metadata.modified_or_now()).into();
 
@@ -422,6 +560,95 @@ async fn inner_update_file(
 			)
 			.await?;
 
+			let new_size = size_to_db_int(fs_metadata.len());
+
+			// Hardlinks share their content, so the other paths of the same inode and device get
+			// the new size too. Only one of them has its bytes counted, so the statistics and the
+			// directory sizes move by its change alone, whichever of them was edited
+			let other_links = db
+				.file_path()
+				.find_many(vec![
+					file_path::location_id::equals(location_id),
+					file_path::inode::equals(file_path.inode),
+					file_path::device::equals(file_path.device),
+					file_path::pub_id::not(file_path.pub_id.clone()),
+				])
+				.select(
+					file_path::select!({ pub_id materialized_path name size_in_bytes is_hardlink }),
+				)
+				.exec()
+				.await?;
+
+			if !other_links.is_empty() {
+				sync.write_ops(
+					db,
+					(
+						other_links
+							.iter()
+							.map(|link| {
+								sync.shared_update(
+									sync::file_path::SyncId {
+										pub_id: link.pub_id.clone(),
+									},
+									file_path::size_in_bytes::NAME,
+									json!(new_size),
+								)
+							})
+							.collect(),
+						db.file_path().update_many(
+							vec![file_path::pub_id::in_vec(
+								other_links.iter().map(|link| link.pub_id.clone()).collect(),
+							)],
+							vec![file_path::size_in_bytes::set(new_size)],
+						),
+					),
+				)
+				.await?;
+			}
+
+			let counted = if file_path.is_hardlink {
+				other_links
+					.iter()
+					.find(|link| !link.is_hardlink)
+					.map(|link| (&link.materialized_path, &link.name, link.size_in_bytes))
+			} else {
+				Some((
+					&file_path.materialized_path,
+					&file_path.name,
+					file_path.size_in_bytes,
+				))
+			};
+
+			let (old_counted_size, new_counted_size) = counted
+				.map(|(_, _, old_size)| (old_size, new_size))
+				.unwrap_or_default();
+			let size_delta = new_counted_size - old_counted_size;
+
+			update_location_statistics(
+				db,
+				location_id,
+				FilePathsStatisticsDelta {
+					count: 0,
+					bytes: size_delta,
+				},
+			)
+			.await?;
+
+			if let Some((materialized_path, name, _)) = counted {
+				let mut directory_sizes = DirectorySizesDeltas::default();
+				directory_sizes.add(
+					materialized_path,
+					name,
+					DirectorySizeDelta {
+						bytes: size_delta,
+						items: 0,
+					},
+				);
+				directory_sizes.apply(location_id, db).await?;
+			}
+
+			invalidate_size_tree(library, location_id).await;
+
 			if let Some(ref object) = file_path.object {
 				// if this file had a thumbnail previously, we update it to match the new content
 				if library.thumbnail_exists(old_cas_id).await? && !file_path.extension.is_empty() {
@@ -437,6 +664,9 @@ async fn inner_update_file(
 				let int_kind = kind as i32;
 
 				if object.kind != int_kind {
+					update_kind_statistics(db, object.kind, -1, -old_counted_size).await?;
+					update_kind_statistics(db, int_kind, 1, new_counted_size).await?;
+
 					sync.write_op(
 						db,
 						sync.shared_update(
@@ -452,16 +682,126 @@ async fn inner_update_file(
 						),
 					)
 					.await?;
+				} else {
+					update_kind_statistics(db, int_kind, 0, size_delta).await?;
 				}
 			}
 
 			invalidate_query!(library, "search.paths");
+		} else {
+			// Same content, maybe it was just touched, so only its modification date changes
+			let date: DateTime<FixedOffset> =
+				DateTime::<Local>::from(fs_metadata.modified_or_now()).into();
+
+			if file_path.date_modified != date {
+				sync.write_op(
+					db,
+					sync.shared_update(
+						sync::file_path::SyncId {
+							pub_id: file_path.pub_id.clone(),
+						},
+						file_path::date_modified::NAME,
+						json!(date),
+					),
+					db.file_path().update(
+						file_path::pub_id::equals(file_path.pub_id.clone()),
+						vec![file_path::date_modified::set(date)],
+					),
+				)
+				.await?;
+			}
 		}
 	}
 
 	Ok(())
 }
 
+/// Picks up changes to the permissions, owner or extended attributes of a path, which come as
+/// metadata events without any change to the file's content
+pub(super) async fn update_posix_metadata(
+	location_id: LocationId,
+	full_path: impl AsRef<Path>,
+	library @ Library { db, sync, .. }: &Library,
+) -> Result<(), LocationManagerError> {
+	let full_path = full_path.as_ref();
+
+	let metadata = match fs::symlink_metadata(full_path).await {
+		Ok(metadata) => metadata,
+		// Already gone, the removal event will take care of it
+		Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+		Err(e) => return Err(FileIOError::from((full_path, e)).into()),
+	};
+
+	let location_path = extract_location_path(location_id, library).await?;
+
+	let Some(file_path) = db
+		.file_path()
+		.find_first(filter_existing_file_path_params(
+			&IsolatedFilePathData::new(location_id, &location_path, full_path, metadata.is_dir())?,
+		))
+		.exec()
+		.await?
+	else {
+		// Not indexed yet, it will get its metadata when it is
+		return Ok(());
+	};
+
+	let (permissions, uid, gid) =
+		posix_metadata::permissions_db_values(FilePathPermissions::from_metadata(&metadata));
+	let xattrs = posix_metadata::read_extended_attributes_async(full_path).await?;
+
+	let (sync_params, db_params): (Vec<_>, Vec<_>) = {
+		use file_path::*;
+
+		[
+			(file_path.permissions != permissions).then(|| {
+				(
+					(permissions::NAME, json!(permissions)),
+					permissions::set(permissions),
+				)
+			}),
+			(file_path.uid != uid).then(|| ((uid::NAME, json!(uid)), uid::set(uid))),
+			(file_path.gid != gid).then(|| ((gid::NAME, json!(gid)), gid::set(gid))),
+			(file_path.xattrs != xattrs)
+				.then(|| ((xattrs::NAME, json!(xattrs)), xattrs::set(xattrs.clone()))),
+		]
+		.into_iter()
+		.flatten()
+		.unzip()
+	};
+
+	if sync_params.is_empty() {
+		return Ok(());
+	}
+
+	sync.write_ops(
+		db,
+		(
+			sync_params
+				.into_iter()
+				.map(|(field, value)| {
+					sync.shared_update(
+						sync::file_path::SyncId {
+							pub_id: file_path.pub_id.clone(),
+						},
+						field,
+						value,
+					)
+				})
+				.collect(),
+			db.file_path().update(
+				file_path::pub_id::equals(file_path.pub_id.clone()),
+				db_params,
+			),
+		),
+	)
+	.await?;
+
+	invalidate_query!(library, "search.paths");
+
+	Ok(())
+}
+
 pub(super) async fn rename(
 	location_id: LocationId,
 	new_path: impl AsRef<Path>,
@@ -539,6 +879,22 @@ pub(super) async fn rename(
 			.exec()
 			.await?;
 
+		// Moving between directories takes the size from the old ancestors to the new ones,
+		// the common ancestors cancel out so a plain rename doesn't update anything
+		let mut directory_sizes = DirectorySizesDeltas::default();
+		directory_sizes.add(
+			&file_path.materialized_path,
+			&file_path.name,
+			DirectorySizeDelta::removed(file_path.size_in_bytes, file_path.items_count),
+		);
+		directory_sizes.add(
+			&new.materialized_path,
+			&new.name,
+			DirectorySizeDelta::added(file_path.size_in_bytes, file_path.items_count),
+		);
+		directory_sizes.apply(location_id, db).await?;
+		invalidate_size_tree(library, location_id).await;
+
 		invalidate_query!(library, "search.paths");
 	}
 
@@ -581,32 +937,81 @@ pub(super) async fn remove_by_file_path(
 		Err(e) if e.kind() == ErrorKind::NotFound => {
 			let db = &library.db;
 
+			// Hardlinks don't count their sizes
+			let size_in_bytes = if file_path.is_hardlink {
+				0
+			} else {
+				file_path.size_in_bytes
+			};
+
 			// if is doesn't, we can remove it safely from our db
 			if file_path.is_dir {
 				delete_directory(
 					library,
 					location_id,
-					Some(file_path.materialized_path.clone()),
+					IsolatedFilePathData::from(file_path).materialized_path_for_children(),
 				)
 				.await?;
+
+				db.file_path()
+					.delete(file_path::pub_id::equals(file_path.pub_id.clone()))
+					.exec()
+					.await?;
 			} else {
 				db.file_path()
 					.delete(file_path::pub_id::equals(file_path.pub_id.clone()))
 					.exec()
 					.await?;
 
+				update_location_statistics(
+					db,
+					location_id,
+					FilePathsStatisticsDelta::removed(size_in_bytes),
+				)
+				.await?;
+
+				if !file_path.is_hardlink {
+					promote_hardlinks(
+						location_id,
+						HashSet::from([(file_path.inode, file_path.device)]),
+						db,
+					)
+					.await?;
+				}
+
 				if let Some(object_id) = file_path.object_id {
-					db.object()
-						.delete_many(vec![
+					// only removing the object if it became an orphan
+					if let Some(object) = db
+						.object()
+						.find_first(vec![
 							object::id::equals(object_id),
 							// https://www.prisma.io/docs/reference/api-reference/prisma-client-reference#none
 							object::file_paths::none(vec![]),
 						])
+						.select(object::select!({ kind }))
 						.exec()
-						.await?;
+						.await?
+					{
+						db.object()
+							.delete(object::id::equals(object_id))
+							.exec()
+							.await?;
+
+						update_kind_statistics(db, object.kind, -1, -file_path.size_in_bytes)
+							.await?;
+					}
 				}
 			}
 
+			let mut directory_sizes = DirectorySizesDeltas::default();
+			directory_sizes.add(
+				&file_path.materialized_path,
+				&file_path.name,
+				DirectorySizeDelta::removed(size_in_bytes, file_path.items_count),
+			);
+			directory_sizes.apply(location_id, db).await?;
+			invalidate_size_tree(library, location_id).await;
+
 			library.orphan_remover.invoke().await;
 		}
 		Err(e) => return Err(FileIOError::from((path, e)).into()),
@@ -689,16 +1094,8 @@ pub(super) async fn extract_inode_and_device_from_path(
 			Err(FilePathError::NotFound(path.into()).into()),
 			|file_path| {
 				Ok((
-					u64::from_le_bytes(
-						file_path.inode[0..8]
-							.try_into()
-							.map_err(|_| LocationManagerError::InvalidInode)?,
-					),
-					u64::from_le_bytes(
-						file_path.device[0..8]
-							.try_into()
-							.map_err(|_| LocationManagerError::InvalidDevice)?,
-					),
+					db_int_to_u64(file_path.inode),
+					db_int_to_u64(file_path.device),
 				))
 			},
 		)
@@ -718,3 +1115,222 @@ pub(super) async fn extract_location_path(
 			|location| Ok(PathBuf::from(location.path)),
 		)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		library::{refresh_location_statistics, test_library},
+		location::poll_location,
+		prisma::location_statistics,
+		util::db::{create_test_location, uuid_to_bytes},
+	};
+
+	#[tokio::test]
+	async fn editing_a_hardlink_counts_its_size_once() {
+		let (library, data_dir) = test_library().await;
+		let location_path = data_dir.path().join("location");
+
+		fs::create_dir_all(location_path.join("docs"))
+			.await
+			.unwrap();
+		fs::write(location_path.join("docs/original.txt"), [1u8; 10])
+			.await
+			.unwrap();
+		fs::hard_link(
+			location_path.join("docs/original.txt"),
+			location_path.join("docs/link.txt"),
+		)
+		.await
+		.unwrap();
+
+		let location = create_test_location(&library.db, &location_path).await;
+
+		for (relative_path, is_dir, is_hardlink, size_in_bytes, items_count) in [
+			("docs", true, false, 10, 2),
+			("docs/original.txt", false, false, 10, 0),
+			("docs/link.txt", false, true, 10, 0),
+		] {
+			let IsolatedFilePathData {
+				materialized_path,
+				name,
+				extension,
+				..
+			} = IsolatedFilePathData::new(
+				location.id,
+				&location_path,
+				location_path.join(relative_path),
+				is_dir,
+			)
+			.unwrap();
+
+			library
+				.db
+				.file_path()
+				.create_unchecked(
+					uuid_to_bytes(Uuid::new_v4()),
+					location.id,
+					materialized_path.to_string(),
+					name.to_string(),
+					extension.to_string(),
+					if is_dir { 2 } else { 1 },
+					1,
+					vec![
+						file_path::is_dir::set(is_dir),
+						file_path::is_hardlink::set(is_hardlink),
+						file_path::size_in_bytes::set(size_in_bytes),
+						file_path::items_count::set(items_count),
+						file_path::cas_id::set((!is_dir).then(|| "stale".to_string())),
+					],
+				)
+				.exec()
+				.await
+				.unwrap();
+		}
+		refresh_location_statistics(&library.db).await.unwrap();
+
+		// Writing in place keeps both names on the same inode
+		fs::write(location_path.join("docs/original.txt"), [2u8; 30])
+			.await
+			.unwrap();
+
+		update_file(location.id, location_path.join("docs/link.txt"), &library)
+			.await
+			.unwrap();
+
+		let sizes = library
+			.db
+			.file_path()
+			.find_many(vec![file_path::location_id::equals(location.id)])
+			.select(file_path::select!({ name size_in_bytes }))
+			.exec()
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|file_path| (file_path.name, file_path.size_in_bytes))
+			.collect::<HashSet<_>>();
+		assert_eq!(
+			sizes,
+			HashSet::from([
+				("docs".to_string(), 30),
+				("original".to_string(), 30),
+				("link".to_string(), 30),
+			])
+		);
+
+		let statistics = library
+			.db
+			.location_statistics()
+			.find_unique(location_statistics::location_id::equals(location.id))
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(statistics.file_path_count, 2);
+		assert_eq!(statistics.total_bytes, 30);
+	}
+
+	#[cfg(target_family = "unix")]
+	async fn create_symlink(
+		location_id: LocationId,
+		path: PathBuf,
+		target: &str,
+		library: &Library,
+	) -> file_path::Data {
+		fs::symlink(target, &path).await.unwrap();
+		create_file(
+			location_id,
+			&path,
+			&fs::metadata(&path).await.unwrap(),
+			library,
+		)
+		.await
+		.unwrap();
+
+		library
+			.db
+			.file_path()
+			.find_first(vec![
+				file_path::location_id::equals(location_id),
+				file_path::materialized_path::equals("/docs/".to_string()),
+				file_path::name::equals(path.file_name().unwrap().to_string_lossy().to_string()),
+			])
+			.exec()
+			.await
+			.unwrap()
+			.unwrap()
+	}
+
+	#[cfg(target_family = "unix")]
+	#[tokio::test]
+	async fn created_symlinks_are_indexed_like_the_walker_does() {
+		let (library, data_dir) = test_library().await;
+		let location_path = data_dir.path().join("location");
+
+		fs::create_dir_all(location_path.join("docs"))
+			.await
+			.unwrap();
+		fs::create_dir_all(location_path.join("photos"))
+			.await
+			.unwrap();
+		fs::write(location_path.join("photos/cat.jpg"), [0u8; 10])
+			.await
+			.unwrap();
+		fs::write(location_path.join("notes.txt"), [0u8; 20])
+			.await
+			.unwrap();
+
+		let location_id = create_test_location(&library.db, &location_path).await.id;
+		poll_location(
+			&library,
+			find_location(&library, location_id)
+				.include(location_with_indexer_rules::include())
+				.exec()
+				.await
+				.unwrap()
+				.unwrap(),
+			false,
+		)
+		.await
+		.unwrap();
+
+		let docs = location_path.join("docs");
+
+		let notes = create_symlink(location_id, docs.join("notes"), "../notes.txt", &library).await;
+		assert!(!notes.is_dir);
+		assert_eq!(notes.symlink_target.as_deref(), Some("../notes.txt"));
+
+		// Symbolic links aren't followed unless the location says so
+		let photos = create_symlink(location_id, docs.join("photos"), "../photos", &library).await;
+		assert!(!photos.is_dir);
+		assert_eq!(photos.symlink_target.as_deref(), Some("../photos"));
+
+		library
+			.db
+			.location()
+			.update(
+				location::id::equals(location_id),
+				vec![location::follow_symlinks::set(true)],
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		let followed =
+			create_symlink(location_id, docs.join("followed"), "../photos", &library).await;
+		assert!(followed.is_dir);
+		assert_eq!(followed.symlink_target.as_deref(), Some("../photos"));
+		assert!(library
+			.db
+			.file_path()
+			.find_first(vec![
+				file_path::location_id::equals(location_id),
+				file_path::materialized_path::equals("/docs/followed/".to_string()),
+				file_path::name::equals("cat".to_string()),
+			])
+			.exec()
+			.await
+			.unwrap()
+			.is_some());
+	}
+}
@@ -30,6 +30,7 @@ use tokio::{fs, time::Instant};
 use tracing::{error, trace};
 
 use super::{
+	burst::BurstCoalescer,
 	utils::{create_dir_or_file, extract_inode_and_device_from_path, remove, rename, update_file},
 	EventHandler, INodeAndDevice, InstantAndPath, HUNDRED_MILLIS, ONE_SECOND,
 };
@@ -46,6 +47,7 @@ pub(super) struct WindowsEventHandler<'lib> {
 	rename_to_map: BTreeMap<INodeAndDevice, InstantAndPath>,
 	to_remove_files: HashMap<INodeAndDevice, InstantAndPath>,
 	removal_buffer: Vec<(INodeAndDevice, InstantAndPath)>,
+	burst: BurstCoalescer,
 }
 
 #[async_trait]
@@ -64,6 +66,7 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 			rename_to_map: BTreeMap::new(),
 			to_remove_files: HashMap::new(),
 			removal_buffer: Vec::new(),
+			burst: BurstCoalescer::new(location_id),
 		}
 	}
 
@@ -90,7 +93,7 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 
 					// We found a new path for this old path, so we can rename it instead of removing and creating it
 					rename(self.location_id, &paths[0], &old_path, self.library).await?;
-				} else {
+				} else if !self.burst.coalesce(&paths[0]) {
 					let metadata =
 						create_dir_or_file(self.location_id, &paths[0], self.library).await?;
 
@@ -103,7 +106,8 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 			EventKind::Modify(ModifyKind::Any) => {
 				let path = &paths[0];
 				// Windows emite events of update right after create events
-				if !self.recently_created_files.contains_key(path) {
+				if !self.recently_created_files.contains_key(path) && !self.burst.is_coalesced(path)
+				{
 					let metadata = fs::metadata(path)
 						.await
 						.map_err(|e| FileIOError::from((path, e)))?;
@@ -160,6 +164,8 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 	}
 
 	async fn tick(&mut self) {
+		self.burst.tick(self.library).await;
+
 		// Cleaning out recently created files that are older than 1 second
 		if self.last_check_recently_files.elapsed() > ONE_SECOND {
 			self.last_check_recently_files = Instant::now();
@@ -1,7 +1,10 @@
 use crate::{
 	invalidate_query,
-	job::{Job, JobManagerError},
-	library::Library,
+	job::{Job, JobError, JobManagerError},
+	library::{
+		remove_orphans_from_kind_statistics, update_location_statistics, FilePathsStatisticsDelta,
+		Library,
+	},
 	object::{
 		file_identifier::{self, file_identifier_job::FileIdentifierJobInit},
 		preview::{shallow_thumbnailer, thumbnailer_job::ThumbnailerJobInit},
@@ -9,6 +12,7 @@ use crate::{
 	prisma::{file_path, indexer_rules_in_location, location, node, object, PrismaClient},
 	sync,
 	util::{db::uuid_to_bytes, error::FileIOError},
+	volume::link_location_to_volume,
 };
 
 use std::{
@@ -23,7 +27,7 @@ use serde::Deserialize;
 use serde_json::json;
 use specta::Type;
 use tokio::{fs, io};
-use tracing::{debug, info};
+use tracing::{debug, error, info};
 use uuid::Uuid;
 
 mod error;
@@ -31,11 +35,16 @@ pub mod file_path_helper;
 pub mod indexer;
 mod manager;
 mod metadata;
+pub mod size_tree;
 
 pub use error::LocationError;
-use indexer::IndexerJobInit;
-pub use manager::{LocationManager, LocationManagerError};
+use indexer::{promote_hardlinks, IndexerJobInit};
+pub use manager::{
+	LocationManager, LocationManagerError, LocationRelinkedEvent, LocationWatchLimitedEvent,
+	WatcherMode,
+};
 use metadata::SpacedriveLocationMetadataFile;
+use size_tree::invalidate_size_tree;
 
 pub type LocationId = i32;
 
@@ -208,7 +217,11 @@ impl LocationCreateArgs {
 /// and a vector of indexer rules ids to add or remove from the location.
 ///
 /// It is important to note that only the indexer rule ids in this vector will be used from now on.
-/// Old rules that aren't in this vector will be purged.
+/// Old rules that aren't in this vector will be purged, and if the rules changed the location is
+/// walked again to index and remove paths accordingly.
+/// The same happens when the location starts or stops following symbolic links, after removing
+/// the ones already indexed, as they're indexed differently now.
+/// Changing the `watcher_mode` restarts the location's watcher with the new mode.
 #[derive(Type, Deserialize)]
 pub struct LocationUpdateArgs {
 	pub id: i32,
@@ -216,6 +229,8 @@ pub struct LocationUpdateArgs {
 	pub generate_preview_media: Option<bool>,
 	pub sync_preview_media: Option<bool>,
 	pub hidden: Option<bool>,
+	pub follow_symlinks: Option<bool>,
+	pub watcher_mode: Option<WatcherMode>,
 	pub indexer_rules_ids: Vec<i32>,
 }
 
@@ -248,6 +263,23 @@ impl LocationUpdateArgs {
 			}),
 			self.hidden
 				.map(|v| ((location::hidden::NAME, json!(v)), location::hidden::set(v))),
+			self.follow_symlinks
+				.filter(|follow_symlinks| &location.follow_symlinks != follow_symlinks)
+				.map(|v| {
+					(
+						(location::follow_symlinks::NAME, json!(v)),
+						location::follow_symlinks::set(v),
+					)
+				}),
+			self.watcher_mode
+				.map(|mode| mode as i32)
+				.filter(|mode| &location.watcher_mode != mode)
+				.map(|v| {
+					(
+						(location::watcher_mode::NAME, json!(v)),
+						location::watcher_mode::set(v),
+					)
+				}),
 		]
 		.into_iter()
 		.flatten()
@@ -293,8 +325,12 @@ impl LocationUpdateArgs {
 			.collect::<HashSet<_>>();
 
 		let new_rules_ids = self.indexer_rules_ids.into_iter().collect::<HashSet<_>>();
+		let rules_changed = current_rules_ids != new_rules_ids;
+		let follow_symlinks_changed = self.follow_symlinks.map_or(false, |follow_symlinks| {
+			location.follow_symlinks != follow_symlinks
+		});
 
-		if current_rules_ids != new_rules_ids {
+		if rules_changed {
 			let rule_ids_to_add = new_rules_ids
 				.difference(&current_rules_ids)
 				.copied()
@@ -321,6 +357,37 @@ impl LocationUpdateArgs {
 			}
 		}
 
+		let watcher_mode_changed = self
+			.watcher_mode
+			.map_or(false, |mode| location.watcher_mode != mode as i32);
+
+		if watcher_mode_changed && location.node_id == library.node_local_id {
+			// Stopping the watcher makes it pick up the new mode, to use it once it's started again
+			let location_manager = library.location_manager();
+			location_manager
+				.stop_watcher(self.id, library.clone())
+				.await?;
+			location_manager
+				.reinit_watcher(self.id, library.clone())
+				.await?;
+		}
+
+		if follow_symlinks_changed && location.node_id == library.node_local_id {
+			indexer::remove_symlink_file_paths(self.id, db).await?;
+		}
+
+		if rules_changed || follow_symlinks_changed {
+			reevaluate_location_indexer_rules(
+				library,
+				find_location(library, self.id)
+					.include(location_with_indexer_rules::include())
+					.exec()
+					.await?
+					.ok_or(LocationError::IdNotFound(self.id))?,
+			)
+			.await?;
+		}
+
 		Ok(())
 	}
 }
@@ -332,6 +399,45 @@ pub fn find_location(library: &Library, location_id: i32) -> location::FindUniqu
 		.find_unique(location::id::equals(location_id))
 }
 
+/// The locations that can be accessed right now. The ones on unplugged drives can still be browsed
+/// through what was indexed of them, but nothing can be done to their files
+pub async fn online_location_ids(library: &Library) -> Result<HashSet<LocationId>, LocationError> {
+	let online = library.location_manager().get_online().await;
+
+	Ok(library
+		.db
+		.location()
+		.find_many(vec![location::pub_id::in_vec(online.into_iter().collect())])
+		.select(location::select!({ id }))
+		.exec()
+		.await?
+		.into_iter()
+		.map(|location| location.id)
+		.collect())
+}
+
+pub async fn check_location_is_online(
+	library: &Library,
+	location_id: LocationId,
+) -> Result<(), LocationError> {
+	let location = find_location(library, location_id)
+		.select(location::select!({ pub_id }))
+		.exec()
+		.await?
+		.ok_or(LocationError::IdNotFound(location_id))?;
+
+	if library
+		.location_manager()
+		.get_online()
+		.await
+		.contains(&location.pub_id)
+	{
+		Ok(())
+	} else {
+		Err(LocationError::Offline(location_id))
+	}
+}
+
 async fn link_location_and_indexer_rules(
 	library: &Library,
 	location_id: i32,
@@ -368,6 +474,7 @@ pub async fn scan_location(
 				IndexerJobInit {
 					location,
 					sub_path: None,
+					incremental: true,
 				},
 				"scan_location",
 			)
@@ -383,6 +490,80 @@ pub async fn scan_location(
 		.await
 }
 
+/// Walks the whole location again after its indexer rules changed, so the indexer adds the paths
+/// that are now accepted and removes the ones that are now rejected, with their descendants.
+/// Unchanged directories must be read too, as the rules decide differently for their entries
+pub async fn reevaluate_location_indexer_rules(
+	library: &Library,
+	location: location_with_indexer_rules::Data,
+) -> Result<(), JobManagerError> {
+	if location.node_id != library.node_local_id {
+		return Ok(());
+	}
+
+	let location_base_data = location::Data::from(&location);
+
+	library
+		.spawn_job(
+			Job::new_with_action(
+				IndexerJobInit {
+					location,
+					sub_path: None,
+					incremental: false,
+				},
+				"reevaluate_indexer_rules",
+			)
+			.queue_next(FileIdentifierJobInit {
+				location: location_base_data.clone(),
+				sub_path: None,
+			})
+			.queue_next(ThumbnailerJobInit {
+				location: location_base_data,
+				sub_path: None,
+			}),
+		)
+		.await
+}
+
+/// The ids of the locations using any of `indexer_rule_ids`, found before the rules change, as
+/// deleting a rule unlinks it from its locations
+pub async fn find_locations_with_indexer_rules(
+	db: &PrismaClient,
+	indexer_rule_ids: Vec<i32>,
+) -> Result<Vec<LocationId>, QueryError> {
+	Ok(db
+		.location()
+		.find_many(vec![location::indexer_rules::some(vec![
+			indexer_rules_in_location::indexer_rule_id::in_vec(indexer_rule_ids),
+		])])
+		.select(location::select!({ id }))
+		.exec()
+		.await?
+		.into_iter()
+		.map(|location| location.id)
+		.collect())
+}
+
+/// Reevaluates the indexer rules of each location in `location_ids`, with the rules they use now,
+/// see [`reevaluate_location_indexer_rules`]
+pub async fn reevaluate_locations_indexer_rules(
+	library: &Library,
+	location_ids: Vec<LocationId>,
+) -> Result<(), LocationError> {
+	for location in library
+		.db
+		.location()
+		.find_many(vec![location::id::in_vec(location_ids)])
+		.include(location_with_indexer_rules::include())
+		.exec()
+		.await?
+	{
+		reevaluate_location_indexer_rules(library, location).await?;
+	}
+
+	Ok(())
+}
+
 #[cfg(feature = "location-watcher")]
 pub async fn scan_location_sub_path(
 	library: &Library,
@@ -402,6 +583,7 @@ pub async fn scan_location_sub_path(
 				IndexerJobInit {
 					location,
 					sub_path: Some(sub_path.clone()),
+					incremental: true,
 				},
 				"scan_location_sub_path",
 			)
@@ -437,6 +619,35 @@ pub async fn light_scan_location(
 	Ok(())
 }
 
+/// Picks up the changes to a location we can't be notified of, walking all of it, or only the
+/// directories that changed when `incremental` (see [`indexer::poll`]). The files found get
+/// identified and their thumbnails generated, as in a light scan of their directories
+pub async fn poll_location(
+	library: &Library,
+	location: location_with_indexer_rules::Data,
+	incremental: bool,
+) -> Result<(), JobManagerError> {
+	if location.node_id != library.node_local_id {
+		return Ok(());
+	}
+
+	let dirs_with_new_files = indexer::poll(&location, library, incremental)
+		.await
+		.map_err(JobError::from)?;
+
+	let location_base_data = location::Data::from(&location);
+
+	for materialized_path in dirs_with_new_files {
+		// Materialized paths start with a slash, the location root being just the slash
+		let sub_path = PathBuf::from(materialized_path.trim_start_matches('/'));
+
+		file_identifier::shallow(&location_base_data, &sub_path, library).await?;
+		shallow_thumbnailer(&location_base_data, &sub_path, library).await?;
+	}
+
+	Ok(())
+}
+
 pub async fn relink_location(
 	library: &Library,
 	location_path: impl AsRef<Path>,
@@ -562,7 +773,7 @@ async fn create_location(
 					(
 						location::node::NAME,
 						json!(sync::node::SyncId {
-							pub_id: uuid_to_bytes(library.id)
+							pub_id: uuid_to_bytes(sync.node())
 						}),
 					),
 					(location::name::NAME, json!(&name)),
@@ -587,6 +798,13 @@ async fn create_location(
 		link_location_and_indexer_rules(library, location.id, indexer_rules_ids).await?;
 	}
 
+	if let Err(e) = link_location_to_volume(library, location.id, &location.path).await {
+		error!(
+			"Failed to link location to its volume: <id='{}', error='{e:#?}'>",
+			location.id
+		);
+	}
+
 	// Updating our location variable to include information about the indexer rules
 	let location = find_location(library, location.id)
 		.include(location_with_indexer_rules::include())
@@ -657,13 +875,28 @@ pub async fn delete_directory(
 	};
 
 	// Fetching all object_ids from all children file_paths
-	let object_ids = db
+	let children = db
 		.file_path()
 		.find_many(children_params.clone())
-		.select(file_path::select!({ object_id }))
+		.select(file_path::select!({ object_id is_dir size_in_bytes inode device is_hardlink }))
 		.exec()
-		.await?
-		.into_iter()
+		.await?;
+
+	// Hardlinks don't count their sizes
+	let removed_statistics = children
+		.iter()
+		.filter(|file_path| !file_path.is_dir)
+		.map(|file_path| {
+			FilePathsStatisticsDelta::removed(if file_path.is_hardlink {
+				0
+			} else {
+				file_path.size_in_bytes
+			})
+		})
+		.collect();
+
+	let object_ids = children
+		.iter()
 		.filter_map(|file_path| file_path.object_id)
 		.collect();
 
@@ -671,6 +904,16 @@ pub async fn delete_directory(
 	// delete all children file_paths
 	db.file_path().delete_many(children_params).exec().await?;
 
+	remove_orphans_from_kind_statistics(
+		db,
+		children.iter().filter_map(|file_path| {
+			file_path
+				.object_id
+				.map(|object_id| (object_id, file_path.size_in_bytes))
+		}),
+	)
+	.await?;
+
 	// delete all children objects
 	db.object()
 		.delete_many(vec![
@@ -681,6 +924,22 @@ pub async fn delete_directory(
 		.exec()
 		.await?;
 
+	update_location_statistics(db, location_id, removed_statistics).await?;
+
+	// Hardlinks of the removed files outside of the directory count their sizes from now on
+	promote_hardlinks(
+		location_id,
+		children
+			.iter()
+			.filter(|file_path| !file_path.is_dir && !file_path.is_hardlink)
+			.map(|file_path| (file_path.inode, file_path.device))
+			.collect(),
+		db,
+	)
+	.await?;
+
+	invalidate_size_tree(library, location_id).await;
+
 	invalidate_query!(library, "search.paths");
 
 	Ok(())
@@ -700,10 +959,16 @@ impl From<location_with_indexer_rules::Data> for location::Data {
 			generate_preview_media: data.generate_preview_media,
 			sync_preview_media: data.sync_preview_media,
 			hidden: data.hidden,
+			follow_symlinks: data.follow_symlinks,
+			watcher_mode: data.watcher_mode,
+			volume_id: data.volume_id,
 			date_created: data.date_created,
 			node: None,
+			volume: None,
 			file_paths: None,
 			indexer_rules: None,
+			statistics: None,
+			watcher_checkpoint: None,
 		}
 	}
 }
@@ -722,10 +987,16 @@ impl From<&location_with_indexer_rules::Data> for location::Data {
 			generate_preview_media: data.generate_preview_media,
 			sync_preview_media: data.sync_preview_media,
 			hidden: data.hidden,
+			follow_symlinks: data.follow_symlinks,
+			watcher_mode: data.watcher_mode,
+			volume_id: data.volume_id,
 			date_created: data.date_created,
 			node: None,
+			volume: None,
 			file_paths: None,
 			indexer_rules: None,
+			statistics: None,
+			watcher_checkpoint: None,
 		}
 	}
 }
@@ -0,0 +1,301 @@
+use crate::{invalidate_query, library::Library, prisma::PrismaClient, util::db::db_int_to_size};
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	sync::Arc,
+};
+
+use prisma_client_rust::{raw, PrismaValue, QueryError};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use specta::Type;
+use tokio::sync::RwLock;
+
+use super::LocationId;
+
+/// A directory with the cumulative size and file count of everything below it,
+/// suitable for treemap or sunburst views
+#[serde_as]
+#[derive(Serialize, Type, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeTreeNode {
+	pub name: String,
+	#[specta(type = String)]
+	#[serde_as(as = "DisplayFromStr")]
+	pub size_in_bytes: u64,
+	pub file_count: u32,
+	/// Sorted by size, biggest first. Empty when the depth limit was reached
+	pub children: Vec<SizeTreeNode>,
+}
+
+impl SizeTreeNode {
+	/// Builds the tree of a location from the total size and file count of the files
+	/// directly inside each directory, keyed by the directory's materialized path
+	pub fn build(directories: impl IntoIterator<Item = (String, u64, u32)>) -> Self {
+		#[derive(Default)]
+		struct Builder {
+			size_in_bytes: u64,
+			file_count: u32,
+			children: BTreeMap<String, Builder>,
+		}
+
+		impl Builder {
+			fn finish(self, name: String) -> SizeTreeNode {
+				let mut children = self
+					.children
+					.into_iter()
+					.map(|(name, child)| child.finish(name))
+					.collect::<Vec<_>>();
+
+				children.sort_by(|a, b| b.size_in_bytes.cmp(&a.size_in_bytes));
+
+				SizeTreeNode {
+					name,
+					size_in_bytes: self.size_in_bytes,
+					file_count: self.file_count,
+					children,
+				}
+			}
+		}
+
+		let mut root = Builder::default();
+
+		for (materialized_path, size_in_bytes, file_count) in directories {
+			let mut current = &mut root;
+			current.size_in_bytes += size_in_bytes;
+			current.file_count += file_count;
+
+			for name in materialized_path.split('/').filter(|name| !name.is_empty()) {
+				current = current.children.entry(name.to_string()).or_default();
+				current.size_in_bytes += size_in_bytes;
+				current.file_count += file_count;
+			}
+		}
+
+		root.finish(String::new())
+	}
+
+	/// Finds the node of a directory, given its path relative to the location root
+	pub fn find(&self, relative_path: &str) -> Option<&Self> {
+		relative_path
+			.split('/')
+			.filter(|name| !name.is_empty())
+			.try_fold(self, |node, name| {
+				node.children.iter().find(|child| child.name == name)
+			})
+	}
+
+	/// Clones this node, keeping only `max_depth` levels of descendants
+	pub fn with_max_depth(&self, max_depth: u32) -> Self {
+		Self {
+			name: self.name.clone(),
+			size_in_bytes: self.size_in_bytes,
+			file_count: self.file_count,
+			children: if max_depth == 0 {
+				vec![]
+			} else {
+				self.children
+					.iter()
+					.map(|child| child.with_max_depth(max_depth - 1))
+					.collect()
+			},
+		}
+	}
+}
+
+/// Size trees are expensive to compute for big locations, so we keep them around
+/// until the watcher or the indexer change something in the location
+#[derive(Default, Clone)]
+pub struct SizeTreeCache {
+	trees: Arc<RwLock<CachedTrees>>,
+}
+
+#[derive(Default)]
+struct CachedTrees {
+	trees: HashMap<LocationId, Arc<SizeTreeNode>>,
+	/// How many times each location was invalidated, so a tree computed while its location
+	/// changed isn't cached
+	invalidations: HashMap<LocationId, u64>,
+}
+
+impl SizeTreeCache {
+	pub async fn get_or_compute(
+		&self,
+		location_id: LocationId,
+		db: &PrismaClient,
+	) -> Result<Arc<SizeTreeNode>, QueryError> {
+		let invalidations = {
+			let cached = self.trees.read().await;
+
+			if let Some(tree) = cached.trees.get(&location_id) {
+				return Ok(Arc::clone(tree));
+			}
+
+			cached
+				.invalidations
+				.get(&location_id)
+				.copied()
+				.unwrap_or_default()
+		};
+
+		#[derive(Deserialize)]
+		struct DirectoryRow {
+			materialized_path: String,
+			size_in_bytes: i64,
+			file_count: i32,
+		}
+
+		// Hardlinks are left out, as their bytes are already counted by the file they link to
+		let tree = Arc::new(SizeTreeNode::build(
+			db._query_raw::<DirectoryRow>(raw!(
+				"SELECT materialized_path, \
+					COALESCE(SUM(size_in_bytes), 0) AS size_in_bytes, \
+					COUNT(*) AS file_count \
+					FROM file_path WHERE location_id = {} AND NOT is_dir AND NOT is_hardlink \
+					GROUP BY materialized_path",
+				PrismaValue::Int(location_id as i64)
+			))
+			.exec()
+			.await?
+			.into_iter()
+			.map(|row| {
+				(
+					row.materialized_path,
+					db_int_to_size(row.size_in_bytes),
+					row.file_count as u32,
+				)
+			}),
+		));
+
+		let mut cached = self.trees.write().await;
+		if cached
+			.invalidations
+			.get(&location_id)
+			.copied()
+			.unwrap_or_default()
+			== invalidations
+		{
+			cached.trees.insert(location_id, Arc::clone(&tree));
+		}
+
+		Ok(tree)
+	}
+
+	pub async fn invalidate(&self, location_id: LocationId) {
+		let mut cached = self.trees.write().await;
+		cached.trees.remove(&location_id);
+		*cached.invalidations.entry(location_id).or_default() += 1;
+	}
+}
+
+/// Drops the cached size tree of a location and has the frontend ask for it again. Called wherever
+/// the sizes or paths of a location's file paths change
+pub async fn invalidate_size_tree(library: &Library, location_id: LocationId) {
+	library.size_tree_cache.invalidate(location_id).await;
+	invalidate_query!(library, "locations.sizeTree");
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		prisma::file_path,
+		util::db::{create_test_location, test_db, uuid_to_bytes},
+	};
+
+	use uuid::Uuid;
+
+	fn leaf(name: &str, size_in_bytes: u64, file_count: u32) -> SizeTreeNode {
+		SizeTreeNode {
+			name: name.to_string(),
+			size_in_bytes,
+			file_count,
+			children: vec![],
+		}
+	}
+
+	#[test]
+	fn test_build_size_tree() {
+		let tree = SizeTreeNode::build([
+			("/".to_string(), 10, 1),
+			("/photos/".to_string(), 100, 2),
+			("/photos/2023/".to_string(), 1000, 3),
+			("/docs/".to_string(), 50, 1),
+		]);
+
+		assert_eq!(
+			tree,
+			SizeTreeNode {
+				name: String::new(),
+				size_in_bytes: 1160,
+				file_count: 7,
+				children: vec![
+					SizeTreeNode {
+						name: "photos".to_string(),
+						size_in_bytes: 1100,
+						file_count: 5,
+						children: vec![leaf("2023", 1000, 3)],
+					},
+					leaf("docs", 50, 1),
+				],
+			}
+		);
+	}
+
+	#[test]
+	fn test_find_and_max_depth() {
+		let tree =
+			SizeTreeNode::build([("/a/b/c/".to_string(), 1, 1), ("/a/d/".to_string(), 2, 1)]);
+
+		let a = tree.find("a").expect("a must exist");
+		assert_eq!(a.size_in_bytes, 3);
+		assert_eq!(tree.find("/a/b/"), tree.find("a/b"));
+		assert_eq!(tree.find("a/b/c"), Some(&leaf("c", 1, 1)));
+		assert_eq!(tree.find("a/x"), None);
+
+		let pruned = tree.with_max_depth(1);
+		assert_eq!(pruned.children.len(), 1);
+		assert!(pruned.children[0].children.is_empty());
+		assert_eq!(pruned.children[0].size_in_bytes, 3);
+	}
+
+	#[tokio::test]
+	async fn test_size_tree_cache() {
+		let (db, data_dir) = test_db().await;
+		let location = create_test_location(&db, data_dir.path()).await;
+		let cache = SizeTreeCache::default();
+
+		let create_file = |name: &str, is_hardlink| {
+			db.file_path().create_unchecked(
+				uuid_to_bytes(Uuid::new_v4()),
+				location.id,
+				"/docs/".to_string(),
+				name.to_string(),
+				"txt".to_string(),
+				1,
+				1,
+				vec![
+					file_path::size_in_bytes::set(10),
+					file_path::is_hardlink::set(is_hardlink),
+				],
+			)
+		};
+
+		create_file("original", false).exec().await.unwrap();
+		create_file("link", true).exec().await.unwrap();
+
+		// Hardlinks count neither their bytes nor themselves
+		let tree = cache.get_or_compute(location.id, &db).await.unwrap();
+		assert_eq!(tree.find("docs"), Some(&leaf("docs", 10, 1)));
+
+		create_file("other", false).exec().await.unwrap();
+		assert_eq!(
+			cache.get_or_compute(location.id, &db).await.unwrap(),
+			tree,
+			"the cached tree is kept until it's invalidated"
+		);
+
+		cache.invalidate(location.id).await;
+		let tree = cache.get_or_compute(location.id, &db).await.unwrap();
+		assert_eq!(tree.find("docs"), Some(&leaf("docs", 20, 2)));
+	}
+}
@@ -1,9 +1,18 @@
+use prisma_client_rust::{raw, PrismaValue};
+use serde::Deserialize;
 use serde_json::{Map, Value};
+use tracing::error;
 
-use crate::util::migrator::MigratorError;
+use crate::{
+	library::refresh_library_statistics,
+	prisma::{file_path, PrismaClient},
+	util::{db::u64_to_db_int, migrator::MigratorError},
+};
 
 pub(crate) const NODE_VERSION: u32 = 0;
 pub(crate) const LIBRARY_VERSION: u32 = 0;
+/// The version of the data in a library's database, stored as its SQLite `user_version`.
+pub(crate) const LIBRARY_DB_VERSION: i64 = 2;
 
 /// Used to run migrations at a node level. This is useful for breaking changes to the `NodeConfig` file.
 pub fn migration_node(version: u32, _config: &mut Map<String, Value>) -> Result<(), MigratorError> {
@@ -23,3 +32,122 @@ pub fn migration_library(
 		v => unreachable!("Missing migration for library version {}", v),
 	}
 }
+
+/// Used to migrate data on a library's database that the schema migrations can't handle by themselves.
+/// This is run when a library is loaded, right after the schema migrations, and only runs the steps
+/// newer than the database's `user_version`, which is then set to `LIBRARY_DB_VERSION`.
+pub async fn migration_library_db(db: &PrismaClient) -> Result<(), MigratorError> {
+	#[derive(Deserialize)]
+	struct UserVersion {
+		user_version: i64,
+	}
+
+	let version = db
+		._query_raw::<UserVersion>(raw!("PRAGMA user_version"))
+		.exec()
+		.await?
+		.first()
+		.map_or(0, |row| row.user_version);
+
+	if version > LIBRARY_DB_VERSION {
+		return Err(MigratorError::YourAppIsOutdated);
+	}
+
+	for version in version..LIBRARY_DB_VERSION {
+		match version {
+			0 => migrate_file_path_inode_and_device_to_integers(db).await?,
+			// Statistics only make the library overview less accurate if they're wrong, which isn't
+			// a reason to keep the library from loading
+			1 => {
+				if let Err(e) = refresh_library_statistics(db).await {
+					error!(
+						"Failed to compute the statistics of a library indexed before them: {e:#?}"
+					);
+				}
+			}
+			v => unreachable!("Missing migration for library database version {}", v),
+		}
+	}
+
+	if version < LIBRARY_DB_VERSION {
+		db._execute_raw(raw!(&format!("PRAGMA user_version = {LIBRARY_DB_VERSION}")))
+			.exec()
+			.await?;
+	}
+
+	Ok(())
+}
+
+/// `inode` and `device` used to be stored as little endian blobs, the schema migration changed these
+/// columns to integers but SQLite keeps the old values as they were, so we convert them here.
+async fn migrate_file_path_inode_and_device_to_integers(
+	db: &PrismaClient,
+) -> Result<(), MigratorError> {
+	const BATCH_SIZE: i64 = 1000;
+
+	#[derive(Deserialize)]
+	struct BlobFilePath {
+		id: i32,
+		inode: String,
+		device: String,
+	}
+
+	/// `None` for blobs that weren't written as 8 bytes long integers
+	fn le_hex_to_db_int(hex: &str) -> Option<i64> {
+		let bytes = (0..hex.len())
+			.step_by(2)
+			.map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+			.collect::<Option<Vec<_>>>()?;
+
+		<[u8; 8]>::try_from(bytes)
+			.ok()
+			.map(|bytes| u64_to_db_int(u64::from_le_bytes(bytes)))
+	}
+
+	let mut cursor = 0;
+
+	loop {
+		let file_paths = db
+			._query_raw::<BlobFilePath>(raw!(
+				"SELECT id, hex(inode) AS inode, hex(device) AS device FROM file_path \
+					WHERE id > {} AND (typeof(inode) = 'blob' OR typeof(device) = 'blob') \
+					ORDER BY id LIMIT {}",
+				PrismaValue::Int(cursor),
+				PrismaValue::Int(BATCH_SIZE)
+			))
+			.exec()
+			.await?;
+
+		let Some(last) = file_paths.last() else {
+			break Ok(());
+		};
+		cursor = last.id as i64;
+
+		db._batch(
+			file_paths
+				.into_iter()
+				.filter_map(|file_path| {
+					match (
+						le_hex_to_db_int(&file_path.inode),
+						le_hex_to_db_int(&file_path.device),
+					) {
+						(Some(inode), Some(device)) => Some(db.file_path().update(
+							file_path::id::equals(file_path.id),
+							vec![file_path::inode::set(inode), file_path::device::set(device)],
+						)),
+						// A single unreadable file path shouldn't keep the whole library from loading
+						_ => {
+							error!(
+								"Skipping file path with an invalid inode or device: \
+								<id='{}', inode='{}', device='{}'>",
+								file_path.id, file_path.inode, file_path.device
+							);
+							None
+						}
+					}
+				})
+				.collect::<Vec<_>>(),
+		)
+		.await?;
+	}
+}
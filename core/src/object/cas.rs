@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
 use blake3::Hasher;
 use static_assertions::const_assert;
@@ -60,3 +60,56 @@ pub async fn generate_cas_id(path: impl AsRef<Path>, size: u64) -> Result<String
 
 	Ok(hasher.finalize().to_hex()[..16].to_string())
 }
+
+/// Symbolic links are identified by where they point to, not by the contents they reach,
+/// so links to the same target share an object, which isn't the one of the target itself.
+/// Relative targets are resolved against the link's directory, as the same relative target
+/// points somewhere else from another directory
+pub fn generate_alias_cas_id(link: impl AsRef<Path>, target: impl AsRef<Path>) -> String {
+	let mut resolved = PathBuf::new();
+	for component in link
+		.as_ref()
+		.parent()
+		.unwrap_or_else(|| Path::new(""))
+		.join(target)
+		.components()
+	{
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir => {
+				resolved.pop();
+			}
+			component => resolved.push(component),
+		}
+	}
+
+	let mut hasher = Hasher::new();
+	hasher.update(b"alias:");
+	hasher.update(resolved.to_string_lossy().as_bytes());
+
+	hasher.finalize().to_hex()[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn aliases_are_identified_by_their_resolved_targets() {
+		// Same text, different files
+		assert_ne!(
+			generate_alias_cas_id("/location/docs/link", "../a.txt"),
+			generate_alias_cas_id("/location/photos/2023/link", "../a.txt")
+		);
+
+		// Different text, same file
+		assert_eq!(
+			generate_alias_cas_id("/location/docs/link", "../a.txt"),
+			generate_alias_cas_id("/location/photos/link", "/location/a.txt")
+		);
+		assert_eq!(
+			generate_alias_cas_id("/location/docs/link", "./b.txt"),
+			generate_alias_cas_id("/location/link", "docs/b.txt")
+		);
+	}
+}
@@ -1,14 +1,17 @@
 use crate::{
 	job::JobError,
-	library::Library,
+	library::{update_kinds_statistics, Library},
 	location::file_path_helper::{
 		file_path_for_file_identifier, FilePathError, IsolatedFilePathData,
 	},
-	object::{cas::generate_cas_id, object_for_file_identifier},
+	object::{
+		cas::{generate_alias_cas_id, generate_cas_id},
+		object_for_file_identifier,
+	},
 	prisma::{file_path, location, object, PrismaClient},
 	sync,
 	sync::SyncManager,
-	util::db::uuid_to_bytes,
+	util::db::{size_to_db_int, uuid_to_bytes},
 };
 
 use sd_file_ext::{extensions::Extension, kind::ObjectKind};
@@ -61,20 +64,27 @@ impl FileMetadata {
 	) -> Result<FileMetadata, io::Error> {
 		let path = location_path.as_ref().join(iso_file_path);
 
-		let fs_metadata = fs::metadata(&path).await?;
+		let fs_metadata = fs::symlink_metadata(&path).await?;
 
 		assert!(
 			!fs_metadata.is_dir(),
 			"We can't generate cas_id for directories"
 		);
 
-		// derive Object kind
-		let kind = Extension::resolve_conflicting(&path, false)
-			.await
-			.map(Into::into)
-			.unwrap_or(ObjectKind::Unknown);
+		let (kind, cas_id) = if fs_metadata.is_symlink() {
+			(
+				ObjectKind::Alias,
+				generate_alias_cas_id(&path, fs::read_link(&path).await?),
+			)
+		} else {
+			// derive Object kind
+			let kind = Extension::resolve_conflicting(&path, false)
+				.await
+				.map(Into::into)
+				.unwrap_or(ObjectKind::Unknown);
 
-		let cas_id = generate_cas_id(&path, fs_metadata.len()).await?;
+			(kind, generate_cas_id(&path, fs_metadata.len()).await?)
+		};
 
 		info!("Analyzed file: {path:?} {cas_id:?} {kind:?}");
 
@@ -227,40 +237,50 @@ async fn identifier_job_step(
 			new_objects_cas_ids
 		);
 
+		// File paths with the same cas_id, like hardlinks of the same file, share a new object
+		let mut new_objects_pub_ids = HashMap::with_capacity(new_objects_cas_ids.len());
+
 		let (object_create_args, file_path_update_args): (Vec<_>, Vec<_>) =
 			file_paths_requiring_new_object
 				.iter()
 				.map(|(file_path_pub_id, (meta, fp))| {
-					let object_pub_id = Uuid::new_v4();
-
-					let sync_id = || sync::object::SyncId {
-						pub_id: uuid_to_bytes(object_pub_id),
-					};
-
-					let kind = meta.kind as i32;
-
-					let object_creation_args = (
-						[sync.shared_create(sync_id())]
-							.into_iter()
-							.chain(
-								[
-									(object::date_created::NAME, json!(fp.date_created)),
-									(object::kind::NAME, json!(kind)),
-								]
-								.into_iter()
-								.map(|(f, v)| sync.shared_update(sync_id(), f, v)),
-							)
-							.collect::<Vec<_>>(),
-						object::create_unchecked(
-							uuid_to_bytes(object_pub_id),
-							vec![
-								object::date_created::set(fp.date_created),
-								object::kind::set(kind),
-							],
-						),
-					);
-
-					(object_creation_args, {
+					let mut maybe_object_creation_args = None;
+
+					let object_pub_id =
+						*new_objects_pub_ids.entry(&meta.cas_id).or_insert_with(|| {
+							let object_pub_id = Uuid::new_v4();
+
+							let sync_id = || sync::object::SyncId {
+								pub_id: uuid_to_bytes(object_pub_id),
+							};
+
+							let kind = meta.kind as i32;
+
+							maybe_object_creation_args = Some((
+								[sync.shared_create(sync_id())]
+									.into_iter()
+									.chain(
+										[
+											(object::date_created::NAME, json!(fp.date_created)),
+											(object::kind::NAME, json!(kind)),
+										]
+										.into_iter()
+										.map(|(f, v)| sync.shared_update(sync_id(), f, v)),
+									)
+									.collect::<Vec<_>>(),
+								object::create_unchecked(
+									uuid_to_bytes(object_pub_id),
+									vec![
+										object::date_created::set(fp.date_created),
+										object::kind::set(kind),
+									],
+								),
+							));
+
+							object_pub_id
+						});
+
+					(maybe_object_creation_args, {
 						let (crdt_op, db_op) = file_path_object_connect_ops(
 							*file_path_pub_id,
 							object_pub_id,
@@ -276,7 +296,8 @@ async fn identifier_job_step(
 		// create new object records with assembled values
 		let total_created_files = sync
 			.write_ops(db, {
-				let (sync, db_params): (Vec<_>, Vec<_>) = object_create_args.into_iter().unzip();
+				let (sync, db_params): (Vec<_>, Vec<_>) =
+					object_create_args.into_iter().flatten().unzip();
 
 				(sync.concat(), db.object().create_many(db_params))
 			})
@@ -299,6 +320,23 @@ async fn identifier_job_step(
 			.await?;
 
 			info!("Updated file paths with created objects");
+
+			// File paths sharing a new object have the same content, so any of them has its size
+			update_kinds_statistics(
+				db,
+				file_paths_requiring_new_object
+					.iter()
+					.map(|(_, (meta, _))| {
+						(
+							&meta.cas_id,
+							(meta.kind as i32, size_to_db_int(meta.fs_metadata.len())),
+						)
+					})
+					.collect::<HashMap<_, _>>()
+					.into_values()
+					.map(|(kind, size_in_bytes)| (kind, 1, size_in_bytes)),
+			)
+			.await?;
 		}
 
 		total_created_files as usize
@@ -3,10 +3,16 @@ use crate::{
 	job::{
 		JobError, JobInitData, JobReportUpdate, JobResult, JobState, StatefulJob, WorkerContext,
 	},
+	location::{
+		file_path_helper::posix_metadata::preserve_posix_metadata, size_tree::invalidate_size_tree,
+	},
 	util::error::FileIOError,
 };
 
-use std::{hash::Hash, path::PathBuf};
+use std::{
+	hash::Hash,
+	path::{Path, PathBuf},
+};
 
 use serde::{Deserialize, Serialize};
 use specta::Type;
@@ -70,15 +76,14 @@ impl StatefulJob for FileCopierJob {
 
 	async fn init(&self, ctx: WorkerContext, state: &mut JobState<Self>) -> Result<(), JobError> {
 		let source_fs_info = context_menu_fs_info(
-			&ctx.library.db,
+			&ctx.library,
 			state.init.source_location_id,
 			state.init.source_path_id,
 		)
 		.await?;
 
 		let mut full_target_path =
-			get_location_path_from_location_id(&ctx.library.db, state.init.target_location_id)
-				.await?;
+			get_location_path_from_location_id(&ctx.library, state.init.target_location_id).await?;
 
 		// add the currently viewed subdirectory to the location root
 		full_target_path.push(&state.init.target_path);
@@ -170,6 +175,8 @@ impl StatefulJob for FileCopierJob {
 					fs::copy(&path, &target_path)
 						.await
 						.map_err(|e| FileIOError::from((&target_path, e)))?;
+
+					preserve_posix_metadata(&path, &target_path).await?;
 				}
 			}
 			FileCopierJobStep::Directory { path } => {
@@ -230,8 +237,57 @@ impl StatefulJob for FileCopierJob {
 	}
 
 	async fn finalize(&mut self, ctx: WorkerContext, state: &mut JobState<Self>) -> JobResult {
+		let data = state
+			.data
+			.as_ref()
+			.expect("critical error: missing data on job state");
+
+		if data.source_fs_info.path_data.is_dir {
+			preserve_directories_metadata(&data.source_fs_info.fs_path, &data.target_path).await?;
+		}
+
+		invalidate_size_tree(&ctx.library, state.init.target_location_id).await;
 		invalidate_query!(ctx.library, "search.paths");
 
 		Ok(Some(serde_json::to_value(&state.init)?))
 	}
 }
+
+/// Directories only get their permissions, owner and extended attributes after all their contents
+/// were copied, as they may not let us write into them anymore. Innermost ones go first for the same
+/// reason
+async fn preserve_directories_metadata(source: &Path, target: &Path) -> Result<(), JobError> {
+	let mut to_walk = vec![source.to_path_buf()];
+	let mut dirs = vec![];
+
+	while let Some(path) = to_walk.pop() {
+		let mut dir = fs::read_dir(&path)
+			.await
+			.map_err(|e| FileIOError::from((&path, e)))?;
+
+		while let Some(entry) = dir
+			.next_entry()
+			.await
+			.map_err(|e| FileIOError::from((&path, e)))?
+		{
+			if entry
+				.file_type()
+				.await
+				.map_err(|e| FileIOError::from((entry.path(), e)))?
+				.is_dir()
+			{
+				to_walk.push(entry.path());
+			}
+		}
+
+		dirs.push(path);
+	}
+
+	for path in dirs.into_iter().rev() {
+		let target_path = target.join(path.strip_prefix(source).map_err(|_| JobError::Path)?);
+
+		preserve_posix_metadata(&path, &target_path).await?;
+	}
+
+	Ok(())
+}
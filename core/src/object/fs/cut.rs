@@ -3,10 +3,16 @@ use crate::{
 	job::{
 		JobError, JobInitData, JobReportUpdate, JobResult, JobState, StatefulJob, WorkerContext,
 	},
+	location::{
+		file_path_helper::posix_metadata::preserve_posix_metadata, size_tree::invalidate_size_tree,
+	},
 	util::error::FileIOError,
 };
 
-use std::{hash::Hash, path::PathBuf};
+use std::{
+	hash::Hash,
+	path::{Path, PathBuf},
+};
 
 use serde::{Deserialize, Serialize};
 use specta::Type;
@@ -49,15 +55,14 @@ impl StatefulJob for FileCutterJob {
 
 	async fn init(&self, ctx: WorkerContext, state: &mut JobState<Self>) -> Result<(), JobError> {
 		let source_fs_info = context_menu_fs_info(
-			&ctx.library.db,
+			&ctx.library,
 			state.init.source_location_id,
 			state.init.source_path_id,
 		)
 		.await?;
 
 		let mut full_target_path =
-			get_location_path_from_location_id(&ctx.library.db, state.init.target_location_id)
-				.await?;
+			get_location_path_from_location_id(&ctx.library, state.init.target_location_id).await?;
 		full_target_path.push(&state.init.target_path);
 
 		state.steps.push_back(FileCutterJobStep {
@@ -111,9 +116,15 @@ impl StatefulJob for FileCutterJob {
 			full_output.display()
 		);
 
-		fs::rename(&source_info.fs_path, &full_output)
-			.await
-			.map_err(|e| FileIOError::from((&source_info.fs_path, e)))?;
+		match fs::rename(&source_info.fs_path, &full_output).await {
+			Ok(()) => {}
+			// Locations on different filesystems can't be renamed into each other
+			#[cfg(target_family = "unix")]
+			Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+				move_across_filesystems(&source_info.fs_path, &full_output).await?;
+			}
+			Err(e) => return Err(FileIOError::from((&source_info.fs_path, e)).into()),
+		}
 
 		ctx.progress(vec![JobReportUpdate::CompletedTaskCount(
 			state.step_number + 1,
@@ -122,8 +133,85 @@ impl StatefulJob for FileCutterJob {
 	}
 
 	async fn finalize(&mut self, ctx: WorkerContext, state: &mut JobState<Self>) -> JobResult {
+		invalidate_size_tree(&ctx.library, state.init.source_location_id).await;
+		invalidate_size_tree(&ctx.library, state.init.target_location_id).await;
 		invalidate_query!(ctx.library, "search.paths");
 
 		Ok(Some(serde_json::to_value(&state.init)?))
 	}
 }
+
+/// Copies `source` to `target`, keeping permissions, owner and extended attributes, and then removes
+/// `source`. Directories get their metadata only after being filled, as it may make them read only
+async fn move_across_filesystems(source: &Path, target: &Path) -> Result<(), FileIOError> {
+	let mut to_copy = vec![(source.to_path_buf(), target.to_path_buf())];
+	let mut created_dirs = vec![];
+
+	while let Some((source, target)) = to_copy.pop() {
+		let metadata = fs::symlink_metadata(&source)
+			.await
+			.map_err(|e| FileIOError::from((&source, e)))?;
+
+		if metadata.is_symlink() {
+			#[cfg(target_family = "unix")]
+			{
+				let link_target = fs::read_link(&source)
+					.await
+					.map_err(|e| FileIOError::from((&source, e)))?;
+
+				fs::symlink(link_target, &target)
+					.await
+					.map_err(|e| FileIOError::from((&target, e)))?;
+
+				preserve_posix_metadata(&source, &target).await?;
+			}
+
+			#[cfg(not(target_family = "unix"))]
+			{
+				fs::copy(&source, &target)
+					.await
+					.map_err(|e| FileIOError::from((&target, e)))?;
+			}
+		} else if metadata.is_dir() {
+			fs::create_dir(&target)
+				.await
+				.map_err(|e| FileIOError::from((&target, e)))?;
+
+			let mut dir = fs::read_dir(&source)
+				.await
+				.map_err(|e| FileIOError::from((&source, e)))?;
+
+			while let Some(entry) = dir
+				.next_entry()
+				.await
+				.map_err(|e| FileIOError::from((&source, e)))?
+			{
+				to_copy.push((entry.path(), target.join(entry.file_name())));
+			}
+
+			created_dirs.push((source, target));
+		} else {
+			fs::copy(&source, &target)
+				.await
+				.map_err(|e| FileIOError::from((&target, e)))?;
+
+			preserve_posix_metadata(&source, &target).await?;
+		}
+	}
+
+	// Innermost directories first, so their parents are still writable
+	for (source, target) in created_dirs.into_iter().rev() {
+		preserve_posix_metadata(&source, &target).await?;
+	}
+
+	if fs::symlink_metadata(source)
+		.await
+		.map_err(|e| FileIOError::from((source, e)))?
+		.is_dir()
+	{
+		fs::remove_dir_all(source).await
+	} else {
+		fs::remove_file(source).await
+	}
+	.map_err(|e| FileIOError::from((source, e)))
+}
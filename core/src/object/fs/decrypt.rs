@@ -53,8 +53,7 @@ impl StatefulJob for FileDecryptorJob {
 		// enumerate files to decrypt
 		// populate the steps with them (local file paths)
 		let fs_info =
-			context_menu_fs_info(&ctx.library.db, state.init.location_id, state.init.path_id)
-				.await?;
+			context_menu_fs_info(&ctx.library, state.init.location_id, state.init.path_id).await?;
 
 		state.steps.push_back(FileDecryptorJobStep { fs_info });
 
@@ -3,6 +3,7 @@ use crate::{
 	job::{
 		JobError, JobInitData, JobReportUpdate, JobResult, JobState, StatefulJob, WorkerContext,
 	},
+	location::size_tree::invalidate_size_tree,
 	util::error::FileIOError,
 };
 
@@ -42,8 +43,7 @@ impl StatefulJob for FileDeleterJob {
 
 	async fn init(&self, ctx: WorkerContext, state: &mut JobState<Self>) -> Result<(), JobError> {
 		let fs_info =
-			context_menu_fs_info(&ctx.library.db, state.init.location_id, state.init.path_id)
-				.await?;
+			context_menu_fs_info(&ctx.library, state.init.location_id, state.init.path_id).await?;
 
 		state.steps.push_back(fs_info);
 
@@ -76,6 +76,7 @@ impl StatefulJob for FileDeleterJob {
 	}
 
 	async fn finalize(&mut self, ctx: WorkerContext, state: &mut JobState<Self>) -> JobResult {
+		invalidate_size_tree(&ctx.library, state.init.location_id).await;
 		invalidate_query!(ctx.library, "search.paths");
 
 		Ok(Some(serde_json::to_value(&state.init)?))
@@ -59,7 +59,7 @@ impl StatefulJob for FileEncryptorJob {
 
 	async fn init(&self, ctx: WorkerContext, state: &mut JobState<Self>) -> Result<(), JobError> {
 		state.steps.push_back(
-			context_menu_fs_info(&ctx.library.db, state.init.location_id, state.init.path_id)
+			context_menu_fs_info(&ctx.library, state.init.location_id, state.init.path_id)
 				.await
 				.map_err(|_| JobError::MissingData {
 					value: String::from("file_path that matches both location id and path id"),
@@ -3,6 +3,7 @@ use crate::{
 	job::{
 		JobError, JobInitData, JobReportUpdate, JobResult, JobState, StatefulJob, WorkerContext,
 	},
+	location::size_tree::invalidate_size_tree,
 	util::error::FileIOError,
 };
 
@@ -66,8 +67,7 @@ impl StatefulJob for FileEraserJob {
 
 	async fn init(&self, ctx: WorkerContext, state: &mut JobState<Self>) -> Result<(), JobError> {
 		let fs_info =
-			context_menu_fs_info(&ctx.library.db, state.init.location_id, state.init.path_id)
-				.await?;
+			context_menu_fs_info(&ctx.library, state.init.location_id, state.init.path_id).await?;
 
 		state.data = Some(fs_info.clone());
 
@@ -165,6 +165,7 @@ impl StatefulJob for FileEraserJob {
 				.map_err(|e| FileIOError::from((&data.fs_path, e)))?;
 		}
 
+		invalidate_size_tree(&ctx.library, state.init.location_id).await;
 		invalidate_query!(ctx.library, "search.paths");
 
 		Ok(Some(serde_json::to_value(&state.init)?))
@@ -1,7 +1,8 @@
 use crate::{
 	job::JobError,
+	library::Library,
 	location::file_path_helper::{file_path_with_object, IsolatedFilePathData},
-	prisma::{file_path, location, PrismaClient},
+	prisma::{file_path, location},
 };
 
 use std::{ffi::OsStr, path::PathBuf};
@@ -43,27 +44,39 @@ pub fn osstr_to_string(os_str: Option<&OsStr>) -> Result<String, JobError> {
 }
 
 pub async fn get_location_path_from_location_id(
-	db: &PrismaClient,
+	library: &Library,
 	location_id: i32,
 ) -> Result<PathBuf, JobError> {
-	Ok(db
+	let location = library
+		.db
 		.location()
 		.find_unique(location::id::equals(location_id))
 		.exec()
 		.await?
 		.ok_or(JobError::MissingData {
 			value: String::from("location which matches location_id"),
-		})?
-		.path
-		.into())
+		})?;
+
+	// Jobs can be queued or resumed after the location's drive was unplugged
+	if !library
+		.location_manager()
+		.get_online()
+		.await
+		.contains(&location.pub_id)
+	{
+		return Err(JobError::LocationOffline(location_id));
+	}
+
+	Ok(location.path.into())
 }
 
 pub async fn context_menu_fs_info(
-	db: &PrismaClient,
+	library: &Library,
 	location_id: i32,
 	file_path_id: i32,
 ) -> Result<FsInfo, JobError> {
-	let path_data = db
+	let path_data = library
+		.db
 		.file_path()
 		.find_unique(file_path::id::equals(file_path_id))
 		.include(file_path_with_object::include())
@@ -74,7 +87,7 @@ pub async fn context_menu_fs_info(
 		})?;
 
 	Ok(FsInfo {
-		fs_path: get_location_path_from_location_id(db, location_id)
+		fs_path: get_location_path_from_location_id(library, location_id)
 			.await?
 			.join(IsolatedFilePathData::from(&path_data)),
 		path_data,
@@ -10,16 +10,17 @@ use std::{
 
 use sd_p2p::{
 	spaceblock::{self, BlockSize, SpacedropRequest},
-	spacetime::SpaceTimeStream,
+	spacetime::{SpaceTimeStream, UnicastStream},
 	Event, Manager, ManagerError, MetadataManager, PeerId,
 };
 use sd_sync::CRDTOperation;
 use serde::Serialize;
 use specta::Type;
+use thiserror::Error;
 use tokio::{
 	fs::File,
-	io::{AsyncReadExt, AsyncWriteExt, BufReader},
-	sync::{broadcast, oneshot, Mutex},
+	io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+	sync::{broadcast, mpsc, oneshot, Mutex},
 	time::sleep,
 };
 use tracing::{debug, error, info};
@@ -28,9 +29,12 @@ use uuid::Uuid;
 use crate::{
 	node::{NodeConfig, NodeConfigManager},
 	p2p::{OperatingSystem, SPACEDRIVE_APP_ID},
+	sync::{GetOpsArgs, IngestError, SyncManager, OPS_PER_PAGE},
 };
 
-use super::{Header, PeerMetadata};
+use super::{
+	read_len_prefixed, write_len_prefixed, Header, PeerMetadata, SyncPayload, SyncPayloadError,
+};
 
 /// The amount of time to wait for a Spacedrop request to be accepted or rejected before it's automatically rejected
 const SPACEDROP_TIMEOUT: Duration = Duration::from_secs(60);
@@ -51,6 +55,37 @@ pub enum P2PEvent {
 	// TODO: Expire peer + connection/disconnect
 }
 
+/// What the P2P manager hands over to the libraries' sync managers
+#[derive(Debug)]
+pub enum P2PSyncEvent {
+	/// Operations broadcast by a peer as it created them
+	Operations {
+		library_id: Uuid,
+		operations: Vec<CRDTOperation>,
+	},
+	/// A peer asking for the operations it's missing, to be answered over the stream
+	CatchUp {
+		library_id: Uuid,
+		node: Uuid,
+		args: GetOpsArgs,
+		stream: UnicastStream,
+	},
+	/// A peer to catch up with, as it may have operations we missed while we weren't connected
+	PeerConnected(PeerId),
+}
+
+#[derive(Debug, Error)]
+pub enum CatchUpError {
+	#[error("failed to open a stream to the peer")]
+	Stream,
+	#[error(transparent)]
+	Payload(#[from] SyncPayloadError),
+	#[error("database error: {0}")]
+	Database(#[from] prisma_client_rust::QueryError),
+	#[error("failed to ingest a caught up operation: {0}")]
+	Ingest(#[from] IngestError),
+}
+
 pub struct P2PManager {
 	pub events: broadcast::Sender<P2PEvent>,
 	pub manager: Arc<Manager<PeerMetadata>>,
@@ -61,7 +96,7 @@ pub struct P2PManager {
 impl P2PManager {
 	pub async fn new(
 		node_config: Arc<NodeConfigManager>,
-	) -> Result<(Arc<Self>, broadcast::Receiver<(Uuid, Vec<CRDTOperation>)>), ManagerError> {
+	) -> Result<(Arc<Self>, mpsc::Receiver<P2PSyncEvent>), ManagerError> {
 		let (config, keypair) = {
 			let config = node_config.get().await;
 			(Self::config_to_metadata(&config), config.keypair)
@@ -79,12 +114,12 @@ impl P2PManager {
 		);
 
 		let (tx, _) = broadcast::channel(100);
-		let (tx2, rx2) = broadcast::channel(100);
+		let (tx2, rx2) = mpsc::channel(100);
 
 		let spacedrop_pairing_reqs = Arc::new(Mutex::new(HashMap::new()));
 		tokio::spawn({
 			let events = tx.clone();
-			// let sync_events = tx2.clone();
+			let sync_events = tx2.clone();
 			let spacedrop_pairing_reqs = spacedrop_pairing_reqs.clone();
 
 			async move {
@@ -108,9 +143,18 @@ impl P2PManager {
 							// TODO: Don't just connect to everyone when we find them. We should only do it if we know them.
 							event.dial().await;
 						}
+						Event::PeerConnected(event) => {
+							debug!("Connected to peer '{}'", event.peer_id);
+
+							sync_events
+								.send(P2PSyncEvent::PeerConnected(event.peer_id))
+								.await
+								.map_err(|_| error!("Failed to send event to sync event stream!"))
+								.ok();
+						}
 						Event::PeerMessage(mut event) => {
 							let events = events.clone();
-							let sync_events = tx2.clone();
+							let sync_events = sync_events.clone();
 							let spacedrop_pairing_reqs = spacedrop_pairing_reqs.clone();
 
 							tokio::spawn(async move {
@@ -176,15 +220,51 @@ impl P2PManager {
 										info!("spacedrop({id}): complete");
 									}
 									Header::Sync(library_id, len) => {
-										let mut buf = vec![0; len as usize]; // TODO: Designed for easily being able to be DOS the current Node
-										event.stream.read_exact(&mut buf).await.unwrap();
+										let payload =
+											match SyncPayload::from_stream(&mut event.stream, len)
+												.await
+											{
+												Ok(payload) => payload,
+												Err(e) => {
+													error!("Failed to read sync payload from peer '{}': {e:#?}", event.peer_id);
+													return;
+												}
+											};
 
-										let mut buf: &[u8] = &buf;
-										let operations = rmp_serde::from_read(&mut buf).unwrap();
+										let sync_event = match payload {
+											SyncPayload::Operations(operations) => {
+												debug!("Received {} sync operations for library '{library_id}'", operations.len());
 
-										println!("Received sync events for library '{library_id}': {operations:?}");
+												P2PSyncEvent::Operations {
+													library_id,
+													operations,
+												}
+											}
+											SyncPayload::CatchUp { node, args } => {
+												match event.stream {
+													SpaceTimeStream::Unicast(stream) => {
+														P2PSyncEvent::CatchUp {
+															library_id,
+															node,
+															args,
+															stream,
+														}
+													}
+													_ => {
+														error!("Received sync catch up request from peer '{}' but it's not a unicast stream!", event.peer_id);
+														return;
+													}
+												}
+											}
+										};
 
-										sync_events.send((library_id, operations)).unwrap();
+										sync_events
+											.send(sync_event)
+											.await
+											.map_err(|_| {
+												error!("Failed to send event to sync event stream!")
+											})
+											.ok();
 									}
 								}
 							});
@@ -261,21 +341,139 @@ impl P2PManager {
 		self.events.subscribe()
 	}
 
-	#[allow(unused)] // TODO: Remove `allow(unused)` once integrated
 	pub async fn broadcast_sync_events(&self, library_id: Uuid, event: Vec<CRDTOperation>) {
-		let mut buf = match rmp_serde::to_vec_named(&event) {
+		let buf = match SyncPayload::Operations(event).to_bytes(library_id) {
 			Ok(buf) => buf,
 			Err(e) => {
 				error!("Failed to serialize sync event: {:?}", e);
 				return;
 			}
 		};
-		let mut head_buf = Header::Sync(library_id, buf.len() as u32).to_bytes(); // Max Sync payload is like 4GB
-		head_buf.append(&mut buf);
 
 		debug!("broadcasting sync events. payload_len={}", buf.len());
 
-		self.manager.broadcast(head_buf).await;
+		self.manager.broadcast(buf).await;
+	}
+
+	/// Asks the peer for the operations of the library newer than the ones we know of from each node,
+	/// a page at a time, so a node that was offline for long converges without transferring the
+	/// whole log
+	pub async fn catch_up(
+		&self,
+		peer_id: PeerId,
+		library_id: Uuid,
+		sync: &SyncManager,
+	) -> Result<(), CatchUpError> {
+		let mut stream = self
+			.manager
+			.stream(peer_id)
+			.await
+			.map_err(|_| CatchUpError::Stream)?;
+
+		let ingested = Self::request_catch_up(&mut stream, library_id, sync).await?;
+
+		debug!("Caught up with peer '{peer_id}' on library '{library_id}': {ingested} operations");
+
+		Ok(())
+	}
+
+	/// The asking side of [`P2PManager::catch_up`], returning how many operations were received
+	pub async fn request_catch_up(
+		stream: &mut (impl AsyncRead + AsyncWrite + Unpin),
+		library_id: Uuid,
+		sync: &SyncManager,
+	) -> Result<usize, CatchUpError> {
+		let mut clocks = sync.get_clocks().await?;
+
+		let args = GetOpsArgs {
+			clocks: clocks.iter().map(|(node, clock)| (*node, *clock)).collect(),
+			count: OPS_PER_PAGE,
+		};
+		stream
+			.write_all(
+				&SyncPayload::CatchUp {
+					node: sync.node(),
+					args,
+				}
+				.to_bytes(library_id)?,
+			)
+			.await
+			.map_err(SyncPayloadError::from)?;
+
+		let mut ingested = 0;
+		loop {
+			let operations = read_len_prefixed::<Vec<CRDTOperation>>(stream).await?;
+			let page_len = operations.len();
+
+			for op in operations {
+				let (node, timestamp) = (op.node, op.timestamp);
+
+				match sync.ingest_op(op).await {
+					Ok(()) => {}
+					// Skipped, as it would fail again every time it's asked for
+					Err(e) if e.is_permanent() => {
+						error!("Skipping operation caught up on library '{library_id}': {e:#?}");
+					}
+					// The peer takes the clocks as acknowledged, so they must not go past an
+					// operation that wasn't ingested, or it could be compacted away from the peer
+					// before the next catch up asks for it again
+					Err(e) => {
+						sync.set_clocks(&clocks).await?;
+						return Err(e.into());
+					}
+				}
+
+				let clock = clocks.entry(node).or_insert(timestamp);
+				*clock = (*clock).max(timestamp);
+			}
+
+			// Everything the peer had up to these clocks was received
+			sync.set_clocks(&clocks).await?;
+
+			ingested += page_len;
+
+			if page_len < OPS_PER_PAGE as usize {
+				break;
+			}
+
+			write_len_prefixed(
+				stream,
+				&GetOpsArgs {
+					clocks: clocks.iter().map(|(node, clock)| (*node, *clock)).collect(),
+					count: OPS_PER_PAGE,
+				},
+			)
+			.await?;
+		}
+
+		Ok(ingested)
+	}
+
+	/// Answers a peer's catch up, with pages of operations until one isn't full
+	pub async fn serve_catch_up(
+		mut stream: impl AsyncRead + AsyncWrite + Unpin,
+		node: Uuid,
+		mut args: GetOpsArgs,
+		sync: Option<&SyncManager>,
+	) -> Result<(), CatchUpError> {
+		loop {
+			// Libraries we don't have are answered with no operations
+			let operations = match sync {
+				Some(sync) => {
+					sync.acknowledge(node, &args.clocks).await?;
+					sync.get_ops(args.clone()).await?
+				}
+				None => vec![],
+			};
+
+			write_len_prefixed(&mut stream, &operations).await?;
+
+			if operations.len() < args.count.min(OPS_PER_PAGE) as usize {
+				return Ok(());
+			}
+
+			args = read_len_prefixed(&mut stream).await?;
+		}
 	}
 
 	pub async fn ping(&self) {
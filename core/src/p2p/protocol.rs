@@ -1,11 +1,18 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use uuid::Uuid;
 
 use sd_p2p::{
 	spaceblock::{SpacedropRequest, SpacedropRequestError},
 	spacetime::SpaceTimeStream,
 };
+use sd_sync::CRDTOperation;
+
+use crate::sync::GetOpsArgs;
+
+/// Larger sync payloads are refused, so peers can't make us allocate as much as they want
+const MAX_SYNC_PAYLOAD_LEN: u32 = 64 * 1024 * 1024;
 
 /// TODO
 #[derive(Debug, PartialEq, Eq)]
@@ -99,6 +106,127 @@ impl Header {
 	}
 }
 
+/// What follows a `Header::Sync`, encoded with MessagePack
+#[derive(Debug, Serialize, Deserialize)]
+pub enum SyncPayload {
+	/// Operations broadcast as they're created
+	Operations(Vec<CRDTOperation>),
+	/// The first request of a catch up, only over unicast streams. It's answered with a page of
+	/// operations, and the requests and pages that follow are only prefixed by their length.
+	/// The node asking is named so the clocks it sends can be taken as its acknowledgement
+	CatchUp { node: Uuid, args: GetOpsArgs },
+}
+
+#[derive(Debug, Error)]
+pub enum SyncPayloadError {
+	#[error("io error: {0}")]
+	Io(#[from] std::io::Error),
+	#[error("sync payload is too large: {0} bytes")]
+	TooLarge(u32),
+	#[error("error encoding sync payload: {0}")]
+	Encode(#[from] rmp_serde::encode::Error),
+	#[error("error decoding sync payload: {0}")]
+	Decode(#[from] rmp_serde::decode::Error),
+}
+
+impl SyncPayload {
+	pub fn to_bytes(&self, library_id: Uuid) -> Result<Vec<u8>, SyncPayloadError> {
+		let mut payload = rmp_serde::to_vec_named(self)?;
+
+		let mut bytes = Header::Sync(library_id, payload.len() as u32).to_bytes();
+		bytes.append(&mut payload);
+
+		Ok(bytes)
+	}
+
+	pub async fn from_stream(
+		stream: &mut (impl AsyncRead + Unpin),
+		len: u32,
+	) -> Result<Self, SyncPayloadError> {
+		read_payload(stream, len).await
+	}
+}
+
+/// Writes the requests and pages of a catch up that follow its first request
+pub async fn write_len_prefixed(
+	stream: &mut (impl AsyncWrite + Unpin),
+	value: &impl Serialize,
+) -> Result<(), SyncPayloadError> {
+	let payload = rmp_serde::to_vec_named(value)?;
+
+	stream
+		.write_all(&(payload.len() as u32).to_le_bytes())
+		.await?;
+	stream.write_all(&payload).await?;
+	stream.flush().await?;
+
+	Ok(())
+}
+
+pub async fn read_len_prefixed<T: DeserializeOwned>(
+	stream: &mut (impl AsyncRead + Unpin),
+) -> Result<T, SyncPayloadError> {
+	let len = stream.read_u32_le().await?;
+
+	read_payload(stream, len).await
+}
+
+async fn read_payload<T: DeserializeOwned>(
+	stream: &mut (impl AsyncRead + Unpin),
+	len: u32,
+) -> Result<T, SyncPayloadError> {
+	if len > MAX_SYNC_PAYLOAD_LEN {
+		return Err(SyncPayloadError::TooLarge(len));
+	}
+
+	let mut buf = vec![0; len as usize];
+	stream.read_exact(&mut buf).await?;
+
+	Ok(rmp_serde::from_slice(&buf)?)
+}
+
+#[cfg(test)]
+mod sync_payload_tests {
+	use super::*;
+
+	use tokio::io::duplex;
+	use uhlc::NTP64;
+
+	#[tokio::test]
+	async fn catch_up_requests_round_trip() {
+		let (mut a, mut b) = duplex(1024);
+		let node = Uuid::new_v4();
+
+		write_len_prefixed(
+			&mut a,
+			&GetOpsArgs {
+				clocks: vec![(node, NTP64(42))],
+				count: 10,
+			},
+		)
+		.await
+		.unwrap();
+
+		let args = read_len_prefixed::<GetOpsArgs>(&mut b).await.unwrap();
+		assert_eq!(args.clocks, vec![(node, NTP64(42))]);
+		assert_eq!(args.count, 10);
+	}
+
+	#[tokio::test]
+	async fn oversized_payloads_are_refused() {
+		let (mut a, mut b) = duplex(64);
+
+		a.write_all(&(MAX_SYNC_PAYLOAD_LEN + 1).to_le_bytes())
+			.await
+			.unwrap();
+
+		assert!(matches!(
+			read_len_prefixed::<GetOpsArgs>(&mut b).await,
+			Err(SyncPayloadError::TooLarge(_))
+		));
+	}
+}
+
 // TODO: Unit test it because binary protocols are error prone
 // #[cfg(test)]
 // mod tests {
@@ -1,14 +1,30 @@
 #![allow(clippy::unwrap_used, clippy::panic)] // TODO: Brendan remove this once you've got error handling here
 
-use crate::prisma::*;
+use crate::{library::LibraryManager, prisma::*};
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+	collections::{BTreeMap, HashMap, HashSet},
+	sync::Arc,
+	time::Duration,
+};
 
 use sd_sync::*;
 
-use serde_json::{from_value, json, to_vec, Value};
-use tokio::sync::broadcast::{self, Receiver, Sender};
-use uhlc::{HLCBuilder, HLC, NTP64};
+use chrono::{DateTime, TimeZone, Utc};
+use prisma_client_rust::{
+	operator::{and, or},
+	ModelTypes,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{from_value, json, to_vec, Map, Value};
+use specta::Type;
+use thiserror::Error;
+use tokio::{
+	sync::broadcast::{self, Receiver, Sender},
+	time::{interval, MissedTickBehavior},
+};
+use tracing::{debug, error, warn};
+use uhlc::{HLCBuilder, Timestamp, HLC, NTP64};
 use uuid::Uuid;
 
 use super::ModelSyncData;
@@ -19,24 +35,288 @@ pub enum SyncMessage {
 	Created(CRDTOperation),
 }
 
+#[derive(Error, Debug)]
+pub enum IngestError {
+	#[error("database error: {0}")]
+	Database(#[from] prisma_client_rust::QueryError),
+	#[error("operation for a model that isn't synced, or with an invalid record id: {0:?}")]
+	InvalidOperation(CRDTOperationType),
+	#[error("create operation is missing the '{0}' field")]
+	MissingField(&'static str),
+	#[error("invalid value for the '{0}' field")]
+	InvalidField(&'static str),
+	#[error("update operation for an unknown field: '{0}'")]
+	UnknownField(String),
+}
+
+impl IngestError {
+	/// Errors of the operation itself, which every retry would fail with again, unlike database
+	/// errors that may be transient
+	pub fn is_permanent(&self) -> bool {
+		!matches!(self, Self::Database(_))
+	}
+}
+
+/// What an operation does to its record, be it shared or a relation
+#[derive(Debug)]
+enum Change {
+	Create(Map<String, Value>),
+	Update { field: String, value: Value },
+	Delete,
+}
+
+impl From<SharedOperationData> for Change {
+	fn from(data: SharedOperationData) -> Self {
+		match data {
+			SharedOperationData::Create(SharedOperationCreateData::Unique(values)) => {
+				Self::Create(values)
+			}
+			SharedOperationData::Create(SharedOperationCreateData::Atomic) => {
+				Self::Create(Map::new())
+			}
+			SharedOperationData::Update { field, value } => Self::Update { field, value },
+			SharedOperationData::Delete => Self::Delete,
+		}
+	}
+}
+
+impl From<RelationOperationData> for Change {
+	fn from(data: RelationOperationData) -> Self {
+		match data {
+			RelationOperationData::Create => Self::Create(Map::new()),
+			RelationOperationData::Update { field, value } => Self::Update { field, value },
+			RelationOperationData::Delete => Self::Delete,
+		}
+	}
+}
+
+impl Change {
+	/// Last writer wins, per field. Given the changes made to the same record after this one, in
+	/// the order they happened, returns what's left to apply of it. Creations take the values of
+	/// the updates that were ingested before them, as those couldn't be applied yet
+	fn resolve(self, newer: Vec<Change>) -> Option<Self> {
+		let overwritten = newer.iter().any(|newer| match (&self, newer) {
+			// Deleted records stay deleted, and recreated ones aren't deleted again
+			(_, Self::Delete) | (Self::Delete, Self::Create(_)) => true,
+			(
+				Self::Update { field, .. },
+				Self::Update {
+					field: newer_field, ..
+				},
+			) => field == newer_field,
+			_ => false,
+		});
+
+		if overwritten {
+			return None;
+		}
+
+		Some(match self {
+			Self::Create(mut values) => {
+				for change in newer {
+					if let Self::Update { field, value } = change {
+						values.insert(field, value);
+					}
+				}
+
+				Self::Create(values)
+			}
+			change => change,
+		})
+	}
+}
+
+/// What's left of the history of a record once compacted
+#[derive(Debug, PartialEq)]
+struct Compaction {
+	/// Indexes of the changes still needed to rebuild the record
+	keep: Vec<usize>,
+	/// The values of the creation kept, with the updates after it folded in
+	snapshot: Option<Map<String, Value>>,
+}
+
+/// Collapses the history of a record, oldest first. Everything before the record was last created
+/// or deleted is superseded, and so are the updates to a field older than its newest one. With
+/// `fold_updates`, the updates after the creation are folded into a snapshot of the record
+fn compact(history: &[Change], fold_updates: bool) -> Compaction {
+	let deleted = history
+		.iter()
+		.rposition(|change| matches!(change, Change::Delete));
+
+	// Creating a record that exists does nothing, so the first creation is the one that counts
+	let created = history
+		.iter()
+		.enumerate()
+		.skip(deleted.map_or(0, |deleted| deleted + 1))
+		.find(|(_, change)| matches!(change, Change::Create(_)))
+		.map(|(created, _)| created);
+
+	match (created, deleted) {
+		// Deleted records stay deleted, whatever is done to them after
+		(None, Some(deleted)) => Compaction {
+			keep: vec![deleted],
+			snapshot: None,
+		},
+		(None, None) => Compaction {
+			keep: newest_updates(history, 0),
+			snapshot: None,
+		},
+		(Some(created), _) => {
+			let updates = newest_updates(history, created + 1);
+
+			let mut values = match &history[created] {
+				Change::Create(values) if fold_updates && !updates.is_empty() => values.clone(),
+				_ => {
+					return Compaction {
+						keep: [created].into_iter().chain(updates).collect(),
+						snapshot: None,
+					}
+				}
+			};
+
+			for update in updates {
+				if let Change::Update { field, value } = &history[update] {
+					values.insert(field.clone(), value.clone());
+				}
+			}
+
+			Compaction {
+				keep: vec![created],
+				snapshot: Some(values),
+			}
+		}
+	}
+}
+
+/// Indexes of the newest update to each field, from `from` on
+fn newest_updates(history: &[Change], from: usize) -> Vec<usize> {
+	let mut fields = HashSet::new();
+
+	let mut updates = history
+		.iter()
+		.enumerate()
+		.skip(from)
+		.rev()
+		.filter(|(_, change)| match change {
+			Change::Update { field, .. } => fields.insert(field),
+			_ => false,
+		})
+		.map(|(update, _)| update)
+		.collect::<Vec<_>>();
+
+	updates.reverse();
+	updates
+}
+
+/// Splits operations sorted by their record into the history of each record
+fn group_records<T, K: PartialEq>(ops: Vec<T>, key: impl Fn(&T) -> K) -> Vec<Vec<T>> {
+	let mut records: Vec<Vec<T>> = vec![];
+
+	for op in ops {
+		match records.last_mut() {
+			Some(record) if key(&record[0]) == key(&op) => record.push(op),
+			_ => records.push(vec![op]),
+		}
+	}
+
+	records
+}
+
+/// Operations are ordered by their timestamps, and the ids of their nodes when those are the same,
+/// so every node picks the same winner
+fn is_newer(timestamp: i64, node: &[u8], than: &CRDTOperation) -> bool {
+	(timestamp as u64, node) > (than.timestamp.0, than.node.as_bytes().as_slice())
+}
+
+/// The `kind` of a shared operation in the log
+fn shared_op_kind(data: &SharedOperationData) -> &'static str {
+	match data {
+		SharedOperationData::Create(_) => "c",
+		SharedOperationData::Update { .. } => "u",
+		SharedOperationData::Delete => "d",
+	}
+}
+
+/// The `kind` of a relation operation in the log
+fn relation_op_kind(data: &RelationOperationData) -> &'static str {
+	match data {
+		RelationOperationData::Create => "c",
+		RelationOperationData::Update { .. } => "u",
+		RelationOperationData::Delete => "d",
+	}
+}
+
+/// Takes a required field out of the values of a create operation
+fn take<T: DeserializeOwned>(
+	values: &mut Map<String, Value>,
+	field: &'static str,
+) -> Result<T, IngestError> {
+	from_value(
+		values
+			.remove(field)
+			.ok_or(IngestError::MissingField(field))?,
+	)
+	.map_err(|_| IngestError::InvalidField(field))
+}
+
+/// Relations are synced as the unique field of the record they point to, and its value
+fn take_relation(
+	values: &mut Map<String, Value>,
+	field: &'static str,
+) -> Result<(String, Value), IngestError> {
+	take::<Map<String, Value>>(values, field)?
+		.into_iter()
+		.next()
+		.ok_or(IngestError::InvalidField(field))
+}
+
+/// Most operations sent at once when catching up with another node
+pub const OPS_PER_PAGE: u32 = 1000;
+
+/// Asks for the operations newer than the clocks, the latest timestamps known of each node.
+/// Every operation of the nodes not in them is new
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GetOpsArgs {
+	pub clocks: Vec<(Uuid, NTP64)>,
+	pub count: u32,
+}
+
+/// Most operations of a table compacted at once
+const COMPACTION_PAGE_SIZE: i64 = 10_000;
+
+/// How often the sync logs are compacted
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The size of a library's sync log
+#[derive(Serialize, Type, Debug)]
+pub struct SyncLogStats {
+	pub owned_operations: u32,
+	pub shared_operations: u32,
+	pub relation_operations: u32,
+	/// Operations up to this date can be compacted, as every known node has them
+	pub compactable_until: Option<DateTime<Utc>>,
+}
+
 pub struct SyncManager {
 	db: Arc<PrismaClient>,
+	/// The id the library's nodes used to sync as, before they synced as themselves. Its
+	/// operations are still in the log, but it's not a node that can catch up
+	library: Uuid,
 	node: Uuid,
-	_clocks: HashMap<Uuid, NTP64>,
 	clock: HLC,
 	pub tx: Sender<SyncMessage>,
 }
 
 impl SyncManager {
-	pub fn new(db: &Arc<PrismaClient>, node: Uuid) -> (Self, Receiver<SyncMessage>) {
+	pub fn new(db: &Arc<PrismaClient>, library: Uuid, node: Uuid) -> (Self, Receiver<SyncMessage>) {
 		let (tx, rx) = broadcast::channel(64);
 
 		(
 			Self {
 				db: db.clone(),
+				library,
 				node,
 				clock: HLCBuilder::new().with_id(node.into()).build(),
-				_clocks: Default::default(),
 				tx,
 			},
 			rx,
@@ -65,296 +345,1387 @@ impl SyncManager {
 				})
 				.collect::<Vec<_>>();
 
-			let shared = _ops
-				.iter()
-				.filter_map(|op| match &op.typ {
-					CRDTOperationType::Shared(shared_op) => {
-						let kind = match &shared_op.data {
-							SharedOperationData::Create(_) => "c",
-							SharedOperationData::Update { .. } => "u",
-							SharedOperationData::Delete => "d",
-						};
+			let shared = _ops
+				.iter()
+				.filter_map(|op| match &op.typ {
+					CRDTOperationType::Shared(shared_op) => Some(tx.shared_operation().create(
+						op.id.as_bytes().to_vec(),
+						op.timestamp.0 as i64,
+						shared_op.model.to_string(),
+						to_vec(&shared_op.record_id).unwrap(),
+						shared_op_kind(&shared_op.data).to_string(),
+						to_vec(&shared_op.data).unwrap(),
+						node::pub_id::equals(op.node.as_bytes().to_vec()),
+						vec![],
+					)),
+					_ => None,
+				})
+				.collect::<Vec<_>>();
+
+			let relation = _ops
+				.iter()
+				.filter_map(|op| match &op.typ {
+					CRDTOperationType::Relation(relation_op) => {
+						Some(tx.relation_operation().create(
+							op.id.as_bytes().to_vec(),
+							op.timestamp.0 as i64,
+							relation_op.relation.clone(),
+							relation_op.relation_item.as_bytes().to_vec(),
+							relation_op.relation_group.as_bytes().to_vec(),
+							relation_op_kind(&relation_op.data).to_string(),
+							to_vec(&relation_op.data).unwrap(),
+							node::pub_id::equals(op.node.as_bytes().to_vec()),
+							vec![],
+						))
+					}
+					_ => None,
+				})
+				.collect::<Vec<_>>();
+
+			let (res, _) = tx._batch((queries, (owned, shared, relation))).await?;
+
+			for op in _ops {
+				self.tx.send(SyncMessage::Created(op)).ok();
+			}
+
+			res
+		};
+		#[cfg(not(feature = "sync-messages"))]
+		let res = tx._batch([queries]).await?.remove(0);
+
+		Ok(res)
+	}
+
+	pub async fn write_op<'item, Q: prisma_client_rust::BatchItem<'item>>(
+		&self,
+		tx: &PrismaClient,
+		op: CRDTOperation,
+		query: Q,
+	) -> prisma_client_rust::Result<<Q as prisma_client_rust::BatchItemParent>::ReturnValue> {
+		let ret = match &op.typ {
+			CRDTOperationType::Owned(owned_op) => {
+				tx._batch((
+					tx.owned_operation().create(
+						op.id.as_bytes().to_vec(),
+						op.timestamp.0 as i64,
+						to_vec(&owned_op.items).unwrap(),
+						owned_op.model.clone(),
+						node::pub_id::equals(op.node.as_bytes().to_vec()),
+						vec![],
+					),
+					query,
+				))
+				.await?
+				.1
+			}
+			CRDTOperationType::Shared(shared_op) => {
+				tx._batch((
+					tx.shared_operation().create(
+						op.id.as_bytes().to_vec(),
+						op.timestamp.0 as i64,
+						shared_op.model.to_string(),
+						to_vec(&shared_op.record_id).unwrap(),
+						shared_op_kind(&shared_op.data).to_string(),
+						to_vec(&shared_op.data).unwrap(),
+						node::pub_id::equals(op.node.as_bytes().to_vec()),
+						vec![],
+					),
+					query,
+				))
+				.await?
+				.1
+			}
+			CRDTOperationType::Relation(relation_op) => {
+				tx._batch((
+					tx.relation_operation().create(
+						op.id.as_bytes().to_vec(),
+						op.timestamp.0 as i64,
+						relation_op.relation.clone(),
+						relation_op.relation_item.as_bytes().to_vec(),
+						relation_op.relation_group.as_bytes().to_vec(),
+						relation_op_kind(&relation_op.data).to_string(),
+						to_vec(&relation_op.data).unwrap(),
+						node::pub_id::equals(op.node.as_bytes().to_vec()),
+						vec![],
+					),
+					query,
+				))
+				.await?
+				.1
+			}
+		};
+
+		self.tx.send(SyncMessage::Created(op)).ok();
+
+		Ok(ret)
+	}
+
+	pub fn node(&self) -> Uuid {
+		self.node
+	}
+
+	/// Up to when every operation of each node is in the log: the latest operation of this node,
+	/// and for the others up to when they were caught up with. Operations broadcast after those
+	/// don't count, as the ones before them may have been missed
+	pub async fn get_clocks(&self) -> prisma_client_rust::Result<HashMap<Uuid, NTP64>> {
+		let db = &self.db;
+
+		let mut clocks = HashMap::new();
+
+		for node in db
+			.node()
+			.find_many(vec![])
+			.select(node::select!({ id pub_id caught_up_to }))
+			.exec()
+			.await?
+		{
+			let Ok(node_pub_id) = Uuid::from_slice(&node.pub_id) else {
+				continue;
+			};
+
+			if node_pub_id != self.node {
+				if let Some(timestamp) = node.caught_up_to {
+					clocks.insert(node_pub_id, NTP64(timestamp as u64));
+				}
+				continue;
+			}
+
+			let timestamps = [
+				db.shared_operation()
+					.find_first(vec![shared_operation::node_id::equals(node.id)])
+					.order_by(shared_operation::timestamp::order(SortOrder::Desc))
+					.exec()
+					.await?
+					.map(|op| op.timestamp),
+				db.relation_operation()
+					.find_first(vec![relation_operation::node_id::equals(node.id)])
+					.order_by(relation_operation::timestamp::order(SortOrder::Desc))
+					.exec()
+					.await?
+					.map(|op| op.timestamp),
+				db.owned_operation()
+					.find_first(vec![owned_operation::node_id::equals(node.id)])
+					.order_by(owned_operation::timestamp::order(SortOrder::Desc))
+					.exec()
+					.await?
+					.map(|op| op.timestamp),
+			];
+
+			if let Some(timestamp) = timestamps.into_iter().flatten().max() {
+				clocks.insert(node_pub_id, NTP64(timestamp as u64));
+			}
+		}
+
+		Ok(clocks)
+	}
+
+	/// Saves up to when the other nodes were caught up with, once their operations were received
+	pub async fn set_clocks(
+		&self,
+		clocks: &HashMap<Uuid, NTP64>,
+	) -> prisma_client_rust::Result<()> {
+		let db = &self.db;
+
+		db._batch(
+			clocks
+				.iter()
+				.filter(|(node, _)| **node != self.node)
+				.map(|(node, clock)| {
+					db.node().update_many(
+						vec![node::pub_id::equals(node.as_bytes().to_vec())],
+						vec![node::caught_up_to::set(Some(clock.0 as i64))],
+					)
+				})
+				.collect::<Vec<_>>(),
+		)
+		.await?;
+
+		Ok(())
+	}
+
+	/// The operations newer than the clocks, oldest first, so the next page can be asked for with
+	/// the clocks advanced by the operations received
+	pub async fn get_ops(
+		&self,
+		args: GetOpsArgs,
+	) -> prisma_client_rust::Result<Vec<CRDTOperation>> {
+		let db = &self.db;
+
+		let count = args.count.min(OPS_PER_PAGE);
+
+		let asked = args.clocks.into_iter().collect::<HashMap<_, _>>();
+
+		// Only what this node is caught up with is sent, so the asking node doesn't advance its
+		// clocks past operations neither of them have. Every operation of the nodes it doesn't
+		// know of is new to it
+		let bounds = self
+			.get_clocks()
+			.await?
+			.into_iter()
+			.map(|(node, clock)| {
+				(
+					node.as_bytes().to_vec(),
+					asked.get(&node).map(|asked| asked.0 as i64),
+					clock.0 as i64,
+				)
+			})
+			.collect::<Vec<_>>();
+
+		let shared = db
+			.shared_operation()
+			.find_many(vec![or(bounds
+				.iter()
+				.map(|(node, after, until)| {
+					and(vec![
+						shared_operation::node::is(vec![node::pub_id::equals(node.clone())]),
+						shared_operation::timestamp::gt(after.unwrap_or(i64::MIN)),
+						shared_operation::timestamp::lte(*until),
+					])
+				})
+				.collect())])
+			.order_by(shared_operation::timestamp::order(SortOrder::Asc))
+			.take(count as i64)
+			.include(shared_operation::include!({ node: select { pub_id } }))
+			.exec()
+			.await?
+			.into_iter()
+			.flat_map(|op| {
+				Some(CRDTOperation {
+					id: Uuid::from_slice(&op.id).ok()?,
+					node: Uuid::from_slice(&op.node.pub_id).ok()?,
+					timestamp: NTP64(op.timestamp as u64),
+					typ: CRDTOperationType::Shared(SharedOperation {
+						record_id: serde_json::from_slice(&op.record_id).ok()?,
+						model: op.model,
+						data: serde_json::from_slice(&op.data).ok()?,
+					}),
+				})
+			});
+
+		let relation = db
+			.relation_operation()
+			.find_many(vec![or(bounds
+				.iter()
+				.map(|(node, after, until)| {
+					and(vec![
+						relation_operation::node::is(vec![node::pub_id::equals(node.clone())]),
+						relation_operation::timestamp::gt(after.unwrap_or(i64::MIN)),
+						relation_operation::timestamp::lte(*until),
+					])
+				})
+				.collect())])
+			.order_by(relation_operation::timestamp::order(SortOrder::Asc))
+			.take(count as i64)
+			.include(relation_operation::include!({ node: select { pub_id } }))
+			.exec()
+			.await?
+			.into_iter()
+			.flat_map(|op| {
+				Some(CRDTOperation {
+					id: Uuid::from_slice(&op.id).ok()?,
+					node: Uuid::from_slice(&op.node.pub_id).ok()?,
+					timestamp: NTP64(op.timestamp as u64),
+					typ: CRDTOperationType::Relation(RelationOperation {
+						relation_item: Uuid::from_slice(&op.item_id).ok()?,
+						relation_group: Uuid::from_slice(&op.group_id).ok()?,
+						relation: op.relation,
+						data: serde_json::from_slice(&op.data).ok()?,
+					}),
+				})
+			});
+
+		let owned = db
+			.owned_operation()
+			.find_many(vec![or(bounds
+				.iter()
+				.map(|(node, after, until)| {
+					and(vec![
+						owned_operation::node::is(vec![node::pub_id::equals(node.clone())]),
+						owned_operation::timestamp::gt(after.unwrap_or(i64::MIN)),
+						owned_operation::timestamp::lte(*until),
+					])
+				})
+				.collect())])
+			.order_by(owned_operation::timestamp::order(SortOrder::Asc))
+			.take(count as i64)
+			.include(owned_operation::include!({ node: select { pub_id } }))
+			.exec()
+			.await?
+			.into_iter()
+			.flat_map(|op| {
+				Some(CRDTOperation {
+					id: Uuid::from_slice(&op.id).ok()?,
+					node: Uuid::from_slice(&op.node.pub_id).ok()?,
+					timestamp: NTP64(op.timestamp as u64),
+					typ: CRDTOperationType::Owned(OwnedOperation {
+						model: op.model,
+						items: serde_json::from_slice(&op.data).ok()?,
+					}),
+				})
+			});
+
+		// Each kind of operation was paginated on its own, the page is the oldest of all of them
+		let mut ops = shared.chain(relation).chain(owned).collect::<Vec<_>>();
+		ops.sort_by(|a, b| (a.timestamp, a.node).cmp(&(b.timestamp, b.node)));
+		ops.truncate(count as usize);
+
+		Ok(ops)
+	}
+
+	/// Saves the clocks a node sent when catching up with this one, as the operations it has
+	pub async fn acknowledge(
+		&self,
+		node: Uuid,
+		clocks: &[(Uuid, NTP64)],
+	) -> prisma_client_rust::Result<()> {
+		self.db
+			.node()
+			.update_many(
+				vec![node::pub_id::equals(node.as_bytes().to_vec())],
+				vec![node::sync_clocks::set(Some(to_vec(clocks).unwrap()))],
+			)
+			.exec()
+			.await?;
+
+		Ok(())
+	}
+
+	/// The timestamp every known node has the operations up to, if older than the retention
+	/// window. Nodes that never caught up with this one could be missing anything, so they hold
+	/// compaction back. Without other nodes only the retention window does, as those joining later
+	/// catch up from the compacted history
+	async fn compaction_horizon(
+		&self,
+		retention: Duration,
+	) -> prisma_client_rust::Result<Option<NTP64>> {
+		let now = self.clock.new_timestamp().get_time().0;
+		let mut horizon = NTP64(now.saturating_sub(NTP64::from(retention).0));
+
+		let clocks = self.get_clocks().await?;
+
+		let peers = self
+			.db
+			.node()
+			.find_many(vec![node::pub_id::not_in_vec(vec![
+				self.node.as_bytes().to_vec(),
+				self.library.as_bytes().to_vec(),
+			])])
+			.select(node::select!({ sync_clocks }))
+			.exec()
+			.await?;
+
+		for peer in peers {
+			let peer_clocks = match peer
+				.sync_clocks
+				.and_then(|clocks| serde_json::from_slice::<Vec<(Uuid, NTP64)>>(&clocks).ok())
+			{
+				Some(peer_clocks) => peer_clocks.into_iter().collect::<HashMap<_, _>>(),
+				None => return Ok(None),
+			};
+
+			for (node, clock) in &clocks {
+				// Only the operations the peer is missing hold compaction back
+				match peer_clocks.get(node) {
+					Some(peer_clock) if peer_clock >= clock => {}
+					Some(peer_clock) => horizon = horizon.min(*peer_clock),
+					None => return Ok(None),
+				}
+			}
+		}
+
+		Ok(Some(horizon))
+	}
+
+	/// Collapses the history of the records up to the timestamp every known node has, once it's
+	/// out of the retention window. Returns how many operations were removed
+	pub async fn compact(&self, retention: Duration) -> prisma_client_rust::Result<usize> {
+		let horizon = match self.compaction_horizon(retention).await? {
+			Some(horizon) => horizon,
+			None => return Ok(0),
+		};
+
+		Ok(self.compact_shared(horizon).await?
+			+ self.compact_relation(horizon).await?
+			+ self.compact_owned(horizon).await?)
+	}
+
+	async fn compact_shared(&self, horizon: NTP64) -> prisma_client_rust::Result<usize> {
+		let db = &self.db;
+
+		let mut removed = 0;
+		// Operations before this one in the table's order are kept by the pages already compacted
+		let mut skip = 0;
+
+		loop {
+			let ops = db
+				.shared_operation()
+				.find_many(vec![shared_operation::timestamp::lte(horizon.0 as i64)])
+				.order_by(shared_operation::model::order(SortOrder::Asc))
+				.order_by(shared_operation::record_id::order(SortOrder::Asc))
+				.order_by(shared_operation::timestamp::order(SortOrder::Asc))
+				.skip(skip)
+				.take(COMPACTION_PAGE_SIZE)
+				.exec()
+				.await?;
+
+			let is_last_page = ops.len() < COMPACTION_PAGE_SIZE as usize;
+
+			let mut records = group_records(ops, |op| (op.model.clone(), op.record_id.clone()));
+
+			// The history of the last record may go on in the next page
+			if !is_last_page {
+				if records.len() > 1 {
+					records.pop();
+				} else if let Some(record) = records.pop() {
+					records.push(
+						db.shared_operation()
+							.find_many(vec![
+								shared_operation::model::equals(record[0].model.clone()),
+								shared_operation::record_id::equals(record[0].record_id.clone()),
+								shared_operation::timestamp::lte(horizon.0 as i64),
+							])
+							.order_by(shared_operation::timestamp::order(SortOrder::Asc))
+							.exec()
+							.await?,
+					);
+				}
+			}
+
+			let mut superseded = vec![];
+			let mut snapshots = vec![];
+
+			for record in records {
+				let history = record
+					.iter()
+					.map(|op| {
+						serde_json::from_slice::<SharedOperationData>(&op.data).map(Change::from)
+					})
+					.collect::<Result<Vec<_>, _>>();
+
+				// Records with operations we can't read are left as they are
+				let compaction = match history {
+					Ok(history) => compact(&history, true),
+					Err(_) => Compaction {
+						keep: (0..record.len()).collect(),
+						snapshot: None,
+					},
+				};
+
+				skip += compaction.keep.len() as i64;
+
+				if let Some(values) = compaction.snapshot {
+					snapshots.push(db.shared_operation().update(
+						shared_operation::id::equals(record[compaction.keep[0]].id.clone()),
+						vec![shared_operation::data::set(
+							to_vec(&SharedOperationData::Create(
+								SharedOperationCreateData::Unique(values),
+							))
+							.unwrap(),
+						)],
+					));
+				}
+
+				superseded.extend(
+					record
+						.into_iter()
+						.enumerate()
+						.filter(|(i, _)| !compaction.keep.contains(i))
+						.map(|(_, op)| op.id),
+				);
+			}
+
+			removed += superseded.len();
+
+			db._batch((
+				db.shared_operation()
+					.delete_many(vec![shared_operation::id::in_vec(superseded)]),
+				snapshots,
+			))
+			.await?;
+
+			if is_last_page {
+				return Ok(removed);
+			}
+		}
+	}
+
+	/// Like [`SyncManager::compact_shared`], but relations have no values to fold updates into
+	async fn compact_relation(&self, horizon: NTP64) -> prisma_client_rust::Result<usize> {
+		let db = &self.db;
+
+		let mut removed = 0;
+		let mut skip = 0;
+
+		loop {
+			let ops = db
+				.relation_operation()
+				.find_many(vec![relation_operation::timestamp::lte(horizon.0 as i64)])
+				.order_by(relation_operation::relation::order(SortOrder::Asc))
+				.order_by(relation_operation::item_id::order(SortOrder::Asc))
+				.order_by(relation_operation::group_id::order(SortOrder::Asc))
+				.order_by(relation_operation::timestamp::order(SortOrder::Asc))
+				.skip(skip)
+				.take(COMPACTION_PAGE_SIZE)
+				.exec()
+				.await?;
+
+			let is_last_page = ops.len() < COMPACTION_PAGE_SIZE as usize;
+
+			let mut records = group_records(ops, |op| {
+				(op.relation.clone(), op.item_id.clone(), op.group_id.clone())
+			});
+
+			if !is_last_page {
+				if records.len() > 1 {
+					records.pop();
+				} else if let Some(record) = records.pop() {
+					records.push(
+						db.relation_operation()
+							.find_many(vec![
+								relation_operation::relation::equals(record[0].relation.clone()),
+								relation_operation::item_id::equals(record[0].item_id.clone()),
+								relation_operation::group_id::equals(record[0].group_id.clone()),
+								relation_operation::timestamp::lte(horizon.0 as i64),
+							])
+							.order_by(relation_operation::timestamp::order(SortOrder::Asc))
+							.exec()
+							.await?,
+					);
+				}
+			}
+
+			let mut superseded = vec![];
+
+			for record in records {
+				let history = record
+					.iter()
+					.map(|op| {
+						serde_json::from_slice::<RelationOperationData>(&op.data).map(Change::from)
+					})
+					.collect::<Result<Vec<_>, _>>();
+
+				let keep = match history {
+					Ok(history) => compact(&history, false).keep,
+					Err(_) => (0..record.len()).collect(),
+				};
+
+				skip += keep.len() as i64;
+
+				superseded.extend(
+					record
+						.into_iter()
+						.enumerate()
+						.filter(|(i, _)| !keep.contains(i))
+						.map(|(_, op)| op.id),
+				);
+			}
+
+			removed += superseded.len();
+
+			db.relation_operation()
+				.delete_many(vec![relation_operation::id::in_vec(superseded)])
+				.exec()
+				.await?;
+
+			if is_last_page {
+				return Ok(removed);
+			}
+		}
+	}
+
+	/// Owned operations change many records at once, so they're rewritten as one per record. No
+	/// model is owned yet, so they're few enough to be compacted all at once
+	async fn compact_owned(&self, horizon: NTP64) -> prisma_client_rust::Result<usize> {
+		let db = &self.db;
+
+		let ops = db
+			.owned_operation()
+			.find_many(vec![owned_operation::timestamp::lte(horizon.0 as i64)])
+			.order_by(owned_operation::timestamp::order(SortOrder::Asc))
+			.exec()
+			.await?;
+
+		// The history of each record, with the index of the operation of each change
+		let mut records = BTreeMap::<(String, String), (Value, Vec<(usize, Change)>)>::new();
+
+		for (i, op) in ops.iter().enumerate() {
+			let Ok(items) = serde_json::from_slice::<Vec<OwnedOperationItem>>(&op.data) else {
+				return Ok(0);
+			};
+
+			let mut push = |id: Value, change: Change| {
+				records
+					.entry((op.model.clone(), id.to_string()))
+					.or_insert_with(|| (id, vec![]))
+					.1
+					.push((i, change));
+			};
+
+			for item in items {
+				match item.data {
+					OwnedOperationData::Create(values) => {
+						push(item.id, Change::Create(values.into_iter().collect()))
+					}
+					OwnedOperationData::CreateMany { values, .. } => {
+						for (id, values) in values {
+							push(id, Change::Create(values.into_iter().collect()));
+						}
+					}
+					OwnedOperationData::Update(values) => {
+						for (field, value) in values {
+							push(item.id.clone(), Change::Update { field, value });
+						}
+					}
+					OwnedOperationData::Delete => push(item.id, Change::Delete),
+				}
+			}
+		}
+
+		let mut compacted = vec![];
+		let mut superseded_changes = 0;
+
+		for ((model, _), (id, history)) in records {
+			let (ops_of_changes, changes): (Vec<_>, Vec<_>) = history.into_iter().unzip();
+
+			let compaction = compact(&changes, true);
+			superseded_changes += changes.len() - compaction.keep.len();
+
+			let data = match (compaction.snapshot, &changes[compaction.keep[0]]) {
+				(Some(values), _) => OwnedOperationData::Create(values.into_iter().collect()),
+				(None, Change::Create(values)) => {
+					OwnedOperationData::Create(values.clone().into_iter().collect())
+				}
+				(None, Change::Update { .. }) => OwnedOperationData::Update(
+					compaction
+						.keep
+						.iter()
+						.filter_map(|i| match &changes[*i] {
+							Change::Update { field, value } => Some((field.clone(), value.clone())),
+							_ => None,
+						})
+						.collect(),
+				),
+				(None, Change::Delete) => OwnedOperationData::Delete,
+			};
+
+			let newest = &ops[ops_of_changes[compaction.keep[compaction.keep.len() - 1]]];
+
+			compacted.push(db.owned_operation().create(
+				Uuid::new_v4().as_bytes().to_vec(),
+				newest.timestamp,
+				to_vec(&vec![OwnedOperationItem { id, data }]).unwrap(),
+				model,
+				node::id::equals(newest.node_id),
+				vec![],
+			));
+		}
+
+		if superseded_changes == 0 {
+			return Ok(0);
+		}
+
+		let removed = ops.len().saturating_sub(compacted.len());
+
+		db._batch((
+			db.owned_operation()
+				.delete_many(vec![owned_operation::id::in_vec(
+					ops.into_iter().map(|op| op.id).collect(),
+				)]),
+			compacted,
+		))
+		.await?;
+
+		Ok(removed)
+	}
+
+	pub async fn stats(&self, retention: Duration) -> prisma_client_rust::Result<SyncLogStats> {
+		let db = &self.db;
+
+		let (owned_operations, shared_operations, relation_operations) = db
+			._batch((
+				db.owned_operation().count(vec![]),
+				db.shared_operation().count(vec![]),
+				db.relation_operation().count(vec![]),
+			))
+			.await?;
+
+		Ok(SyncLogStats {
+			owned_operations: owned_operations as u32,
+			shared_operations: shared_operations as u32,
+			relation_operations: relation_operations as u32,
+			compactable_until: self
+				.compaction_horizon(retention)
+				.await?
+				.and_then(|horizon| Utc.timestamp_opt(horizon.as_secs() as i64, 0).single()),
+		})
+	}
+
+	/// Applies an operation from another node and adds it to the log, in the same transaction, so
+	/// an operation is never logged without its change, or applied without being logged. Shared
+	/// and relation operations are applied with last writer wins per field. No model is owned yet,
+	/// so owned operations are rejected, as there's nothing they could be applied to
+	pub async fn ingest_op(&self, op: CRDTOperation) -> Result<(), IngestError> {
+		if let CRDTOperationType::Owned(_) = &op.typ {
+			return Err(IngestError::InvalidOperation(op.typ));
+		}
+
+		let data = ModelSyncData::from_op(op.typ.clone())
+			.ok_or_else(|| IngestError::InvalidOperation(op.typ.clone()))?;
+
+		let msg = SyncMessage::Ingested(op.clone());
+		let timestamp = Timestamp::new(op.timestamp, op.node.into());
+
+		let ingested = self
+			.db
+			._transaction()
+			.run(|db| async move {
+				// Nodes can send the same operations more than once
+				if Self::was_ingested(&db, &op).await? {
+					return Ok::<_, IngestError>(false);
+				}
+
+				db.node()
+					.upsert(
+						node::pub_id::equals(op.node.as_bytes().to_vec()),
+						node::create(op.node.as_bytes().to_vec(), "TEMP".to_string(), vec![]),
+						vec![],
+					)
+					.exec()
+					.await?;
+
+				match data {
+					ModelSyncData::Location(id, data) => {
+						if let Some(change) = Self::resolve_shared(&db, &op, data).await? {
+							Self::apply_location(&db, id, change).await?;
+						}
+					}
+					ModelSyncData::FilePath(id, data) => {
+						if let Some(change) = Self::resolve_shared(&db, &op, data).await? {
+							Self::apply_file_path(&db, id, change).await?;
+						}
+					}
+					ModelSyncData::Object(id, data) => {
+						if let Some(change) = Self::resolve_shared(&db, &op, data).await? {
+							let created = matches!(change, Change::Create(_));
+							let pub_id = id.pub_id.clone();
+
+							Self::apply_object(&db, id, change).await?;
+
+							if created {
+								Self::apply_pending_tags_on_object(
+									&db,
+									relation_operation::item_id::equals(pub_id),
+								)
+								.await?;
+							}
+						}
+					}
+					ModelSyncData::Key(id, data) => {
+						if let Some(change) = Self::resolve_shared(&db, &op, data).await? {
+							Self::apply_key(&db, id, change).await?;
+						}
+					}
+					ModelSyncData::Tag(id, data) => {
+						if let Some(change) = Self::resolve_shared(&db, &op, data).await? {
+							let created = matches!(change, Change::Create(_));
+							let pub_id = id.pub_id.clone();
+
+							Self::apply_tag(&db, id, change).await?;
+
+							if created {
+								Self::apply_pending_tags_on_object(
+									&db,
+									relation_operation::group_id::equals(pub_id),
+								)
+								.await?;
+							}
+						}
+					}
+					ModelSyncData::Album(id, data) => {
+						if let Some(change) = Self::resolve_shared(&db, &op, data).await? {
+							Self::apply_album(&db, id, change).await?;
+						}
+					}
+					ModelSyncData::TagOnObject(relation_op) => {
+						if let Some(change) = Self::resolve_relation(&db, &op, &relation_op).await?
+						{
+							Self::apply_tag_on_object(
+								&db,
+								&relation_op.relation_item,
+								&relation_op.relation_group,
+								change,
+							)
+							.await?;
+						}
+					}
+				}
+
+				match op.typ {
+					CRDTOperationType::Shared(shared_op) => {
+						db.shared_operation()
+							.create(
+								op.id.as_bytes().to_vec(),
+								op.timestamp.0 as i64,
+								shared_op.model.to_string(),
+								to_vec(&shared_op.record_id).unwrap(),
+								shared_op_kind(&shared_op.data).to_string(),
+								to_vec(&shared_op.data).unwrap(),
+								node::pub_id::equals(op.node.as_bytes().to_vec()),
+								vec![],
+							)
+							.exec()
+							.await?;
+					}
+					CRDTOperationType::Relation(relation_op) => {
+						db.relation_operation()
+							.create(
+								op.id.as_bytes().to_vec(),
+								op.timestamp.0 as i64,
+								relation_op.relation,
+								relation_op.relation_item.as_bytes().to_vec(),
+								relation_op.relation_group.as_bytes().to_vec(),
+								relation_op_kind(&relation_op.data).to_string(),
+								to_vec(&relation_op.data).unwrap(),
+								node::pub_id::equals(op.node.as_bytes().to_vec()),
+								vec![],
+							)
+							.exec()
+							.await?;
+					}
+					CRDTOperationType::Owned(_) => unreachable!("owned operations are rejected"),
+				}
+
+				Ok(true)
+			})
+			.await?;
+
+		if !ingested {
+			return Ok(());
+		}
+
+		// So the operations created here come after every one seen from the other nodes
+		if let Err(e) = self.clock.update_with_timestamp(&timestamp) {
+			warn!("Failed to update the clock with an ingested operation: {e}");
+		}
+
+		self.tx.send(msg).ok();
+
+		Ok(())
+	}
+
+	async fn was_ingested(db: &PrismaClient, op: &CRDTOperation) -> Result<bool, IngestError> {
+		let id = op.id.as_bytes().to_vec();
+
+		Ok(match &op.typ {
+			CRDTOperationType::Shared(_) => db
+				.shared_operation()
+				.find_unique(shared_operation::id::equals(id))
+				.exec()
+				.await?
+				.is_some(),
+			CRDTOperationType::Relation(_) => db
+				.relation_operation()
+				.find_unique(relation_operation::id::equals(id))
+				.exec()
+				.await?
+				.is_some(),
+			CRDTOperationType::Owned(_) => db
+				.owned_operation()
+				.find_unique(owned_operation::id::equals(id))
+				.exec()
+				.await?
+				.is_some(),
+		})
+	}
+
+	async fn resolve_shared(
+		db: &PrismaClient,
+		op: &CRDTOperation,
+		data: SharedOperationData,
+	) -> Result<Option<Change>, IngestError> {
+		let (model, record_id) = match &op.typ {
+			CRDTOperationType::Shared(shared_op) => (&shared_op.model, &shared_op.record_id),
+			_ => unreachable!("shared models only come from shared operations"),
+		};
+
+		let newer = db
+			.shared_operation()
+			.find_many(vec![
+				shared_operation::model::equals(model.clone()),
+				shared_operation::record_id::equals(to_vec(record_id).unwrap()),
+				shared_operation::timestamp::gte(op.timestamp.0 as i64),
+			])
+			.order_by(shared_operation::timestamp::order(SortOrder::Asc))
+			.include(shared_operation::include!({ node: select { pub_id } }))
+			.exec()
+			.await?
+			.into_iter()
+			.filter(|newer| is_newer(newer.timestamp, &newer.node.pub_id, op))
+			.flat_map(|newer| serde_json::from_slice::<SharedOperationData>(&newer.data))
+			.map(Change::from)
+			.collect();
+
+		Ok(Change::from(data).resolve(newer))
+	}
 
-						Some(tx.shared_operation().create(
-							op.id.as_bytes().to_vec(),
-							op.timestamp.0 as i64,
-							shared_op.model.to_string(),
-							to_vec(&shared_op.record_id).unwrap(),
-							kind.to_string(),
-							to_vec(&shared_op.data).unwrap(),
-							node::pub_id::equals(op.node.as_bytes().to_vec()),
-							vec![],
-						))
-					}
-					_ => None,
-				})
-				.collect::<Vec<_>>();
+	async fn resolve_relation(
+		db: &PrismaClient,
+		op: &CRDTOperation,
+		relation_op: &RelationOperation,
+	) -> Result<Option<Change>, IngestError> {
+		let newer = db
+			.relation_operation()
+			.find_many(vec![
+				relation_operation::relation::equals(relation_op.relation.clone()),
+				relation_operation::item_id::equals(relation_op.relation_item.as_bytes().to_vec()),
+				relation_operation::group_id::equals(
+					relation_op.relation_group.as_bytes().to_vec(),
+				),
+				relation_operation::timestamp::gte(op.timestamp.0 as i64),
+			])
+			.order_by(relation_operation::timestamp::order(SortOrder::Asc))
+			.include(relation_operation::include!({ node: select { pub_id } }))
+			.exec()
+			.await?
+			.into_iter()
+			.filter(|newer| is_newer(newer.timestamp, &newer.node.pub_id, op))
+			.flat_map(|newer| serde_json::from_slice::<RelationOperationData>(&newer.data))
+			.map(Change::from)
+			.collect();
 
-			let (res, _) = tx._batch((queries, (owned, shared))).await?;
+		Ok(Change::from(relation_op.data.clone()).resolve(newer))
+	}
 
-			for op in _ops {
-				self.tx.send(SyncMessage::Created(op)).ok();
+	async fn apply_location(
+		db: &PrismaClient,
+		id: location::SyncId,
+		change: Change,
+	) -> Result<(), IngestError> {
+		let location = db
+			.location()
+			.find_unique(location::pub_id::equals(id.pub_id.clone()))
+			.select(location::select!({ id }))
+			.exec()
+			.await?;
+
+		match (change, location) {
+			(Change::Create(mut values), None) => {
+				db.location()
+					.create(
+						id.pub_id,
+						take(&mut values, location::name::NAME)?,
+						take(&mut values, location::path::NAME)?,
+						{
+							let (field, value) = take_relation(&mut values, location::node::NAME)?;
+							node::UniqueWhereParam::deserialize(&field, value)
+								.ok_or(IngestError::InvalidField(location::node::NAME))?
+						},
+						values
+							.into_iter()
+							.flat_map(|(k, v)| location::SetParam::deserialize(&k, v))
+							.collect(),
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Update { field, value }, Some(_)) => {
+				db.location()
+					.update(
+						location::pub_id::equals(id.pub_id),
+						vec![location::SetParam::deserialize(&field, value)
+							.ok_or(IngestError::UnknownField(field))?],
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Delete, Some(location)) => {
+				db._batch((
+					db.indexer_rules_in_location().delete_many(vec![
+						indexer_rules_in_location::location_id::equals(location.id),
+					]),
+					db.file_path()
+						.delete_many(vec![file_path::location_id::equals(location.id)]),
+					db.location().delete(location::id::equals(location.id)),
+				))
+				.await?;
 			}
+			// Already created, or not yet, in which case the update comes along with the creation
+			_ => {}
+		}
 
-			res
-		};
-		#[cfg(not(feature = "sync-messages"))]
-		let res = tx._batch([queries]).await?.remove(0);
+		Ok(())
+	}
 
-		Ok(res)
+	async fn apply_file_path(
+		db: &PrismaClient,
+		id: file_path::SyncId,
+		change: Change,
+	) -> Result<(), IngestError> {
+		let file_path = db
+			.file_path()
+			.find_unique(file_path::pub_id::equals(id.pub_id.clone()))
+			.select(file_path::select!({ id }))
+			.exec()
+			.await?;
+
+		match (change, file_path) {
+			(Change::Create(mut values), None) => {
+				db.file_path()
+					.create(
+						id.pub_id,
+						{
+							let (field, value) =
+								take_relation(&mut values, file_path::location::NAME)?;
+							location::UniqueWhereParam::deserialize(&field, value)
+								.ok_or(IngestError::InvalidField(file_path::location::NAME))?
+						},
+						take(&mut values, file_path::materialized_path::NAME)?,
+						take(&mut values, file_path::name::NAME)?,
+						values
+							.remove(file_path::extension::NAME)
+							.map(from_value)
+							.transpose()
+							.map_err(|_| IngestError::InvalidField(file_path::extension::NAME))?
+							.unwrap_or_default(),
+						take(&mut values, file_path::inode::NAME)?,
+						take(&mut values, file_path::device::NAME)?,
+						values
+							.into_iter()
+							.flat_map(|(k, v)| file_path::SetParam::deserialize(&k, v))
+							.collect(),
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Update { field, value }, Some(_)) => {
+				db.file_path()
+					.update(
+						file_path::pub_id::equals(id.pub_id),
+						vec![file_path::SetParam::deserialize(&field, value)
+							.ok_or(IngestError::UnknownField(field))?],
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Delete, Some(file_path)) => {
+				db.file_path()
+					.delete(file_path::id::equals(file_path.id))
+					.exec()
+					.await?;
+			}
+			_ => {}
+		}
+
+		Ok(())
 	}
 
-	pub async fn write_op<'item, Q: prisma_client_rust::BatchItem<'item>>(
-		&self,
-		tx: &PrismaClient,
-		op: CRDTOperation,
-		query: Q,
-	) -> prisma_client_rust::Result<<Q as prisma_client_rust::BatchItemParent>::ReturnValue> {
-		let ret = match &op.typ {
-			CRDTOperationType::Owned(owned_op) => {
-				tx._batch((
-					tx.owned_operation().create(
-						op.id.as_bytes().to_vec(),
-						op.timestamp.0 as i64,
-						to_vec(&owned_op.items).unwrap(),
-						owned_op.model.clone(),
-						node::pub_id::equals(op.node.as_bytes().to_vec()),
-						vec![],
+	async fn apply_object(
+		db: &PrismaClient,
+		id: object::SyncId,
+		change: Change,
+	) -> Result<(), IngestError> {
+		let object = db
+			.object()
+			.find_unique(object::pub_id::equals(id.pub_id.clone()))
+			.select(object::select!({ id }))
+			.exec()
+			.await?;
+
+		match (change, object) {
+			(Change::Create(values), None) => {
+				db.object()
+					.create(
+						id.pub_id,
+						values
+							.into_iter()
+							.flat_map(|(k, v)| object::SetParam::deserialize(&k, v))
+							.collect(),
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Update { field, value }, Some(_)) => {
+				db.object()
+					.update(
+						object::pub_id::equals(id.pub_id),
+						vec![object::SetParam::deserialize(&field, value)
+							.ok_or(IngestError::UnknownField(field))?],
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Delete, Some(object)) => {
+				// Its paths are kept, they'll be identified again
+				db._batch((
+					db.file_path().update_many(
+						vec![file_path::object_id::equals(Some(object.id))],
+						vec![file_path::object_id::set(None)],
 					),
-					query,
+					db.tag_on_object()
+						.delete_many(vec![tag_on_object::object_id::equals(object.id)]),
+					db.label_on_object()
+						.delete_many(vec![label_on_object::object_id::equals(object.id)]),
+					db.object_in_space()
+						.delete_many(vec![object_in_space::object_id::equals(object.id)]),
+					db.object_in_album()
+						.delete_many(vec![object_in_album::object_id::equals(object.id)]),
+					db.object().delete(object::id::equals(object.id)),
 				))
-				.await?
-				.1
+				.await?;
 			}
-			CRDTOperationType::Shared(shared_op) => {
-				let kind = match &shared_op.data {
-					SharedOperationData::Create(_) => "c",
-					SharedOperationData::Update { .. } => "u",
-					SharedOperationData::Delete => "d",
-				};
+			_ => {}
+		}
 
-				tx._batch((
-					tx.shared_operation().create(
-						op.id.as_bytes().to_vec(),
-						op.timestamp.0 as i64,
-						shared_op.model.to_string(),
-						to_vec(&shared_op.record_id).unwrap(),
-						kind.to_string(),
-						to_vec(&shared_op.data).unwrap(),
-						node::pub_id::equals(op.node.as_bytes().to_vec()),
-						vec![],
-					),
-					query,
-				))
-				.await?
-				.1
+		Ok(())
+	}
+
+	async fn apply_key(
+		db: &PrismaClient,
+		id: key::SyncId,
+		change: Change,
+	) -> Result<(), IngestError> {
+		let key = db
+			.key()
+			.find_unique(key::uuid::equals(id.uuid.clone()))
+			.select(key::select!({ id }))
+			.exec()
+			.await?;
+
+		match (change, key) {
+			(Change::Create(mut values), None) => {
+				db.key()
+					.create(
+						id.uuid,
+						take(&mut values, key::version::NAME)?,
+						take(&mut values, key::key_type::NAME)?,
+						take(&mut values, key::algorithm::NAME)?,
+						take(&mut values, key::hashing_algorithm::NAME)?,
+						take(&mut values, key::content_salt::NAME)?,
+						take(&mut values, key::master_key::NAME)?,
+						take(&mut values, key::master_key_nonce::NAME)?,
+						take(&mut values, key::key_nonce::NAME)?,
+						take(&mut values, key::key::NAME)?,
+						take(&mut values, key::salt::NAME)?,
+						values
+							.into_iter()
+							.flat_map(|(k, v)| key::SetParam::deserialize(&k, v))
+							.collect(),
+					)
+					.exec()
+					.await?;
 			}
-			_ => todo!(),
-		};
+			(Change::Update { field, value }, Some(_)) => {
+				db.key()
+					.update(
+						key::uuid::equals(id.uuid),
+						vec![key::SetParam::deserialize(&field, value)
+							.ok_or(IngestError::UnknownField(field))?],
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Delete, Some(key)) => {
+				db.key().delete(key::id::equals(key.id)).exec().await?;
+			}
+			_ => {}
+		}
 
-		self.tx.send(SyncMessage::Created(op)).ok();
+		Ok(())
+	}
 
-		Ok(ret)
+	async fn apply_tag(
+		db: &PrismaClient,
+		id: tag::SyncId,
+		change: Change,
+	) -> Result<(), IngestError> {
+		let tag = db
+			.tag()
+			.find_unique(tag::pub_id::equals(id.pub_id.clone()))
+			.select(tag::select!({ id }))
+			.exec()
+			.await?;
+
+		match (change, tag) {
+			(Change::Create(values), None) => {
+				db.tag()
+					.create(
+						id.pub_id,
+						values
+							.into_iter()
+							.flat_map(|(k, v)| tag::SetParam::deserialize(&k, v))
+							.collect(),
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Update { field, value }, Some(_)) => {
+				db.tag()
+					.update(
+						tag::pub_id::equals(id.pub_id),
+						vec![tag::SetParam::deserialize(&field, value)
+							.ok_or(IngestError::UnknownField(field))?],
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Delete, Some(tag)) => {
+				db._batch((
+					db.tag_on_object()
+						.delete_many(vec![tag_on_object::tag_id::equals(tag.id)]),
+					db.tag().delete(tag::id::equals(tag.id)),
+				))
+				.await?;
+			}
+			_ => {}
+		}
+
+		Ok(())
 	}
 
-	pub async fn get_ops(&self) -> prisma_client_rust::Result<Vec<CRDTOperation>> {
-		Ok(self
-			.db
-			.shared_operation()
-			.find_many(vec![])
-			.order_by(shared_operation::timestamp::order(SortOrder::Asc))
-			.include(shared_operation::include!({ node: select {
-                pub_id
-            } }))
+	async fn apply_album(
+		db: &PrismaClient,
+		id: album::SyncId,
+		change: Change,
+	) -> Result<(), IngestError> {
+		let album = db
+			.album()
+			.find_unique(album::pub_id::equals(id.pub_id.clone()))
+			.select(album::select!({ id }))
 			.exec()
-			.await?
-			.into_iter()
-			.flat_map(|op| {
-				Some(CRDTOperation {
-					id: Uuid::from_slice(&op.id).ok()?,
-					node: Uuid::from_slice(&op.node.pub_id).ok()?,
-					timestamp: NTP64(op.timestamp as u64),
-					typ: CRDTOperationType::Shared(SharedOperation {
-						record_id: serde_json::from_slice(&op.record_id).ok()?,
-						model: op.model,
-						data: serde_json::from_slice(&op.data).ok()?,
-					}),
-				})
-			})
-			.collect())
+			.await?;
+
+		match (change, album) {
+			(Change::Create(mut values), None) => {
+				db.album()
+					.create(
+						id.pub_id,
+						take(&mut values, album::name::NAME)?,
+						values
+							.into_iter()
+							.flat_map(|(k, v)| album::SetParam::deserialize(&k, v))
+							.collect(),
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Update { field, value }, Some(_)) => {
+				db.album()
+					.update(
+						album::pub_id::equals(id.pub_id),
+						vec![album::SetParam::deserialize(&field, value)
+							.ok_or(IngestError::UnknownField(field))?],
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Delete, Some(album)) => {
+				db._batch((
+					db.object_in_album()
+						.delete_many(vec![object_in_album::album_id::equals(album.id)]),
+					db.album().delete(album::id::equals(album.id)),
+				))
+				.await?;
+			}
+			_ => {}
+		}
+
+		Ok(())
 	}
 
-	pub async fn ingest_op(&self, op: CRDTOperation) -> prisma_client_rust::Result<()> {
-		let db = &self.db;
+	async fn apply_tag_on_object(
+		db: &PrismaClient,
+		object_pub_id: &Uuid,
+		tag_pub_id: &Uuid,
+		change: Change,
+	) -> Result<(), IngestError> {
+		let (Some(tag), Some(object)) = (
+			db.tag()
+				.find_unique(tag::pub_id::equals(tag_pub_id.as_bytes().to_vec()))
+				.select(tag::select!({ id }))
+				.exec()
+				.await?,
+			db.object()
+				.find_unique(object::pub_id::equals(object_pub_id.as_bytes().to_vec()))
+				.select(object::select!({ id }))
+				.exec()
+				.await?,
+		) else {
+			// Relations are gone along with any of their sides, and the ones with a side that
+			// wasn't ingested yet are applied once it is, by `apply_pending_tags_on_object`
+			return Ok(());
+		};
 
-		db.node()
-			.upsert(
-				node::pub_id::equals(op.node.as_bytes().to_vec()),
-				node::create(op.node.as_bytes().to_vec(), "TEMP".to_string(), vec![]),
-				vec![],
-			)
+		let tag_on_object = db
+			.tag_on_object()
+			.find_unique(tag_on_object::tag_id_object_id(tag.id, object.id))
 			.exec()
 			.await?;
 
-		let msg = SyncMessage::Ingested(op.clone());
-
-		match ModelSyncData::from_op(op.typ.clone()).unwrap() {
-			ModelSyncData::FilePath(id, shared_op) => match shared_op {
-				SharedOperationData::Create(SharedOperationCreateData::Unique(mut data)) => {
-					db.file_path()
-						.create(
-							id.pub_id,
-							{
-								let val: std::collections::HashMap<String, Value> =
-									from_value(data.remove(file_path::location::NAME).unwrap())
-										.unwrap();
-								let val = val.into_iter().next().unwrap();
-
-								location::UniqueWhereParam::deserialize(&val.0, val.1).unwrap()
-							},
-							serde_json::from_value(
-								data.remove(file_path::materialized_path::NAME).unwrap(),
-							)
-							.unwrap(),
-							serde_json::from_value(data.remove(file_path::name::NAME).unwrap())
-								.unwrap(),
-							serde_json::from_value(
-								data.remove(file_path::extension::NAME)
-									.unwrap_or_else(|| serde_json::Value::String("".to_string())),
-							)
-							.unwrap(),
-							serde_json::from_value(data.remove(file_path::inode::NAME).unwrap())
-								.unwrap(),
-							serde_json::from_value(data.remove(file_path::device::NAME).unwrap())
-								.unwrap(),
-							data.into_iter()
-								.flat_map(|(k, v)| file_path::SetParam::deserialize(&k, v))
-								.collect(),
-						)
-						.exec()
-						.await?;
-				}
-				SharedOperationData::Update { field, value } => {
-					self.db
-						.file_path()
-						.update(
-							file_path::pub_id::equals(id.pub_id),
-							vec![file_path::SetParam::deserialize(&field, value).unwrap()],
-						)
-						.exec()
-						.await?;
-				}
-				_ => todo!(),
-			},
-			ModelSyncData::Location(id, shared_op) => match shared_op {
-				SharedOperationData::Create(SharedOperationCreateData::Unique(mut data)) => {
-					db.location()
-						.create(
-							id.pub_id,
-							serde_json::from_value(data.remove(location::name::NAME).unwrap())
-								.unwrap(),
-							serde_json::from_value(data.remove(location::path::NAME).unwrap())
-								.unwrap(),
-							{
-								let val: std::collections::HashMap<String, Value> =
-									from_value(data.remove(location::node::NAME).unwrap()).unwrap();
-								let val = val.into_iter().next().unwrap();
-
-								node::UniqueWhereParam::deserialize(&val.0, val.1).unwrap()
-							},
-							data.into_iter()
-								.flat_map(|(k, v)| location::SetParam::deserialize(&k, v))
-								.collect(),
-						)
-						.exec()
-						.await?;
-				}
-				_ => todo!(),
-			},
-			ModelSyncData::Object(id, shared_op) => match shared_op {
-				SharedOperationData::Create(_) => {
-					db.object()
-						.upsert(
-							object::pub_id::equals(id.pub_id.clone()),
-							object::create(id.pub_id, vec![]),
-							vec![],
-						)
-						.exec()
-						.await
-						.ok();
-				}
-				SharedOperationData::Update { field, value } => {
-					db.object()
-						.update(
-							object::pub_id::equals(id.pub_id),
-							vec![object::SetParam::deserialize(&field, value).unwrap()],
-						)
-						.exec()
-						.await?;
-				}
-				_ => todo!(),
-			},
-			ModelSyncData::Tag(id, shared_op) => match shared_op {
-				SharedOperationData::Create(create_data) => match create_data {
-					SharedOperationCreateData::Unique(create_data) => {
-						db.tag()
-							.create(
-								id.pub_id,
-								create_data
-									.into_iter()
-									.flat_map(|(field, value)| {
-										tag::SetParam::deserialize(&field, value)
-									})
-									.collect(),
-							)
-							.exec()
-							.await?;
-					}
-					_ => unreachable!(),
-				},
-				SharedOperationData::Update { field, value } => {
-					db.tag()
-						.update(
-							tag::pub_id::equals(id.pub_id),
-							vec![tag::SetParam::deserialize(&field, value).unwrap()],
-						)
-						.exec()
-						.await?;
-				}
-				SharedOperationData::Delete => {
-					db.tag()
-						.delete(tag::pub_id::equals(id.pub_id))
-						.exec()
-						.await?;
-				}
-			},
-			_ => todo!(),
+		match (change, tag_on_object) {
+			(Change::Create(values), None) => {
+				db.tag_on_object()
+					.create(
+						tag::id::equals(tag.id),
+						object::id::equals(object.id),
+						values
+							.into_iter()
+							.flat_map(|(k, v)| tag_on_object::SetParam::deserialize(&k, v))
+							.collect(),
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Update { field, value }, Some(_)) => {
+				db.tag_on_object()
+					.update(
+						tag_on_object::tag_id_object_id(tag.id, object.id),
+						vec![tag_on_object::SetParam::deserialize(&field, value)
+							.ok_or(IngestError::UnknownField(field))?],
+					)
+					.exec()
+					.await?;
+			}
+			(Change::Delete, Some(_)) => {
+				db.tag_on_object()
+					.delete(tag_on_object::tag_id_object_id(tag.id, object.id))
+					.exec()
+					.await?;
+			}
+			_ => {}
 		}
 
-		if let CRDTOperationType::Shared(shared_op) = op.typ {
-			let kind = match &shared_op.data {
-				SharedOperationData::Create(_) => "c",
-				SharedOperationData::Update { .. } => "u",
-				SharedOperationData::Delete => "d",
+		Ok(())
+	}
+
+	/// Replays the logged operations of the tags on an object that was just created, or on the
+	/// objects of a tag that was, as they were ingested before it and couldn't be applied then
+	async fn apply_pending_tags_on_object(
+		db: &PrismaClient,
+		side: relation_operation::WhereParam,
+	) -> Result<(), IngestError> {
+		let pending = db
+			.relation_operation()
+			.find_many(vec![
+				relation_operation::relation::equals(tag_on_object::Types::MODEL.to_string()),
+				side,
+			])
+			.order_by(relation_operation::timestamp::order(SortOrder::Asc))
+			.exec()
+			.await?;
+
+		for relation_op in pending {
+			let (Ok(object_pub_id), Ok(tag_pub_id), Ok(data)) = (
+				Uuid::from_slice(&relation_op.item_id),
+				Uuid::from_slice(&relation_op.group_id),
+				serde_json::from_slice::<RelationOperationData>(&relation_op.data),
+			) else {
+				continue;
 			};
 
-			db.shared_operation()
-				.create(
-					op.id.as_bytes().to_vec(),
-					op.timestamp.0 as i64,
-					shared_op.model.to_string(),
-					to_vec(&shared_op.record_id).unwrap(),
-					kind.to_string(),
-					to_vec(&shared_op.data).unwrap(),
-					node::pub_id::equals(op.node.as_bytes().to_vec()),
-					vec![],
-				)
-				.exec()
-				.await?;
+			// In order, so they leave the relation as the latest of them did
+			Self::apply_tag_on_object(db, &object_pub_id, &tag_pub_id, Change::from(data)).await?;
 		}
 
-		self.tx.send(msg).ok();
-
 		Ok(())
 	}
 
@@ -491,4 +1862,223 @@ impl SyncManager {
 			},
 		}))
 	}
+	pub fn shared_delete<
+		TSyncId: SyncId<ModelTypes = TModel>,
+		TModel: SyncType<Marker = SharedSyncType>,
+	>(
+		&self,
+		id: TSyncId,
+	) -> CRDTOperation {
+		self.new_op(CRDTOperationType::Shared(SharedOperation {
+			model: TModel::MODEL.to_string(),
+			record_id: json!(id),
+			data: SharedOperationData::Delete,
+		}))
+	}
+
+	pub fn relation_create<TModel: SyncType<Marker = RelationSyncType>>(
+		&self,
+		item: Uuid,
+		group: Uuid,
+	) -> CRDTOperation {
+		self.new_relation_op::<TModel>(item, group, RelationOperationData::Create)
+	}
+	pub fn relation_update<TModel: SyncType<Marker = RelationSyncType>>(
+		&self,
+		item: Uuid,
+		group: Uuid,
+		field: &str,
+		value: Value,
+	) -> CRDTOperation {
+		self.new_relation_op::<TModel>(
+			item,
+			group,
+			RelationOperationData::Update {
+				field: field.to_string(),
+				value,
+			},
+		)
+	}
+	pub fn relation_delete<TModel: SyncType<Marker = RelationSyncType>>(
+		&self,
+		item: Uuid,
+		group: Uuid,
+	) -> CRDTOperation {
+		self.new_relation_op::<TModel>(item, group, RelationOperationData::Delete)
+	}
+
+	fn new_relation_op<TModel: SyncType<Marker = RelationSyncType>>(
+		&self,
+		item: Uuid,
+		group: Uuid,
+		data: RelationOperationData,
+	) -> CRDTOperation {
+		self.new_op(CRDTOperationType::Relation(RelationOperation {
+			relation_item: item,
+			relation_group: group,
+			relation: TModel::MODEL.to_string(),
+			data,
+		}))
+	}
+}
+
+/// Compacts the sync log of every library every so often
+pub async fn compact_operations(library_manager: Arc<LibraryManager>) {
+	let mut compaction_interval = interval(COMPACTION_INTERVAL);
+	compaction_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+	loop {
+		compaction_interval.tick().await;
+
+		for library in library_manager.get_all_libraries().await {
+			match library.sync.compact(library.config.sync_retention()).await {
+				Ok(0) => {}
+				Ok(removed) => debug!(
+					"Compacted the sync log of library '{}', removing {removed} operations",
+					library.id
+				),
+				Err(e) => error!(
+					"Failed to compact the sync log of library '{}': {e:#?}",
+					library.id
+				),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn update(field: &str, value: Value) -> Change {
+		Change::Update {
+			field: field.to_string(),
+			value,
+		}
+	}
+
+	#[test]
+	fn newer_updates_win() {
+		assert!(update("name", json!("old"))
+			.resolve(vec![update("name", json!("new"))])
+			.is_none());
+
+		assert!(matches!(
+			update("name", json!("new")).resolve(vec![update("color", json!("red"))]),
+			Some(Change::Update { field, .. }) if field == "name"
+		));
+	}
+
+	#[test]
+	fn deletions_win_over_older_changes() {
+		assert!(update("name", json!("new"))
+			.resolve(vec![Change::Delete])
+			.is_none());
+		assert!(Change::Create(Map::new())
+			.resolve(vec![Change::Delete])
+			.is_none());
+		assert!(Change::Delete
+			.resolve(vec![Change::Create(Map::new())])
+			.is_none());
+	}
+
+	#[test]
+	fn creations_take_the_updates_ingested_before_them() {
+		let resolved = Change::Create(Map::from_iter([
+			("name".to_string(), json!("created")),
+			("color".to_string(), json!("blue")),
+		]))
+		.resolve(vec![
+			update("name", json!("first")),
+			update("name", json!("second")),
+		]);
+
+		let Some(Change::Create(values)) = resolved else {
+			panic!("creation was dropped");
+		};
+
+		assert_eq!(values["name"], json!("second"));
+		assert_eq!(values["color"], json!("blue"));
+	}
+
+	#[test]
+	fn compaction_keeps_the_newest_update_of_each_field() {
+		let history = [
+			update("name", json!("old")),
+			update("color", json!("red")),
+			update("name", json!("new")),
+		];
+
+		assert_eq!(
+			compact(&history, true),
+			Compaction {
+				keep: vec![1, 2],
+				snapshot: None
+			}
+		);
+	}
+
+	#[test]
+	fn compaction_folds_updates_into_creations() {
+		let history = [
+			update("name", json!("before")),
+			Change::Create(Map::from_iter([
+				("name".to_string(), json!("created")),
+				("color".to_string(), json!("blue")),
+			])),
+			update("name", json!("first")),
+			update("name", json!("second")),
+		];
+
+		let compaction = compact(&history, true);
+		assert_eq!(compaction.keep, vec![1]);
+
+		let values = compaction.snapshot.unwrap();
+		assert_eq!(values["name"], json!("second"));
+		assert_eq!(values["color"], json!("blue"));
+
+		// Relations have no values to fold updates into
+		assert_eq!(
+			compact(&history, false),
+			Compaction {
+				keep: vec![1, 3],
+				snapshot: None
+			}
+		);
+	}
+
+	#[test]
+	fn compaction_starts_from_the_last_deletion() {
+		assert_eq!(
+			compact(
+				&[
+					Change::Create(Map::new()),
+					update("name", json!("deleted")),
+					Change::Delete,
+					update("name", json!("ignored")),
+				],
+				true
+			),
+			Compaction {
+				keep: vec![2],
+				snapshot: None
+			}
+		);
+
+		assert_eq!(
+			compact(
+				&[
+					Change::Create(Map::new()),
+					Change::Delete,
+					Change::Create(Map::new()),
+					Change::Create(Map::new()),
+				],
+				true
+			),
+			Compaction {
+				keep: vec![2],
+				snapshot: None
+			}
+		);
+	}
 }
@@ -1,4 +1,6 @@
 mod manager;
+#[cfg(test)]
+mod tests;
 
 pub use crate::prisma_sync::*;
 pub use manager::*;
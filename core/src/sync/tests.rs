@@ -0,0 +1,745 @@
+//! Two nodes sharing a library in the same process, loaded like the node loads its libraries, with
+//! in-memory streams and channels standing in for P2P, to check that what's done on one of them
+//! ends up on the other
+
+use crate::{
+	job::JobManager,
+	library::{Library, LibraryManager},
+	location::LocationManager,
+	node::NodeConfigManager,
+	p2p::{Header, P2PManager, SyncPayload},
+	prisma::{location, node, object, tag, tag_on_object},
+	sync,
+	util::db::load_and_migrate,
+	NodeContext,
+};
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use sd_sync::{CRDTOperation, CRDTOperationType, OwnedOperation};
+
+use serde_json::json;
+use tempfile::{tempdir, TempDir};
+use tokio::{
+	io::{duplex, AsyncReadExt},
+	sync::broadcast::{self, error::TryRecvError, Receiver},
+};
+use uhlc::NTP64;
+use uuid::Uuid;
+
+use super::{IngestError, SyncManager, SyncMessage, OPS_PER_PAGE};
+
+/// What's synced of a library, sorted so it can be compared across nodes
+#[derive(Debug, PartialEq)]
+struct SyncedState {
+	locations: Vec<(Vec<u8>, String, String)>,
+	tags: Vec<(Vec<u8>, Option<String>, Option<String>)>,
+	objects: Vec<(Vec<u8>, Option<String>)>,
+	tags_on_objects: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+fn library_db_path(data_dir: &TempDir) -> PathBuf {
+	data_dir.path().join("library.db")
+}
+
+/// A node's copy of the library, loaded without P2P, so nothing but the tests moves operations
+/// between nodes
+struct TestNode {
+	library: Library,
+	/// The operations created by the library, to be relayed to the other node
+	created: Receiver<SyncMessage>,
+	/// Where the node keeps its config and the library's database
+	data_dir: TempDir,
+}
+
+impl TestNode {
+	async fn new(library_id: Uuid) -> Self {
+		Self::load(library_id, tempdir().unwrap()).await
+	}
+
+	/// Loads the library in `data_dir` like the node does, as the node whose config is there
+	async fn load(library_id: Uuid, data_dir: TempDir) -> Self {
+		let config = NodeConfigManager::new(data_dir.path().to_path_buf())
+			.await
+			.unwrap();
+
+		let (library, created) = LibraryManager::load_without_p2p(
+			library_id,
+			library_db_path(&data_dir),
+			Default::default(),
+			NodeContext {
+				config,
+				jobs: JobManager::new(),
+				location_manager: LocationManager::new(),
+				event_bus_tx: broadcast::channel(1024).0,
+			},
+		)
+		.await
+		.unwrap();
+
+		Self {
+			library,
+			created,
+			data_dir,
+		}
+	}
+
+	/// Loads the library again, like the node does when it's restarted
+	async fn reload(self) -> Self {
+		let Self {
+			library, data_dir, ..
+		} = self;
+
+		Self::load(library.id, data_dir).await
+	}
+
+	/// Stands in for P2P broadcasts, ingesting on the other node the operations created here since
+	/// the last time
+	async fn relay_to(&mut self, other: &TestNode) {
+		loop {
+			match self.created.try_recv() {
+				Ok(SyncMessage::Created(op)) => other.library.sync.ingest_op(op).await.unwrap(),
+				Ok(SyncMessage::Ingested(_)) => {}
+				Err(TryRecvError::Empty) => return,
+				Err(e) => panic!("operations to relay were lost, relay more often: {e}"),
+			}
+		}
+	}
+
+	/// Stands in for a P2P connection, catching up with the other node over an in-memory stream.
+	/// Returns how many operations were received
+	async fn catch_up_with(&self, other: &TestNode) -> usize {
+		let (mut asking, mut answering) = duplex(64 * 1024);
+		let library_id = self.library.id;
+
+		let answer = async move {
+			// What the P2P manager reads before handing the stream over: a `Header::Sync`, made of
+			// its discriminator, the library id and the length of the payload
+			let mut header = [0; 21];
+			answering.read_exact(&mut header).await.unwrap();
+			let len = u32::from_le_bytes(header[17..].try_into().unwrap());
+			assert_eq!(header.to_vec(), Header::Sync(library_id, len).to_bytes());
+
+			let (node, args) = match SyncPayload::from_stream(&mut answering, len).await.unwrap() {
+				SyncPayload::CatchUp { node, args } => (node, args),
+				payload => panic!("expected a catch up request, got {payload:?}"),
+			};
+
+			P2PManager::serve_catch_up(answering, node, args, Some(other.library.sync.as_ref()))
+				.await
+				.unwrap();
+		};
+
+		let (ingested, ()) = tokio::join!(
+			P2PManager::request_catch_up(&mut asking, library_id, &self.library.sync),
+			answer
+		);
+
+		ingested.unwrap()
+	}
+
+	async fn synced_state(&self) -> SyncedState {
+		let db = &self.library.db;
+
+		let mut state = SyncedState {
+			locations: db
+				.location()
+				.find_many(vec![])
+				.exec()
+				.await
+				.unwrap()
+				.into_iter()
+				.map(|location| (location.pub_id, location.name, location.path))
+				.collect(),
+			tags: db
+				.tag()
+				.find_many(vec![])
+				.exec()
+				.await
+				.unwrap()
+				.into_iter()
+				.map(|tag| (tag.pub_id, tag.name, tag.color))
+				.collect(),
+			objects: db
+				.object()
+				.find_many(vec![])
+				.exec()
+				.await
+				.unwrap()
+				.into_iter()
+				.map(|object| (object.pub_id, object.note))
+				.collect(),
+			tags_on_objects: db
+				.tag_on_object()
+				.find_many(vec![])
+				.include(tag_on_object::include!({
+					tag: select { pub_id }
+					object: select { pub_id }
+				}))
+				.exec()
+				.await
+				.unwrap()
+				.into_iter()
+				.map(|tag_on_object| (tag_on_object.tag.pub_id, tag_on_object.object.pub_id))
+				.collect(),
+		};
+
+		state.locations.sort();
+		state.tags.sort();
+		state.objects.sort();
+		state.tags_on_objects.sort();
+
+		state
+	}
+
+	// The edits below are synced like the API does them
+
+	async fn create_location(&self, path: &str) -> Vec<u8> {
+		let Library { db, sync, .. } = &self.library;
+
+		let pub_id = Uuid::new_v4().as_bytes().to_vec();
+
+		sync.write_op(
+			db,
+			sync.unique_shared_create(
+				sync::location::SyncId {
+					pub_id: pub_id.clone(),
+				},
+				[
+					(
+						location::node::NAME,
+						json!(sync::node::SyncId {
+							pub_id: sync.node().as_bytes().to_vec()
+						}),
+					),
+					(location::name::NAME, json!("Synced")),
+					(location::path::NAME, json!(path)),
+				],
+			),
+			db.location().create(
+				pub_id.clone(),
+				"Synced".to_string(),
+				path.to_string(),
+				node::id::equals(self.library.node_local_id),
+				vec![],
+			),
+		)
+		.await
+		.unwrap();
+
+		pub_id
+	}
+
+	async fn create_tag(&self, name: &str) -> Vec<u8> {
+		let Library { db, sync, .. } = &self.library;
+
+		let pub_id = Uuid::new_v4().as_bytes().to_vec();
+
+		sync.write_op(
+			db,
+			sync.unique_shared_create(
+				sync::tag::SyncId {
+					pub_id: pub_id.clone(),
+				},
+				[
+					(tag::name::NAME, json!(name)),
+					(tag::color::NAME, json!("blue")),
+				],
+			),
+			db.tag().create(
+				pub_id.clone(),
+				vec![
+					tag::name::set(Some(name.to_string())),
+					tag::color::set(Some("blue".to_string())),
+				],
+			),
+		)
+		.await
+		.unwrap();
+
+		pub_id
+	}
+
+	async fn rename_tag(&self, pub_id: &[u8], name: &str) {
+		let Library { db, sync, .. } = &self.library;
+
+		sync.write_op(
+			db,
+			sync.shared_update(
+				sync::tag::SyncId {
+					pub_id: pub_id.to_vec(),
+				},
+				tag::name::NAME,
+				json!(name),
+			),
+			db.tag().update(
+				tag::pub_id::equals(pub_id.to_vec()),
+				vec![tag::name::set(Some(name.to_string()))],
+			),
+		)
+		.await
+		.unwrap();
+	}
+
+	async fn delete_tag(&self, pub_id: &[u8]) {
+		let Library { db, sync, .. } = &self.library;
+
+		sync.write_op(
+			db,
+			sync.shared_delete(sync::tag::SyncId {
+				pub_id: pub_id.to_vec(),
+			}),
+			db.tag().delete(tag::pub_id::equals(pub_id.to_vec())),
+		)
+		.await
+		.unwrap();
+	}
+
+	async fn create_object(&self) -> Vec<u8> {
+		let Library { db, sync, .. } = &self.library;
+
+		let pub_id = Uuid::new_v4().as_bytes().to_vec();
+
+		sync.write_op(
+			db,
+			sync.shared_create(sync::object::SyncId {
+				pub_id: pub_id.clone(),
+			}),
+			db.object().create(pub_id.clone(), vec![]),
+		)
+		.await
+		.unwrap();
+
+		pub_id
+	}
+
+	async fn set_note(&self, pub_id: &[u8], note: &str) {
+		let Library { db, sync, .. } = &self.library;
+
+		sync.write_op(
+			db,
+			sync.shared_update(
+				sync::object::SyncId {
+					pub_id: pub_id.to_vec(),
+				},
+				object::note::NAME,
+				json!(note),
+			),
+			db.object().update(
+				object::pub_id::equals(pub_id.to_vec()),
+				vec![object::note::set(Some(note.to_string()))],
+			),
+		)
+		.await
+		.unwrap();
+	}
+
+	async fn tag_object(&self, tag_pub_id: &[u8], object_pub_id: &[u8]) {
+		let Library { db, sync, .. } = &self.library;
+
+		sync.write_op(
+			db,
+			sync.relation_create::<tag_on_object::Types>(
+				Uuid::from_slice(object_pub_id).unwrap(),
+				Uuid::from_slice(tag_pub_id).unwrap(),
+			),
+			db.tag_on_object().create(
+				tag::pub_id::equals(tag_pub_id.to_vec()),
+				object::pub_id::equals(object_pub_id.to_vec()),
+				vec![],
+			),
+		)
+		.await
+		.unwrap();
+	}
+}
+
+#[tokio::test]
+async fn edits_on_both_nodes_converge() {
+	let library_id = Uuid::new_v4();
+	let mut a = TestNode::new(library_id).await;
+	let mut b = TestNode::new(library_id).await;
+
+	a.create_location("/Synced").await;
+	a.relay_to(&b).await;
+
+	let tag = a.create_tag("Work").await;
+	a.relay_to(&b).await;
+	b.rename_tag(&tag, "Personal").await;
+	b.relay_to(&a).await;
+
+	let object = b.create_object().await;
+	b.relay_to(&a).await;
+	a.tag_object(&tag, &object).await;
+	a.set_note(&object, "Taken on a").await;
+	a.relay_to(&b).await;
+
+	let state = a.synced_state().await;
+	assert_eq!(state, b.synced_state().await);
+
+	assert_eq!(state.locations.len(), 1);
+	assert_eq!(
+		state.tags,
+		vec![(
+			tag.clone(),
+			Some("Personal".to_string()),
+			Some("blue".to_string())
+		)]
+	);
+	assert_eq!(
+		state.objects,
+		vec![(object.clone(), Some("Taken on a".to_string()))]
+	);
+	assert_eq!(state.tags_on_objects, vec![(tag, object)]);
+
+	// Locations are linked to the node they were created on, not the one they were synced to
+	let location = b
+		.library
+		.db
+		.location()
+		.find_many(vec![])
+		.include(location::include!({ node: select { pub_id } }))
+		.exec()
+		.await
+		.unwrap()
+		.remove(0);
+	assert_eq!(
+		location.node.pub_id,
+		a.library.sync.node().as_bytes().to_vec()
+	);
+	assert_ne!(location.node_id, b.library.node_local_id);
+}
+
+#[tokio::test]
+async fn concurrent_edits_converge() {
+	let library_id = Uuid::new_v4();
+	let mut a = TestNode::new(library_id).await;
+	let mut b = TestNode::new(library_id).await;
+
+	let renamed = a.create_tag("Renamed").await;
+	let deleted = a.create_tag("Deleted").await;
+	a.relay_to(&b).await;
+
+	// Both nodes edit the same tags before hearing of each other
+	a.rename_tag(&renamed, "By a").await;
+	b.rename_tag(&renamed, "By b").await;
+	a.delete_tag(&deleted).await;
+	b.rename_tag(&deleted, "Renamed after being deleted").await;
+
+	a.relay_to(&b).await;
+	b.relay_to(&a).await;
+
+	let state = a.synced_state().await;
+	assert_eq!(state, b.synced_state().await);
+
+	// The last rename wins, and deleted tags stay deleted
+	assert_eq!(state.tags.len(), 1);
+	assert_eq!(state.tags[0].1.as_deref(), Some("By b"));
+}
+
+#[tokio::test]
+async fn catching_up_converges_after_being_offline() {
+	let library_id = Uuid::new_v4();
+	let a = TestNode::new(library_id).await;
+	let b = TestNode::new(library_id).await;
+
+	// More than a page of operations, so catching up takes a few
+	for i in 0..=OPS_PER_PAGE {
+		a.create_tag(&format!("Tag {i}")).await;
+	}
+	b.create_tag("Created offline").await;
+
+	assert_eq!(b.catch_up_with(&a).await, OPS_PER_PAGE as usize + 1);
+	assert_eq!(a.catch_up_with(&b).await, 1);
+
+	let state = a.synced_state().await;
+	assert_eq!(state, b.synced_state().await);
+	assert_eq!(state.tags.len(), OPS_PER_PAGE as usize + 2);
+
+	// Only what's new is sent
+	assert_eq!(b.catch_up_with(&a).await, 0);
+	assert_eq!(a.catch_up_with(&b).await, 0);
+}
+
+#[tokio::test]
+async fn compaction_waits_for_the_other_nodes() {
+	let library_id = Uuid::new_v4();
+	let a = TestNode::new(library_id).await;
+	let mut b = TestNode::new(library_id).await;
+
+	b.create_tag("Created on b").await;
+	b.relay_to(&a).await;
+
+	let tag = a.create_tag("Compacted").await;
+	a.rename_tag(&tag, "Renamed once").await;
+	a.rename_tag(&tag, "Renamed twice").await;
+
+	// b is known, but hasn't said what it has
+	assert_eq!(a.library.sync.compact(Duration::ZERO).await.unwrap(), 0);
+
+	// The clocks sent when catching up are what's acknowledged, so those of the first catch up
+	// don't have a's operations yet
+	b.catch_up_with(&a).await;
+	b.catch_up_with(&a).await;
+
+	// The creation and the renames are folded into one snapshot
+	assert_eq!(a.library.sync.compact(Duration::ZERO).await.unwrap(), 2);
+	assert_eq!(a.synced_state().await, b.synced_state().await);
+}
+
+#[tokio::test]
+async fn libraries_without_other_nodes_are_compacted() {
+	let library_id = Uuid::new_v4();
+	let a = TestNode::new(library_id).await;
+
+	let tag = a.create_tag("Compacted").await;
+	a.rename_tag(&tag, "Renamed once").await;
+	a.rename_tag(&tag, "Renamed twice").await;
+
+	// Only the retention window holds it back
+	assert_eq!(
+		a.library
+			.sync
+			.compact(Duration::from_secs(60 * 60))
+			.await
+			.unwrap(),
+		0
+	);
+	assert_eq!(a.library.sync.compact(Duration::ZERO).await.unwrap(), 2);
+
+	// A node joining later gets the same state from the compacted history
+	let b = TestNode::new(library_id).await;
+	b.catch_up_with(&a).await;
+	assert_eq!(a.synced_state().await, b.synced_state().await);
+}
+
+#[tokio::test]
+async fn relations_ingested_before_their_sides_are_applied_with_them() {
+	let library_id = Uuid::new_v4();
+	let mut a = TestNode::new(library_id).await;
+	let b = TestNode::new(library_id).await;
+
+	let tag = a.create_tag("Work").await;
+	let object = a.create_object().await;
+	a.tag_object(&tag, &object).await;
+
+	let mut created = vec![];
+	while let Ok(msg) = a.created.try_recv() {
+		if let SyncMessage::Created(op) = msg {
+			created.push(op);
+		}
+	}
+
+	// Broadcasts can arrive in any order, here the tagging comes first
+	for op in created.into_iter().rev() {
+		b.library.sync.ingest_op(op).await.unwrap();
+	}
+
+	let state = b.synced_state().await;
+	assert_eq!(state.tags_on_objects, vec![(tag, object)]);
+	assert_eq!(state, a.synced_state().await);
+}
+
+#[tokio::test]
+async fn failed_ingestions_are_rolled_back() {
+	let library_id = Uuid::new_v4();
+	let a = TestNode::new(library_id).await;
+	let b = TestNode::new(library_id).await;
+
+	// Locations can't be created without their paths
+	let op = a.library.sync.unique_shared_create(
+		sync::location::SyncId {
+			pub_id: Uuid::new_v4().as_bytes().to_vec(),
+		},
+		[(location::name::NAME, json!("Without a path"))],
+	);
+	assert!(matches!(
+		b.library.sync.ingest_op(op).await,
+		Err(IngestError::MissingField(_))
+	));
+
+	// Neither the node that sent it nor the operation were saved
+	assert_eq!(b.library.db.node().count(vec![]).exec().await.unwrap(), 1);
+	assert_eq!(
+		b.library
+			.db
+			.shared_operation()
+			.count(vec![])
+			.exec()
+			.await
+			.unwrap(),
+		0
+	);
+
+	// No model is owned, so there's nothing owned operations could be applied to
+	let op = CRDTOperation {
+		node: a.library.sync.node(),
+		timestamp: NTP64(0),
+		id: Uuid::new_v4(),
+		typ: CRDTOperationType::Owned(OwnedOperation {
+			model: "volume".to_string(),
+			items: vec![],
+		}),
+	};
+	assert!(matches!(
+		b.library.sync.ingest_op(op).await,
+		Err(IngestError::InvalidOperation(_))
+	));
+	assert_eq!(
+		b.library
+			.db
+			.owned_operation()
+			.count(vec![])
+			.exec()
+			.await
+			.unwrap(),
+		0
+	);
+}
+
+#[tokio::test]
+async fn libraries_are_loaded_as_the_node_loading_them() {
+	let library_id = Uuid::new_v4();
+	let a = TestNode::new(library_id).await;
+	let node_id = a.library.sync.node();
+
+	let nodes = a.library.db.node().find_many(vec![]).exec().await.unwrap();
+	assert_eq!(nodes.len(), 1);
+	assert_eq!(nodes[0].pub_id, node_id.as_bytes().to_vec());
+	assert_eq!(nodes[0].id, a.library.node_local_id);
+
+	a.create_location("/Synced").await;
+
+	// The node is found by its id when the library is loaded again, not created once more
+	let a = a.reload().await;
+	assert_eq!(a.library.sync.node(), node_id);
+	assert_eq!(a.library.db.node().count(vec![]).exec().await.unwrap(), 1);
+	assert_eq!(
+		a.library
+			.db
+			.location()
+			.count(vec![location::node_id::equals(a.library.node_local_id)])
+			.exec()
+			.await
+			.unwrap(),
+		1
+	);
+}
+
+#[tokio::test]
+async fn libraries_synced_as_themselves_are_moved_to_the_node() {
+	let library_id = Uuid::new_v4();
+	let data_dir = tempdir().unwrap();
+
+	// Before nodes synced as themselves, what was local to a node was linked to the library's id
+	let legacy_location = {
+		let db = load_and_migrate(&format!("file:{}", library_db_path(&data_dir).display()))
+			.await
+			.unwrap();
+
+		let legacy = db
+			.node()
+			.create(library_id.as_bytes().to_vec(), "Legacy".to_string(), vec![])
+			.exec()
+			.await
+			.unwrap();
+
+		db.location()
+			.create(
+				Uuid::new_v4().as_bytes().to_vec(),
+				"Legacy".to_string(),
+				"/Legacy".to_string(),
+				node::id::equals(legacy.id),
+				vec![],
+			)
+			.exec()
+			.await
+			.unwrap()
+	};
+
+	let mut a = TestNode::load(library_id, data_dir).await;
+	assert_ne!(a.library.sync.node(), library_id);
+
+	let location = a
+		.library
+		.db
+		.location()
+		.find_unique(location::id::equals(legacy_location.id))
+		.exec()
+		.await
+		.unwrap()
+		.unwrap();
+	assert_eq!(location.node_id, a.library.node_local_id);
+	assert_ne!(location.node_id, legacy_location.node_id);
+
+	// What's created from now on is synced as the node, and other nodes link it to it
+	let b = TestNode::new(library_id).await;
+	a.create_location("/Synced").await;
+	a.relay_to(&b).await;
+
+	let synced = b
+		.library
+		.db
+		.location()
+		.find_first(vec![location::path::equals("/Synced".to_string())])
+		.include(location::include!({ node: select { pub_id } }))
+		.exec()
+		.await
+		.unwrap()
+		.unwrap();
+	assert_eq!(
+		synced.node.pub_id,
+		a.library.sync.node().as_bytes().to_vec()
+	);
+}
+
+#[tokio::test]
+async fn operations_logged_as_the_library_are_caught_up_with() {
+	let library_id = Uuid::new_v4();
+	let data_dir = tempdir().unwrap();
+
+	// Before nodes synced as themselves, their operations were logged as the library's
+	let tag = {
+		let db = Arc::new(
+			load_and_migrate(&format!("file:{}", library_db_path(&data_dir).display()))
+				.await
+				.unwrap(),
+		);
+
+		db.node()
+			.create(library_id.as_bytes().to_vec(), "Legacy".to_string(), vec![])
+			.exec()
+			.await
+			.unwrap();
+
+		let (sync, _) = SyncManager::new(&db, library_id, library_id);
+		let pub_id = Uuid::new_v4().as_bytes().to_vec();
+
+		sync.write_op(
+			&db,
+			sync.unique_shared_create(
+				sync::tag::SyncId {
+					pub_id: pub_id.clone(),
+				},
+				[(tag::name::NAME, json!("Legacy"))],
+			),
+			db.tag().create(
+				pub_id.clone(),
+				vec![tag::name::set(Some("Legacy".to_string()))],
+			),
+		)
+		.await
+		.unwrap();
+
+		pub_id
+	};
+
+	let a = TestNode::load(library_id, data_dir).await;
+	let b = TestNode::new(library_id).await;
+
+	assert_eq!(b.catch_up_with(&a).await, 1);
+
+	let state = b.synced_state().await;
+	assert_eq!(state, a.synced_state().await);
+	assert_eq!(state.tags, vec![(tag, Some("Legacy".to_string()), None)]);
+}
@@ -90,3 +90,77 @@ pub fn chain_optional_iter<T>(
 pub fn uuid_to_bytes(uuid: Uuid) -> Vec<u8> {
 	uuid.as_bytes().to_vec()
 }
+
+/// SQLite only has signed 64 bit integers, so unsigned identifiers (inodes, devices) are stored
+/// with their bits reinterpreted as signed. This is lossless, but values above `i64::MAX` become
+/// negative, so it's only meant for values that are compared for equality
+pub fn u64_to_db_int(value: u64) -> i64 {
+	i64::from_ne_bytes(value.to_ne_bytes())
+}
+
+/// Sizes are filtered by range, ordered and summed, so instead of being reinterpreted like
+/// [`u64_to_db_int`] does, the ones that don't fit a signed integer are saturated to `i64::MAX`
+pub fn size_to_db_int(size: u64) -> i64 {
+	i64::try_from(size).unwrap_or(i64::MAX)
+}
+
+/// Reads back sizes written by [`size_to_db_int`], taking the negative ones that drifting
+/// statistics can reach as empty
+pub fn db_int_to_size(value: i64) -> u64 {
+	u64::try_from(value).unwrap_or(0)
+}
+
+/// Reverts the conversion made by [`u64_to_db_int`]
+pub fn db_int_to_u64(value: i64) -> u64 {
+	u64::from_ne_bytes(value.to_ne_bytes())
+}
+
+/// A library database in a temporary directory, migrated to the latest schema, for tests
+#[cfg(test)]
+pub(crate) async fn test_db() -> (PrismaClient, tempfile::TempDir) {
+	let data_dir = tempfile::tempdir().expect("failed to create a temporary directory");
+	let db = load_and_migrate(&format!(
+		"file:{}",
+		data_dir.path().join("library.db").display()
+	))
+	.await
+	.expect("failed to create the test database");
+
+	(db, data_dir)
+}
+
+/// Creates a location at `path` of the first node, or of a new one if there's none yet, for tests
+/// that need file paths in the database
+#[cfg(test)]
+pub(crate) async fn create_test_location(
+	db: &PrismaClient,
+	path: impl AsRef<std::path::Path>,
+) -> prisma::location::Data {
+	let node = match db
+		.node()
+		.find_first(vec![])
+		.exec()
+		.await
+		.expect("failed to find the test node")
+	{
+		Some(node) => node,
+		None => db
+			.node()
+			.create(uuid_to_bytes(Uuid::new_v4()), "Test".to_string(), vec![])
+			.exec()
+			.await
+			.expect("failed to create the test node"),
+	};
+
+	db.location()
+		.create(
+			uuid_to_bytes(Uuid::new_v4()),
+			"Test".to_string(),
+			path.as_ref().to_string_lossy().to_string(),
+			prisma::node::id::equals(node.id),
+			vec![],
+		)
+		.exec()
+		.await
+		.expect("failed to create the test location")
+}
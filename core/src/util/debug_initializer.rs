@@ -114,6 +114,7 @@ impl InitConfig {
 							LibraryConfig {
 								name: lib.name,
 								description: lib.description.unwrap_or("".to_string()),
+								..Default::default()
 							},
 						)
 						.await?;
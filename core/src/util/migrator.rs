@@ -114,6 +114,8 @@ pub enum MigratorError {
 	InvalidType(&'static str),
 	#[error("custom migration error: {0}")]
 	Custom(String),
+	#[error("database error while migrating: {0}")]
+	Database(#[from] prisma_client_rust::QueryError),
 }
 
 #[cfg(test)]
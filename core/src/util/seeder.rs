@@ -1,5 +1,5 @@
 use crate::{
-	location::indexer::rules::{IndexerRule, IndexerRuleError, RulePerKind},
+	location::indexer::rules::{IndexerRule, IndexerRuleError, RulePerKind, SD_IGNORE_FILE_NAME},
 	prisma::PrismaClient,
 };
 use thiserror::Error;
@@ -129,6 +129,14 @@ pub async fn indexer_rules_seeder(client: &PrismaClient) -> Result<(), SeederErr
 					[".git".to_string()].into_iter().collect(),
 				)],
 			),
+			IndexerRule::new(
+				"Respect Ignore Files".to_string(),
+				false,
+				vec![RulePerKind::RejectByIgnoreFiles(vec![
+					".gitignore".to_string(),
+					SD_IGNORE_FILE_NAME.to_string(),
+				])],
+			),
 			IndexerRule::new(
 				"Only Images".to_string(),
 				false,
@@ -1,14 +1,35 @@
 use crate::{
-	library::Library,
-	prisma::volume::{self, *},
+	api::CoreEvent,
+	invalidate_query,
+	library::{Library, LibraryManager},
+	location::LocationId,
+	prisma::{
+		location,
+		volume::{self, *},
+		PrismaClient,
+	},
 };
 
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use specta::Type;
-use std::{fmt::Display, process::Command};
+use std::{
+	collections::{BTreeSet, HashMap},
+	fmt::Display,
+	path::{Path, PathBuf},
+	process::Command,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
 use sysinfo::{DiskExt, System, SystemExt};
 use thiserror::Error;
+use tokio::{
+	sync::broadcast,
+	task,
+	time::{interval, MissedTickBehavior},
+};
+use tracing::{debug, error};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Type)]
 #[allow(clippy::upper_case_acronyms)]
@@ -33,6 +54,9 @@ impl Display for DiskType {
 pub struct Volume {
 	pub name: String,
 	pub mount_point: String,
+	/// The filesystem UUID, which identifies the volume wherever it's mounted
+	pub uuid: Option<String>,
+	pub label: Option<String>,
 	#[specta(type = String)]
 	#[serde_as(as = "DisplayFromStr")]
 	pub total_capacity: u64,
@@ -45,6 +69,59 @@ pub struct Volume {
 	pub is_root_filesystem: bool,
 }
 
+/// Filesystems that don't update a directory's modification time reliably when its entries change,
+/// like FAT ones, or where it depends on the server, like network ones
+const UNRELIABLE_DIR_MTIME_FILE_SYSTEMS: [&str; 16] = [
+	"vfat",
+	"msdos",
+	"fat",
+	"fat16",
+	"fat32",
+	"exfat",
+	"fuseblk",
+	"nfs",
+	"nfs4",
+	"cifs",
+	"smbfs",
+	"smb3",
+	"afpfs",
+	"webdav",
+	"9p",
+	"fuse.sshfs",
+];
+
+/// Filesystems whose changes are made by other machines, or in userspace, which native file system
+/// watchers don't see, so their locations have to be polled for changes
+const NETWORK_FILE_SYSTEMS: [&str; 13] = [
+	"nfs",
+	"nfs4",
+	"cifs",
+	"smbfs",
+	"smb3",
+	"afpfs",
+	"webdav",
+	"9p",
+	"fuse.sshfs",
+	"fuse.rclone",
+	"fuse.s3fs",
+	"fuse.glusterfs",
+	"ceph",
+];
+
+/// Sent when the volumes of this node are mounted or unmounted
+#[derive(Serialize, Debug, Clone, Type)]
+#[serde(tag = "type")]
+pub enum VolumeEvent {
+	Mounted { volume: Volume },
+	Unmounted { mount_point: String },
+}
+
+/// How often the mount points are checked for volumes being mounted or unmounted
+const VOLUME_MONITOR_INTERVAL: Duration = Duration::from_secs(5);
+/// How often the volumes are saved even if no mount point changed, to keep their capacities,
+/// which the library statistics are computed from, up to date
+const VOLUME_CAPACITY_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Error, Debug)]
 pub enum VolumeError {
 	#[error("Database error: {0}")]
@@ -59,45 +136,323 @@ impl From<VolumeError> for rspc::Error {
 	}
 }
 
-pub async fn save_volume(library: &Library) -> Result<(), VolumeError> {
-	let volumes = get_volumes()?;
+/// Saves the `volumes` mounted on this node, found by their UUIDs wherever they're mounted now, and
+/// links the locations to the volumes they're in. Volumes that aren't mounted anymore are deleted,
+/// unless they hold locations, so we still know in which drive an offline location is
+pub async fn save_library_volumes(
+	library: &Library,
+	volumes: Vec<Volume>,
+) -> Result<(), VolumeError> {
+	let db = &library.db;
+
+	save_volumes(db, library.node_local_id, volumes).await?;
+
+	for location in db
+		.location()
+		.find_many(vec![location::node_id::equals(library.node_local_id)])
+		.select(location::select!({ id path }))
+		.exec()
+		.await?
+	{
+		link_location_to_volume(library, location.id, &location.path).await?;
+	}
+
+	invalidate_query!(library, "volumes.list");
+
+	Ok(())
+}
+
+/// Saves the `volumes` mounted on the node, marking the ones that aren't mounted anymore
+async fn save_volumes(
+	db: &PrismaClient,
+	node_id: i32,
+	volumes: Vec<Volume>,
+) -> Result<(), VolumeError> {
+	let mut mounted_ids = Vec::with_capacity(volumes.len());
 
-	// enter all volumes associate with this client add to db
 	for volume in volumes {
 		let params = vec![
+			uuid::set(volume.uuid.clone()),
+			label::set(volume.label.clone()),
 			disk_type::set(volume.disk_type.map(|t| t.to_string())),
 			filesystem::set(volume.file_system.clone()),
 			total_bytes_capacity::set(volume.total_capacity.to_string()),
 			total_bytes_available::set(volume.available_capacity.to_string()),
+			is_mounted::set(true),
 		];
 
-		library
-			.db
-			.volume()
-			.upsert(
-				node_id_mount_point_name(
-					library.node_local_id,
-					volume.mount_point.to_string(),
-					volume.name.to_string(),
-				),
-				volume::create(
-					library.node_local_id,
-					volume.name,
-					volume.mount_point,
-					params.clone(),
-				),
-				params,
-			)
-			.exec()
-			.await?;
+		let saved = if let Some(uuid) = &volume.uuid {
+			// Whatever was mounted here before isn't anymore, it's handled at the end along with
+			// the rest of the volumes that aren't mounted
+			let known = db
+				.volume()
+				.find_unique(node_id_uuid(node_id, uuid.clone()))
+				.exec()
+				.await?;
+
+			if let Some(known) = known {
+				db.volume()
+					.update(
+						id::equals(known.id),
+						params
+							.into_iter()
+							.chain([name::set(volume.name), mount_point::set(volume.mount_point)])
+							.collect(),
+					)
+					.exec()
+					.await?
+			} else {
+				db.volume()
+					.create(node_id, volume.name, volume.mount_point, params)
+					.exec()
+					.await?
+			}
+		} else {
+			// Without a UUID, a volume is only known by where it's mounted, as long as it isn't one
+			// with a UUID that was mounted there before
+			let known = db
+				.volume()
+				.find_first(vec![
+					node_id::equals(node_id),
+					mount_point::equals(volume.mount_point.clone()),
+					name::equals(volume.name.clone()),
+					uuid::equals(None),
+				])
+				.exec()
+				.await?;
+
+			if let Some(known) = known {
+				db.volume()
+					.update(id::equals(known.id), params)
+					.exec()
+					.await?
+			} else {
+				db.volume()
+					.create(node_id, volume.name, volume.mount_point, params)
+					.exec()
+					.await?
+			}
+		};
+
+		mounted_ids.push(saved.id);
 	}
-	// cleanup: remove all unmodified volumes associate with this client
+
+	let not_mounted = vec![node_id::equals(node_id), id::not_in_vec(mounted_ids)];
+
+	db._batch((
+		db.volume().delete_many(
+			not_mounted
+				.clone()
+				.into_iter()
+				.chain([locations::none(vec![])])
+				.collect(),
+		),
+		db.volume()
+			.update_many(not_mounted, vec![is_mounted::set(false)]),
+	))
+	.await?;
+
+	Ok(())
+}
+
+/// Links a location to the mounted volume it's in, the one with the longest mount point containing
+/// it. Locations in unmounted volumes keep the volume they were in
+pub async fn link_location_to_volume(
+	library: &Library,
+	location_id: LocationId,
+	location_path: impl AsRef<Path>,
+) -> Result<(), VolumeError> {
+	let location_path = location_path.as_ref();
+
+	let Some(volume) = library
+		.db
+		.volume()
+		.find_many(vec![
+			node_id::equals(library.node_local_id),
+			is_mounted::equals(true),
+		])
+		.exec()
+		.await?
+		.into_iter()
+		.filter(|volume| location_path.starts_with(&volume.mount_point))
+		.max_by_key(|volume| volume.mount_point.len())
+	else {
+		return Ok(());
+	};
+
+	library
+		.db
+		.location()
+		.update_many(
+			vec![location::id::equals(location_id)],
+			vec![location::volume_id::set(Some(volume.id))],
+		)
+		.exec()
+		.await?;
 
 	Ok(())
 }
 
+/// Watches the mount points of this node, saving the volumes of every library when they change or
+/// every `VOLUME_CAPACITY_REFRESH_INTERVAL`, and telling the UI when volumes are mounted or unmounted
+pub async fn monitor_volumes(
+	library_manager: Arc<LibraryManager>,
+	event_bus_tx: broadcast::Sender<CoreEvent>,
+) {
+	let mut check_interval = interval(VOLUME_MONITOR_INTERVAL);
+	check_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+	// Volumes mounted before we started aren't news, we just save them
+	let mut mount_points = None;
+	let mut saved_at = Instant::now();
+
+	loop {
+		check_interval.tick().await;
+
+		let current = match task::spawn_blocking(get_mount_points).await {
+			Ok(current) => current,
+			Err(e) => {
+				error!("Failed to run the task getting the mount points: {e:#?}");
+				continue;
+			}
+		};
+
+		if mount_points.as_ref() == Some(&current)
+			&& saved_at.elapsed() < VOLUME_CAPACITY_REFRESH_INTERVAL
+		{
+			continue;
+		}
+
+		// Listing the volumes with their capacities scans every disk, so it's done once per tick
+		// for all libraries, and away from the async runtime
+		let volumes = match task::spawn_blocking(get_volumes).await {
+			Ok(Ok(volumes)) => volumes,
+			Ok(Err(e)) => {
+				error!("Failed to get volumes: {e:#?}");
+				continue;
+			}
+			Err(e) => {
+				error!("Failed to run the task getting the volumes: {e:#?}");
+				continue;
+			}
+		};
+
+		if let Some(previous) = mount_points
+			.replace(current.clone())
+			.filter(|previous| previous != &current)
+		{
+			for volume in &volumes {
+				if !previous.contains(Path::new(&volume.mount_point)) {
+					debug!("Volume mounted: {}", volume.mount_point);
+					event_bus_tx
+						.send(CoreEvent::Volume(VolumeEvent::Mounted {
+							volume: volume.clone(),
+						}))
+						.ok();
+				}
+			}
+
+			for mount_point in previous.difference(&current) {
+				debug!("Volume unmounted: {}", mount_point.display());
+				event_bus_tx
+					.send(CoreEvent::Volume(VolumeEvent::Unmounted {
+						mount_point: mount_point.to_string_lossy().to_string(),
+					}))
+					.ok();
+			}
+		}
+
+		for library in library_manager.get_all_libraries().await {
+			if let Err(e) = save_library_volumes(&library, volumes.clone()).await {
+				error!(
+					"Failed to save volumes of library: <id='{}', error='{e:#?}'>",
+					library.id
+				);
+			}
+		}
+
+		saved_at = Instant::now();
+	}
+}
+
+/// If the filesystem where `path` is updates directories' modification times when their entries
+/// change, so the indexer can skip directories that didn't change. Unknown filesystems aren't trusted
+pub fn directory_mtimes_are_reliable(path: impl AsRef<Path>) -> bool {
+	file_system_of(path).map_or(false, |file_system| {
+		!UNRELIABLE_DIR_MTIME_FILE_SYSTEMS
+			.iter()
+			.any(|unreliable| file_system.eq_ignore_ascii_case(unreliable))
+	})
+}
+
+/// If `path` is in a network or FUSE filesystem, where native watchers miss changes
+pub fn is_network_file_system(path: impl AsRef<Path>) -> bool {
+	file_system_of(path).map_or(false, |file_system| {
+		NETWORK_FILE_SYSTEMS
+			.iter()
+			.any(|network| file_system.eq_ignore_ascii_case(network))
+	})
+}
+
+/// The disks of this node, shared so looking up the filesystem of a path doesn't list them all again
+/// every time a watcher or an indexer job is created
+static DISKS: Lazy<Mutex<CachedDisks>> = Lazy::new(|| {
+	Mutex::new(CachedDisks {
+		system: System::new(),
+		refreshed_at: None,
+	})
+});
+
+struct CachedDisks {
+	system: System,
+	refreshed_at: Option<Instant>,
+}
+
+/// Runs `f` with the disks of this node, listing them again if they're older than `max_age`
+fn with_disks<T>(max_age: Duration, f: impl FnOnce(&[sysinfo::Disk]) -> T) -> T {
+	let mut disks = DISKS.lock().unwrap_or_else(|e| e.into_inner());
+
+	if disks
+		.refreshed_at
+		.map_or(true, |refreshed_at| refreshed_at.elapsed() >= max_age)
+	{
+		disks.system.refresh_disks_list();
+		disks.refreshed_at = Some(Instant::now());
+	}
+
+	f(disks.system.disks())
+}
+
+/// The filesystem of the volume where `path` is, the one with the longest mount point containing it.
+/// The disks are listed again at most once every `VOLUME_MONITOR_INTERVAL`
+fn file_system_of(path: impl AsRef<Path>) -> Option<String> {
+	let path = path.as_ref();
+
+	with_disks(VOLUME_MONITOR_INTERVAL, |disks| {
+		disks
+			.iter()
+			.filter(|disk| path.starts_with(disk.mount_point()))
+			.max_by_key(|disk| disk.mount_point().as_os_str().len())
+			.map(|disk| String::from_utf8_lossy(disk.file_system()).to_string())
+	})
+}
+
+/// Where the volumes of this node are currently mounted, a lot cheaper than [`get_volumes`] as it
+/// doesn't look into their capacities. It lists the disks, so it blocks
+pub fn get_mount_points() -> BTreeSet<PathBuf> {
+	with_disks(Duration::ZERO, |disks| {
+		disks
+			.iter()
+			.map(|disk| disk.mount_point().to_path_buf())
+			.collect()
+	})
+}
+
 // TODO: Error handling in this function
 pub fn get_volumes() -> Result<Vec<Volume>, VolumeError> {
+	let uuids = disk_identifiers("by-uuid");
+	let labels = disk_identifiers("by-label");
+
 	System::new_all()
 		.disks()
 		.iter()
@@ -108,6 +463,14 @@ pub fn get_volumes() -> Result<Vec<Volume>, VolumeError> {
 			let name = disk.name().to_str().unwrap_or("Volume").to_string();
 			let is_removable = disk.is_removable();
 
+			let device = std::fs::canonicalize(disk.name()).ok();
+			let uuid = device
+				.as_ref()
+				.and_then(|device| uuids.get(device).cloned());
+			let label = device
+				.as_ref()
+				.and_then(|device| labels.get(device).cloned());
+
 			let file_system = String::from_utf8(disk.file_system().to_vec())
 				.unwrap_or_else(|_| "Err".to_string());
 
@@ -140,6 +503,8 @@ pub fn get_volumes() -> Result<Vec<Volume>, VolumeError> {
 				name,
 				is_root_filesystem: mount_point == "/",
 				mount_point,
+				uuid,
+				label,
 				total_capacity,
 				available_capacity,
 				is_removable,
@@ -150,6 +515,57 @@ pub fn get_volumes() -> Result<Vec<Volume>, VolumeError> {
 		.collect::<Result<Vec<_>, _>>()
 }
 
+/// The filesystem UUIDs or labels of the block devices, by their device paths, from the symlinks
+/// udev keeps in `/dev/disk/<kind>`. We don't know them on other systems
+fn disk_identifiers(kind: &str) -> HashMap<PathBuf, String> {
+	#[cfg(target_os = "linux")]
+	{
+		std::fs::read_dir(Path::new("/dev/disk").join(kind))
+			.map(|entries| {
+				entries
+					.filter_map(Result::ok)
+					.filter_map(|entry| {
+						let device = std::fs::canonicalize(entry.path()).ok()?;
+						Some((device, unescape_udev(&entry.file_name().to_string_lossy())))
+					})
+					.collect()
+			})
+			.unwrap_or_default()
+	}
+
+	#[cfg(not(target_os = "linux"))]
+	{
+		let _ = kind; // To avoid unused variable warning
+		HashMap::new()
+	}
+}
+
+/// udev escapes the characters that can't be in file names, like `/` and spaces in labels, as `\xHH`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn unescape_udev(name: &str) -> String {
+	let mut bytes = Vec::with_capacity(name.len());
+	let mut rest = name.as_bytes();
+
+	while let Some((&byte, tail)) = rest.split_first() {
+		if byte == b'\\' && tail.first() == Some(&b'x') {
+			if let Some(decoded) = tail
+				.get(1..3)
+				.and_then(|hex| std::str::from_utf8(hex).ok())
+				.and_then(|hex| u8::from_str_radix(hex, 16).ok())
+			{
+				bytes.push(decoded);
+				rest = &tail[3..];
+				continue;
+			}
+		}
+
+		bytes.push(byte);
+		rest = tail;
+	}
+
+	String::from_utf8_lossy(&bytes).to_string()
+}
+
 // #[test]
 // fn test_get_volumes() {
 //   let volumes = get_volumes()?;
@@ -158,3 +574,113 @@ pub fn get_volumes() -> Result<Vec<Volume>, VolumeError> {
 // }
 
 // Adapted from: https://github.com/kimlimjustin/xplorer/blob/f4f3590d06783d64949766cc2975205a3b689a56/src-tauri/src/drives.rs
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::{
+		prisma::SortOrder,
+		util::db::{create_test_location, test_db},
+	};
+
+	#[test]
+	fn udev_escapes_are_decoded() {
+		assert_eq!(unescape_udev(r"My\x20Drive"), "My Drive");
+		assert_eq!(unescape_udev(r"a\x2fb"), "a/b");
+		assert_eq!(unescape_udev("2C4A-1B3F"), "2C4A-1B3F");
+		// Not an escape, so kept as is
+		assert_eq!(unescape_udev(r"back\slash\x"), r"back\slash\x");
+	}
+
+	fn test_volume(name: &str, mount_point: &str, uuid: Option<&str>) -> Volume {
+		Volume {
+			name: name.to_string(),
+			mount_point: mount_point.to_string(),
+			uuid: uuid.map(str::to_string),
+			label: None,
+			total_capacity: 100,
+			available_capacity: 50,
+			is_removable: true,
+			disk_type: None,
+			file_system: None,
+			is_root_filesystem: false,
+		}
+	}
+
+	#[tokio::test]
+	async fn drives_replaced_at_a_mount_point_are_kept_while_holding_locations() {
+		let (db, _data_dir) = test_db().await;
+		let location = create_test_location(&db, "/mnt/usb/photos").await;
+		let node_id = location.node_id;
+
+		save_volumes(
+			&db,
+			node_id,
+			vec![
+				test_volume("/dev/sdb1", "/mnt/usb", Some("1111-AAAA")),
+				test_volume("/dev/sdc1", "/mnt/other", Some("2222-BBBB")),
+			],
+		)
+		.await
+		.unwrap();
+
+		let with_location = db
+			.volume()
+			.find_unique(node_id_uuid(node_id, "1111-AAAA".to_string()))
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+
+		db.location()
+			.update(
+				location::id::equals(location.id),
+				vec![location::volume_id::set(Some(with_location.id))],
+			)
+			.exec()
+			.await
+			.unwrap();
+
+		// Other drives are mounted where both were, with the same device names
+		save_volumes(
+			&db,
+			node_id,
+			vec![
+				test_volume("/dev/sdb1", "/mnt/usb", Some("3333-CCCC")),
+				test_volume("/dev/sdc1", "/mnt/other", None),
+			],
+		)
+		.await
+		.unwrap();
+
+		let volumes = db
+			.volume()
+			.find_many(vec![node_id::equals(node_id)])
+			.order_by(id::order(SortOrder::Asc))
+			.exec()
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|volume| (volume.uuid, volume.mount_point, volume.is_mounted))
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			volumes,
+			vec![
+				(Some("1111-AAAA".to_string()), "/mnt/usb".to_string(), false),
+				(Some("3333-CCCC".to_string()), "/mnt/usb".to_string(), true),
+				(None, "/mnt/other".to_string(), true),
+			]
+		);
+
+		let location = db
+			.location()
+			.find_unique(location::id::equals(location.id))
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(location.volume_id, Some(with_location.id));
+	}
+}
@@ -35,29 +35,38 @@ enum ModelSyncType<'a> {
 
 impl<'a> ModelSyncType<'a> {
 	fn from_attribute(attr: Attribute, model: ModelWalker<'a>) -> Option<Self> {
-		let id = attr
-			.field("id")
-			.map(|field| match field {
-				AttributeFieldValue::Single(s) => vec![*s],
-				AttributeFieldValue::List(l) => l.clone(),
-			})
-			.unwrap_or_else(|| {
-				model
-					.primary_key()
-					.as_ref()
-					.unwrap()
-					.fields()
-					.map(|f| f.name())
-					.collect()
-			})
-			.into_iter()
-			.flat_map(|name| model.fields().find(|f| f.name() == name))
-			.collect();
+		let fields = |name: &str| {
+			attr.field(name)
+				.map(|field| match field {
+					AttributeFieldValue::Single(s) => vec![*s],
+					AttributeFieldValue::List(l) => l.clone(),
+				})
+				.map(|names| {
+					names
+						.into_iter()
+						.flat_map(|name| model.fields().find(|f| f.name() == name))
+						.collect::<Vec<_>>()
+				})
+		};
+
+		let id = fields("id").unwrap_or_else(|| {
+			model
+				.primary_key()
+				.as_ref()
+				.unwrap()
+				.fields()
+				.flat_map(|f| model.fields().find(|field| field.name() == f.name()))
+				.collect()
+		});
 
 		Some(match attr.name {
 			"local" => Self::Local { id },
 			"owned" => Self::Owned { id },
 			"shared" => Self::Shared { id },
+			"relation" => Self::Relation {
+				item: fields("item")?,
+				group: fields("group")?,
+			},
 			_ => return None,
 		})
 	}
@@ -67,7 +76,7 @@ impl<'a> ModelSyncType<'a> {
 			Self::Owned { id } => id.clone(),
 			Self::Local { id } => id.clone(),
 			Self::Shared { id } => id.clone(),
-			_ => vec![],
+			Self::Relation { item, group } => item.iter().chain(group).cloned().collect(),
 		}
 	}
 }
@@ -152,7 +161,7 @@ impl PrismaGenerator for SDSyncGenerator {
                     match field.refine() {
                         RefinedFieldWalker::Scalar(scalar_field) => {
                        		(!scalar_field.is_in_required_relation()).then(|| quote! {
-                                #model_name_snake::#field_name_snake::set(::serde_json::from_value(val).unwrap()),
+                                #model_name_snake::#field_name_snake::set(::serde_json::from_value(val).ok()?),
                             })
                         },
                         RefinedFieldWalker::Relation(relation_field) => {
@@ -162,11 +171,11 @@ impl PrismaGenerator for SDSyncGenerator {
                                 Some(i)  => {
                                     if i.count() == 1 {
                                         Some(quote! {{
-                                            let val: std::collections::HashMap<String, ::serde_json::Value> = ::serde_json::from_value(val).unwrap();
-                                            let val = val.into_iter().next().unwrap();
+                                            let val: std::collections::HashMap<String, ::serde_json::Value> = ::serde_json::from_value(val).ok()?;
+                                            let val = val.into_iter().next()?;
 
                                             #model_name_snake::#field_name_snake::connect(
-                                                #relation_model_name_snake::UniqueWhereParam::deserialize(&val.0, val.1).unwrap()
+                                                #relation_model_name_snake::UniqueWhereParam::deserialize(&val.0, val.1)?
                                             )
                                         }})
                                     } else { None }
@@ -201,7 +210,7 @@ impl PrismaGenerator for SDSyncGenerator {
 
                             Some(quote!(#model_name_snake::#field_name_snake::NAME =>
                                 #model_name_snake::#field_name_snake::equals(
-                                    ::serde_json::from_value(val).unwrap()
+                                    ::serde_json::from_value(val).ok()?
                                 ),
                             ))
                         }
@@ -245,19 +254,21 @@ impl PrismaGenerator for SDSyncGenerator {
 					let model_name_pascal = pascal_ident(model.name());
 
 					sync_type.and_then(|a| {
-						let data_type = match a {
-							ModelSyncType::Owned { .. } => quote!(OwnedOperationData),
-							ModelSyncType::Shared { .. } => quote!(SharedOperationData),
-							ModelSyncType::Relation { .. } => {
-								quote!(RelationOperationData)
-							}
+						let variant = match a {
+							// Owned operations carry many items, each with their own id
+							ModelSyncType::Owned { .. } => quote! {
+								#model_name_pascal(Vec<(#model_name_snake::SyncId, sd_sync::OwnedOperationData)>)
+							},
+							ModelSyncType::Shared { .. } => quote! {
+								#model_name_pascal(#model_name_snake::SyncId, sd_sync::SharedOperationData)
+							},
+							// Relations are identified by the pub ids of their item and group
+							ModelSyncType::Relation { .. } => quote! {
+								#model_name_pascal(sd_sync::RelationOperation)
+							},
 							_ => return None,
 						};
 
-						let variant = quote! {
-							#model_name_pascal(#model_name_snake::SyncId, sd_sync::#data_type)
-						};
-
 						let op_type_enum = quote!(sd_sync::CRDTOperationType);
 
 						let cond = quote!(if op.model == prisma::#model_name_snake::NAME);
@@ -266,7 +277,19 @@ impl PrismaGenerator for SDSyncGenerator {
 							ModelSyncType::Owned { .. } => {
 								quote! {
 									#op_type_enum::Owned(op) #cond =>
-										Self::#model_name_pascal(serde_json::from_value(op.record_id).ok()?, op.data)
+										Self::#model_name_pascal(
+											op.items
+												.into_iter()
+												.flat_map(|item| match item.data {
+													sd_sync::OwnedOperationData::CreateMany { values, .. } => values
+														.into_iter()
+														.map(|(id, data)| (id, sd_sync::OwnedOperationData::Create(data)))
+														.collect(),
+													data => vec![(item.id, data)],
+												})
+												.map(|(id, data)| Some((serde_json::from_value(id).ok()?, data)))
+												.collect::<Option<_>>()?
+										)
 								}
 							}
 							ModelSyncType::Shared { .. } => {
@@ -275,12 +298,12 @@ impl PrismaGenerator for SDSyncGenerator {
 										Self::#model_name_pascal(serde_json::from_value(op.record_id).ok()?, op.data)
 								}
 							}
-							// ModelSyncType::Relation { .. } => {
-							// 	quote! {
-							// 		(#model_name_str, sd_sync::CRDTOperation::Relation(op)) =>
-							// 			Self::#model_name_pascal()
-							// 	}
-							// }
+							ModelSyncType::Relation { .. } => {
+								quote! {
+									#op_type_enum::Relation(op) if op.relation == prisma::#model_name_snake::NAME =>
+										Self::#model_name_pascal(op)
+								}
+							}
 							_ => return None,
 						};
 
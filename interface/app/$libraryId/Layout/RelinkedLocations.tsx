@@ -0,0 +1,19 @@
+import { useLibrarySubscription } from '@sd/client';
+import { useToasts } from '~/hooks/useToasts';
+
+// The core relinks offline locations on its own when their drive is mounted somewhere else
+export default () => {
+	const { addToast } = useToasts();
+
+	useLibrarySubscription(['locations.relinked'], {
+		onData: ({ old_path, new_path }) => {
+			addToast({
+				title: 'Location relinked',
+				subtitle: `Its drive moved from ${old_path} to ${new_path}`,
+				duration: 5000
+			});
+		}
+	});
+
+	return null;
+};
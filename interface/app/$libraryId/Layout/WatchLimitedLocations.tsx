@@ -0,0 +1,22 @@
+import { useLibrarySubscription } from '@sd/client';
+import { useToasts } from '~/hooks/useToasts';
+
+// Locations with more directories than the system lets us watch are partly polled instead
+export default () => {
+	const { addToast } = useToasts();
+
+	useLibrarySubscription(['locations.watchLimited'], {
+		onData: ({ max_user_watches }) => {
+			addToast({
+				title: 'Location is too big to be watched',
+				subtitle:
+					max_user_watches !== null
+						? `Its changes may take a while to show up. Raise fs.inotify.max_user_watches (${max_user_watches}) to watch all of it`
+						: 'Its changes may take a while to show up',
+				duration: 10000
+			});
+		}
+	});
+
+	return null;
+};
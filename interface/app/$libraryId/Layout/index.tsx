@@ -12,8 +12,10 @@ import {
 import { useOperatingSystem, useZodRouteParams } from '~/hooks';
 import { usePlatform } from '~/util/Platform';
 import { QuickPreview } from '../Explorer/QuickPreview';
+import RelinkedLocations from './RelinkedLocations';
 import Sidebar from './Sidebar';
 import Toasts from './Toasts';
+import WatchLimitedLocations from './WatchLimitedLocations';
 
 const Layout = () => {
 	const { libraries, library } = useClientContext();
@@ -55,6 +57,8 @@ const Layout = () => {
 							<Outlet />
 						</Suspense>
 						<QuickPreview />
+						<RelinkedLocations />
+						<WatchLimitedLocations />
 					</LibraryContextProvider>
 				) : (
 					<h1 className="p-4 text-white">
@@ -25,7 +25,8 @@ export const Component = () => {
 		editLibrary.mutate({
 			id: library.uuid,
 			name: value.name ?? null,
-			description: value.description ?? null
+			description: value.description ?? null,
+			sync_retention_days: null
 		})
 	);
 
@@ -14,10 +14,15 @@ import IndexerRuleEditor from './IndexerRuleEditor';
 const FlexCol = tw.label`flex flex-col flex-1`;
 const ToggleSection = tw.label`flex flex-row w-full`;
 
+// In the same order as the numbers stored for them, see `WatcherMode` in the core
+const WATCHER_MODES = ['Auto', 'Native', 'Polling'] as const;
+
 const schema = z.object({
 	name: z.string(),
 	path: z.string(),
 	hidden: z.boolean(),
+	followSymlinks: z.boolean(),
+	watcherMode: z.enum(WATCHER_MODES),
 	indexerRulesIds: z.array(z.number()),
 	locationType: z.string(),
 	syncPreviewMedia: z.boolean(),
@@ -33,7 +38,8 @@ export const Component = () => {
 		schema,
 		defaultValues: {
 			indexerRulesIds: [],
-			locationType: 'normal'
+			locationType: 'normal',
+			watcherMode: 'Auto'
 		}
 	});
 
@@ -79,6 +85,8 @@ export const Component = () => {
 					path: data.path,
 					name: data.name,
 					hidden: data.hidden,
+					followSymlinks: data.follow_symlinks,
+					watcherMode: WATCHER_MODES[data.watcher_mode] ?? 'Auto',
 					locationType: 'normal', // temp
 					indexerRulesIds: data.indexer_rules.map((i) => i.indexer_rule.id),
 					syncPreviewMedia: data.sync_preview_media,
@@ -89,11 +97,21 @@ export const Component = () => {
 	});
 
 	const onSubmit = form.handleSubmit(
-		({ name, hidden, indexerRulesIds, syncPreviewMedia, generatePreviewMedia }) =>
+		({
+			name,
+			hidden,
+			followSymlinks,
+			watcherMode,
+			indexerRulesIds,
+			syncPreviewMedia,
+			generatePreviewMedia
+		}) =>
 			updateLocation.mutateAsync({
 				id: locationId,
 				name,
 				hidden,
+				follow_symlinks: followSymlinks,
+				watcher_mode: watcherMode,
 				indexer_rules_ids: indexerRulesIds,
 				sync_preview_media: syncPreviewMedia,
 				generate_preview_media: generatePreviewMedia
@@ -178,6 +196,35 @@ export const Component = () => {
 					</RadioGroup.Root>
 				</div>
 				<Divider />
+				<div className="space-y-2">
+					<Label className="grow">Watch for Changes</Label>
+					<RadioGroup.Root
+						className="flex flex-row !space-y-0 space-x-2"
+						{...form.register('watcherMode')}
+					>
+						<RadioGroup.Item key="Auto" value="Auto">
+							<h1 className="font-bold">Automatic</h1>
+							<p className="text-sm text-ink-faint">
+								Network and FUSE drives are polled, others are watched by the
+								system.
+							</p>
+						</RadioGroup.Item>
+						<RadioGroup.Item key="Native" value="Native">
+							<h1 className="font-bold">System</h1>
+							<p className="text-sm text-ink-faint">
+								Changes are picked up as soon as the system notifies them.
+							</p>
+						</RadioGroup.Item>
+						<RadioGroup.Item key="Polling" value="Polling">
+							<h1 className="font-bold">Polling</h1>
+							<p className="text-sm text-ink-faint">
+								The Location is checked for changes every 30 seconds, for drives
+								the system can't watch.
+							</p>
+						</RadioGroup.Item>
+					</RadioGroup.Root>
+				</div>
+				<Divider />
 				<div className="space-y-2">
 					<ToggleSection>
 						<Label className="grow">Generate preview media for this Location</Label>
@@ -198,6 +245,15 @@ export const Component = () => {
 						</Label>
 						<Switch {...form.register('hidden')} size="sm" />
 					</ToggleSection>
+					<ToggleSection>
+						<Label className="grow">
+							Follow symbolic links to folders{' '}
+							<Tooltip label="Indexes the contents of folders reached through symbolic links, instead of only the links themselves. Links that would loop back into a folder being indexed are never followed.">
+								<Info className="inline" />
+							</Tooltip>
+						</Label>
+						<Switch {...form.register('followSymlinks')} size="sm" />
+					</ToggleSection>
 				</div>
 				<Divider />
 				<Controller
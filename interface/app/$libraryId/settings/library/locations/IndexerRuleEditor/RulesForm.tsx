@@ -16,7 +16,16 @@ const ruleKinds: UnionToTuple<RuleKind> = [
 	'AcceptFilesByGlob',
 	'RejectFilesByGlob',
 	'AcceptIfChildrenDirectoriesArePresent',
-	'RejectIfChildrenDirectoriesArePresent'
+	'RejectIfChildrenDirectoriesArePresent',
+	'RejectByIgnoreFiles',
+	'AcceptFilesBySize',
+	'RejectFilesBySize',
+	'AcceptFilesByAge',
+	'RejectFilesByAge',
+	'AcceptFilesByAttributes',
+	'RejectFilesByAttributes',
+	'AcceptFilesByExtendedAttributes',
+	'RejectFilesByExtendedAttributes'
 ];
 const ruleKindEnum = z.enum(ruleKinds);
 
@@ -5,7 +5,7 @@ export type Procedures = {
     queries: 
         { key: "buildInfo", input: never, result: BuildInfo } | 
         { key: "categories.list", input: LibraryArgs<null>, result: { [key in Category]: number } } | 
-        { key: "files.get", input: LibraryArgs<GetArgs>, result: { id: number; pub_id: number[]; kind: number; key_id: number | null; hidden: boolean; favorite: boolean; important: boolean; has_thumbnail: boolean; has_thumbstrip: boolean; has_video_preview: boolean; ipfs_id: string | null; note: string | null; date_created: string; date_accessed: string | null; file_paths: FilePath[]; media_data: MediaData | null } | null } | 
+        { key: "files.get", input: LibraryArgs<GetArgs>, result: ObjectWithMediaData | null } | 
         { key: "invalidation.test-invalidate", input: never, result: number } | 
         { key: "jobs.getHistory", input: LibraryArgs<null>, result: JobReport[] } | 
         { key: "jobs.getRunning", input: LibraryArgs<null>, result: JobReport[] } | 
@@ -19,16 +19,21 @@ export type Procedures = {
         { key: "keys.listMounted", input: LibraryArgs<null>, result: string[] } | 
         { key: "library.list", input: never, result: LibraryConfigWrapped[] } | 
         { key: "library.statistics", input: LibraryArgs<null>, result: Statistics } | 
+        { key: "library.statisticsBreakdown", input: LibraryArgs<null>, result: StatisticsBreakdown } | 
         { key: "locations.get", input: LibraryArgs<number>, result: Location | null } | 
         { key: "locations.getWithRules", input: LibraryArgs<number>, result: LocationWithIndexerRules | null } | 
+        { key: "locations.indexer_rules.export", input: LibraryArgs<IndexerRulesExportArgs>, result: string } | 
         { key: "locations.indexer_rules.get", input: LibraryArgs<number>, result: IndexerRule } | 
         { key: "locations.indexer_rules.list", input: LibraryArgs<null>, result: IndexerRule[] } | 
         { key: "locations.indexer_rules.listForLocation", input: LibraryArgs<number>, result: IndexerRule[] } | 
-        { key: "locations.list", input: LibraryArgs<null>, result: { id: number; pub_id: number[]; node_id: number; name: string; path: string; total_capacity: number | null; available_capacity: number | null; is_archived: boolean; generate_preview_media: boolean; sync_preview_media: boolean; hidden: boolean; date_created: string; node: Node }[] } | 
+        { key: "locations.indexer_rules.preview", input: LibraryArgs<IndexerRulesPreviewArgs>, result: IndexerRulesPreview } | 
+        { key: "locations.list", input: LibraryArgs<null>, result: { id: number; pub_id: number[]; node_id: number; name: string; path: string; total_capacity: number | null; available_capacity: number | null; is_archived: boolean; generate_preview_media: boolean; sync_preview_media: boolean; hidden: boolean; follow_symlinks: boolean; watcher_mode: number; volume_id: number | null; date_created: string; node: Node }[] } | 
+        { key: "locations.sizeTree", input: LibraryArgs<SizeTreeArgs>, result: SizeTreeNode } | 
         { key: "nodeState", input: never, result: NodeState } | 
         { key: "search.objects", input: LibraryArgs<ObjectSearchArgs>, result: SearchData<ExplorerItem> } | 
         { key: "search.paths", input: LibraryArgs<FilePathSearchArgs>, result: SearchData<ExplorerItem> } | 
         { key: "sync.messages", input: LibraryArgs<null>, result: CRDTOperation[] } | 
+        { key: "sync.stats", input: LibraryArgs<null>, result: SyncLogStats } | 
         { key: "tags.get", input: LibraryArgs<number>, result: Tag | null } | 
         { key: "tags.getForObject", input: LibraryArgs<number>, result: Tag[] } | 
         { key: "tags.list", input: LibraryArgs<null>, result: Tag[] } | 
@@ -76,6 +81,8 @@ export type Procedures = {
         { key: "locations.fullRescan", input: LibraryArgs<number>, result: null } | 
         { key: "locations.indexer_rules.create", input: LibraryArgs<IndexerRuleCreateArgs>, result: null } | 
         { key: "locations.indexer_rules.delete", input: LibraryArgs<number>, result: null } | 
+        { key: "locations.indexer_rules.import", input: LibraryArgs<IndexerRulesImportArgs>, result: RulesImportReport } | 
+        { key: "locations.indexer_rules.update", input: LibraryArgs<IndexerRuleUpdateArgs>, result: null } | 
         { key: "locations.relink", input: LibraryArgs<string>, result: null } | 
         { key: "locations.update", input: LibraryArgs<LocationUpdateArgs>, result: null } | 
         { key: "nodes.changeNodeName", input: ChangeNodeNameArgs, result: NodeConfig } | 
@@ -90,8 +97,11 @@ export type Procedures = {
         { key: "jobs.newThumbnail", input: LibraryArgs<null>, result: string } | 
         { key: "locations.online", input: never, result: number[][] } | 
         { key: "locations.quickRescan", input: LibraryArgs<LightScanArgs>, result: null } | 
+        { key: "locations.relinked", input: LibraryArgs<null>, result: LocationRelinkedEvent } | 
+        { key: "locations.watchLimited", input: LibraryArgs<null>, result: LocationWatchLimitedEvent } | 
         { key: "p2p.events", input: never, result: P2PEvent } | 
-        { key: "sync.newMessage", input: LibraryArgs<null>, result: CRDTOperation }
+        { key: "sync.newMessage", input: LibraryArgs<null>, result: CRDTOperation } | 
+        { key: "volumes.events", input: never, result: VolumeEvent }
 };
 
 /**
@@ -118,7 +128,7 @@ export type CreateLibraryArgs = { name: string }
 
 export type DiskType = "SSD" | "HDD" | "Removable"
 
-export type EditLibraryArgs = { id: string; name: string | null; description: string | null }
+export type EditLibraryArgs = { id: string; name: string | null; description: string | null; sync_retention_days: number | null }
 
 /**
  * This should be used for passing an encrypted key around.
@@ -127,7 +137,7 @@ export type EditLibraryArgs = { id: string; name: string | null; description: st
  */
 export type EncryptedKey = number[]
 
-export type ExplorerItem = { type: "Path"; has_thumbnail: boolean; item: FilePathWithObject } | { type: "Object"; has_thumbnail: boolean; item: ObjectWithFilePaths }
+export type ExplorerItem = { type: "Path"; has_thumbnail: boolean; online: boolean; item: FilePathWithObject } | { type: "Object"; has_thumbnail: boolean; online: boolean; item: ObjectWithFilePaths }
 
 export type FileCopierJobInit = { source_location_id: number; source_path_id: number; target_location_id: number; target_path: string; target_file_name_suffix: string | null }
 
@@ -141,15 +151,21 @@ export type FileEncryptorJobInit = { location_id: number; path_id: number; key_u
 
 export type FileEraserJobInit = { location_id: number; path_id: number; passes: string }
 
-export type FilePath = { id: number; pub_id: number[]; is_dir: boolean; cas_id: string | null; integrity_checksum: string | null; location_id: number; materialized_path: string; name: string; extension: string; size_in_bytes: string; inode: number[]; device: number[]; object_id: number | null; key_id: number | null; date_created: string; date_modified: string; date_indexed: string }
+/**
+ * A file path as sent to the frontend.
+ * 
+ * Sizes, inodes and devices are unsigned 64 bit integers, which JavaScript numbers can't hold,
+ * so they're sent as strings.
+ */
+export type FilePath = { id: number; pub_id: number[]; is_dir: boolean; cas_id: string | null; integrity_checksum: string | null; location_id: number; materialized_path: string; name: string; extension: string; size_in_bytes: string; inode: string; device: string; items_count: number; symlink_target: string | null; is_hardlink: boolean; object_id: number | null; key_id: number | null; permissions: number | null; uid: number | null; gid: number | null; xattrs: string | null; date_created: string; date_modified: string; date_indexed: string }
 
-export type FilePathFilterArgs = { locationId?: number | null; search?: string; extension?: string | null; createdAt?: OptionalRange<string>; path?: string | null; object?: ObjectFilterArgs | null }
+export type FilePathFilterArgs = { locationId?: number | null; search?: string; extension?: string | null; createdAt?: OptionalRange<string>; modifiedAt?: OptionalRange<string>; indexedAt?: OptionalRange<string>; sizeInBytes?: SizeRange; path?: string | null; object?: ObjectFilterArgs | null }
 
 export type FilePathSearchArgs = { take?: number | null; order?: FilePathSearchOrdering | null; cursor?: number[] | null; filter?: FilePathFilterArgs }
 
 export type FilePathSearchOrdering = { name: SortOrder } | { sizeInBytes: SortOrder } | { dateCreated: SortOrder } | { dateModified: SortOrder } | { dateIndexed: SortOrder } | { object: ObjectSearchOrdering }
 
-export type FilePathWithObject = { id: number; pub_id: number[]; is_dir: boolean; cas_id: string | null; integrity_checksum: string | null; location_id: number; materialized_path: string; name: string; extension: string; size_in_bytes: string; inode: number[]; device: number[]; object_id: number | null; key_id: number | null; date_created: string; date_modified: string; date_indexed: string; object: Object | null }
+export type FilePathWithObject = ({ id: number; pub_id: number[]; is_dir: boolean; cas_id: string | null; integrity_checksum: string | null; location_id: number; materialized_path: string; name: string; extension: string; size_in_bytes: string; inode: string; device: string; items_count: number; symlink_target: string | null; is_hardlink: boolean; object_id: number | null; key_id: number | null; permissions: number | null; uid: number | null; gid: number | null; xattrs: string | null; date_created: string; date_modified: string; date_indexed: string }) & { object: Object | null }
 
 export type GenerateThumbsForLocationArgs = { id: number; path: string }
 
@@ -173,9 +189,42 @@ export type IndexerRule = { id: number; name: string; default: boolean; rules_pe
  * 
  * In case of `RuleKind::AcceptIfChildrenDirectoriesArePresent` or `RuleKind::RejectIfChildrenDirectoriesArePresent` the
  * `parameters` field must be a vector of strings containing the names of the directories.
+ * 
+ * In case of `RuleKind::RejectByIgnoreFiles` the `parameters` field must be a vector of strings
+ * containing the names of the ignore files, like `.gitignore` or `.sdignore`.
+ * 
+ * In case of `RuleKind::AcceptFilesBySize` or `RuleKind::RejectFilesBySize` the `parameters` field
+ * must contain the minimum and maximum size in bytes, and in case of `RuleKind::AcceptFilesByAge` or
+ * `RuleKind::RejectFilesByAge` the minimum and maximum time since the last modification in seconds.
+ * An empty string means that the range is unbounded on that side.
+ * 
+ * In case of `RuleKind::AcceptFilesByAttributes` or `RuleKind::RejectFilesByAttributes` the
+ * `parameters` field must contain `hidden` and/or `system`.
+ * 
+ * In case of `RuleKind::AcceptFilesByExtendedAttributes` or `RuleKind::RejectFilesByExtendedAttributes`
+ * the `parameters` field must contain the names of the extended attributes, like `user.sd.ignore`.
  */
 export type IndexerRuleCreateArgs = { name: string; dry_run: boolean; rules: ([RuleKind, string[]])[] }
 
+/**
+ * `IndexerRuleUpdateArgs` is the argument received from the client using rspc to edit an indexer rule.
+ * When `rules` is present, it replaces all the current rules, using the same format of
+ * [`IndexerRuleCreateArgs`].
+ */
+export type IndexerRuleUpdateArgs = { id: number; name: string | null; rules: ([RuleKind, string[]])[] | null }
+
+export type IndexerRulesExportArgs = { indexer_rules_ids: number[]; format: RulesDocumentFormat }
+
+export type IndexerRulesImportArgs = { document: string; format: RulesDocumentFormat; conflict: RulesImportConflict }
+
+/**
+ * Result of walking a location with a set of indexer rules without saving anything.
+ * The children of rejected directories aren't walked, so they aren't counted.
+ */
+export type IndexerRulesPreview = { accepted_count: number; rejected_count: number; accepted_sample: PreviewedPath[]; rejected_sample: PreviewedPath[]; truncated: boolean; errors_count: number }
+
+export type IndexerRulesPreviewArgs = { location_id: number; sub_path?: string | null; indexer_rules_ids: number[]; rules: ([RuleKind, string[]])[]; sample_size?: number | null }
+
 export type InvalidateOperationEvent = { key: string; arg: any; result: any | null }
 
 export type JobReport = { id: string; name: string; action: string | null; data: number[] | null; metadata: any | null; is_background: boolean; errors_text: string[]; created_at: string | null; started_at: string | null; completed_at: string | null; parent_id: string | null; status: JobStatus; task_count: number; completed_task_count: number; message: string; estimated_completion: string }
@@ -184,6 +233,8 @@ export type JobStatus = "Queued" | "Running" | "Completed" | "Canceled" | "Faile
 
 export type KeyAddArgs = { algorithm: Algorithm; hashing_algorithm: HashingAlgorithm; key: Protected<string>; library_sync: boolean; automount: boolean }
 
+export type KindStatistics = { kind: number; object_count: number; total_bytes: string }
+
 /**
  * Can wrap a query argument to require it to contain a `library_id` and provide helpers for working with libraries.
  */
@@ -192,13 +243,13 @@ export type LibraryArgs<T> = { library_id: string; arg: T }
 /**
  * LibraryConfig holds the configuration for a specific library. This is stored as a '{uuid}.sdlibrary' file.
  */
-export type LibraryConfig = { name: string; description: string }
+export type LibraryConfig = { name: string; description: string; sync_retention_days: number | null }
 
 export type LibraryConfigWrapped = { uuid: string; config: LibraryConfig }
 
 export type LightScanArgs = { location_id: number; sub_path: string }
 
-export type Location = { id: number; pub_id: number[]; node_id: number; name: string; path: string; total_capacity: number | null; available_capacity: number | null; is_archived: boolean; generate_preview_media: boolean; sync_preview_media: boolean; hidden: boolean; date_created: string }
+export type Location = { id: number; pub_id: number[]; node_id: number; name: string; path: string; total_capacity: number | null; available_capacity: number | null; is_archived: boolean; generate_preview_media: boolean; sync_preview_media: boolean; hidden: boolean; follow_symlinks: boolean; watcher_mode: number; volume_id: number | null; date_created: string }
 
 /**
  * `LocationCreateArgs` is the argument received from the client using `rspc` to create a new location.
@@ -207,17 +258,32 @@ export type Location = { id: number; pub_id: number[]; node_id: number; name: st
  */
 export type LocationCreateArgs = { path: string; dry_run: boolean; indexer_rules_ids: number[] }
 
+/**
+ * Sent when the drive of an offline location was found mounted somewhere else, and the location
+ * was relinked to where it is now
+ */
+export type LocationRelinkedEvent = { library_id: string; location_id: number; old_path: string; new_path: string }
+
 /**
  * `LocationUpdateArgs` is the argument received from the client using `rspc` to update a location.
  * It contains the id of the location to be updated, possible a name to change the current location's name
  * and a vector of indexer rules ids to add or remove from the location.
  * 
  * It is important to note that only the indexer rule ids in this vector will be used from now on.
- * Old rules that aren't in this vector will be purged.
+ * Old rules that aren't in this vector will be purged, and if the rules changed the location is
+ * walked again to index and remove paths accordingly.
+ */
+export type LocationStatistics = { location_id: number; file_path_count: number; total_bytes: string }
+
+export type LocationUpdateArgs = { id: number; name: string | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; follow_symlinks: boolean | null; watcher_mode: WatcherMode | null; indexer_rules_ids: number[] }
+
+/**
+ * Sent when a location has more directories than the system allows us to watch, so only some of
+ * them are watched and the rest of the location is polled for changes
  */
-export type LocationUpdateArgs = { id: number; name: string | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; indexer_rules_ids: number[] }
+export type LocationWatchLimitedEvent = { library_id: string; location_id: number; max_user_watches: number | null }
 
-export type LocationWithIndexerRules = { id: number; pub_id: number[]; node_id: number; name: string; path: string; total_capacity: number | null; available_capacity: number | null; is_archived: boolean; generate_preview_media: boolean; sync_preview_media: boolean; hidden: boolean; date_created: string; indexer_rules: { indexer_rule: IndexerRule }[] }
+export type LocationWithIndexerRules = { id: number; pub_id: number[]; node_id: number; name: string; path: string; total_capacity: number | null; available_capacity: number | null; is_archived: boolean; generate_preview_media: boolean; sync_preview_media: boolean; hidden: boolean; follow_symlinks: boolean; watcher_mode: number; volume_id: number | null; date_created: string; indexer_rules: { indexer_rule: IndexerRule }[] }
 
 export type MasterPasswordChangeArgs = { password: Protected<string>; algorithm: Algorithm; hashing_algorithm: HashingAlgorithm }
 
@@ -225,6 +291,10 @@ export type MaybeNot<T> = T | { not: T }
 
 export type MediaData = { id: number; pixel_width: number | null; pixel_height: number | null; longitude: number | null; latitude: number | null; fps: number | null; capture_device_make: string | null; capture_device_model: string | null; capture_device_software: string | null; duration_seconds: number | null; codecs: string | null; streams: number | null }
 
+export type MediaDataFilterArgs = { pixelWidth?: OptionalRange<number>; pixelHeight?: OptionalRange<number>; durationSeconds?: OptionalRange<number>; latitude?: OptionalRange<number>; longitude?: OptionalRange<number>; captureDeviceMake?: string | null; captureDeviceModel?: string | null }
+
+export type MediaDataSearchOrdering = { pixelWidth: SortOrder } | { pixelHeight: SortOrder } | { durationSeconds: SortOrder } | { latitude: SortOrder } | { longitude: SortOrder }
+
 export type Node = { id: number; pub_id: number[]; name: string; platform: number; version: string | null; last_seen: string; timezone: string | null; date_created: string }
 
 /**
@@ -243,18 +313,23 @@ export type Nonce = { XChaCha20Poly1305: number[] } | { Aes256Gcm: number[] }
 
 export type Object = { id: number; pub_id: number[]; kind: number; key_id: number | null; hidden: boolean; favorite: boolean; important: boolean; has_thumbnail: boolean; has_thumbstrip: boolean; has_video_preview: boolean; ipfs_id: string | null; note: string | null; date_created: string; date_accessed: string | null }
 
-export type ObjectFilterArgs = { favorite?: boolean | null; hidden?: ObjectHiddenFilter; dateAccessed?: MaybeNot<string | null> | null; kind?: number[]; tags?: number[] }
+export type ObjectFilterArgs = { favorite?: boolean | null; hidden?: ObjectHiddenFilter; dateAccessed?: MaybeNot<string | null> | null; kind?: number[]; tags?: number[]; mediaData?: MediaDataFilterArgs | null }
 
 export type ObjectHiddenFilter = "exclude" | "include"
 
 export type ObjectSearchArgs = { take?: number | null; order?: ObjectSearchOrdering | null; cursor?: number[] | null; filter?: ObjectFilterArgs }
 
-export type ObjectSearchOrdering = { dateAccessed: SortOrder }
+export type ObjectSearchOrdering = { dateAccessed: SortOrder } | { dateCreated: SortOrder } | { kind: SortOrder } | { mediaData: MediaDataSearchOrdering }
 
 export type ObjectValidatorArgs = { id: number; path: string }
 
+/**
+ * An object and its file paths, as sent to the frontend.
+ */
 export type ObjectWithFilePaths = { id: number; pub_id: number[]; kind: number; key_id: number | null; hidden: boolean; favorite: boolean; important: boolean; has_thumbnail: boolean; has_thumbstrip: boolean; has_video_preview: boolean; ipfs_id: string | null; note: string | null; date_created: string; date_accessed: string | null; file_paths: FilePath[] }
 
+export type ObjectWithMediaData = ({ id: number; pub_id: number[]; kind: number; key_id: number | null; hidden: boolean; favorite: boolean; important: boolean; has_thumbnail: boolean; has_thumbstrip: boolean; has_video_preview: boolean; ipfs_id: string | null; note: string | null; date_created: string; date_accessed: string | null; file_paths: FilePath[] }) & { media_data: MediaData | null }
+
 export type OnboardingConfig = { password: Protected<string>; algorithm: Algorithm; hashing_algorithm: HashingAlgorithm }
 
 /**
@@ -287,6 +362,12 @@ export type PeerId = string
 
 export type PeerMetadata = { name: string; operating_system: OperatingSystem | null; version: string | null; email: string | null; img_url: string | null }
 
+/**
+ * A path found while previewing indexer rules, with the kind of rule that decided its fate and
+ * the names of the rules of that kind which agreed with the decision
+ */
+export type PreviewedPath = { path: string; rule_kind: RuleKind | null; rule_names: string[] }
+
 export type Protected<T> = T
 
 export type RelationOperation = { relation_item: string; relation_group: string; relation: string; data: RelationOperationData }
@@ -297,7 +378,16 @@ export type RenameFileArgs = { location_id: number; file_name: string; new_file_
 
 export type RestoreBackupArgs = { password: Protected<string>; secret_key: Protected<string>; path: string }
 
-export type RuleKind = "AcceptFilesByGlob" | "RejectFilesByGlob" | "AcceptIfChildrenDirectoriesArePresent" | "RejectIfChildrenDirectoriesArePresent"
+export type RuleKind = "AcceptFilesByGlob" | "RejectFilesByGlob" | "AcceptIfChildrenDirectoriesArePresent" | "RejectIfChildrenDirectoriesArePresent" | "RejectByIgnoreFiles" | "AcceptFilesBySize" | "RejectFilesBySize" | "AcceptFilesByAge" | "RejectFilesByAge" | "AcceptFilesByAttributes" | "RejectFilesByAttributes" | "AcceptFilesByExtendedAttributes" | "RejectFilesByExtendedAttributes"
+
+export type RulesDocumentFormat = "Json" | "Toml"
+
+/**
+ * What to do when an imported indexer rule has the same name of one already in the library
+ */
+export type RulesImportConflict = "Skip" | "Replace" | "Rename"
+
+export type RulesImportReport = { created: string[]; replaced: string[]; replaced_ids: number[]; skipped: string[] }
 
 /**
  * This should be used for passing a salt around.
@@ -318,12 +408,32 @@ export type SharedOperationCreateData = { u: { [key: string]: any } } | "a"
 
 export type SharedOperationData = SharedOperationCreateData | { field: string; value: any } | null
 
+/**
+ * Same as [`OptionalRange`], but sizes are received as strings like in [`crate::volume::Volume`],
+ * so they don't lose precision on the frontend
+ */
+export type SizeRange = { from: string | null; to: string | null }
+
+export type SizeTreeArgs = { location_id: number; sub_path?: string | null; max_depth?: number | null }
+
+/**
+ * A directory with the cumulative size and file count of everything below it,
+ * suitable for treemap or sunburst views
+ */
+export type SizeTreeNode = { name: string; size_in_bytes: string; file_count: number; children: SizeTreeNode[] }
+
 export type SortOrder = "Asc" | "Desc"
 
 export type SpacedropArgs = { peer_id: PeerId; file_path: string[] }
 
+/**
+ * The byte counts of the statistics are unsigned 64 bit integers, which JavaScript numbers
+ * can't hold, so they're sent as strings
+ */
 export type Statistics = { id: number; date_captured: string; total_object_count: number; library_db_size: string; total_bytes_used: string; total_bytes_capacity: string; total_unique_bytes: string; total_bytes_free: string; preview_media_bytes: string }
 
+export type StatisticsBreakdown = { kinds: KindStatistics[]; locations: LocationStatistics[] }
+
 /**
  * This is a stored key, and can be freely written to the database.
  * 
@@ -341,6 +451,11 @@ export type StoredKeyType = "User" | "Root"
  */
 export type StoredKeyVersion = "V1"
 
+/**
+ * The size of a library's sync log
+ */
+export type SyncLogStats = { owned_operations: number; shared_operations: number; relation_operations: number; compactable_until: string | null }
+
 export type Tag = { id: number; pub_id: number[]; name: string | null; color: string | null; total_objects: number | null; redundancy_goal: number | null; date_created: string; date_modified: string }
 
 export type TagAssignArgs = { object_id: number; tag_id: number; unassign: boolean }
@@ -351,4 +466,16 @@ export type TagUpdateArgs = { id: number; name: string | null; color: string | n
 
 export type UnlockKeyManagerArgs = { password: Protected<string>; secret_key: Protected<string> }
 
-export type Volume = { name: string; mount_point: string; total_capacity: string; available_capacity: string; is_removable: boolean; disk_type: DiskType | null; file_system: string | null; is_root_filesystem: boolean }
+export type Volume = { name: string; mount_point: string; uuid: string | null; label: string | null; total_capacity: string; available_capacity: string; is_removable: boolean; disk_type: DiskType | null; file_system: string | null; is_root_filesystem: boolean }
+
+/**
+ * Sent when the volumes of this node are mounted or unmounted
+ */
+export type VolumeEvent = { type: "Mounted"; volume: Volume } | { type: "Unmounted"; mount_point: string }
+
+/**
+ * How the changes to a location are picked up. Native watchers are notified by the operating
+ * system, but miss the changes made to network and FUSE filesystems by other machines or processes,
+ * whose locations are polled instead. `Auto` chooses by the location's filesystem
+ */
+export type WatcherMode = "Auto" | "Native" | "Polling"